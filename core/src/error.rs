@@ -0,0 +1,50 @@
+use core::fmt;
+
+// The subset of halo2_merkle_tree's errors native verification can raise; the full crate converts
+// each into its variant of the same name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    // The number of path elements and path indices differ.
+    LengthMismatch { elements: usize, indices: usize },
+    // The path length differs from the depth it was expected to have.
+    DepthMismatch { expected: usize, actual: usize },
+    // The path index at the given layer is neither 0 nor 1.
+    InvalidIndex { layer: usize },
+    // A value is not the canonical encoding of an element of the target field.
+    NonCanonical,
+    // The tree arity is not implemented; only binary trees are.
+    UnsupportedArity(usize),
+    // Serialized data was written in a format version this build does not understand.
+    UnsupportedVersion(u8),
+    // Serialized data does not follow its format; the message says where.
+    Malformed(&'static str),
+    // A path leads to a different root than the one it was checked against.
+    RootMismatch,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::LengthMismatch { elements, indices } => {
+                write!(f, "path has {} elements but {} indices", elements, indices)
+            }
+            Error::DepthMismatch { expected, actual } => write!(
+                f,
+                "path has length {} but depth {} was expected",
+                actual, expected
+            ),
+            Error::InvalidIndex { layer } => {
+                write!(f, "path index at layer {} is not 0 or 1", layer)
+            }
+            Error::NonCanonical => write!(f, "value is not a canonical field element"),
+            Error::UnsupportedArity(arity) => {
+                write!(f, "trees of arity {} are not supported", arity)
+            }
+            Error::UnsupportedVersion(version) => {
+                write!(f, "format version {} is not supported", version)
+            }
+            Error::Malformed(reason) => write!(f, "malformed data: {}", reason),
+            Error::RootMismatch => write!(f, "the path does not lead to the expected root"),
+        }
+    }
+}
@@ -0,0 +1,138 @@
+/*
+Endianness-explicit, checked encodings of field elements: 32-byte little- and big-endian reprs,
+decimal strings, 0x hex and Montgomery limbs. Decoders reject values at or above the modulus and
+malformed strings with `Error::NonCanonical` instead of reducing them. `halo2_merkle_tree`'s
+`serialization` module is built on these.
+*/
+
+use crate::error::Error;
+use alloc::string::String;
+use alloc::vec::Vec;
+use ff::PrimeField;
+
+// Fields with a 32-byte little-endian repr, as the pasta fields and bn254's scalar field have.
+pub trait CanonicalField: PrimeField<Repr = [u8; 32]> {}
+
+impl<F: PrimeField<Repr = [u8; 32]>> CanonicalField for F {}
+
+pub fn to_bytes_le<F: CanonicalField>(value: &F) -> [u8; 32] {
+    value.to_repr()
+}
+
+pub fn to_bytes_be<F: CanonicalField>(value: &F) -> [u8; 32] {
+    let mut bytes = value.to_repr();
+    bytes.reverse();
+    bytes
+}
+
+pub fn from_bytes_le_checked<F: CanonicalField>(bytes: &[u8; 32]) -> Result<F, Error> {
+    Option::from(F::from_repr(*bytes)).ok_or(Error::NonCanonical)
+}
+
+pub fn from_bytes_be_checked<F: CanonicalField>(bytes: &[u8; 32]) -> Result<F, Error> {
+    let mut le = *bytes;
+    le.reverse();
+    from_bytes_le_checked(&le)
+}
+
+pub fn to_decimal_string<F: CanonicalField>(value: &F) -> String {
+    let mut bytes = to_bytes_be(value);
+    let mut digits = Vec::new();
+    // Repeated division of the big-endian number by 10.
+    while bytes.iter().any(|byte| *byte != 0) {
+        let mut remainder = 0u32;
+        for byte in bytes.iter_mut() {
+            let acc = (remainder << 8) | *byte as u32;
+            *byte = (acc / 10) as u8;
+            remainder = acc % 10;
+        }
+        digits.push(b'0' + remainder as u8);
+    }
+    if digits.is_empty() {
+        digits.push(b'0');
+    }
+    digits.reverse();
+    String::from_utf8(digits).expect("digits are ASCII")
+}
+
+pub fn from_decimal_str<F: CanonicalField>(s: &str) -> Result<F, Error> {
+    if s.is_empty() || (s.len() > 1 && s.starts_with('0')) {
+        return Err(Error::NonCanonical);
+    }
+    let mut bytes = [0u8; 32];
+    for c in s.chars() {
+        let mut carry = c.to_digit(10).ok_or(Error::NonCanonical)?;
+        // bytes = bytes * 10 + digit, big-endian.
+        for byte in bytes.iter_mut().rev() {
+            let acc = *byte as u32 * 10 + carry;
+            *byte = acc as u8;
+            carry = acc >> 8;
+        }
+        if carry != 0 {
+            return Err(Error::NonCanonical);
+        }
+    }
+    from_bytes_be_checked(&bytes)
+}
+
+// 0x followed by the 64 hex digits of the big-endian value, as block explorers and ethers print.
+pub fn to_hex<F: CanonicalField>(value: &F) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut hex = String::with_capacity(66);
+    hex.push_str("0x");
+    for byte in to_bytes_be(value) {
+        hex.push(DIGITS[(byte >> 4) as usize] as char);
+        hex.push(DIGITS[(byte & 0xf) as usize] as char);
+    }
+    hex
+}
+
+// Accepts 0x-prefixed hex of either case, with or without leading zeros (JS libraries often strip
+// them), but not an empty digit string or a value at or above the modulus.
+pub fn from_hex<F: CanonicalField>(s: &str) -> Result<F, Error> {
+    let digits = s.strip_prefix("0x").ok_or(Error::NonCanonical)?;
+    if digits.is_empty() || digits.len() > 64 {
+        return Err(Error::NonCanonical);
+    }
+    let mut bytes = [0u8; 32];
+    for (i, c) in digits.chars().rev().enumerate() {
+        let nibble = c.to_digit(16).ok_or(Error::NonCanonical)? as u8;
+        bytes[31 - i / 2] |= nibble << (4 * (i % 2));
+    }
+    from_bytes_be_checked(&bytes)
+}
+
+// The Montgomery factor R = 2^256 mod p.
+fn montgomery_r<F: CanonicalField>() -> F {
+    F::from(2).pow_vartime([256])
+}
+
+fn limbs_to_bytes(limbs: &[u64; 4]) -> [u8; 32] {
+    let mut bytes = [0u8; 32];
+    for (chunk, limb) in bytes.chunks_mut(8).zip(limbs.iter()) {
+        chunk.copy_from_slice(&limb.to_le_bytes());
+    }
+    bytes
+}
+
+// A raw 32-byte little-endian repr, as written by halo2, arkworks' serializers and circom's .wtns
+// witness files.
+pub fn from_raw_le<F: CanonicalField>(bytes: &[u8; 32]) -> Result<F, Error> {
+    from_bytes_le_checked(bytes)
+}
+
+// Four little-endian u64 limbs holding x * R mod p. The limbs themselves must be below p.
+pub fn from_montgomery_limbs<F: CanonicalField>(limbs: &[u64; 4]) -> Result<F, Error> {
+    let montgomery: F = from_bytes_le_checked(&limbs_to_bytes(limbs))?;
+    let r_inv: F = Option::from(montgomery_r::<F>().invert()).ok_or(Error::NonCanonical)?;
+    Ok(montgomery * r_inv)
+}
+
+pub fn to_montgomery_limbs<F: CanonicalField>(value: &F) -> [u64; 4] {
+    let bytes = to_bytes_le(&(*value * montgomery_r::<F>()));
+    let mut limbs = [0u64; 4];
+    for (limb, chunk) in limbs.iter_mut().zip(bytes.chunks(8)) {
+        *limb = u64::from_le_bytes(chunk.try_into().expect("chunks are 8 bytes"));
+    }
+    limbs
+}
@@ -0,0 +1,20 @@
+/*
+The native half of halo2_merkle_tree without halo2: canonical field encodings, Merkle path checks
+and decoding of the wire format, over any `ff` prime field with a 32-byte little-endian repr. It is
+no_std with alloc, so an embedded or WASM verifier that only checks native paths (not SNARKs) can
+depend on it alone. The hash is the caller's: path checks take it as a closure, so the same code
+serves Poseidon, the dummy hash or anything else.
+
+halo2_merkle_tree re-exports or wraps everything here, so users of the full crate don't need it.
+*/
+
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+pub mod error;
+pub mod field;
+pub mod path;
+pub mod wire;
+
+pub use error::Error;
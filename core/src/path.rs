@@ -0,0 +1,105 @@
+/*
+Native Merkle path checks with the hash supplied by the caller as `hash(level, left, right)`. At
+each level the running digest is the left input when the index is zero and the right input
+otherwise, as in the circuits, with level 0 hashing the leaf with its sibling.
+*/
+
+use crate::error::Error;
+use ff::Field;
+
+// The root a path leads to. Indices are not checked: anything but zero counts as one, so use
+// `verify_path` on untrusted paths.
+pub fn fold_path<F: Field>(
+    leaf: F,
+    elements: &[F],
+    indices: &[F],
+    mut hash: impl FnMut(usize, F, F) -> F,
+) -> F {
+    let mut digest = leaf;
+    for (level, (element, index)) in elements.iter().zip(indices.iter()).enumerate() {
+        digest = if *index == F::zero() {
+            hash(level, digest, *element)
+        } else {
+            hash(level, *element, digest)
+        };
+    }
+    digest
+}
+
+// Checks that the path has one index per element and `depth` of each, that every index is a bit,
+// and that it leads to `root`.
+pub fn verify_path<F: Field>(
+    root: F,
+    depth: usize,
+    leaf: F,
+    elements: &[F],
+    indices: &[F],
+    hash: impl FnMut(usize, F, F) -> F,
+) -> Result<(), Error> {
+    if elements.len() != indices.len() {
+        return Err(Error::LengthMismatch {
+            elements: elements.len(),
+            indices: indices.len(),
+        });
+    }
+    if elements.len() != depth {
+        return Err(Error::DepthMismatch {
+            expected: depth,
+            actual: elements.len(),
+        });
+    }
+    if let Some(layer) = indices
+        .iter()
+        .position(|index| *index != F::zero() && *index != F::one())
+    {
+        return Err(Error::InvalidIndex { layer });
+    }
+    if fold_path(leaf, elements, indices, hash) != root {
+        return Err(Error::RootMismatch);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fold_path, verify_path};
+    use crate::error::Error;
+    use pasta_curves::Fp;
+
+    #[test]
+    fn test() {
+        // A hash that is not symmetric, so a swapped pair gives a different digest.
+        let hash =
+            |level: usize, left: Fp, right: Fp| left * Fp::from(3) + right + Fp::from(level as u64);
+        let elements = [Fp::from(10), Fp::from(20), Fp::from(30)];
+        let indices = [Fp::one(), Fp::zero(), Fp::one()];
+        let leaf = Fp::from(7);
+        // Level 0: (10, 7); level 1: (d, 20); level 2: (30, d).
+        let d0 = Fp::from(10 * 3 + 7);
+        let d1 = d0 * Fp::from(3) + Fp::from(20) + Fp::one();
+        let root = Fp::from(30 * 3) + d1 + Fp::from(2);
+        assert_eq!(fold_path(leaf, &elements, &indices, hash), root);
+        verify_path(root, 3, leaf, &elements, &indices, hash).unwrap();
+
+        assert_eq!(
+            verify_path(root, 3, Fp::from(8), &elements, &indices, hash),
+            Err(Error::RootMismatch)
+        );
+        assert_eq!(
+            verify_path(root, 4, leaf, &elements, &indices, hash),
+            Err(Error::DepthMismatch {
+                expected: 4,
+                actual: 3
+            })
+        );
+        assert!(matches!(
+            verify_path(root, 3, leaf, &elements, &indices[..2], hash),
+            Err(Error::LengthMismatch { .. })
+        ));
+        let not_bits = [Fp::one(), Fp::from(2), Fp::one()];
+        assert_eq!(
+            verify_path(root, 3, leaf, &elements, &not_bits, hash),
+            Err(Error::InvalidIndex { layer: 1 })
+        );
+    }
+}
@@ -0,0 +1,180 @@
+/*
+Decoding of halo2_merkle_tree's versioned wire framing (see its `wire` module for the layout):
+
+    | version: u8 | hash: u8 | arity: u8 | depth: u8 | root: 32 bytes LE |
+    | kind: u8 | len: u32 BE | payload |
+
+`Frame::from_bytes` is as strict as the full crate's `MerkleProof::from_bytes`, except that the hash
+id is returned as the raw byte: which hashes exist is the full crate's business, and a native
+verifier has to bring the hash anyway.
+*/
+
+use crate::error::Error;
+use crate::field::{from_bytes_le_checked, CanonicalField};
+use alloc::vec::Vec;
+
+pub const WIRE_VERSION: u8 = 1;
+
+// Reads from the front of a byte slice, failing on truncation.
+pub struct Reader<'a>(pub &'a [u8]);
+
+impl<'a> Reader<'a> {
+    pub fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.0.len() < len {
+            return Err(Error::Malformed("truncated"));
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    pub fn byte(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn field<F: CanonicalField>(&mut self) -> Result<F, Error> {
+        let bytes: &[u8; 32] = self.take(32)?.try_into().expect("took 32 bytes");
+        from_bytes_le_checked(bytes)
+    }
+}
+
+pub fn index_from_bit<F: CanonicalField>(layer: usize, bit: u64) -> Result<F, Error> {
+    match bit {
+        0 => Ok(F::zero()),
+        1 => Ok(F::one()),
+        _ => Err(Error::InvalidIndex { layer }),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FramePayload<F> {
+    Path {
+        leaf: F,
+        elements: Vec<F>,
+        indices: Vec<F>,
+    },
+    // An opaque halo2 proof.
+    Proof(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame<F> {
+    // The wire hash id, unchecked.
+    pub hash: u8,
+    pub arity: u8,
+    pub depth: u8,
+    pub root: F,
+    pub payload: FramePayload<F>,
+}
+
+impl<F: CanonicalField> Frame<F> {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader(bytes);
+        let version = reader.byte()?;
+        if version != WIRE_VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        let hash = reader.byte()?;
+        let arity = reader.byte()?;
+        let depth = reader.byte()?;
+        let root = reader.field()?;
+        let kind = reader.byte()?;
+        let len = u32::from_be_bytes(reader.take(4)?.try_into().expect("took 4 bytes")) as usize;
+        let mut body = Reader(reader.take(len)?);
+        if !reader.0.is_empty() {
+            return Err(Error::Malformed("trailing bytes"));
+        }
+        // Only binary trees are defined, whatever the payload.
+        if arity != 2 {
+            return Err(Error::UnsupportedArity(arity as usize));
+        }
+
+        let payload = match kind {
+            0 => {
+                let leaf = body.field()?;
+                let elements = (0..depth)
+                    .map(|_| body.field())
+                    .collect::<Result<Vec<F>, _>>()?;
+                let indices = body
+                    .take(depth as usize)?
+                    .iter()
+                    .enumerate()
+                    .map(|(layer, bit)| index_from_bit(layer, *bit as u64))
+                    .collect::<Result<Vec<F>, _>>()?;
+                if !body.0.is_empty() {
+                    return Err(Error::Malformed("path payload length does not match depth"));
+                }
+                FramePayload::Path {
+                    leaf,
+                    elements,
+                    indices,
+                }
+            }
+            1 => FramePayload::Proof(body.0.to_vec()),
+            _ => return Err(Error::Malformed("unknown payload kind")),
+        };
+
+        Ok(Self {
+            hash,
+            arity,
+            depth,
+            root,
+            payload,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Frame, FramePayload, WIRE_VERSION};
+    use crate::error::Error;
+    use crate::field::to_bytes_le;
+    use crate::path::verify_path;
+    use pasta_curves::Fp;
+
+    #[test]
+    fn test() {
+        let hash = |_: usize, left: Fp, right: Fp| left + right + right;
+        let leaf = Fp::from(5);
+        let elements = [Fp::from(1), Fp::from(2)];
+        let indices = [Fp::zero(), Fp::one()];
+        let root = Fp::from(2) + Fp::from(2 * (5 + 2));
+
+        let mut bytes = vec![WIRE_VERSION, 1, 2, 2];
+        bytes.extend_from_slice(&to_bytes_le(&root));
+        bytes.push(0);
+        bytes.extend_from_slice(&(32 * 3 + 2u32).to_be_bytes());
+        for field in [leaf, elements[0], elements[1]] {
+            bytes.extend_from_slice(&to_bytes_le(&field));
+        }
+        bytes.extend_from_slice(&[0, 1]);
+
+        let frame = Frame::<Fp>::from_bytes(&bytes).unwrap();
+        assert_eq!(frame.hash, 1);
+        assert_eq!(frame.root, root);
+        let FramePayload::Path {
+            leaf,
+            elements,
+            indices: decoded,
+        } = frame.payload
+        else {
+            panic!("expected a path payload");
+        };
+        assert_eq!(decoded, indices);
+        verify_path(root, 2, leaf, &elements, &decoded, hash).unwrap();
+
+        let corrupt = |offset: usize, value: u8| {
+            let mut bytes = bytes.clone();
+            bytes[offset] = value;
+            Frame::<Fp>::from_bytes(&bytes)
+        };
+        assert_eq!(corrupt(0, 2), Err(Error::UnsupportedVersion(2)));
+        assert_eq!(corrupt(2, 4), Err(Error::UnsupportedArity(4)));
+        assert_eq!(
+            corrupt(bytes.len() - 1, 2),
+            Err(Error::InvalidIndex { layer: 1 })
+        );
+        assert_eq!(corrupt(41 + 31, 0xff), Err(Error::NonCanonical));
+        assert!(Frame::<Fp>::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,352 @@
+/*
+A lightweight underconstraint check. The circuit is laid out into a recorder instead of a prover,
+which notes every advice assignment, selector enable and copy constraint, and every advice cell a
+gate queries at the rows its selectors are enabled on. Two kinds of finding are reported:
+
+- advice cells that are assigned but neither queried by an active gate nor copy constrained, so a
+  prover could put anything there, and
+- selectors that are allocated but never enabled, usually a gate that was meant to be used.
+
+The gate check is an over-approximation (a cell counts as constrained if any selector of a gate
+querying it is on), so it misses gates whose constraints happen to be trivially satisfied, but it
+has no false positives from gates. Cells only read by lookups are reported; check those by hand.
+Findings are grouped by region name with digits stripped, i.e. per chip ("layer 7" -> "layer").
+*/
+
+use crate::error::Error;
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::Value,
+    plonk::{
+        Advice, Any, Assigned, Assignment, Circuit, Column, ConstraintSystem, Error as PlonkError,
+        Fixed, FloorPlanner, Instance, Selector,
+    },
+};
+use std::collections::{BTreeMap, HashSet};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnconstrainedCell {
+    pub region: String,
+    pub column: usize,
+    pub row: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnalysisReport {
+    pub unconstrained: Vec<UnconstrainedCell>,
+    // One past the last row with an advice assignment or an enabled selector.
+    pub rows: usize,
+    pub selectors: usize,
+    pub unused_selectors: usize,
+}
+
+impl AnalysisReport {
+    pub fn is_clean(&self) -> bool {
+        self.unconstrained.is_empty() && self.unused_selectors == 0
+    }
+
+    // The number of unconstrained cells per chip, i.e. per region name without its digits.
+    pub fn by_chip(&self) -> BTreeMap<String, usize> {
+        let mut chips = BTreeMap::new();
+        for cell in &self.unconstrained {
+            let chip: String = cell
+                .region
+                .chars()
+                .filter(|c| !c.is_ascii_digit())
+                .collect();
+            *chips.entry(chip.trim().to_string()).or_insert(0) += 1;
+        }
+        chips
+    }
+}
+
+#[derive(Default)]
+struct Recorder {
+    region: Option<String>,
+    // (column, row, region) of every advice assignment.
+    advice: Vec<(usize, usize, String)>,
+    // Advice cells that appear in a copy constraint.
+    copied: HashSet<(usize, usize)>,
+    enabled: Vec<(Selector, usize)>,
+}
+
+impl<F: FieldExt> Assignment<F> for Recorder {
+    fn enter_region<NR, N>(&mut self, name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        self.region = Some(name_fn().into());
+    }
+
+    fn exit_region(&mut self) {
+        self.region = None;
+    }
+
+    fn enable_selector<A, AR>(
+        &mut self,
+        _annotation: A,
+        selector: &Selector,
+        row: usize,
+    ) -> Result<(), PlonkError>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.enabled.push((*selector, row));
+        Ok(())
+    }
+
+    fn query_instance(
+        &self,
+        _column: Column<Instance>,
+        _row: usize,
+    ) -> Result<Value<F>, PlonkError> {
+        Ok(Value::unknown())
+    }
+
+    fn assign_advice<V, VR, A, AR>(
+        &mut self,
+        _annotation: A,
+        column: Column<Advice>,
+        row: usize,
+        to: V,
+    ) -> Result<(), PlonkError>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        let _ = to();
+        let region = self.region.clone().unwrap_or_default();
+        self.advice.push((column.index(), row, region));
+        Ok(())
+    }
+
+    fn assign_fixed<V, VR, A, AR>(
+        &mut self,
+        _annotation: A,
+        _column: Column<Fixed>,
+        _row: usize,
+        _to: V,
+    ) -> Result<(), PlonkError>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        Ok(())
+    }
+
+    fn copy(
+        &mut self,
+        left_column: Column<Any>,
+        left_row: usize,
+        right_column: Column<Any>,
+        right_row: usize,
+    ) -> Result<(), PlonkError> {
+        for (column, row) in [(left_column, left_row), (right_column, right_row)] {
+            if *column.column_type() == Any::Advice {
+                self.copied.insert((column.index(), row));
+            }
+        }
+        Ok(())
+    }
+
+    fn fill_from_row(
+        &mut self,
+        _column: Column<Fixed>,
+        _row: usize,
+        _to: Value<Assigned<F>>,
+    ) -> Result<(), PlonkError> {
+        Ok(())
+    }
+
+    fn push_namespace<NR, N>(&mut self, _name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn pop_namespace(&mut self, _gadget_name: Option<String>) {}
+}
+
+// The selectors and (advice column, rotation) pairs a gate polynomial refers to.
+type Queries = (Vec<Selector>, Vec<(usize, i32)>);
+
+fn merge(mut a: Queries, b: Queries) -> Queries {
+    a.0.extend(b.0);
+    a.1.extend(b.1);
+    a
+}
+
+// Lays `circuit` out into a recorder, next to the constraint system it was configured with.
+fn record<F: FieldExt, C: Circuit<F>>(
+    circuit: &C,
+) -> Result<(ConstraintSystem<F>, Recorder), Error> {
+    let mut cs = ConstraintSystem::default();
+    let config = C::configure(&mut cs);
+    let mut recorder = Recorder::default();
+    C::FloorPlanner::synthesize(&mut recorder, circuit, config, cs.constants().clone())?;
+    Ok((cs, recorder))
+}
+
+// The selectors and queried cells of each gate, in gate order.
+fn gate_queries<F: FieldExt>(cs: &ConstraintSystem<F>) -> Vec<Queries> {
+    cs.gates()
+        .iter()
+        .map(|gate| {
+            gate.polynomials()
+                .iter()
+                .map(|poly| {
+                    poly.evaluate(
+                        &|_| (vec![], vec![]),
+                        &|selector| (vec![selector], vec![]),
+                        &|_| (vec![], vec![]),
+                        &|query| (vec![], vec![(query.column_index(), query.rotation().0)]),
+                        &|_| (vec![], vec![]),
+                        &|a| a,
+                        &merge,
+                        &merge,
+                        &|a, _| a,
+                    )
+                })
+                .fold((vec![], vec![]), merge)
+        })
+        .collect()
+}
+
+// Whether each gate of the circuit is enabled on some row, in gate order. A gate without
+// selectors counts as enabled.
+pub(crate) fn active_gates<F: FieldExt, C: Circuit<F>>(circuit: &C) -> Result<Vec<bool>, Error> {
+    let (cs, recorder) = record(circuit)?;
+    Ok(gate_queries(&cs)
+        .iter()
+        .map(|(selectors, _)| {
+            selectors.is_empty()
+                || recorder
+                    .enabled
+                    .iter()
+                    .any(|(selector, _)| selectors.contains(selector))
+        })
+        .collect())
+}
+
+pub fn analyze<F: FieldExt, C: Circuit<F>>(circuit: &C) -> Result<AnalysisReport, Error> {
+    let (cs, recorder) = record(circuit)?;
+
+    let mut constrained: HashSet<(usize, usize)> = recorder.copied.clone();
+    let max_row = recorder
+        .advice
+        .iter()
+        .map(|(_, row, _)| *row)
+        .max()
+        .unwrap_or(0);
+    for (selectors, cells) in gate_queries(&cs) {
+        // A gate without selectors applies to every row.
+        let rows: Vec<usize> = if selectors.is_empty() {
+            (0..=max_row).collect()
+        } else {
+            recorder
+                .enabled
+                .iter()
+                .filter(|(selector, _)| selectors.contains(selector))
+                .map(|(_, row)| *row)
+                .collect()
+        };
+        for row in rows {
+            for (column, rotation) in &cells {
+                let target = row as i64 + *rotation as i64;
+                if target >= 0 {
+                    constrained.insert((*column, target as usize));
+                }
+            }
+        }
+    }
+
+    let mut unconstrained: Vec<UnconstrainedCell> = recorder
+        .advice
+        .iter()
+        .filter(|(column, row, _)| !constrained.contains(&(*column, *row)))
+        .map(|(column, row, region)| UnconstrainedCell {
+            region: region.clone(),
+            column: *column,
+            row: *row,
+        })
+        .collect();
+    unconstrained.dedup();
+
+    let mut used: Vec<Selector> = vec![];
+    for (selector, _) in &recorder.enabled {
+        if !used.contains(selector) {
+            used.push(*selector);
+        }
+    }
+    let rows = recorder
+        .advice
+        .iter()
+        .map(|(_, row, _)| *row)
+        .chain(recorder.enabled.iter().map(|(_, row)| *row))
+        .max()
+        .map_or(0, |row| row + 1);
+    Ok(AnalysisReport {
+        unconstrained,
+        rows,
+        selectors: cs.num_selectors(),
+        unused_selectors: cs.num_selectors().saturating_sub(used.len()),
+    })
+}
+
+mod tests {
+    use super::analyze;
+    use crate::circuits::leaf_encoding::LeafEncodingCircuit;
+    use halo2_proofs::{circuit::*, pasta::Fp, plonk::*};
+
+    // Assigns a cell that nothing constrains.
+    #[derive(Default)]
+    struct DanglingCircuit;
+
+    impl Circuit<Fp> for DanglingCircuit {
+        type Config = Column<Advice>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+            meta.selector();
+            meta.advice_column()
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<Fp>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "dangling 3",
+                |mut region| {
+                    region.assign_advice(|| "dangling", config, 0, || Value::known(Fp::one()))
+                },
+            )?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test() {
+        let circuit = LeafEncodingCircuit::<Fp>::new(&[1, 2, 3]).unwrap();
+        let report = analyze(&circuit).unwrap();
+        assert!(report.is_clean(), "{:?}", report);
+
+        let report = analyze(&DanglingCircuit).unwrap();
+        assert_eq!(report.unconstrained.len(), 1);
+        assert_eq!(report.rows, 1);
+        assert_eq!(report.unused_selectors, 1);
+        assert_eq!(report.by_chip().get("dangling"), Some(&1));
+    }
+}
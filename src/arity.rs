@@ -0,0 +1,157 @@
+/*
+Tree arity selection for research into wider trees, behind the `unstable-t5` feature.
+`select_poseidon` picks the arity for a leaf count: a binary tree hashed with P128Pow5T3, or a
+quaternary one hashed with P128Pow5T5, which is half as deep but whose permutation is wider and has
+more rounds. Both are costed the same way, depth * (rows of one hash + one row per child) + 1,
+with the hash rows laid out by `analyze`, so the comparison is like for like but is an estimate.
+
+The result cannot be acted on yet: P128Pow5T5's parameters are unanalysed, quaternary membership
+circuits are not implemented, and `estimate_k` rejects arity 4.
+*/
+
+use crate::analysis::analyze;
+use crate::chips::poseidon::P128Pow5T5;
+use crate::circuits::poseidon::PoseidonCircuit;
+use crate::error::Error;
+use halo2_gadgets::poseidon::primitives::P128Pow5T3;
+use halo2_proofs::circuit::Value;
+use std::fmt;
+
+// A Poseidon instantiation and the tree arity it hashes: one rate word per child.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoseidonParams {
+    pub arity: usize,
+    pub width: usize,
+    pub rate: usize,
+}
+
+impl PoseidonParams {
+    pub const T3: Self = Self {
+        arity: 2,
+        width: 3,
+        rate: 2,
+    };
+    pub const T5: Self = Self {
+        arity: 4,
+        width: 5,
+        rate: 4,
+    };
+}
+
+// One candidate's estimated cost for the requested leaf count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HashCost {
+    pub params: PoseidonParams,
+    pub depth: usize,
+    pub rows: usize,
+}
+
+impl fmt::Display for HashCost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "arity {} (T{}, rate {}): depth {}, ~{} rows",
+            self.params.arity, self.params.width, self.params.rate, self.depth, self.rows
+        )
+    }
+}
+
+// The arity `select_poseidon` chose, with every candidate it weighed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashSelection {
+    pub leaves: u64,
+    pub chosen: HashCost,
+    pub candidates: Vec<HashCost>,
+}
+
+impl fmt::Display for HashSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "leaves: {}", self.leaves)?;
+        writeln!(f, "selected: {}", self.chosen)?;
+        for (i, candidate) in self.candidates.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "candidate: {}", candidate)?;
+        }
+        Ok(())
+    }
+}
+
+// The depth of the shallowest tree of `arity` with room for `leaves`, at least 1.
+fn tree_depth(leaves: u64, arity: u64) -> usize {
+    let mut depth = 1;
+    let mut capacity = arity;
+    while capacity < leaves {
+        capacity = capacity.saturating_mul(arity);
+        depth += 1;
+    }
+    depth
+}
+
+fn hash_cost(params: PoseidonParams, leaves: u64) -> Result<HashCost, Error> {
+    let hash_rows = match params {
+        PoseidonParams::T3 => {
+            let circuit = PoseidonCircuit::<P128Pow5T3, 3, 2, 2>::new(
+                [Value::unknown(); 2],
+                Value::unknown(),
+            );
+            analyze(&circuit)?.rows
+        }
+        PoseidonParams::T5 => {
+            let circuit = PoseidonCircuit::<P128Pow5T5, 5, 4, 4>::new(
+                [Value::unknown(); 4],
+                Value::unknown(),
+            );
+            analyze(&circuit)?.rows
+        }
+        _ => return Err(Error::UnsupportedArity(params.arity)),
+    };
+    let depth = tree_depth(leaves, params.arity as u64);
+    Ok(HashCost {
+        params,
+        depth,
+        rows: depth * (hash_rows + params.arity) + 1,
+    })
+}
+
+// Chooses between T3 over a binary tree and T5 over a quaternary one for `leaves` leaves, whichever
+// has fewer estimated rows. Ties go to the binary tree, the one the membership circuits implement.
+pub fn select_poseidon(leaves: u64) -> Result<HashSelection, Error> {
+    if leaves == 0 {
+        return Err(Error::Malformed("a tree needs at least one leaf"));
+    }
+    let candidates = [PoseidonParams::T3, PoseidonParams::T5]
+        .iter()
+        .map(|params| hash_cost(*params, leaves))
+        .collect::<Result<Vec<HashCost>, Error>>()?;
+    let chosen = candidates
+        .iter()
+        .copied()
+        .min_by_key(|candidate| candidate.rows)
+        .ok_or(Error::Malformed("no Poseidon candidates"))?;
+    Ok(HashSelection {
+        leaves,
+        chosen,
+        candidates,
+    })
+}
+
+mod tests {
+    use super::{select_poseidon, PoseidonParams};
+
+    #[test]
+    fn test() {
+        // Two leaves are one level either way, and T3 is the cheaper hash; a million leaves are
+        // 20 binary levels against 10 quaternary ones.
+        let small = select_poseidon(2).unwrap();
+        assert_eq!(small.chosen.params, PoseidonParams::T3);
+        let large = select_poseidon(1 << 20).unwrap();
+        assert_eq!(large.chosen.params, PoseidonParams::T5);
+        assert_eq!(large.chosen.depth, 10);
+        let fewest = large.candidates.iter().map(|c| c.rows).min().unwrap();
+        assert_eq!(large.chosen.rows, fewest);
+        assert!(large.to_string().contains("selected: arity 4"));
+        assert!(select_poseidon(0).is_err());
+    }
+}
@@ -0,0 +1,63 @@
+/*
+Conversions between this crate's field elements and arkworks fields, for pipelines that build trees
+with arkworks (e.g. ark-pallas) and prove membership here. They go through the canonical
+little-endian encoding and fail with `Error::NonCanonical` when the value does not fit the target
+field, so converting between fields with different moduli is caught instead of silently reduced.
+Orphan rules rule out `From` impls between two foreign types, hence plain functions.
+
+There is no proof conversion: arkworks has no IPA/Halo2 proof type to convert to.
+*/
+
+use crate::error::Error;
+use crate::serialization::{from_bytes_le_checked, to_bytes_le, CanonicalField};
+use ark_ff::{BigInteger, PrimeField as ArkPrimeField};
+
+// e.g. `let x: ark_pallas::Fq = to_ark(leaf)?;` for a Pallas `Fp` leaf.
+pub fn to_ark<F: CanonicalField, A: ArkPrimeField>(value: F) -> Result<A, Error> {
+    let bytes = to_bytes_le(&value);
+    let converted = A::from_le_bytes_mod_order(&bytes);
+    if converted.into_bigint().to_bytes_le()[..] != bytes[..] {
+        return Err(Error::NonCanonical);
+    }
+    Ok(converted)
+}
+
+pub fn from_ark<A: ArkPrimeField, F: CanonicalField>(value: A) -> Result<F, Error> {
+    let bytes = value.into_bigint().to_bytes_le();
+    let repr: [u8; 32] = bytes.try_into().map_err(|_| Error::NonCanonical)?;
+    from_bytes_le_checked(&repr)
+}
+
+// Converts a whole path or leaf set at once.
+pub fn vec_from_ark<A: ArkPrimeField, F: CanonicalField>(values: &[A]) -> Result<Vec<F>, Error> {
+    values.iter().map(|value| from_ark(*value)).collect()
+}
+
+mod tests {
+    use super::{from_ark, to_ark, vec_from_ark};
+    use ark_ff::{Fp256, MontBackend, MontConfig};
+    use halo2_proofs::pasta::{Fp, Fq};
+
+    // The Pallas base field, as ark-pallas defines it.
+    #[derive(MontConfig)]
+    #[modulus = "28948022309329048855892746252171976963363056481941560715954676764349967630337"]
+    #[generator = "5"]
+    pub struct PallasConfig;
+    type ArkFp = Fp256<MontBackend<PallasConfig, 4>>;
+
+    #[test]
+    fn test() {
+        let x = Fp::from(123456789) * Fp::from(987654321);
+        let converted: ArkFp = to_ark(x).unwrap();
+        assert_eq!(
+            converted,
+            ArkFp::from(123456789u64) * ArkFp::from(987654321u64)
+        );
+        assert_eq!(from_ark::<_, Fp>(converted).unwrap(), x);
+        assert_eq!(to_ark::<_, ArkFp>(-Fp::one()).unwrap(), -ArkFp::from(1u64));
+        assert_eq!(vec_from_ark::<_, Fp>(&[converted]).unwrap(), vec![x]);
+
+        // Fq's modulus is larger, so Fq's -1 has no Fp counterpart.
+        assert!(to_ark::<_, ArkFp>(-Fq::one()).is_err());
+    }
+}
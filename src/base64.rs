@@ -0,0 +1,239 @@
+/*
+Copy-pasteable text forms of a `MerkleProof` for web and mobile verifiers: URL-safe unpadded base64
+for query strings and QR codes, and a standard-alphabet `data:` URI. The encoded bytes are one flag
+byte followed by the binary wire format, whose own header carries the version, hash, arity, depth
+and root:
+
+    flag 0: wire bytes
+    flag 1: zstd-compressed wire bytes (the `zstd` feature)
+
+Decoding is strict: characters outside the alphabet, padding where there should be none and
+non-zero trailing bits are rejected, so every proof has exactly one text form.
+*/
+
+use crate::error::Error;
+use crate::serialization::CanonicalField;
+use crate::wire::MerkleProof;
+
+pub const DATA_URI_PREFIX: &str = "data:application/vnd.halo2-merkle-proof;base64,";
+
+// Decompressed proofs larger than this are refused, so a small input can't expand without bound.
+#[cfg(feature = "zstd")]
+pub const MAX_DECOMPRESSED: usize = 1 << 20;
+
+const FLAG_RAW: u8 = 0;
+const FLAG_ZSTD: u8 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alphabet {
+    // A-Z a-z 0-9 + / with = padding, as data URIs use.
+    Standard,
+    // A-Z a-z 0-9 - _ without padding, safe in URLs.
+    UrlSafe,
+}
+
+impl Alphabet {
+    fn symbols(self) -> &'static [u8; 64] {
+        match self {
+            Alphabet::Standard => {
+                b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/"
+            }
+            Alphabet::UrlSafe => {
+                b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_"
+            }
+        }
+    }
+}
+
+pub fn encode(bytes: &[u8], alphabet: Alphabet) -> String {
+    let symbols = alphabet.symbols();
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let word = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, byte)| acc | (*byte as u32) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            out.push(symbols[(word >> (18 - 6 * i)) as usize & 63] as char);
+        }
+        if alphabet == Alphabet::Standard {
+            for _ in chunk.len()..3 {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+pub fn decode(s: &str, alphabet: Alphabet) -> Result<Vec<u8>, Error> {
+    let symbols = alphabet.symbols();
+    let s = match alphabet {
+        Alphabet::Standard => {
+            if s.len() % 4 != 0 {
+                return Err(Error::Malformed("base64 length is not a multiple of 4"));
+            }
+            let trimmed = s.trim_end_matches('=');
+            if s.len() - trimmed.len() > 2 {
+                return Err(Error::Malformed("too much base64 padding"));
+            }
+            trimmed
+        }
+        Alphabet::UrlSafe => s,
+    };
+    if s.len() % 4 == 1 {
+        return Err(Error::Malformed("base64 length is invalid"));
+    }
+    let values = s
+        .bytes()
+        .map(|c| {
+            symbols
+                .iter()
+                .position(|symbol| *symbol == c)
+                .map(|value| value as u32)
+                .ok_or(Error::Malformed("invalid base64 character"))
+        })
+        .collect::<Result<Vec<u32>, Error>>()?;
+
+    let mut bytes = Vec::with_capacity(values.len() * 3 / 4);
+    for chunk in values.chunks(4) {
+        let word = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, value)| acc | value << (18 - 6 * i));
+        let len = chunk.len() - 1;
+        // The bits below the last whole byte must be zero.
+        if word & ((1 << (24 - 8 * len)) - 1) != 0 {
+            return Err(Error::Malformed("non-zero trailing base64 bits"));
+        }
+        bytes.extend((0..len).map(|i| (word >> (16 - 8 * i)) as u8));
+    }
+    Ok(bytes)
+}
+
+fn framed(flag: u8, body: Vec<u8>) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(1 + body.len());
+    bytes.push(flag);
+    bytes.extend(body);
+    bytes
+}
+
+fn unframe<F: CanonicalField>(bytes: &[u8]) -> Result<MerkleProof<F>, Error> {
+    let (flag, body) = bytes.split_first().ok_or(Error::Malformed("empty proof"))?;
+    match *flag {
+        FLAG_RAW => MerkleProof::from_bytes(body),
+        #[cfg(feature = "zstd")]
+        FLAG_ZSTD => {
+            let body = zstd::bulk::decompress(body, MAX_DECOMPRESSED)
+                .map_err(|_| Error::Malformed("invalid zstd data"))?;
+            MerkleProof::from_bytes(&body)
+        }
+        #[cfg(not(feature = "zstd"))]
+        FLAG_ZSTD => Err(Error::Malformed("zstd support is not enabled")),
+        _ => Err(Error::Malformed("unknown compression flag")),
+    }
+}
+
+impl<F: CanonicalField> MerkleProof<F> {
+    pub fn to_base64(&self) -> Result<String, Error> {
+        Ok(encode(
+            &framed(FLAG_RAW, self.to_bytes()?),
+            Alphabet::UrlSafe,
+        ))
+    }
+
+    // Compressed with zstd at `level` (1 to 22; 3 is zstd's default).
+    #[cfg(feature = "zstd")]
+    pub fn to_base64_compressed(&self, level: i32) -> Result<String, Error> {
+        let body = zstd::bulk::compress(&self.to_bytes()?, level)
+            .map_err(|_| Error::Malformed("zstd compression failed"))?;
+        Ok(encode(&framed(FLAG_ZSTD, body), Alphabet::UrlSafe))
+    }
+
+    pub fn from_base64(s: &str) -> Result<Self, Error> {
+        unframe(&decode(s, Alphabet::UrlSafe)?)
+    }
+
+    pub fn to_data_uri(&self) -> Result<String, Error> {
+        let bytes = framed(FLAG_RAW, self.to_bytes()?);
+        Ok(format!(
+            "{}{}",
+            DATA_URI_PREFIX,
+            encode(&bytes, Alphabet::Standard)
+        ))
+    }
+
+    pub fn from_data_uri(uri: &str) -> Result<Self, Error> {
+        let data = uri
+            .strip_prefix(DATA_URI_PREFIX)
+            .ok_or(Error::Malformed("not a merkle proof data URI"))?;
+        unframe(&decode(data, Alphabet::Standard)?)
+    }
+}
+
+mod tests {
+    use super::{decode, encode, Alphabet};
+    use crate::chips::merkle::DomainSeparation;
+    use crate::tree::MerkleTree;
+    use crate::wire::{HashId, MerkleProof, Payload};
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        // The test vectors of RFC 4648.
+        for (plain, standard) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ] {
+            assert_eq!(encode(plain.as_bytes(), Alphabet::Standard), standard);
+            assert_eq!(
+                decode(standard, Alphabet::Standard).unwrap(),
+                plain.as_bytes()
+            );
+            let url = standard.trim_end_matches('=');
+            assert_eq!(encode(plain.as_bytes(), Alphabet::UrlSafe), url);
+            assert_eq!(decode(url, Alphabet::UrlSafe).unwrap(), plain.as_bytes());
+        }
+        assert_eq!(encode(&[0xfb, 0xff], Alphabet::UrlSafe), "-_8");
+        for bad in ["Zg", "Zg=", "Zh==", "Z===", "Zm9v!"] {
+            assert!(decode(bad, Alphabet::Standard).is_err(), "{}", bad);
+        }
+        for bad in ["Zg==", "Zh", "Z", "Zm+v"] {
+            assert!(decode(bad, Alphabet::UrlSafe).is_err(), "{}", bad);
+        }
+
+        let separation = DomainSeparation::Legacy;
+        let leaves: Vec<Fp> = (0..4u64).map(Fp::from).collect();
+        let tree = MerkleTree::new(2, &leaves, separation).unwrap();
+        let proof = MerkleProof {
+            hash: HashId::Poseidon(separation),
+            arity: 2,
+            depth: 2,
+            root: tree.root(),
+            payload: Payload::Path(tree.path(1).unwrap()),
+        };
+        let text = proof.to_base64().unwrap();
+        assert!(text
+            .bytes()
+            .all(|c| c.is_ascii_alphanumeric() || c == b'-' || c == b'_'));
+        assert_eq!(MerkleProof::<Fp>::from_base64(&text).unwrap(), proof);
+        let uri = proof.to_data_uri().unwrap();
+        assert_eq!(MerkleProof::<Fp>::from_data_uri(&uri).unwrap(), proof);
+        assert!(MerkleProof::<Fp>::from_data_uri(&text).is_err());
+
+        #[cfg(feature = "zstd")]
+        {
+            let opaque = MerkleProof::<Fp> {
+                payload: Payload::Proof(vec![0; 1000]),
+                ..proof
+            };
+            let compressed = opaque.to_base64_compressed(3).unwrap();
+            assert!(compressed.len() < opaque.to_base64().unwrap().len() / 4);
+            assert_eq!(MerkleProof::<Fp>::from_base64(&compressed).unwrap(), opaque);
+        }
+    }
+}
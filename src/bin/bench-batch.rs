@@ -0,0 +1,109 @@
+/*
+Measures what sharing the Poseidon initial state saves on a batch of hashes: proves BatchHashCircuit
+over the same pairs with per-hash initialization and with `PoseidonChip::hash_batch`, then
+StreamingBatchCircuit, which generates the pairs during synthesis, and prints the rows, k, proving
+time and proof size of each:
+
+    cargo run --release --bin bench-batch -- --leaves 64
+
+`--leaves` is the number of leaves hashed in pairs, 64 by default.
+*/
+
+use halo2_merkle_tree::analysis::analyze;
+use halo2_merkle_tree::circuits::poseidon::{BatchHashCircuit, StreamingBatchCircuit};
+use halo2_merkle_tree::error::Error;
+use halo2_merkle_tree::estimate::{check_circuit_k, min_k};
+use halo2_merkle_tree::proving::{keygen, prove, seeded_rng, TEST_SEED};
+use halo2_proofs::{
+    circuit::Value,
+    pasta::{EqAffine, Fp},
+    plonk::Circuit,
+    poly::commitment::Params,
+};
+use std::process;
+use std::time::Instant;
+
+const USAGE: &str = "usage: bench-batch [--leaves 64]";
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<usize, String> {
+    let mut leaves = 64;
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--leaves" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("{} needs a value\n{}", flag, USAGE))?;
+                leaves = value
+                    .parse()
+                    .map_err(|_| format!("invalid value {:?} for {}", value, flag))?;
+            }
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    if leaves == 0 || leaves % 2 != 0 {
+        return Err(format!("--leaves must be even and positive\n{}", USAGE));
+    }
+    Ok(leaves)
+}
+
+fn pair(i: usize) -> [Fp; 2] {
+    [Fp::from(2 * i as u64), Fp::from(2 * i as u64 + 1)]
+}
+
+// `circuit` builds a fresh copy of the circuit, as proving consumes it.
+fn bench<C: Circuit<Fp>>(
+    leaves: usize,
+    init: &str,
+    circuit: impl Fn() -> C,
+    digests: Vec<Fp>,
+) -> Result<(), Error> {
+    let instances = vec![digests];
+    let rows = analyze(&circuit())?.rows;
+    let k = min_k(&circuit(), instances.clone())?;
+    let params: Params<EqAffine> = Params::new(k);
+    check_circuit_k(k, &circuit(), instances.clone(), init)?;
+    let pk = keygen(&params, &circuit())?;
+    let start = Instant::now();
+    let proof = prove(&params, &pk, circuit(), &instances, seeded_rng(TEST_SEED))?;
+    let prove_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+    println!(
+        "{},{},{},{},{:.3},{}",
+        leaves,
+        init,
+        rows,
+        k,
+        prove_ms,
+        proof.len()
+    );
+    Ok(())
+}
+
+fn run(leaves: usize) -> Result<(), Error> {
+    let streaming = || StreamingBatchCircuit::new(leaves / 2, pair);
+    let digests = streaming().digests();
+    for shared in [false, true] {
+        let circuit = || BatchHashCircuit {
+            pairs: (0..leaves / 2).map(|i| pair(i).map(Value::known)).collect(),
+            shared,
+        };
+        let init = if shared { "shared" } else { "per-hash" };
+        bench(leaves, init, circuit, digests.clone())?;
+    }
+    bench(leaves, "streaming", streaming, digests)
+}
+
+fn main() {
+    let leaves = match parse_args(std::env::args().skip(1)) {
+        Ok(leaves) => leaves,
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(2);
+        }
+    };
+    println!("leaves,init,rows,k,prove_ms,proof_bytes");
+    if let Err(err) = run(leaves) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
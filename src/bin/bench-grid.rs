@@ -0,0 +1,311 @@
+/*
+Benchmarks membership proofs over a grid of tree shapes and prints one machine-readable record per
+grid point:
+
+    cargo run --release --bin bench-grid -- --depths 8,16,20,32 --hashes poseidon,sha256 --arities 2,4
+
+Each record has the rows the circuit occupies, the smallest k it fits in, the proving and
+verification wall time in milliseconds and the proof size. Proofs use the seeded RNG, so proof
+bytes are comparable across runs. Combinations this crate does not implement (sha256, arities
+other than 2) are still reported, with the metrics left empty and the reason in `error`, so the
+output always covers the whole grid. `--format json` prints a JSON array instead of CSV, and
+`--planner v1` lays every circuit out with halo2's V1 floor planner instead of the simple one.
+`--k 12` proves every point at k = 12 instead of the smallest k; points that don't fit report
+"k=12 insufficient for depth 32 Poseidon; need k=…" before any keygen.
+*/
+
+use halo2_merkle_tree::analysis::analyze;
+use halo2_merkle_tree::builder::{Hasher, MerkleCircuit, MerkleCircuitBuilder};
+use halo2_merkle_tree::chips::merkle::DomainSeparation;
+use halo2_merkle_tree::error::Error;
+use halo2_merkle_tree::estimate::{check_k, min_k, CircuitParams};
+use halo2_merkle_tree::instance::PublicInputs;
+use halo2_merkle_tree::planner::Planner;
+use halo2_merkle_tree::proving::{keygen, prove, seeded_rng, verify, TEST_SEED};
+use halo2_merkle_tree::tree::{MerklePath, MerkleTree};
+use halo2_proofs::{
+    pasta::{EqAffine, Fp},
+    plonk::Circuit,
+    poly::commitment::Params,
+};
+use std::process;
+use std::time::Instant;
+
+const USAGE: &str = "usage: bench-grid [--depths 8,16,20,32] [--hashes poseidon,dummy] \
+                     [--arities 2] [--format csv|json] [--planner simple|v1] [--k 12]";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Csv,
+    Json,
+}
+
+struct Options {
+    depths: Vec<usize>,
+    hashes: Vec<String>,
+    arities: Vec<usize>,
+    format: Format,
+    planner: Planner,
+    // A fixed k for every point; None picks the smallest that fits.
+    k: Option<u32>,
+}
+
+struct Measurement {
+    rows: usize,
+    k: u32,
+    prove_ms: f64,
+    verify_ms: f64,
+    proof_bytes: usize,
+}
+
+struct Record {
+    depth: usize,
+    hash: String,
+    arity: usize,
+    result: Result<Measurement, String>,
+}
+
+fn parse_list<T: std::str::FromStr>(flag: &str, value: &str) -> Result<Vec<T>, String> {
+    value
+        .split(',')
+        .map(|item| {
+            item.trim()
+                .parse()
+                .map_err(|_| format!("invalid value {:?} for {}", item, flag))
+        })
+        .collect()
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        depths: vec![8, 16, 20, 32],
+        hashes: vec!["poseidon".to_string()],
+        arities: vec![2],
+        format: Format::Csv,
+        planner: Planner::Simple,
+        k: None,
+    };
+    while let Some(flag) = args.next() {
+        if flag == "--help" || flag == "-h" {
+            return Err(USAGE.to_string());
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("{} needs a value\n{}", flag, USAGE))?;
+        match flag.as_str() {
+            "--depths" => options.depths = parse_list(&flag, &value)?,
+            "--hashes" => options.hashes = parse_list(&flag, &value)?,
+            "--arities" => options.arities = parse_list(&flag, &value)?,
+            "--format" => {
+                options.format = match value.as_str() {
+                    "csv" => Format::Csv,
+                    "json" => Format::Json,
+                    _ => return Err(format!("unknown format {:?}\n{}", value, USAGE)),
+                }
+            }
+            "--planner" => {
+                options.planner = match value.as_str() {
+                    "simple" => Planner::Simple,
+                    "v1" => Planner::V1,
+                    _ => return Err(format!("unknown planner {:?}\n{}", value, USAGE)),
+                }
+            }
+            "--k" => {
+                options.k = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("invalid value {:?} for {}", value, flag))?,
+                )
+            }
+            _ => return Err(format!("unknown option {:?}\n{}", flag, USAGE)),
+        }
+    }
+    Ok(options)
+}
+
+fn measure<C: Circuit<Fp>>(
+    circuit: C,
+    instances: Vec<Vec<Fp>>,
+    k: Option<u32>,
+) -> Result<Measurement, Error> {
+    let rows = analyze(&circuit)?.rows;
+    let k = match k {
+        Some(k) => k,
+        None => min_k(&circuit, instances.clone())?,
+    };
+    let params: Params<EqAffine> = Params::new(k);
+    let pk = keygen(&params, &circuit)?;
+
+    let start = Instant::now();
+    let proof = prove(&params, &pk, circuit, &instances, seeded_rng(TEST_SEED))?;
+    let prove_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+    let start = Instant::now();
+    verify(&params, pk.get_vk(), &proof, &instances)?;
+    let verify_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+    Ok(Measurement {
+        rows,
+        k,
+        prove_ms,
+        verify_ms,
+        proof_bytes: proof.len(),
+    })
+}
+
+// Proves membership of leaf 1 in a tree of the given depth holding leaves 1..=4, at k if given.
+fn bench(
+    depth: usize,
+    hasher: Hasher,
+    planner: Planner,
+    k: Option<u32>,
+) -> Result<Measurement, Error> {
+    let separation = DomainSeparation::default();
+    if let Some(k) = k {
+        let mut params = CircuitParams::new(depth, hasher);
+        params.separation = separation;
+        params.planner = planner;
+        check_k(k, &params)?;
+    }
+    let leaves: Vec<Fp> = (1..=4u64).map(Fp::from).collect();
+    let (path, root) = match hasher {
+        Hasher::Poseidon => {
+            let tree = MerkleTree::new(depth, &leaves, separation)?;
+            (tree.path(1)?, tree.root())
+        }
+        // The placeholder hash is a + b, so with zero siblings the root is the leaf itself.
+        Hasher::Dummy => {
+            let zeros = vec![Fp::zero(); depth];
+            let path = MerklePath {
+                leaf: leaves[1],
+                elements: zeros.clone(),
+                indices: zeros,
+            };
+            (path, leaves[1])
+        }
+    };
+
+    let (circuit, _) = MerkleCircuitBuilder::new()
+        .depth(depth)
+        .hasher(hasher)
+        .separation(separation)
+        .planner(planner)
+        .path(&path)
+        .build()?;
+    let instances = circuit.instances(&PublicInputs::membership(path.leaf, root))?;
+    match circuit {
+        MerkleCircuit::Dummy(circuit) => measure(circuit, instances, k),
+        MerkleCircuit::Poseidon(circuit) => measure(circuit, instances, k),
+        MerkleCircuit::PackedDummy(circuit) => measure(circuit, instances, k),
+        MerkleCircuit::PackedPoseidon(circuit) => measure(circuit, instances, k),
+    }
+}
+
+fn run(depth: usize, hash: &str, arity: usize, options: &Options) -> Result<Measurement, String> {
+    if arity != 2 {
+        return Err(Error::UnsupportedArity(arity).to_string());
+    }
+    let hasher = match hash {
+        "poseidon" => Hasher::Poseidon,
+        "dummy" => Hasher::Dummy,
+        _ => return Err(format!("hash {} is not implemented", hash)),
+    };
+    bench(depth, hasher, options.planner, options.k).map_err(|err| err.to_string())
+}
+
+fn print_csv(records: &[Record]) {
+    println!("depth,hash,arity,rows,k,prove_ms,verify_ms,proof_bytes,error");
+    for record in records {
+        match &record.result {
+            Ok(m) => println!(
+                "{},{},{},{},{},{:.3},{:.3},{},",
+                record.depth,
+                record.hash,
+                record.arity,
+                m.rows,
+                m.k,
+                m.prove_ms,
+                m.verify_ms,
+                m.proof_bytes
+            ),
+            Err(err) => println!(
+                "{},{},{},,,,,,\"{}\"",
+                record.depth,
+                record.hash,
+                record.arity,
+                err.replace('"', "\"\"")
+            ),
+        }
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn print_json(records: &[Record]) {
+    let entries: Vec<String> = records
+        .iter()
+        .map(|record| {
+            let metrics = match &record.result {
+                Ok(m) => format!(
+                    "\"rows\":{},\"k\":{},\"prove_ms\":{:.3},\"verify_ms\":{:.3},\"proof_bytes\":{},\"error\":null",
+                    m.rows, m.k, m.prove_ms, m.verify_ms, m.proof_bytes
+                ),
+                Err(err) => format!(
+                    "\"rows\":null,\"k\":null,\"prove_ms\":null,\"verify_ms\":null,\"proof_bytes\":null,\"error\":{}",
+                    json_string(err)
+                ),
+            };
+            format!(
+                "  {{\"depth\":{},\"hash\":{},\"arity\":{},{}}}",
+                record.depth,
+                json_string(&record.hash),
+                record.arity,
+                metrics
+            )
+        })
+        .collect();
+    println!("[\n{}\n]", entries.join(",\n"));
+}
+
+fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(2);
+        }
+    };
+
+    let mut records = vec![];
+    for &depth in &options.depths {
+        for hash in &options.hashes {
+            for &arity in &options.arities {
+                eprintln!("depth {}, {}, arity {}", depth, hash, arity);
+                records.push(Record {
+                    depth,
+                    hash: hash.clone(),
+                    arity,
+                    result: run(depth, hash, arity, &options),
+                });
+            }
+        }
+    }
+
+    match options.format {
+        Format::Csv => print_csv(&records),
+        Format::Json => print_json(&records),
+    }
+}
@@ -0,0 +1,50 @@
+/*
+Reports the gate coverage recorded by a test run: for each gate, the number of MockProver runs that
+enabled it and that made it fail, flagging gates no negative test reaches:
+
+    COVERAGE_LOG=/tmp/coverage.log cargo test
+    cargo run --bin coverage-report -- /tmp/coverage.log
+
+Without an argument the log is read from COVERAGE_LOG. Only runs through `coverage::mock_verify`
+are recorded, and the log is appended to, so remove it before a fresh run.
+*/
+
+use halo2_merkle_tree::coverage::{Coverage, LOG_VAR};
+use std::process;
+
+const USAGE: &str = "usage: coverage-report [log]";
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<String, String> {
+    let path = match args.next() {
+        Some(path) => path,
+        None => std::env::var(LOG_VAR).map_err(|_| USAGE.to_string())?,
+    };
+    if args.next().is_some() {
+        return Err(USAGE.to_string());
+    }
+    Ok(path)
+}
+
+fn main() {
+    let path = match parse_args(std::env::args().skip(1)) {
+        Ok(path) => path,
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(2);
+        }
+    };
+    let log = match std::fs::read_to_string(&path) {
+        Ok(log) => log,
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            process::exit(1);
+        }
+    };
+    match Coverage::from_log(&log) {
+        Ok(coverage) => print!("{}", coverage.report()),
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,296 @@
+/*
+Bitcoin SPV inclusion, checked natively: a txid's double-SHA256 Merkle branch up to the header's
+merkle_root, and the 80-byte header's own double-SHA256 at or below the target its compact `bits`
+encode. Hashes are kept in Bitcoin's internal byte order (the reverse of how explorers print them).
+
+An index with bits above the branch is rejected, so one proof can't be replayed at other
+positions. A 64-byte transaction hashes exactly like an inner node, so a branch could stop one
+level early and present a node as a txid; `verify_transaction` takes the raw transaction, checks
+its txid and rejects 64-byte ones. `verify` checks a bare txid and can't tell the two apart.
+
+This module is native only, and the SPV circuit is not built yet. It needs a SHA-256 chip whose
+digest is an assigned cell, and halo2_gadgets' Table16 gadget only returns digest values, so the
+branch could not be constrained to the header. `SpvProof` is what such a circuit would take as its
+witness.
+*/
+
+use crate::error::Error;
+use sha2::{Digest, Sha256};
+
+pub const HEADER_LEN: usize = 80;
+
+pub fn sha256d(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(Sha256::digest(data)).into()
+}
+
+fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut data = [0u8; 64];
+    data[..32].copy_from_slice(left);
+    data[32..].copy_from_slice(right);
+    sha256d(&data)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockHeader {
+    pub version: i32,
+    pub prev_block: [u8; 32],
+    pub merkle_root: [u8; 32],
+    pub time: u32,
+    pub bits: u32,
+    pub nonce: u32,
+}
+
+impl BlockHeader {
+    pub fn from_bytes(bytes: &[u8; HEADER_LEN]) -> Self {
+        let word = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        Self {
+            version: word(0) as i32,
+            prev_block: bytes[4..36].try_into().unwrap(),
+            merkle_root: bytes[36..68].try_into().unwrap(),
+            time: word(68),
+            bits: word(72),
+            nonce: word(76),
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0u8; HEADER_LEN];
+        bytes[..4].copy_from_slice(&self.version.to_le_bytes());
+        bytes[4..36].copy_from_slice(&self.prev_block);
+        bytes[36..68].copy_from_slice(&self.merkle_root);
+        bytes[68..72].copy_from_slice(&self.time.to_le_bytes());
+        bytes[72..76].copy_from_slice(&self.bits.to_le_bytes());
+        bytes[76..].copy_from_slice(&self.nonce.to_le_bytes());
+        bytes
+    }
+
+    pub fn hash(&self) -> [u8; 32] {
+        sha256d(&self.to_bytes())
+    }
+
+    // The target `bits` encode, big-endian. Negative and overflowing encodings are rejected, as
+    // by Bitcoin Core.
+    pub fn target(&self) -> Result<[u8; 32], Error> {
+        let exponent = (self.bits >> 24) as usize;
+        let mantissa = self.bits & 0x007f_ffff;
+        if self.bits & 0x0080_0000 != 0 && mantissa != 0 {
+            return Err(Error::Malformed("header bits encode a negative target"));
+        }
+        let mut target = [0u8; 32];
+        for (i, byte) in mantissa.to_be_bytes()[1..].iter().enumerate() {
+            // Byte i of the mantissa has weight 256^(exponent - 1 - i).
+            match (32 + i).checked_sub(exponent) {
+                Some(at) if at < 32 => target[at] = *byte,
+                Some(_) => {}
+                None if *byte == 0 => {}
+                None => return Err(Error::Malformed("header bits overflow 256 bits")),
+            }
+        }
+        Ok(target)
+    }
+
+    pub fn meets_target(&self) -> Result<bool, Error> {
+        let mut hash = self.hash();
+        hash.reverse();
+        Ok(hash <= self.target()?)
+    }
+}
+
+// A transaction's inclusion proof: its txid, its position in the block and the sibling hashes
+// from the transaction layer up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpvProof {
+    pub txid: [u8; 32],
+    pub index: u32,
+    pub branch: Vec<[u8; 32]>,
+}
+
+impl SpvProof {
+    // The root the branch leads to. Index bits above the branch are ignored here and rejected
+    // by `verify`.
+    pub fn merkle_root(&self) -> [u8; 32] {
+        self.branch
+            .iter()
+            .enumerate()
+            .fold(self.txid, |node, (level, sibling)| {
+                if self.index.checked_shr(level as u32).unwrap_or(0) & 1 == 0 {
+                    hash_pair(&node, sibling)
+                } else {
+                    hash_pair(sibling, &node)
+                }
+            })
+    }
+
+    pub fn verify(&self, header: &BlockHeader) -> Result<(), Error> {
+        if self
+            .index
+            .checked_shr(self.branch.len() as u32)
+            .unwrap_or(0)
+            != 0
+        {
+            return Err(Error::Malformed("index has bits above the branch"));
+        }
+        if self.merkle_root() != header.merkle_root {
+            return Err(Error::RootMismatch);
+        }
+        if !header.meets_target()? {
+            return Err(Error::InsufficientWork);
+        }
+        Ok(())
+    }
+
+    // `verify` for the serialized transaction `tx`, which must hash to the txid and must not be
+    // 64 bytes long.
+    pub fn verify_transaction(&self, header: &BlockHeader, tx: &[u8]) -> Result<(), Error> {
+        if tx.len() == 64 {
+            return Err(Error::Malformed(
+                "64-byte transactions are ambiguous with inner nodes",
+            ));
+        }
+        if sha256d(tx) != self.txid {
+            return Err(Error::LeafMismatch);
+        }
+        self.verify(header)
+    }
+}
+
+// Bitcoin pairs an odd node with a copy of itself.
+fn next_level(level: &[[u8; 32]]) -> Vec<[u8; 32]> {
+    level
+        .chunks(2)
+        .map(|pair| hash_pair(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+        .collect()
+}
+
+pub fn merkle_root(txids: &[[u8; 32]]) -> Result<[u8; 32], Error> {
+    if txids.is_empty() {
+        return Err(Error::EmptyPath);
+    }
+    let mut level = txids.to_vec();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    Ok(level[0])
+}
+
+// The proof for the transaction at `index` among a block's txids.
+pub fn merkle_branch(txids: &[[u8; 32]], index: u32) -> Result<SpvProof, Error> {
+    let txid = *txids.get(index as usize).ok_or(Error::IndexOutOfRange {
+        index: index as usize,
+        leaves: txids.len(),
+    })?;
+    let mut branch = Vec::new();
+    let mut level = txids.to_vec();
+    let mut position = index as usize;
+    while level.len() > 1 {
+        branch.push(*level.get(position ^ 1).unwrap_or(&level[position]));
+        level = next_level(&level);
+        position >>= 1;
+    }
+    Ok(SpvProof {
+        txid,
+        index,
+        branch,
+    })
+}
+
+mod tests {
+    use super::{merkle_branch, merkle_root, sha256d, BlockHeader, SpvProof};
+    use crate::error::Error;
+
+    // Explorer (display) hex to internal byte order.
+    fn internal(hex: &str) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+        }
+        bytes.reverse();
+        bytes
+    }
+
+    #[test]
+    fn test() {
+        // Block 100000 and its four transactions.
+        let txids = [
+            "8c14f0db3df150123e6f3dbbf30f8b955a8249b62ac1d1ff16284aefa3d06d87",
+            "fff2525b8931402dd09222c50775608f75787bd2b87e56995a7bdd30f79702c4",
+            "6359f0868171b1d194cbee1af2f16ea598ae8fad666d9b012c8ed2b79a236ec4",
+            "e9a66845e05d5abc0ad04ec80f774a7e585c6e8db975962d069a522137b80c1d",
+        ]
+        .map(internal);
+        let header = BlockHeader {
+            version: 1,
+            prev_block: internal(
+                "000000000002d01c1fccc21636b607dfd930d31d01c3a62104612a1719011250",
+            ),
+            merkle_root: internal(
+                "f3e94742aca4b5ef85488dc37c06c3282295ffec960994b2c0d5ac2a25a95766",
+            ),
+            time: 1293623863,
+            bits: 0x1b04864c,
+            nonce: 274148111,
+        };
+        assert_eq!(
+            header.hash(),
+            internal("000000000003ba27aa200b1cecaad478d2b00432346c3f1f3986da1afd33e506")
+        );
+        assert_eq!(BlockHeader::from_bytes(&header.to_bytes()), header);
+        assert_eq!(merkle_root(&txids).unwrap(), header.merkle_root);
+        for index in 0..4 {
+            let proof = merkle_branch(&txids, index).unwrap();
+            assert_eq!(proof.branch.len(), 2);
+            assert!(proof.verify(&header).is_ok());
+        }
+        // An odd count duplicates the last node.
+        let proof = merkle_branch(&txids[..3], 2).unwrap();
+        assert_eq!(proof.branch[0], txids[2]);
+        assert_eq!(proof.merkle_root(), merkle_root(&txids[..3]).unwrap());
+
+        let mut wrong = merkle_branch(&txids, 1).unwrap();
+        wrong.index = 0;
+        assert!(wrong.verify(&header).is_err());
+        // The same branch can't be replayed with higher index bits set.
+        let mut replayed = merkle_branch(&txids, 1).unwrap();
+        replayed.index = 1 | 1 << 2;
+        assert_eq!(replayed.merkle_root(), header.merkle_root);
+        assert!(matches!(replayed.verify(&header), Err(Error::Malformed(_))));
+
+        // A 64-byte "transaction" that is really the two children of an inner node verifies as a
+        // bare txid one level up, but not as a transaction.
+        let mut inner = [0u8; 64];
+        inner[..32].copy_from_slice(&txids[0]);
+        inner[32..].copy_from_slice(&txids[1]);
+        let node = merkle_branch(&txids, 0).unwrap();
+        let forged = SpvProof {
+            txid: sha256d(&inner),
+            index: 0,
+            branch: node.branch[1..].to_vec(),
+        };
+        forged.verify(&header).unwrap();
+        assert!(matches!(
+            forged.verify_transaction(&header, &inner),
+            Err(Error::Malformed(_))
+        ));
+        let coinbase = [0x01u8; 60];
+        assert!(matches!(
+            node.verify_transaction(&header, &coinbase),
+            Err(Error::LeafMismatch)
+        ));
+        let easy = BlockHeader {
+            nonce: header.nonce + 1,
+            ..header
+        };
+        assert!(!easy.meets_target().unwrap());
+        assert!(merkle_branch(&txids, 4).is_err());
+        assert!(merkle_root(&[]).is_err());
+
+        // Compact targets: the genesis difficulty, a negative and an overflowing encoding.
+        let target = |bits: u32| BlockHeader { bits, ..header }.target();
+        let mut genesis = [0u8; 32];
+        genesis[4..6].copy_from_slice(&[0xff, 0xff]);
+        assert_eq!(target(0x1d00ffff).unwrap(), genesis);
+        assert!(target(0x04923456).is_err());
+        assert!(target(0x23000001).is_err());
+        assert_eq!(target(0x01003456).unwrap(), [0u8; 32]);
+    }
+}
@@ -0,0 +1,207 @@
+/*
+Borsh serialization of proofs, witnesses and tree snapshots. Field elements are written as their
+32-byte little-endian repr and hash ids as the wire format's byte, so the layouts are
+
+    MerklePath:  leaf: [u8; 32], elements: Vec<[u8; 32]>, indices: Vec<u8>
+    MerkleProof: version: u8, hash: u8, arity: u8, depth: u8, root: [u8; 32], payload: enum {
+                     Path(MerklePath), Proof(Vec<u8>) }
+    MerkleTree:  hash: u8, depth: u8, leaves: Vec<[u8; 32]>, root: [u8; 32]
+
+Deserialization applies the checks of the binary wire format, and a tree's root must match its
+leaves. Errors are reported as `io::ErrorKind::InvalidData` wrapping the crate's `Error`.
+*/
+
+use crate::chips::poseidon::PoseidonSpecFor;
+use crate::error::Error;
+use crate::serialization::{from_bytes_le_checked, to_bytes_le, CanonicalField};
+use crate::tree::{MerklePath, MerkleTree};
+use crate::wire::{index_bits, index_from_bit, HashId, MerkleProof, Payload, WIRE_VERSION};
+use ::borsh::{BorshDeserialize, BorshSerialize};
+use std::io::{self, Read, Write};
+
+fn invalid(err: Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+fn write_field<F: CanonicalField, W: Write>(value: &F, writer: &mut W) -> io::Result<()> {
+    to_bytes_le(value).serialize(writer)
+}
+
+fn read_field<F: CanonicalField, R: Read>(reader: &mut R) -> io::Result<F> {
+    from_bytes_le_checked(&<[u8; 32]>::deserialize_reader(reader)?).map_err(invalid)
+}
+
+fn write_fields<F: CanonicalField, W: Write>(values: &[F], writer: &mut W) -> io::Result<()> {
+    values
+        .iter()
+        .map(to_bytes_le)
+        .collect::<Vec<_>>()
+        .serialize(writer)
+}
+
+fn read_fields<F: CanonicalField, R: Read>(reader: &mut R) -> io::Result<Vec<F>> {
+    Vec::<[u8; 32]>::deserialize_reader(reader)?
+        .iter()
+        .map(|bytes| from_bytes_le_checked(bytes).map_err(invalid))
+        .collect()
+}
+
+impl<F: CanonicalField> BorshSerialize for MerklePath<F> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let bits = index_bits(self.elements.len() as u8, self).map_err(invalid)?;
+        write_field(&self.leaf, writer)?;
+        write_fields(&self.elements, writer)?;
+        bits.serialize(writer)
+    }
+}
+
+impl<F: CanonicalField> BorshDeserialize for MerklePath<F> {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let leaf = read_field(reader)?;
+        let elements = read_fields(reader)?;
+        let indices = Vec::<u8>::deserialize_reader(reader)?
+            .into_iter()
+            .enumerate()
+            .map(|(layer, bit)| index_from_bit(layer, bit as u64).map_err(invalid))
+            .collect::<io::Result<Vec<F>>>()?;
+        let depth =
+            u8::try_from(elements.len()).map_err(|_| invalid(Error::Malformed("path too long")))?;
+        // A bare path carries no arity; paths are binary.
+        Self::try_new(2, depth, leaf, elements, indices).map_err(invalid)
+    }
+}
+
+impl<F: CanonicalField> BorshSerialize for MerkleProof<F> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        [WIRE_VERSION, self.hash.to_byte(), self.arity, self.depth].serialize(writer)?;
+        write_field(&self.root, writer)?;
+        match &self.payload {
+            Payload::Path(path) => {
+                index_bits(self.depth, path).map_err(invalid)?;
+                0u8.serialize(writer)?;
+                path.serialize(writer)
+            }
+            Payload::Proof(proof) => {
+                1u8.serialize(writer)?;
+                proof.serialize(writer)
+            }
+        }
+    }
+}
+
+impl<F: CanonicalField> BorshDeserialize for MerkleProof<F> {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let [version, hash, arity, depth] = <[u8; 4]>::deserialize_reader(reader)?;
+        if version != WIRE_VERSION {
+            return Err(invalid(Error::UnsupportedVersion(version)));
+        }
+        let hash = HashId::from_byte(hash).map_err(invalid)?;
+        let root = read_field(reader)?;
+        let payload = match u8::deserialize_reader(reader)? {
+            0 => {
+                let MerklePath {
+                    leaf,
+                    elements,
+                    indices,
+                } = MerklePath::deserialize_reader(reader)?;
+                let path = MerklePath::try_new(arity, depth, leaf, elements, indices);
+                Payload::Path(path.map_err(invalid)?)
+            }
+            1 => Payload::Proof(Vec::<u8>::deserialize_reader(reader)?),
+            _ => return Err(invalid(Error::Malformed("unknown payload kind"))),
+        };
+        Ok(Self {
+            hash,
+            arity,
+            depth,
+            root,
+            payload,
+        })
+    }
+}
+
+impl<F: PoseidonSpecFor + CanonicalField> BorshSerialize for MerkleTree<F> {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let depth =
+            u8::try_from(self.depth()).map_err(|_| invalid(Error::Malformed("tree too deep")))?;
+        [HashId::Poseidon(self.separation()).to_byte(), depth].serialize(writer)?;
+        write_fields(self.leaves(), writer)?;
+        write_field(&self.root(), writer)
+    }
+}
+
+impl<F: PoseidonSpecFor + CanonicalField> BorshDeserialize for MerkleTree<F> {
+    fn deserialize_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let [hash, depth] = <[u8; 2]>::deserialize_reader(reader)?;
+        let separation = match HashId::from_byte(hash).map_err(invalid)? {
+            HashId::Poseidon(separation) => separation,
+            // The native tree only hashes with Poseidon.
+            HashId::Dummy => return Err(invalid(Error::Malformed("unsupported hash for a tree"))),
+        };
+        let leaves: Vec<F> = read_fields(reader)?;
+        let root: F = read_field(reader)?;
+        let tree = MerkleTree::new(depth as usize, &leaves, separation).map_err(invalid)?;
+        if tree.root() != root {
+            return Err(invalid(Error::Malformed("root does not match the leaves")));
+        }
+        Ok(tree)
+    }
+}
+
+mod tests {
+    use crate::chips::merkle::DomainSeparation;
+    use crate::tree::MerkleTree;
+    use crate::wire::{HashId, MerkleProof, Payload};
+    use ::borsh::{from_slice, to_vec};
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let separation = DomainSeparation::Level;
+        let leaves: Vec<Fp> = (0..5u64).map(Fp::from).collect();
+        let tree = MerkleTree::new(3, &leaves, separation).unwrap();
+
+        let bytes = to_vec(&tree).unwrap();
+        assert_eq!(bytes.len(), 2 + 4 + 5 * 32 + 32);
+        assert_eq!(
+            from_slice::<MerkleTree>(&bytes).unwrap().root(),
+            tree.root()
+        );
+        let mut tampered = bytes.clone();
+        tampered[6] ^= 1;
+        assert!(from_slice::<MerkleTree>(&tampered).is_err());
+
+        let path = tree.path(3).unwrap();
+        let proof = MerkleProof {
+            hash: HashId::Poseidon(separation),
+            arity: 2,
+            depth: 3,
+            root: tree.root(),
+            payload: Payload::Path(path.clone()),
+        };
+        let bytes = to_vec(&proof).unwrap();
+        assert_eq!(from_slice::<MerkleProof>(&bytes).unwrap(), proof);
+        assert_eq!(
+            from_slice::<crate::tree::MerklePath>(&to_vec(&path).unwrap()).unwrap(),
+            path
+        );
+
+        let opaque = MerkleProof::<Fp> {
+            payload: Payload::Proof(vec![9; 4]),
+            ..proof
+        };
+        let opaque_bytes = to_vec(&opaque).unwrap();
+        assert_eq!(from_slice::<MerkleProof>(&opaque_bytes).unwrap(), opaque);
+
+        // A wrong depth in the header, a non-boolean index and trailing bytes.
+        let mut corrupt = bytes.clone();
+        corrupt[3] = 4;
+        assert!(from_slice::<MerkleProof>(&corrupt).is_err());
+        let mut corrupt = bytes.clone();
+        *corrupt.last_mut().unwrap() = 2;
+        assert!(from_slice::<MerkleProof>(&corrupt).is_err());
+        let mut corrupt = bytes;
+        corrupt.push(0);
+        assert!(from_slice::<MerkleProof>(&corrupt).is_err());
+    }
+}
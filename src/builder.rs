@@ -0,0 +1,418 @@
+/*
+A fluent way to assemble the membership circuits without handling raw `Value` vectors or the
+instance row conventions by hand:
+
+    let (circuit, layout) = MerkleCircuitBuilder::new()
+        .depth(20)
+        .hasher(Hasher::Poseidon)
+        .expose_leaf(false)
+        .path(&tree.path(index)?)
+        .build()?;
+
+Leaving out the witness builds the same circuit shape with unknown values, as used for keygen.
+`.leaf_exposure(LeafExposure::Commitment)` with `.blinding(r)` exposes a commitment H(leaf, r) in
+place of the leaf, and `LeafExposure::Private` (what `.expose_leaf(false)` sets) exposes nothing
+about it.
+`.planner(Planner::V1)` lays the circuit out with halo2's V1 floor planner (see `planner`),
+returned as the `Packed` variants.
+*/
+
+use crate::chips::merkle_v3::{DomainSeparation, RootExposure};
+pub use crate::circuits::merkle::Hasher;
+use crate::circuits::{merkle_v2::MerkleTreeV2Circuit, merkle_v3::MerkleTreeV3Circuit};
+use crate::coverage;
+use crate::error::{validate_path, Error};
+use crate::estimate::{check_k, CircuitParams};
+use crate::instance::{
+    known, InstanceBuilder, InstanceLayout, LeafExposure, PublicInputs, PublicInstances,
+};
+use crate::planner::{Packed, Planner};
+#[cfg(not(feature = "zeroize"))]
+use crate::proving::prove;
+use crate::proving::{keygen, RngCore};
+use crate::tree::MerklePath;
+#[cfg(feature = "zeroize")]
+use crate::zeroize::prove_zeroizing as prove;
+use halo2_proofs::{
+    circuit::Value,
+    dev::VerifyFailure,
+    pasta::{EqAffine, Fp},
+    plonk::ProvingKey,
+    poly::commitment::Params,
+};
+
+pub enum MerkleCircuit {
+    Dummy(MerkleTreeV2Circuit<Fp>),
+    Poseidon(MerkleTreeV3Circuit),
+    PackedDummy(Packed<MerkleTreeV2Circuit<Fp>>),
+    PackedPoseidon(Packed<MerkleTreeV3Circuit>),
+}
+
+impl MerkleCircuit {
+    pub fn layout(&self) -> InstanceLayout {
+        match self {
+            MerkleCircuit::Dummy(circuit) => circuit.layout(),
+            MerkleCircuit::Poseidon(circuit) => circuit.layout(),
+            MerkleCircuit::PackedDummy(circuit) => circuit.0.layout(),
+            MerkleCircuit::PackedPoseidon(circuit) => circuit.0.layout(),
+        }
+    }
+
+    // One vector per instance column of the underlying circuit.
+    pub fn instances(&self, inputs: &PublicInputs<Fp>) -> Result<Vec<Vec<Fp>>, Error> {
+        let column = self.layout().column(inputs)?;
+        Ok(match self {
+            MerkleCircuit::Dummy(_) | MerkleCircuit::PackedDummy(_) => vec![column],
+            MerkleCircuit::Poseidon(_) | MerkleCircuit::PackedPoseidon(_) => vec![column, vec![]],
+        })
+    }
+
+    // The shape the circuit was built with, as `estimate` measures it.
+    pub fn circuit_params(&self) -> CircuitParams {
+        let (mut params, planner) = match self {
+            MerkleCircuit::Dummy(circuit) => (dummy_params(circuit), Planner::Simple),
+            MerkleCircuit::Poseidon(circuit) => (poseidon_params(circuit), Planner::Simple),
+            MerkleCircuit::PackedDummy(circuit) => (dummy_params(&circuit.0), Planner::V1),
+            MerkleCircuit::PackedPoseidon(circuit) => (poseidon_params(&circuit.0), Planner::V1),
+        };
+        params.planner = planner;
+        params
+    }
+
+    // Fails with InsufficientK when params' k is too small for the circuit, rather than
+    // partway through synthesis.
+    pub fn keygen(&self, params: &Params<EqAffine>) -> Result<ProvingKey<EqAffine>, Error> {
+        check_k(params.k(), &self.circuit_params())?;
+        match self {
+            MerkleCircuit::Dummy(circuit) => keygen(params, circuit),
+            MerkleCircuit::Poseidon(circuit) => keygen(params, circuit),
+            MerkleCircuit::PackedDummy(circuit) => keygen(params, circuit),
+            MerkleCircuit::PackedPoseidon(circuit) => keygen(params, circuit),
+        }
+    }
+
+    // MockProver's verdict on the underlying circuit; see `coverage::mock_verify`.
+    pub fn mock_verify(
+        &self,
+        k: u32,
+        instances: Vec<Vec<Fp>>,
+    ) -> Result<Result<(), Vec<VerifyFailure>>, Error> {
+        match self {
+            MerkleCircuit::Dummy(circuit) => coverage::mock_verify(k, circuit, instances),
+            MerkleCircuit::Poseidon(circuit) => coverage::mock_verify(k, circuit, instances),
+            MerkleCircuit::PackedDummy(circuit) => coverage::mock_verify(k, circuit, instances),
+            MerkleCircuit::PackedPoseidon(circuit) => coverage::mock_verify(k, circuit, instances),
+        }
+    }
+
+    // Under the `zeroize` feature the witness is wiped once the proof is made. Instances of the
+    // wrong shape are refused before proving.
+    pub fn prove(
+        self,
+        params: &Params<EqAffine>,
+        pk: &ProvingKey<EqAffine>,
+        instances: &[Vec<Fp>],
+        rng: impl RngCore,
+    ) -> Result<Vec<u8>, Error> {
+        InstanceBuilder::check(&self, instances)?;
+        match self {
+            MerkleCircuit::Dummy(circuit) => prove(params, pk, circuit, instances, rng),
+            MerkleCircuit::Poseidon(circuit) => prove(params, pk, circuit, instances, rng),
+            MerkleCircuit::PackedDummy(circuit) => prove(params, pk, circuit, instances, rng),
+            MerkleCircuit::PackedPoseidon(circuit) => prove(params, pk, circuit, instances, rng),
+        }
+    }
+}
+
+impl PublicInstances<Fp> for MerkleCircuit {
+    fn instance_layout(&self) -> InstanceLayout {
+        self.layout()
+    }
+
+    fn public_inputs(&self) -> PublicInputs<Fp> {
+        match self {
+            MerkleCircuit::Dummy(circuit) => circuit.public_inputs(),
+            MerkleCircuit::Poseidon(circuit) => circuit.public_inputs(),
+            MerkleCircuit::PackedDummy(circuit) => circuit.0.public_inputs(),
+            MerkleCircuit::PackedPoseidon(circuit) => circuit.0.public_inputs(),
+        }
+    }
+
+    fn instance_columns(&self) -> usize {
+        match self {
+            MerkleCircuit::Dummy(circuit) => circuit.instance_columns(),
+            MerkleCircuit::Poseidon(circuit) => circuit.instance_columns(),
+            MerkleCircuit::PackedDummy(circuit) => circuit.0.instance_columns(),
+            MerkleCircuit::PackedPoseidon(circuit) => circuit.0.instance_columns(),
+        }
+    }
+}
+
+fn dummy_params(circuit: &MerkleTreeV2Circuit<Fp>) -> CircuitParams {
+    let mut params = CircuitParams::new(circuit.elements.len(), Hasher::Dummy);
+    params.leaf_exposure = circuit.leaf_exposure;
+    params
+}
+
+fn poseidon_params(circuit: &MerkleTreeV3Circuit) -> CircuitParams {
+    let mut params = CircuitParams::new(circuit.elements.len(), Hasher::Poseidon);
+    params.separation = circuit.separation;
+    params.leaf_exposure = circuit.leaf_exposure;
+    params
+}
+
+#[derive(Debug, Clone)]
+pub struct MerkleCircuitBuilder {
+    depth: Option<usize>,
+    hasher: Hasher,
+    separation: DomainSeparation,
+    planner: Planner,
+    leaf_exposure: LeafExposure,
+    blinding: Value<Fp>,
+    leaf: Value<Fp>,
+    elements: Option<Vec<Value<Fp>>>,
+    indices: Option<Vec<Value<Fp>>>,
+}
+
+impl Default for MerkleCircuitBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MerkleCircuitBuilder {
+    pub fn new() -> Self {
+        Self {
+            depth: None,
+            hasher: Hasher::default(),
+            separation: DomainSeparation::default(),
+            planner: Planner::default(),
+            leaf_exposure: LeafExposure::Instance,
+            blinding: Value::unknown(),
+            leaf: Value::unknown(),
+            elements: None,
+            indices: None,
+        }
+    }
+
+    pub fn depth(mut self, depth: usize) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    pub fn hasher(mut self, hasher: Hasher) -> Self {
+        self.hasher = hasher;
+        self
+    }
+
+    pub fn separation(mut self, separation: DomainSeparation) -> Self {
+        self.separation = separation;
+        self
+    }
+
+    pub fn planner(mut self, planner: Planner) -> Self {
+        self.planner = planner;
+        self
+    }
+
+    pub fn expose_leaf(self, expose_leaf: bool) -> Self {
+        self.leaf_exposure(if expose_leaf {
+            LeafExposure::Instance
+        } else {
+            LeafExposure::Private
+        })
+    }
+
+    pub fn leaf_exposure(mut self, leaf_exposure: LeafExposure) -> Self {
+        self.leaf_exposure = leaf_exposure;
+        self
+    }
+
+    // The blinding factor of a committed leaf.
+    pub fn blinding(mut self, blinding: Fp) -> Self {
+        self.blinding = Value::known(blinding);
+        self
+    }
+
+    pub fn leaf(mut self, leaf: Fp) -> Self {
+        self.leaf = Value::known(leaf);
+        self
+    }
+
+    pub fn elements(mut self, elements: &[Fp]) -> Self {
+        self.elements = Some(elements.iter().map(|x| Value::known(*x)).collect());
+        self
+    }
+
+    pub fn indices(mut self, indices: &[Fp]) -> Self {
+        self.indices = Some(indices.iter().map(|x| Value::known(*x)).collect());
+        self
+    }
+
+    pub fn path(self, path: &MerklePath) -> Self {
+        self.leaf(path.leaf)
+            .elements(&path.elements)
+            .indices(&path.indices)
+    }
+
+    pub fn build(self) -> Result<(MerkleCircuit, InstanceLayout), Error> {
+        let depth = self.depth.ok_or(Error::MissingDepth)?;
+        let elements = self
+            .elements
+            .unwrap_or_else(|| vec![Value::unknown(); depth]);
+        let indices = self
+            .indices
+            .unwrap_or_else(|| vec![Value::unknown(); depth]);
+        validate_path(depth, &elements, &indices)?;
+        // A known leaf with an unknown blinding factor would only fail at proving time.
+        if self.leaf_exposure == LeafExposure::Commitment
+            && known(self.leaf).is_some()
+            && known(self.blinding).is_none()
+        {
+            return Err(Error::MissingInstance("commitment"));
+        }
+
+        let circuit = match self.hasher {
+            Hasher::Dummy => {
+                let circuit = MerkleTreeV2Circuit {
+                    leaf: self.leaf,
+                    elements,
+                    indices,
+                    leaf_exposure: self.leaf_exposure,
+                    blinding: self.blinding,
+                };
+                match self.planner {
+                    Planner::Simple => MerkleCircuit::Dummy(circuit),
+                    Planner::V1 => MerkleCircuit::PackedDummy(Packed(circuit)),
+                }
+            }
+            Hasher::Poseidon => {
+                let circuit = MerkleTreeV3Circuit {
+                    leaf: self.leaf,
+                    elements,
+                    indices,
+                    separation: self.separation,
+                    root: RootExposure::Instance,
+                    leaf_exposure: self.leaf_exposure,
+                    blinding: self.blinding,
+                    preimage: None,
+                };
+                match self.planner {
+                    Planner::Simple => MerkleCircuit::Poseidon(circuit),
+                    Planner::V1 => MerkleCircuit::PackedPoseidon(Packed(circuit)),
+                }
+            }
+        };
+        let layout = circuit.layout();
+        Ok((circuit, layout))
+    }
+}
+
+mod tests {
+    use super::{Hasher, MerkleCircuit, MerkleCircuitBuilder};
+    use crate::chips::merkle_v3::DomainSeparation;
+    use crate::error::Error;
+    use crate::instance::{InstanceBuilder, LeafExposure, PublicInputs};
+    use crate::proving::{seeded_rng, TEST_SEED};
+    use crate::tree::MerkleTree;
+    use halo2_proofs::{pasta::Fp, poly::commitment::Params};
+
+    #[test]
+    fn test() {
+        let leaves: Vec<Fp> = (0..12u64).map(Fp::from).collect();
+        let tree = MerkleTree::new(4, &leaves, DomainSeparation::Legacy).unwrap();
+        let path = tree.path(9).unwrap();
+
+        let (circuit, layout) = MerkleCircuitBuilder::new()
+            .depth(4)
+            .hasher(Hasher::Poseidon)
+            .expose_leaf(false)
+            .path(&path)
+            .build()
+            .unwrap();
+        assert_eq!(layout.leaf, None);
+        assert_eq!(layout.root, Some(0));
+
+        let instances = circuit
+            .instances(&PublicInputs::membership(path.leaf, tree.root()))
+            .unwrap();
+        assert_eq!(InstanceBuilder::for_circuit(&circuit).unwrap(), instances);
+        assert!(matches!(circuit, MerkleCircuit::Poseidon(_)));
+        assert_eq!(circuit.mock_verify(10, instances).unwrap(), Ok(()));
+
+        let leaf = Fp::from(7);
+        let elements = [Fp::from(1), Fp::from(2), Fp::from(3)];
+        let indices = [Fp::zero(), Fp::one(), Fp::zero()];
+        let (circuit, _) = MerkleCircuitBuilder::new()
+            .depth(3)
+            .hasher(Hasher::Dummy)
+            .leaf(leaf)
+            .elements(&elements)
+            .indices(&indices)
+            .build()
+            .unwrap();
+        let instances = circuit
+            .instances(&PublicInputs::membership(leaf, Fp::from(13)))
+            .unwrap();
+        assert_eq!(InstanceBuilder::for_circuit(&circuit).unwrap(), instances);
+        assert!(matches!(circuit, MerkleCircuit::Dummy(_)));
+        assert_eq!(circuit.mock_verify(10, instances.clone()).unwrap(), Ok(()));
+
+        // Instances of the wrong shape are refused before they reach halo2.
+        InstanceBuilder::check(&circuit, &instances).unwrap();
+        assert!(matches!(
+            InstanceBuilder::check(&circuit, &[instances[0].clone(), vec![]]),
+            Err(Error::InstanceColumns {
+                expected: 1,
+                found: 2
+            })
+        ));
+        assert!(matches!(
+            InstanceBuilder::check(&circuit, &[instances[0][1..].to_vec()]),
+            Err(Error::InstanceRows {
+                expected: 2,
+                found: 1
+            })
+        ));
+        let params = Params::new(10);
+        let pk = circuit.keygen(&params).unwrap();
+        assert!(matches!(
+            circuit.prove(&params, &pk, &[vec![]], seeded_rng(TEST_SEED)),
+            Err(Error::InstanceRows {
+                expected: 2,
+                found: 0
+            })
+        ));
+
+        // A committed leaf: the dummy hash makes the commitment leaf + blinding.
+        let committed = || {
+            MerkleCircuitBuilder::new()
+                .depth(3)
+                .hasher(Hasher::Dummy)
+                .leaf_exposure(LeafExposure::Commitment)
+                .leaf(leaf)
+                .elements(&elements)
+                .indices(&indices)
+        };
+        let (circuit, layout) = committed().blinding(Fp::from(5)).build().unwrap();
+        assert_eq!((layout.leaf, layout.commitment), (None, Some(0)));
+        let instances = circuit
+            .instances(&PublicInputs::committed(Fp::from(12), Fp::from(13)))
+            .unwrap();
+        assert_eq!(InstanceBuilder::for_circuit(&circuit).unwrap(), instances);
+        assert!(matches!(circuit, MerkleCircuit::Dummy(_)));
+        assert_eq!(circuit.mock_verify(10, instances).unwrap(), Ok(()));
+        assert!(matches!(
+            committed().build(),
+            Err(Error::MissingInstance("commitment"))
+        ));
+
+        assert!(MerkleCircuitBuilder::new().build().is_err());
+        assert!(MerkleCircuitBuilder::new()
+            .depth(2)
+            .elements(&elements)
+            .indices(&indices)
+            .build()
+            .is_err());
+    }
+}
@@ -0,0 +1,121 @@
+/*
+The byte and limb encodings snark-verifier's generated verifiers use. Its EVM verifiers take
+calldata that is every instance as a 32-byte big-endian word, column by column, followed by the
+raw proof bytes (`encode_calldata` in snark-verifier). Aggregation circuits additionally expose the
+KZG accumulator as instances, each base-field coordinate split into LIMBS little-endian limbs of
+LIMB_BITS bits.
+
+This crate proves over pasta with IPA and a Blake2b transcript, which snark-verifier's EVM loader
+(bn254 KZG with a Keccak transcript) cannot verify, so these helpers only become end to end once a
+KZG backend exists (see the README on bn254). They are generic over the field so that the same
+code serves that port.
+*/
+
+use crate::error::Error;
+use crate::serialization::{from_bytes_be_checked, to_bytes_be, to_bytes_le, CanonicalField};
+
+// snark-verifier's defaults for accumulator limbs.
+pub const LIMBS: usize = 4;
+pub const LIMB_BITS: usize = 68;
+
+pub fn encode_calldata<F: CanonicalField>(instances: &[Vec<F>], proof: &[u8]) -> Vec<u8> {
+    let mut calldata: Vec<u8> = instances.iter().flatten().flat_map(to_bytes_be).collect();
+    calldata.extend_from_slice(proof);
+    calldata
+}
+
+// Splits calldata back into instance columns of the given lengths and the proof.
+pub fn decode_calldata<F: CanonicalField>(
+    calldata: &[u8],
+    column_lengths: &[usize],
+) -> Result<(Vec<Vec<F>>, Vec<u8>), Error> {
+    let head_len = column_lengths
+        .iter()
+        .try_fold(0usize, |words, len| words.checked_add(*len))
+        .and_then(|words| words.checked_mul(32))
+        .ok_or(Error::Malformed("instance lengths overflow"))?;
+    if calldata.len() < head_len {
+        return Err(Error::Malformed("calldata shorter than its instances"));
+    }
+    let (head, proof) = calldata.split_at(head_len);
+    let mut chunks = head.chunks(32);
+    let instances = column_lengths
+        .iter()
+        .map(|len| {
+            (0..*len)
+                .map(|_| {
+                    let word = chunks.next().expect("calldata holds every instance");
+                    from_bytes_be_checked(word.try_into().expect("words are 32 bytes"))
+                })
+                .collect()
+        })
+        .collect::<Result<Vec<Vec<F>>, Error>>()?;
+    Ok((instances, proof.to_vec()))
+}
+
+// The `limbs` little-endian limbs of `bits` bits each of a field element. The limbs must cover the
+// whole value.
+pub fn decompose<F: CanonicalField>(value: &F, limbs: usize, bits: usize) -> Result<Vec<F>, Error> {
+    match limbs.checked_mul(bits) {
+        Some(total) if total >= F::NUM_BITS as usize => {}
+        _ => return Err(Error::Malformed("limbs do not cover the field")),
+    }
+    let bytes = to_bytes_le(value);
+    let bit = |i: usize| i < 256 && (bytes[i / 8] >> (i % 8)) & 1 == 1;
+    Ok((0..limbs)
+        .map(|limb| {
+            (0..bits).rev().fold(F::zero(), |acc, i| {
+                let acc = acc.double();
+                if bit(limb * bits + i) {
+                    acc + F::one()
+                } else {
+                    acc
+                }
+            })
+        })
+        .collect())
+}
+
+// The inverse of `decompose`: the sum of limb_i * 2^(bits * i).
+pub fn compose<F: CanonicalField>(limbs: &[F], bits: usize) -> F {
+    let shift = F::from(2).pow_vartime(&[bits as u64]);
+    limbs
+        .iter()
+        .rev()
+        .fold(F::zero(), |acc, limb| acc * shift + limb)
+}
+
+mod tests {
+    use super::{compose, decode_calldata, decompose, encode_calldata, LIMBS, LIMB_BITS};
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let instances = vec![vec![Fp::from(1), Fp::from(0x0203)], vec![-Fp::one()]];
+        let proof = vec![0xaa, 0xbb];
+        let calldata = encode_calldata(&instances, &proof);
+        assert_eq!(calldata.len(), 3 * 32 + 2);
+        assert_eq!(calldata[31], 1);
+        assert_eq!(calldata[62..64], [0x02, 0x03]);
+        assert_eq!(calldata[96..], [0xaa, 0xbb]);
+        assert_eq!(
+            decode_calldata::<Fp>(&calldata, &[2, 1]).unwrap(),
+            (instances, proof)
+        );
+        assert!(decode_calldata::<Fp>(&calldata, &[2, 2]).is_err());
+        assert!(decode_calldata::<Fp>(&calldata, &[usize::MAX / 32 + 1]).is_err());
+
+        let value = -Fp::from(12345);
+        let limbs = decompose(&value, LIMBS, LIMB_BITS).unwrap();
+        assert_eq!(limbs.len(), LIMBS);
+        assert_eq!(compose(&limbs, LIMB_BITS), value);
+        // 2^68 + 5 is 5 in the low limb and 1 in the next.
+        let value = Fp::from(1 << 34) * Fp::from(1 << 34) + Fp::from(5);
+        assert_eq!(
+            decompose(&value, LIMBS, LIMB_BITS).unwrap(),
+            vec![Fp::from(5), Fp::one(), Fp::zero(), Fp::zero()]
+        );
+        assert!(decompose(&value, LIMBS - 1, LIMB_BITS).is_err());
+        assert!(decompose(&value, usize::MAX, 2).is_err());
+    }
+}
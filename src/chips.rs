@@ -1,6 +1,70 @@
+pub mod byte_table;
+pub mod comparator;
+pub mod cond_hash;
+pub mod empty_subtree;
+pub mod forest;
+#[cfg(feature = "dummy-hash")]
 pub mod hash_1;
+#[cfg(feature = "dummy-hash")]
 pub mod hash_2;
+pub mod leaf_encoding;
+pub mod merkle;
+#[cfg(feature = "dummy-hash")]
 pub mod merkle_v1;
+#[cfg(feature = "dummy-hash")]
 pub mod merkle_v2;
+#[cfg(feature = "poseidon")]
 pub mod merkle_v3;
+#[cfg(feature = "poseidon")]
+pub mod nmt;
+#[cfg(feature = "poseidon")]
 pub mod poseidon;
+#[cfg(feature = "poseidon")]
+pub mod prf;
+pub mod product;
+#[cfg(feature = "poseidon")]
+pub mod secp256k1_leaf;
+pub mod sum;
+#[cfg(feature = "poseidon")]
+pub mod variable_depth;
+pub mod window;
+
+use halo2_proofs::{arithmetic::FieldExt, circuit::AssignedCell};
+
+// The root computed by a Merkle chip: the cell to expose or constrain, whose value `known` reads
+// for building instances without recomputing the path natively. `indices` and `elements` hold the
+// assigned path index bits and siblings, leaf layer first, for circuits that constrain the leaf
+// position or reuse the path.
+#[derive(Debug, Clone)]
+pub struct MerkleOutput<F: FieldExt> {
+    pub cell: AssignedCell<F, F>,
+    pub indices: Vec<AssignedCell<F, F>>,
+    pub elements: Vec<AssignedCell<F, F>>,
+}
+
+impl<F: FieldExt> MerkleOutput<F> {
+    pub fn new(cell: AssignedCell<F, F>) -> Self {
+        Self {
+            cell,
+            indices: vec![],
+            elements: vec![],
+        }
+    }
+
+    pub fn with_indices(mut self, indices: Vec<AssignedCell<F, F>>) -> Self {
+        self.indices = indices;
+        self
+    }
+
+    pub fn with_elements(mut self, elements: Vec<AssignedCell<F, F>>) -> Self {
+        self.elements = elements;
+        self
+    }
+
+    // The root, when witnesses are known (i.e. outside keygen).
+    pub fn known(&self) -> Option<F> {
+        let mut root = None;
+        self.cell.value().map(|value| root = Some(*value));
+        root
+    }
+}
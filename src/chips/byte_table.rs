@@ -0,0 +1,473 @@
+/*
+Lookup tables for byte-oriented hash chips, loaded once per circuit and shared by every gadget that
+needs them: a range table of 0..2^8 (or 0..2^16) and a nibble XOR table of (a, b, a ^ b) for
+a, b < 16. On top of them the chip decomposes words into bytes, range checks to any bit width,
+XORs bytes and rotates words, so SHA-256 or Keccak chips built on it add gates but no tables.
+
+Decompositions are a big-endian running sum, acc = radix * acc_prev + limb, with the radix and a
+per-row shift in fixed columns. Every limb is looked up twice, as limb and as limb * shift, which
+bounds it to the limb width (bytes in a 16-bit table have shift 2^8) and the top limb to whatever
+bits remain. A rotation left by r of a w-bit word witnesses word = hi * 2^(w - r) + lo and
+returns lo * 2^r + hi, with hi and lo range checked to r and w - r bits, which also bounds the
+word to w bits.
+
+LeafEncodingChip can reuse the range table of an 8-bit instance through `configure_with_table`.
+*/
+
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*, poly::Rotation};
+use std::marker::PhantomData;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TableWidth {
+    // 256 rows; fits next to the circuits in this crate at small k.
+    #[default]
+    Bits8,
+    // 65536 rows, so k >= 17, for half as many limbs per range check.
+    Bits16,
+}
+
+impl TableWidth {
+    pub fn bits(self) -> usize {
+        match self {
+            TableWidth::Bits8 => 8,
+            TableWidth::Bits16 => 16,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ByteTableConfig {
+    pub advice: [Column<Advice>; 3],
+    pub radix: Column<Fixed>,
+    pub shift: Column<Fixed>,
+    pub range: TableColumn,
+    pub xor: [TableColumn; 3],
+    pub width: TableWidth,
+    pub init_selector: Selector,
+    pub limb_selector: Selector,
+    pub xor_selector: Selector,
+    pub nibble_selector: Selector,
+    pub rotate_selector: Selector,
+    pub instance: Column<Instance>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ByteTableChip<F: FieldExt> {
+    config: ByteTableConfig,
+    _marker: PhantomData<F>,
+}
+
+fn lower_128<F: FieldExt>(cell: &AssignedCell<F, F>) -> Value<u128> {
+    cell.value().map(|value| value.get_lower_128())
+}
+
+impl<F: FieldExt> ByteTableChip<F> {
+    pub fn construct(config: ByteTableConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 3],
+        instance: Column<Instance>,
+        width: TableWidth,
+    ) -> ByteTableConfig {
+        let radix = meta.fixed_column();
+        let shift = meta.fixed_column();
+        let range = meta.lookup_table_column();
+        let xor = [(); 3].map(|_| meta.lookup_table_column());
+        let init_selector = meta.selector();
+        let limb_selector = meta.complex_selector();
+        let xor_selector = meta.selector();
+        let nibble_selector = meta.complex_selector();
+        let rotate_selector = meta.selector();
+        for column in advice.iter() {
+            meta.enable_equality(*column);
+        }
+        meta.enable_equality(instance);
+        let [col_a, col_b, col_c] = advice;
+
+        // Enforces that the running sum starts at zero.
+        meta.create_gate("limbs init", |meta| {
+            let s = meta.query_selector(init_selector);
+            let acc = meta.query_advice(col_b, Rotation::cur());
+            vec![s * acc]
+        });
+
+        // Enforces acc = radix * acc_prev + limb.
+        meta.create_gate("limb", |meta| {
+            let s = meta.query_selector(limb_selector);
+            let limb = meta.query_advice(col_a, Rotation::cur());
+            let acc = meta.query_advice(col_b, Rotation::cur());
+            let acc_prev = meta.query_advice(col_b, Rotation::prev());
+            let radix = meta.query_fixed(radix, Rotation::cur());
+            vec![s * (acc - (acc_prev * radix + limb))]
+        });
+
+        // Enforces that every limb, and the limb times its shift, is in the range table.
+        meta.lookup(|meta| {
+            let s = meta.query_selector(limb_selector);
+            let limb = meta.query_advice(col_a, Rotation::cur());
+            vec![(s * limb, range)]
+        });
+        meta.lookup(|meta| {
+            let s = meta.query_selector(limb_selector);
+            let limb = meta.query_advice(col_a, Rotation::cur());
+            let shift = meta.query_fixed(shift, Rotation::cur());
+            vec![(s * limb * shift, range)]
+        });
+
+        // Enforces that each of a, b and c is 16 * hi + lo, with the nibbles on the next two rows.
+        meta.create_gate("xor", |meta| {
+            let s = meta.query_selector(xor_selector);
+            let sixteen = Expression::Constant(F::from(16));
+            [col_a, col_b, col_c]
+                .iter()
+                .map(|column| {
+                    let byte = meta.query_advice(*column, Rotation::cur());
+                    let hi = meta.query_advice(*column, Rotation::next());
+                    let lo = meta.query_advice(*column, Rotation(2));
+                    s.clone() * (byte - (hi * sixteen.clone() + lo))
+                })
+                .collect::<Vec<_>>()
+        });
+
+        // Enforces that (a, b, c) are nibbles with c = a ^ b.
+        meta.lookup(|meta| {
+            let s = meta.query_selector(nibble_selector);
+            [col_a, col_b, col_c]
+                .iter()
+                .zip(xor.iter())
+                .map(|(column, table)| {
+                    (
+                        s.clone() * meta.query_advice(*column, Rotation::cur()),
+                        *table,
+                    )
+                })
+                .collect()
+        });
+
+        // Enforces word = hi * shift + lo and out = lo * shift_next + hi.
+        meta.create_gate("rotate", |meta| {
+            let s = meta.query_selector(rotate_selector);
+            let word = meta.query_advice(col_a, Rotation::cur());
+            let hi = meta.query_advice(col_b, Rotation::cur());
+            let lo = meta.query_advice(col_c, Rotation::cur());
+            let out = meta.query_advice(col_a, Rotation::next());
+            let lo_shift = meta.query_fixed(shift, Rotation::cur());
+            let hi_shift = meta.query_fixed(shift, Rotation::next());
+            vec![
+                s.clone() * (word - (hi.clone() * lo_shift + lo.clone())),
+                s * (out - (lo * hi_shift + hi)),
+            ]
+        });
+
+        ByteTableConfig {
+            advice,
+            radix,
+            shift,
+            range,
+            xor,
+            width,
+            init_selector,
+            limb_selector,
+            xor_selector,
+            nibble_selector,
+            rotate_selector,
+            instance,
+        }
+    }
+
+    pub fn load_table(&self, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        layouter.assign_table(
+            || "range table",
+            |mut table| {
+                for value in 0..1usize << self.config.width.bits() {
+                    table.assign_cell(
+                        || "value",
+                        self.config.range,
+                        value,
+                        || Value::known(F::from(value as u64)),
+                    )?;
+                }
+                Ok(())
+            },
+        )?;
+        layouter.assign_table(
+            || "nibble xor table",
+            |mut table| {
+                for row in 0..256usize {
+                    let (a, b) = (row >> 4, row & 15);
+                    for (column, value) in self.config.xor.iter().zip([a, b, a ^ b]) {
+                        table.assign_cell(
+                            || "nibble",
+                            *column,
+                            row,
+                            || Value::known(F::from(value as u64)),
+                        )?;
+                    }
+                }
+                Ok(())
+            },
+        )
+    }
+
+    pub fn load_private(
+        &self,
+        mut layouter: impl Layouter<F>,
+        value: Value<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        layouter.assign_region(
+            || "load private",
+            |mut region| {
+                region.assign_advice(|| "private input", self.config.advice[0], 0, || value)
+            },
+        )
+    }
+
+    pub fn expose_public(
+        &self,
+        mut layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
+        row: usize,
+    ) -> Result<(), Error> {
+        layouter.constrain_instance(cell.cell(), self.config.instance, row)
+    }
+
+    fn constrain_equal(
+        &self,
+        mut layouter: impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+        b: &AssignedCell<F, F>,
+    ) -> Result<(), Error> {
+        layouter.assign_region(
+            || "constrain equal",
+            |mut region| region.constrain_equal(a.cell(), b.cell()),
+        )
+    }
+
+    // Assigns `limbs` big-endian, each `limb_bits` wide except the first, which is `top_bits`
+    // wide, and returns their running sum and the limb cells.
+    fn running_sum(
+        &self,
+        mut layouter: impl Layouter<F>,
+        limbs: &[Value<F>],
+        limb_bits: usize,
+        top_bits: usize,
+    ) -> Result<(AssignedCell<F, F>, Vec<AssignedCell<F, F>>), Error> {
+        let table_bits = self.config.width.bits();
+        if limb_bits > table_bits || top_bits > limb_bits {
+            return Err(Error::Synthesis);
+        }
+        layouter.assign_region(
+            || "limbs",
+            |mut region| {
+                // Row 0: | - | 0 |
+                // Enabled Selectors: Init
+                let mut acc_value = Value::known(F::zero());
+                let mut acc =
+                    region.assign_advice(|| "acc", self.config.advice[1], 0, || acc_value)?;
+                self.config.init_selector.enable(&mut region, 0)?;
+
+                // Row i: | Limb | Acc |, with the radix and the limb's shift fixed
+                // Enabled Selectors: Limb
+                let radix = F::from_u128(1 << limb_bits);
+                let mut cells = Vec::with_capacity(limbs.len());
+                for (i, limb) in limbs.iter().enumerate() {
+                    let row = i + 1;
+                    let bits = if i == 0 { top_bits } else { limb_bits };
+                    let shift = F::from_u128(1 << (table_bits - bits));
+                    cells.push(region.assign_advice(
+                        || "limb",
+                        self.config.advice[0],
+                        row,
+                        || *limb,
+                    )?);
+                    region.assign_fixed(
+                        || "radix",
+                        self.config.radix,
+                        row,
+                        || Value::known(radix),
+                    )?;
+                    region.assign_fixed(
+                        || "shift",
+                        self.config.shift,
+                        row,
+                        || Value::known(shift),
+                    )?;
+                    acc_value = acc_value * Value::known(radix) + *limb;
+                    acc =
+                        region.assign_advice(|| "acc", self.config.advice[1], row, || acc_value)?;
+                    self.config.limb_selector.enable(&mut region, row)?;
+                }
+                Ok((acc, cells))
+            },
+        )
+    }
+
+    // Splits `cell` into `count` limbs of `limb_bits` each, the first only `top_bits` wide, and
+    // constrains their sum to it.
+    fn decompose(
+        &self,
+        mut layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
+        count: usize,
+        limb_bits: usize,
+        top_bits: usize,
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        let value = lower_128(cell);
+        let mask = (1u128 << limb_bits) - 1;
+        let limbs: Vec<Value<F>> = (0..count)
+            .map(|i| {
+                let at = (count - 1 - i) * limb_bits;
+                value.map(|v| F::from_u128((v >> at) & mask))
+            })
+            .collect();
+        let (acc, cells) =
+            self.running_sum(layouter.namespace(|| "limbs"), &limbs, limb_bits, top_bits)?;
+        self.constrain_equal(layouter.namespace(|| "sum"), &acc, cell)?;
+        Ok(cells)
+    }
+
+    // Constrains `cell` to be below 2^bits, for bits up to 128.
+    pub fn range_check(
+        &self,
+        layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
+        bits: usize,
+    ) -> Result<(), Error> {
+        if bits > 128 {
+            return Err(Error::Synthesis);
+        }
+        let limb_bits = self.config.width.bits();
+        let count = ((bits + limb_bits - 1) / limb_bits).max(1);
+        let top_bits = bits - (count - 1) * limb_bits;
+        self.decompose(layouter, cell, count, limb_bits, top_bits)?;
+        Ok(())
+    }
+
+    // The `bytes` big-endian bytes of `cell`, which is constrained below 2^(8 * bytes).
+    pub fn decompose_bytes(
+        &self,
+        layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
+        bytes: usize,
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        if bytes == 0 || bytes > 16 {
+            return Err(Error::Synthesis);
+        }
+        self.decompose(layouter, cell, bytes, 8, 8)
+    }
+
+    // The big-endian word of `bytes`, each of which is range checked.
+    pub fn compose_bytes(
+        &self,
+        mut layouter: impl Layouter<F>,
+        bytes: &[AssignedCell<F, F>],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let limbs: Vec<Value<F>> = bytes.iter().map(|byte| byte.value().copied()).collect();
+        let (acc, cells) = self.running_sum(layouter.namespace(|| "limbs"), &limbs, 8, 8)?;
+        for (i, (byte, cell)) in bytes.iter().zip(cells.iter()).enumerate() {
+            self.constrain_equal(layouter.namespace(|| format!("byte {}", i)), byte, cell)?;
+        }
+        Ok(acc)
+    }
+
+    // a ^ b for a byte each. The inputs are range checked by the nibble lookups.
+    pub fn xor_byte(
+        &self,
+        mut layouter: impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+        b: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let (a_value, b_value) = (lower_128(a), lower_128(b));
+        let c_value = a_value.zip(b_value).map(|(a, b)| a ^ b);
+        layouter.assign_region(
+            || "xor",
+            |mut region| {
+                let [col_a, col_b, col_c] = self.config.advice;
+                let field = |value: Value<u128>| value.map(F::from_u128);
+
+                // Row 0: | a | b | c |
+                // Enabled Selectors: Xor
+                a.copy_advice(|| "a", &mut region, col_a, 0)?;
+                b.copy_advice(|| "b", &mut region, col_b, 0)?;
+                let c = region.assign_advice(|| "c", col_c, 0, || field(c_value))?;
+                self.config.xor_selector.enable(&mut region, 0)?;
+
+                // Row 1: | a_hi | b_hi | c_hi |, Row 2: | a_lo | b_lo | c_lo |
+                // Enabled Selectors: Nibble
+                for (row, nibble) in [(1, 4), (2, 0)] {
+                    for (column, value) in [(col_a, a_value), (col_b, b_value), (col_c, c_value)] {
+                        let nibble = value.map(|v| (v >> nibble) & 15);
+                        region.assign_advice(|| "nibble", column, row, || field(nibble))?;
+                    }
+                    self.config.nibble_selector.enable(&mut region, row)?;
+                }
+                Ok(c)
+            },
+        )
+    }
+
+    pub fn xor_bytes(
+        &self,
+        mut layouter: impl Layouter<F>,
+        a: &[AssignedCell<F, F>],
+        b: &[AssignedCell<F, F>],
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        if a.len() != b.len() {
+            return Err(Error::Synthesis);
+        }
+        a.iter()
+            .zip(b.iter())
+            .enumerate()
+            .map(|(i, (a, b))| self.xor_byte(layouter.namespace(|| format!("byte {}", i)), a, b))
+            .collect()
+    }
+
+    // The `word_bits`-bit `word` rotated left by `rotation`; the word is range checked on the way.
+    pub fn rotate_left(
+        &self,
+        mut layouter: impl Layouter<F>,
+        word: &AssignedCell<F, F>,
+        word_bits: usize,
+        rotation: usize,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        if word_bits == 0 || word_bits > 64 || rotation >= word_bits {
+            return Err(Error::Synthesis);
+        }
+        let lo_bits = word_bits - rotation;
+        let value = lower_128(word);
+        let hi_value = value.map(|v| v >> lo_bits);
+        let lo_value = value.map(|v| v & ((1u128 << lo_bits) - 1));
+        let out_value = hi_value.zip(lo_value).map(|(hi, lo)| (lo << rotation) | hi);
+
+        let (out, hi, lo) = layouter.assign_region(
+            || "rotate",
+            |mut region| {
+                let [col_a, col_b, col_c] = self.config.advice;
+                let field = |value: Value<u128>| value.map(F::from_u128);
+
+                // Row 0: | word | hi | lo |, shift 2^(w - r)
+                // Row 1: | out  |    |    |, shift 2^r
+                // Enabled Selectors: Rotate
+                word.copy_advice(|| "word", &mut region, col_a, 0)?;
+                let hi = region.assign_advice(|| "hi", col_b, 0, || field(hi_value))?;
+                let lo = region.assign_advice(|| "lo", col_c, 0, || field(lo_value))?;
+                let out = region.assign_advice(|| "out", col_a, 1, || field(out_value))?;
+                for (row, bits) in [(0, lo_bits), (1, rotation)] {
+                    let shift = Value::known(F::from_u128(1 << bits));
+                    region.assign_fixed(|| "shift", self.config.shift, row, || shift)?;
+                }
+                self.config.rotate_selector.enable(&mut region, 0)?;
+                Ok((out, hi, lo))
+            },
+        )?;
+        self.range_check(layouter.namespace(|| "hi"), &hi, rotation)?;
+        self.range_check(layouter.namespace(|| "lo"), &lo, lo_bits)?;
+        Ok(out)
+    }
+}
@@ -0,0 +1,225 @@
+/*
+Strict ordering of field elements as integers, on top of ByteTableChip's range checks. A value is
+split into a 126-bit high limb and a 128-bit low limb, so it must be below 2^254; that bound is
+below the modulus of both Pasta fields, which makes the split unique and keeps a value from being
+ordered as its alias value + p. Uniform field elements such as Poseidon digests exceed it with
+probability about 2^-129.
+
+a < b is checked on the limbs with a bit c for "the high limbs differ":
+
+    | hi_a | hi_b | c    |
+    | lo_a | lo_b | diff |
+
+with c * (1 - c) = 0, (1 - c) * (hi_b - hi_a) = 0 and
+diff = c * (hi_b - hi_a - 1) + (1 - c) * (lo_b - lo_a - 1), where diff is range checked to 128
+bits. A negative difference wraps to within 2^128 of the modulus and fails the range check.
+*/
+
+use super::byte_table::{ByteTableChip, ByteTableConfig, TableWidth};
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*, poly::Rotation};
+use std::marker::PhantomData;
+
+pub const HI_BITS: usize = 126;
+pub const LO_BITS: usize = 128;
+
+#[derive(Debug, Clone)]
+pub struct ComparatorConfig {
+    pub table: ByteTableConfig,
+    pub split_selector: Selector,
+    pub less_than_selector: Selector,
+}
+
+// A value with its range checked limbs, value = hi * 2^128 + lo.
+#[derive(Debug, Clone)]
+pub struct Limbs<F: FieldExt> {
+    pub value: AssignedCell<F, F>,
+    pub hi: AssignedCell<F, F>,
+    pub lo: AssignedCell<F, F>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ComparatorChip<F: FieldExt> {
+    config: ComparatorConfig,
+    _marker: PhantomData<F>,
+}
+
+fn two_128<F: FieldExt>() -> F {
+    F::from_u128(u128::MAX) + F::one()
+}
+
+impl<F: FieldExt> ComparatorChip<F> {
+    pub fn construct(config: ComparatorConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 3],
+        instance: Column<Instance>,
+    ) -> ComparatorConfig {
+        let table = ByteTableChip::configure(meta, advice, instance, TableWidth::Bits8);
+        let split_selector = meta.selector();
+        let less_than_selector = meta.selector();
+        let [col_a, col_b, col_c] = advice;
+
+        // Enforces value = hi * 2^128 + lo.
+        meta.create_gate("split", |meta| {
+            let s = meta.query_selector(split_selector);
+            let value = meta.query_advice(col_a, Rotation::cur());
+            let hi = meta.query_advice(col_b, Rotation::cur());
+            let lo = meta.query_advice(col_c, Rotation::cur());
+            vec![s * (value - (hi * Expression::Constant(two_128()) + lo))]
+        });
+
+        // Enforces that c is boolean, that the high limbs are equal unless c is set, and that diff
+        // is the difference minus one of the high limbs if c is set and of the low limbs if not.
+        meta.create_gate("less than", |meta| {
+            let s = meta.query_selector(less_than_selector);
+            let hi_a = meta.query_advice(col_a, Rotation::cur());
+            let hi_b = meta.query_advice(col_b, Rotation::cur());
+            let c = meta.query_advice(col_c, Rotation::cur());
+            let lo_a = meta.query_advice(col_a, Rotation::next());
+            let lo_b = meta.query_advice(col_b, Rotation::next());
+            let diff = meta.query_advice(col_c, Rotation::next());
+            let one = Expression::Constant(F::one());
+            let hi_diff = hi_b - hi_a;
+            vec![
+                s.clone() * c.clone() * (one.clone() - c.clone()),
+                s.clone() * (one.clone() - c.clone()) * hi_diff.clone(),
+                s * (diff
+                    - c.clone() * (hi_diff - one.clone())
+                    - (one.clone() - c) * (lo_b - lo_a - one)),
+            ]
+        });
+
+        ComparatorConfig {
+            table,
+            split_selector,
+            less_than_selector,
+        }
+    }
+
+    fn table_chip(&self) -> ByteTableChip<F> {
+        ByteTableChip::construct(self.config.table.clone())
+    }
+
+    pub fn load_table(&self, layouter: impl Layouter<F>) -> Result<(), Error> {
+        self.table_chip().load_table(layouter)
+    }
+
+    pub fn load_private(
+        &self,
+        layouter: impl Layouter<F>,
+        value: Value<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.table_chip().load_private(layouter, value)
+    }
+
+    pub fn expose_public(
+        &self,
+        layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
+        row: usize,
+    ) -> Result<(), Error> {
+        self.table_chip().expose_public(layouter, cell, row)
+    }
+
+    // Splits `cell` into its limbs and range checks them, which also bounds it below 2^254.
+    pub fn split(
+        &self,
+        mut layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
+    ) -> Result<Limbs<F>, Error> {
+        let lo_value = cell.value().map(|value| value.get_lower_128());
+        let hi_value = cell.value().zip(lo_value).map(|(value, lo)| {
+            let shifted = (*value - F::from_u128(lo)) * two_128::<F>().invert().unwrap();
+            shifted.get_lower_128()
+        });
+        let [col_a, col_b, col_c] = self.config.table.advice;
+        let (hi, lo) = layouter.assign_region(
+            || "split",
+            |mut region| {
+                self.config.split_selector.enable(&mut region, 0)?;
+                cell.copy_advice(|| "value", &mut region, col_a, 0)?;
+                let hi = region.assign_advice(|| "hi", col_b, 0, || hi_value.map(F::from_u128))?;
+                let lo = region.assign_advice(|| "lo", col_c, 0, || lo_value.map(F::from_u128))?;
+                Ok((hi, lo))
+            },
+        )?;
+        let table_chip = self.table_chip();
+        table_chip.range_check(layouter.namespace(|| "hi"), &hi, HI_BITS)?;
+        table_chip.range_check(layouter.namespace(|| "lo"), &lo, LO_BITS)?;
+        Ok(Limbs {
+            value: cell.clone(),
+            hi,
+            lo,
+        })
+    }
+
+    // Constrains a < b as integers.
+    pub fn assert_less_than(
+        &self,
+        mut layouter: impl Layouter<F>,
+        a: &Limbs<F>,
+        b: &Limbs<F>,
+    ) -> Result<(), Error> {
+        let limb = |cell: &AssignedCell<F, F>| cell.value().map(|value| value.get_lower_128());
+        let hi_differ = limb(&a.hi).zip(limb(&b.hi)).map(|(a, b)| a != b);
+        let diff_value =
+            a.hi.value()
+                .zip(b.hi.value())
+                .zip(a.lo.value().zip(b.lo.value()))
+                .zip(hi_differ)
+                .map(|(((hi_a, hi_b), (lo_a, lo_b)), differ)| {
+                    if differ {
+                        *hi_b - *hi_a - F::one()
+                    } else {
+                        *lo_b - *lo_a - F::one()
+                    }
+                });
+        let [col_a, col_b, col_c] = self.config.table.advice;
+        let diff = layouter.assign_region(
+            || "less than",
+            |mut region| {
+                self.config.less_than_selector.enable(&mut region, 0)?;
+                a.hi.copy_advice(|| "hi a", &mut region, col_a, 0)?;
+                b.hi.copy_advice(|| "hi b", &mut region, col_b, 0)?;
+                region.assign_advice(
+                    || "high limbs differ",
+                    col_c,
+                    0,
+                    || hi_differ.map(|differ| F::from(differ as u64)),
+                )?;
+                a.lo.copy_advice(|| "lo a", &mut region, col_a, 1)?;
+                b.lo.copy_advice(|| "lo b", &mut region, col_b, 1)?;
+                region.assign_advice(|| "diff", col_c, 1, || diff_value)
+            },
+        )?;
+        self.table_chip()
+            .range_check(layouter.namespace(|| "diff"), &diff, LO_BITS)
+    }
+
+    // Constrains `cells` to be strictly increasing, splitting each value once.
+    pub fn assert_strictly_increasing(
+        &self,
+        mut layouter: impl Layouter<F>,
+        cells: &[AssignedCell<F, F>],
+    ) -> Result<Vec<Limbs<F>>, Error> {
+        let limbs = cells
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| self.split(layouter.namespace(|| format!("split {}", i)), cell))
+            .collect::<Result<Vec<_>, Error>>()?;
+        for (i, pair) in limbs.windows(2).enumerate() {
+            self.assert_less_than(
+                layouter.namespace(|| format!("order {}", i)),
+                &pair[0],
+                &pair[1],
+            )?;
+        }
+        Ok(limbs)
+    }
+}
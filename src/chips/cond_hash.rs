@@ -0,0 +1,129 @@
+/*
+Conditional hashing: out = H(left, right) when an enable bit is set and out = left when it is clear.
+It sits beside MerkleChip's swap gate and is generic over the hash in the same way, through
+`HashInstructions`. It is the step for paths whose length is only known at proving time, such as
+VariableDepthChip's padded layers or bagging a variable number of MMR peaks.
+
+The hash is always computed, and a select region then checks
+
+    | enable | left | hashed |
+    | out    |      |        |
+
+with enable * (1 - enable) = 0 and out = left + enable * (hashed - left), so both branches are
+constrained and a disabled step costs as much as an enabled one.
+
+The chip takes an already configured hash config rather than configuring its own, so it can share
+the hash columns with a Merkle chip in the same circuit.
+*/
+
+use super::merkle::HashInstructions;
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*, poly::Rotation};
+use std::marker::PhantomData;
+
+#[derive(Debug, Clone)]
+pub struct CondHashConfig<C> {
+    pub advice: [Column<Advice>; 3],
+    pub select_selector: Selector,
+    pub hash_config: C,
+}
+
+pub struct CondHashChip<F: FieldExt, H: HashInstructions<F>> {
+    config: CondHashConfig<H::Config>,
+    _marker: PhantomData<(F, H)>,
+}
+
+// Written out for the same reason as MerkleChip's: the Poseidon chip is not `Clone`.
+impl<F: FieldExt, H: HashInstructions<F>> Clone for CondHashChip<F, H> {
+    fn clone(&self) -> Self {
+        Self {
+            config: self.config.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<F: FieldExt, H: HashInstructions<F>> CondHashChip<F, H> {
+    pub fn construct(config: CondHashConfig<H::Config>) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 3],
+        hash_config: H::Config,
+    ) -> CondHashConfig<H::Config> {
+        let [col_a, col_b, col_c] = advice;
+        let select_selector = meta.selector();
+        for column in advice {
+            meta.enable_equality(column);
+        }
+
+        // Enforces that the enable bit is boolean and out = enable ? hashed : left.
+        meta.create_gate("select", |meta| {
+            let s = meta.query_selector(select_selector);
+            let enable = meta.query_advice(col_a, Rotation::cur());
+            let left = meta.query_advice(col_b, Rotation::cur());
+            let hashed = meta.query_advice(col_c, Rotation::cur());
+            let out = meta.query_advice(col_a, Rotation::next());
+            vec![
+                s.clone() * enable.clone() * (Expression::Constant(F::one()) - enable.clone()),
+                s * (out - left.clone() - enable * (hashed - left)),
+            ]
+        });
+
+        CondHashConfig {
+            advice,
+            select_selector,
+            hash_config,
+        }
+    }
+
+    // Returns `hashed` if `enable` is set and `left` if not, with the assigned enable bit.
+    pub fn select(
+        &self,
+        mut layouter: impl Layouter<F>,
+        enable: Value<F>,
+        left: &AssignedCell<F, F>,
+        hashed: &AssignedCell<F, F>,
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+        let [col_a, col_b, col_c] = self.config.advice;
+        layouter.assign_region(
+            || "select",
+            |mut region| {
+                self.config.select_selector.enable(&mut region, 0)?;
+                let enable_cell = region.assign_advice(|| "enable", col_a, 0, || enable)?;
+                left.copy_advice(|| "left", &mut region, col_b, 0)?;
+                hashed.copy_advice(|| "hashed", &mut region, col_c, 0)?;
+                let out = left
+                    .value()
+                    .zip(hashed.value())
+                    .zip(enable)
+                    .map(|((left, hashed), enable)| *left + enable * (*hashed - *left));
+                let out = region.assign_advice(|| "out", col_a, 1, || out)?;
+                Ok((out, enable_cell))
+            },
+        )
+    }
+
+    // H(left, right) if `enable` is set, `left` if not. Returns the output and the enable bit.
+    pub fn hash_or_pass(
+        &self,
+        mut layouter: impl Layouter<F>,
+        enable: Value<F>,
+        left: &AssignedCell<F, F>,
+        right: &AssignedCell<F, F>,
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+        let hash_chip = H::construct(self.config.hash_config.clone());
+        let shared = hash_chip.assign_shared(layouter.namespace(|| "shared"), false)?;
+        let hashed = hash_chip.hash_pair(
+            layouter.namespace(|| "hash"),
+            &shared,
+            left.clone(),
+            right.clone(),
+        )?;
+        self.select(layouter.namespace(|| "select"), enable, left, &hashed)
+    }
+}
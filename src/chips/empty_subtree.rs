@@ -0,0 +1,114 @@
+/*
+Checks sparse-tree siblings against the empty-subtree hashes. The hash of an all-zero subtree at
+each level is a fixed cell, part of the verifying key, so a sibling flagged empty is constrained to
+it:
+
+    | sibling | flag | empty[level] (fixed) |
+
+with flag boolean and flag * (sibling - empty[level]) = 0, one row per level. Circuits take each
+sibling as `Value<Option<F>>`, None where it is empty, so a caller's witness can leave empty
+siblings out; `resolve` fills in the empty hash for the path's sibling cell, and the flag is
+`is_none()`.
+
+The flag is the prover's choice, and nothing ties it to which subtrees are actually empty. A
+sibling left unflagged, empty or not, is unconstrained here and, like any other sibling, only held
+to the public root by the path hash, so a wrong default there fails the root rather than this
+chip. The chip therefore enforces nothing about defaults that the root doesn't already: it pins a
+flagged sibling to the fixed column, and adds a row per level to do so.
+*/
+
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*, poly::Rotation};
+
+#[derive(Debug, Clone)]
+pub struct EmptySubtreeConfig {
+    pub advice: [Column<Advice>; 2],
+    pub defaults: Column<Fixed>,
+    pub selector: Selector,
+}
+
+#[derive(Debug, Clone)]
+pub struct EmptySubtreeChip<F: FieldExt> {
+    config: EmptySubtreeConfig,
+    // empty[level] for levels 0..=depth, as `tree::empty_hashes` returns them.
+    empty: Vec<F>,
+}
+
+impl<F: FieldExt> EmptySubtreeChip<F> {
+    pub fn construct(config: EmptySubtreeConfig, empty: Vec<F>) -> Self {
+        Self { config, empty }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 2],
+    ) -> EmptySubtreeConfig {
+        let col_sibling = advice[0];
+        let col_flag = advice[1];
+        let defaults = meta.fixed_column();
+        let selector = meta.selector();
+        meta.enable_equality(col_sibling);
+        meta.enable_equality(col_flag);
+
+        // Enforces that the flag is boolean, and that a flagged sibling is the empty hash.
+        meta.create_gate("empty sibling", |meta| {
+            let s = meta.query_selector(selector);
+            let sibling = meta.query_advice(col_sibling, Rotation::cur());
+            let flag = meta.query_advice(col_flag, Rotation::cur());
+            let default = meta.query_fixed(defaults, Rotation::cur());
+            vec![
+                s.clone() * flag.clone() * (Expression::Constant(F::one()) - flag.clone()),
+                s * flag * (sibling - default),
+            ]
+        });
+
+        EmptySubtreeConfig {
+            advice: [col_sibling, col_flag],
+            defaults,
+            selector,
+        }
+    }
+
+    // The sibling cell's value at `level`: the empty hash where `sibling` is None.
+    pub fn resolve(&self, level: usize, sibling: Value<Option<F>>) -> Value<F> {
+        let default = self.empty.get(level).copied().unwrap_or_else(F::zero);
+        sibling.map(|sibling| sibling.unwrap_or(default))
+    }
+
+    // Constrains siblings[level] to empty[level] wherever empty[level] is set, and returns the
+    // assigned flags, leaf layer first.
+    pub fn constrain_siblings(
+        &self,
+        mut layouter: impl Layouter<F>,
+        siblings: &[AssignedCell<F, F>],
+        empty: &[Value<bool>],
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        if siblings.len() != empty.len() || siblings.len() >= self.empty.len() {
+            return Err(Error::Synthesis);
+        }
+        layouter.assign_region(
+            || "empty siblings",
+            |mut region| {
+                let mut flags = Vec::with_capacity(siblings.len());
+                // Row level: | sibling | flag | empty[level] |
+                for (level, (sibling, empty)) in siblings.iter().zip(empty.iter()).enumerate() {
+                    sibling.copy_advice(|| "sibling", &mut region, self.config.advice[0], level)?;
+                    let flag = empty.map(|empty| F::from(empty as u64));
+                    flags.push(region.assign_advice(
+                        || "flag",
+                        self.config.advice[1],
+                        level,
+                        || flag,
+                    )?);
+                    region.assign_fixed(
+                        || "empty hash",
+                        self.config.defaults,
+                        level,
+                        || Value::known(self.empty[level]),
+                    )?;
+                    self.config.selector.enable(&mut region, level)?;
+                }
+                Ok(flags)
+            },
+        )
+    }
+}
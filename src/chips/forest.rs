@@ -0,0 +1,158 @@
+/*
+Selects one of N public roots with a private one-hot vector, so a single Merkle path can be checked
+against whichever tree of a forest the leaf lives in. Each row absorbs one root:
+
+    | bit | root | acc | count |
+
+with bit boolean, acc += bit * root and count += bit. The final count is pinned to 1 (this needs a
+constants column, e.g. the Poseidon chip's), so acc ends up as exactly the selected root.
+*/
+
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*, poly::Rotation};
+use std::marker::PhantomData;
+
+#[derive(Debug, Clone)]
+pub struct ForestConfig {
+    pub advice: [Column<Advice>; 4],
+    pub instance: Column<Instance>,
+    pub init_selector: Selector,
+    pub select_selector: Selector,
+}
+
+// The cells produced by `ForestChip::select`: the chosen root and the one-hot bits.
+#[derive(Debug, Clone)]
+pub struct Selection<F: FieldExt> {
+    pub root: AssignedCell<F, F>,
+    pub bits: Vec<AssignedCell<F, F>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ForestChip<F: FieldExt> {
+    config: ForestConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: FieldExt> ForestChip<F> {
+    pub fn construct(config: ForestConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 4],
+        instance: Column<Instance>,
+    ) -> ForestConfig {
+        let [col_bit, col_root, col_acc, col_count] = advice;
+        let init_selector = meta.selector();
+        let select_selector = meta.selector();
+        for column in advice.iter() {
+            meta.enable_equality(*column);
+        }
+        meta.enable_equality(instance);
+
+        // Enforces that both running sums start at zero.
+        meta.create_gate("forest init", |meta| {
+            let s = meta.query_selector(init_selector);
+            let acc = meta.query_advice(col_acc, Rotation::cur());
+            let count = meta.query_advice(col_count, Rotation::cur());
+            vec![s.clone() * acc, s * count]
+        });
+
+        // Enforces bit in {0, 1}, acc = acc_prev + bit * root and count = count_prev + bit.
+        meta.create_gate("forest select", |meta| {
+            let s = meta.query_selector(select_selector);
+            let bit = meta.query_advice(col_bit, Rotation::cur());
+            let root = meta.query_advice(col_root, Rotation::cur());
+            let acc = meta.query_advice(col_acc, Rotation::cur());
+            let acc_prev = meta.query_advice(col_acc, Rotation::prev());
+            let count = meta.query_advice(col_count, Rotation::cur());
+            let count_prev = meta.query_advice(col_count, Rotation::prev());
+            vec![
+                s.clone() * bit.clone() * (Expression::Constant(F::one()) - bit.clone()),
+                s.clone() * (acc - acc_prev - bit.clone() * root),
+                s * (count - count_prev - bit),
+            ]
+        });
+
+        ForestConfig {
+            advice,
+            instance,
+            init_selector,
+            select_selector,
+        }
+    }
+
+    // Reads the roots from instance rows `first_row..first_row + bits.len()` and returns the one
+    // picked out by `bits`.
+    pub fn select(
+        &self,
+        mut layouter: impl Layouter<F>,
+        bits: &[Value<F>],
+        first_row: usize,
+    ) -> Result<Selection<F>, Error> {
+        let [col_bit, col_root, col_acc, col_count] = self.config.advice;
+        layouter.assign_region(
+            || "forest select",
+            |mut region| {
+                // Row 0: | - | - | 0 | 0 |
+                // Enabled Selectors: Init
+                let mut acc_value = Value::known(F::zero());
+                let mut count_value = Value::known(F::zero());
+                let mut acc = region.assign_advice(|| "acc", col_acc, 0, || acc_value)?;
+                let mut count = region.assign_advice(|| "count", col_count, 0, || count_value)?;
+                self.config.init_selector.enable(&mut region, 0)?;
+
+                // Row j: | Bit | Root | Acc | Count |
+                // Enabled Selectors: Select
+                let mut bit_cells = Vec::with_capacity(bits.len());
+                for (j, bit) in bits.iter().enumerate() {
+                    let row = j + 1;
+                    let bit_cell = region.assign_advice(|| "bit", col_bit, row, || *bit)?;
+                    let root = region.assign_advice_from_instance(
+                        || "root",
+                        self.config.instance,
+                        first_row + j,
+                        col_root,
+                        row,
+                    )?;
+                    acc_value = acc_value + *bit * root.value().map(|x| x.to_owned());
+                    count_value = count_value + *bit;
+                    acc = region.assign_advice(|| "acc", col_acc, row, || acc_value)?;
+                    count = region.assign_advice(|| "count", col_count, row, || count_value)?;
+                    self.config.select_selector.enable(&mut region, row)?;
+                    bit_cells.push(bit_cell);
+                }
+
+                region.constrain_constant(count.cell(), F::one())?;
+                Ok(Selection {
+                    root: acc,
+                    bits: bit_cells,
+                })
+            },
+        )
+    }
+
+    pub fn expose_public(
+        &self,
+        mut layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
+        row: usize,
+    ) -> Result<(), Error> {
+        layouter.constrain_instance(cell.cell(), self.config.instance, row)
+    }
+
+    pub fn constrain_equal(
+        &self,
+        mut layouter: impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+        b: &AssignedCell<F, F>,
+    ) -> Result<(), Error> {
+        layouter.assign_region(
+            || "constrain equal",
+            |mut region| region.constrain_equal(a.cell(), b.cell()),
+        )
+    }
+}
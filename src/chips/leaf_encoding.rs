@@ -0,0 +1,296 @@
+/*
+Packs up to 31 bytes into a single field element inside the circuit. Every byte is range checked
+against an 8-bit lookup table, so the packed value is below 2^248 and therefore below the modulus:
+two different byte strings of the same length can never map to the same leaf.
+
+A 32-byte external hash keeps all of its bits and is encoded canonically: `hash_to_leaf` reads it
+as a big-endian integer and refuses a digest at or above p, and `pack_hash` packs the same bytes
+in-circuit and checks that they are below p, by subtracting them from p - 1 byte by byte with a
+boolean borrow per byte, each difference range checked and the last borrow zero. A digest h and
+h + p therefore can't both stand for the same leaf. About three in four uniformly random digests
+are at or above the Pallas modulus; `ToLeaf` for [u8; 32] splits a digest into a 31-byte limb and
+its last byte instead, which accepts every digest.
+*/
+
+use crate::error;
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*, poly::Rotation};
+use std::marker::PhantomData;
+
+pub const PACKED_BYTES: usize = 31;
+
+pub const HASH_BYTES: usize = 32;
+
+pub(crate) fn pack_be<F: FieldExt>(bytes: &[u8]) -> F {
+    bytes.iter().fold(F::zero(), |acc, byte| {
+        acc * F::from(256) + F::from(*byte as u64)
+    })
+}
+
+// Big-endian packing, matching the order in which `LeafEncodingChip::pack` absorbs bytes.
+pub fn pack_bytes<F: FieldExt>(bytes: &[u8]) -> Result<F, error::Error> {
+    if bytes.len() > PACKED_BYTES {
+        return Err(error::Error::EncodingTooLong {
+            max: PACKED_BYTES,
+            len: bytes.len(),
+        });
+    }
+    Ok(pack_be(bytes))
+}
+
+// The big-endian digest as a field element, as `LeafEncodingChip::pack_hash` computes it. Digests
+// at or above p have no canonical encoding.
+pub fn hash_to_leaf<F: FieldExt>(hash: &[u8; HASH_BYTES]) -> Result<F, error::Error> {
+    let mut repr = F::Repr::default();
+    for (byte, digest) in repr.as_mut().iter_mut().zip(hash.iter().rev()) {
+        *byte = *digest;
+    }
+    Option::from(F::from_repr(repr)).ok_or(error::Error::NonCanonical)
+}
+
+// The big-endian bytes of p - 1, which a canonical digest must not exceed.
+fn max_be_bytes<F: FieldExt>() -> [u8; HASH_BYTES] {
+    let mut bytes = [0u8; HASH_BYTES];
+    for (byte, repr) in bytes
+        .iter_mut()
+        .zip((-F::one()).to_repr().as_ref().iter().rev())
+    {
+        *byte = *repr;
+    }
+    bytes
+}
+
+#[derive(Debug, Clone)]
+pub struct LeafEncodingConfig {
+    pub advice: [Column<Advice>; 2],
+    pub table: TableColumn,
+    pub init_selector: Selector,
+    pub pack_selector: Selector,
+    // The bytes of p - 1 and the rows of the canonical check in `pack_hash`.
+    pub modulus: Column<Fixed>,
+    pub canonical_selector: Selector,
+    pub instance: Column<Instance>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LeafEncodingChip<F: FieldExt> {
+    config: LeafEncodingConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: FieldExt> LeafEncodingChip<F> {
+    pub fn construct(config: LeafEncodingConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 2],
+        instance: Column<Instance>,
+    ) -> LeafEncodingConfig {
+        let table = meta.lookup_table_column();
+        Self::configure_with_table(meta, advice, instance, table)
+    }
+
+    // Checks bytes against a table loaded elsewhere, which must hold exactly 0..256: the range
+    // table of a `TableWidth::Bits8` ByteTableChip. Don't call `load_table` then.
+    pub fn configure_with_table(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 2],
+        instance: Column<Instance>,
+        table: TableColumn,
+    ) -> LeafEncodingConfig {
+        let col_byte = advice[0];
+        let col_acc = advice[1];
+        let init_selector = meta.selector();
+        let pack_selector = meta.complex_selector();
+        let modulus = meta.fixed_column();
+        let canonical_selector = meta.complex_selector();
+        meta.enable_equality(col_byte);
+        meta.enable_equality(col_acc);
+        meta.enable_equality(instance);
+
+        // Enforces that the running sum starts at zero.
+        meta.create_gate("init", |meta| {
+            let s = meta.query_selector(init_selector);
+            let acc = meta.query_advice(col_acc, Rotation::cur());
+            vec![s * acc]
+        });
+
+        // Enforces acc = 256 * acc_prev + byte.
+        meta.create_gate("pack", |meta| {
+            let s = meta.query_selector(pack_selector);
+            let byte = meta.query_advice(col_byte, Rotation::cur());
+            let acc = meta.query_advice(col_acc, Rotation::cur());
+            let acc_prev = meta.query_advice(col_acc, Rotation::prev());
+            vec![s * (acc - (acc_prev * Expression::Constant(F::from(256)) + byte))]
+        });
+
+        // Enforces that every packed byte is in [0, 256).
+        meta.lookup(|meta| {
+            let s = meta.query_selector(pack_selector);
+            let byte = meta.query_advice(col_byte, Rotation::cur());
+            vec![(s * byte, table)]
+        });
+
+        // In the canonical check the accumulator column holds the borrow out of each byte, least
+        // significant byte first, so the borrow in is the previous row's. Enforces that the borrow
+        // is boolean and that (p - 1)'s byte + 256 * borrow - byte - borrow in is in [0, 256).
+        meta.create_gate("canonical", |meta| {
+            let s = meta.query_selector(canonical_selector);
+            let borrow = meta.query_advice(col_acc, Rotation::cur());
+            vec![s * borrow.clone() * (Expression::Constant(F::one()) - borrow)]
+        });
+
+        meta.lookup(|meta| {
+            let s = meta.query_selector(canonical_selector);
+            let byte = meta.query_advice(col_byte, Rotation::cur());
+            let borrow = meta.query_advice(col_acc, Rotation::cur());
+            let borrow_in = meta.query_advice(col_acc, Rotation::prev());
+            let max = meta.query_fixed(modulus, Rotation::cur());
+            let difference = max + borrow * Expression::Constant(F::from(256)) - byte - borrow_in;
+            vec![(s * difference, table)]
+        });
+
+        LeafEncodingConfig {
+            advice: [col_byte, col_acc],
+            table,
+            init_selector,
+            pack_selector,
+            modulus,
+            canonical_selector,
+            instance,
+        }
+    }
+
+    pub fn load_table(&self, mut layouter: impl Layouter<F>) -> Result<(), Error> {
+        layouter.assign_table(
+            || "byte table",
+            |mut table| {
+                for byte in 0..256 {
+                    table.assign_cell(
+                        || "byte",
+                        self.config.table,
+                        byte,
+                        || Value::known(F::from(byte as u64)),
+                    )?;
+                }
+                Ok(())
+            },
+        )
+    }
+
+    pub fn expose_public(
+        &self,
+        mut layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
+        row: usize,
+    ) -> Result<(), Error> {
+        layouter.constrain_instance(cell.cell(), self.config.instance, row)
+    }
+
+    pub fn pack(
+        &self,
+        layouter: impl Layouter<F>,
+        bytes: &[Value<u8>],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        if bytes.len() > PACKED_BYTES {
+            return Err(Error::Synthesis);
+        }
+        let (acc, _) = self.pack_bytes(layouter, bytes)?;
+        Ok(acc)
+    }
+
+    // A 32-byte hash, matching `hash_to_leaf`. A digest at or above p fails the canonical check.
+    pub fn pack_hash(
+        &self,
+        mut layouter: impl Layouter<F>,
+        hash: &[Value<u8>; HASH_BYTES],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let (acc, bytes) = self.pack_bytes(layouter.namespace(|| "pack"), hash)?;
+        let max = max_be_bytes::<F>();
+        layouter.assign_region(
+            || "canonical",
+            |mut region| {
+                // Row 0: | - | 0 |, the borrow into the least significant byte.
+                // Enabled Selectors: Init
+                let mut borrow = Value::known(0u64);
+                region.assign_advice(
+                    || "borrow",
+                    self.config.advice[1],
+                    0,
+                    || borrow.map(F::from),
+                )?;
+                self.config.init_selector.enable(&mut region, 0)?;
+
+                // Row j: | Byte | Borrow |, byte 32 - j of the big-endian digest.
+                // Enabled Selectors: Canonical
+                for (row, i) in (0..HASH_BYTES).rev().enumerate().map(|(j, i)| (j + 1, i)) {
+                    bytes[i].copy_advice(|| "byte", &mut region, self.config.advice[0], row)?;
+                    region.assign_fixed(
+                        || "p - 1",
+                        self.config.modulus,
+                        row,
+                        || Value::known(F::from(max[i] as u64)),
+                    )?;
+                    borrow = borrow
+                        .zip(hash[i])
+                        .map(|(borrow, byte)| (byte as u64 + borrow > max[i] as u64) as u64);
+                    region.assign_advice(
+                        || "borrow",
+                        self.config.advice[1],
+                        row,
+                        || borrow.map(F::from),
+                    )?;
+                    self.config.canonical_selector.enable(&mut region, row)?;
+                }
+
+                // The last borrow must be zero, i.e. the digest is at most p - 1.
+                // Enabled Selectors: Init
+                self.config.init_selector.enable(&mut region, HASH_BYTES)
+            },
+        )?;
+        Ok(acc)
+    }
+
+    // The packed value and the assigned byte cells, in order.
+    #[allow(clippy::type_complexity)]
+    fn pack_bytes(
+        &self,
+        mut layouter: impl Layouter<F>,
+        bytes: &[Value<u8>],
+    ) -> Result<(AssignedCell<F, F>, Vec<AssignedCell<F, F>>), Error> {
+        layouter.assign_region(
+            || "pack bytes",
+            |mut region| {
+                // Row 0: | - | 0 |
+                // Enabled Selectors: Init
+                let mut acc_value = Value::known(F::zero());
+                let mut acc =
+                    region.assign_advice(|| "acc", self.config.advice[1], 0, || acc_value)?;
+                self.config.init_selector.enable(&mut region, 0)?;
+                let mut cells = Vec::with_capacity(bytes.len());
+
+                // Row i: | Byte | Acc |
+                // Enabled Selectors: Pack
+                for (i, byte) in bytes.iter().enumerate() {
+                    let row = i + 1;
+                    let byte_value = byte.map(|b| F::from(b as u64));
+                    cells.push(region.assign_advice(
+                        || "byte",
+                        self.config.advice[0],
+                        row,
+                        || byte_value,
+                    )?);
+                    acc_value = acc_value * Value::known(F::from(256)) + byte_value;
+                    acc =
+                        region.assign_advice(|| "acc", self.config.advice[1], row, || acc_value)?;
+                    self.config.pack_selector.enable(&mut region, row)?;
+                }
+                Ok((acc, cells))
+            },
+        )
+    }
+}
@@ -0,0 +1,436 @@
+/*
+A Merkle membership chip shared by every hash. The swap gate that orders each layer's children
+and checks its index bit is the same whatever the hash, so it lives here once and the hash is
+plugged in through `HashInstructions`: MerkleTreeV2Chip is `MerkleChip<F, Hash2Chip<F>>` and
+MerkleTreeV3Chip is `MerkleChip<F, PoseidonChip<..>>`. A new hash chip only needs to implement the
+trait.
+
+`merkle_prove` constructs the hash chip once and lets it assign whatever it can share between
+layers up front. For Poseidon that is the initial sponge state, so each untagged layer costs one
+permutation rather than also re-assigning the state and absorbing the children in regions of their
+own. The improvement is partial: tagged layers (`DomainSeparation::Level`) hash three words, more
+than one absorption, and still go through `Hash`, so each of them assigns its own initial state
+constants next to its level tag. Sharing that state would need the second absorption added into
+the permuted state, and halo2_gadgets only builds that input through crate-private sponge types.
+*/
+
+#[cfg(feature = "dummy-hash")]
+use super::hash_2::{Hash2Chip, Hash2Config};
+#[cfg(feature = "poseidon")]
+use super::poseidon::{InitialState, PoseidonChip, PoseidonConfig};
+use super::MerkleOutput;
+use crate::error;
+#[cfg(feature = "poseidon")]
+use halo2_gadgets::poseidon::primitives::Spec;
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*, poly::Rotation};
+use std::{fmt::Debug, marker::PhantomData};
+
+// A two-to-one hash gadget usable for the layers of a Merkle tree.
+pub trait HashInstructions<F: FieldExt>: Sized {
+    type Config: Debug + Clone;
+
+    fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 3],
+        instance: Column<Instance>,
+    ) -> Self::Config;
+
+    fn construct(config: Self::Config) -> Self;
+
+    // Cells the hash assigns once per path and reuses in every layer, `()` if it has none.
+    // `tagged` is set when the layers will go through `hash_tagged`.
+    type Shared: Clone;
+
+    fn assign_shared(
+        &self,
+        layouter: impl Layouter<F>,
+        tagged: bool,
+    ) -> Result<Self::Shared, Error>;
+
+    fn hash_pair(
+        &self,
+        layouter: impl Layouter<F>,
+        shared: &Self::Shared,
+        left: AssignedCell<F, F>,
+        right: AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error>;
+
+    // Hashes (tag, left, right) for `DomainSeparation::Level`. Hashes without a three-input form
+    // don't support domain separation.
+    fn hash_tagged(
+        &self,
+        _layouter: impl Layouter<F>,
+        _shared: &Self::Shared,
+        _tag: AssignedCell<F, F>,
+        _left: AssignedCell<F, F>,
+        _right: AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        Err(Error::Synthesis)
+    }
+}
+
+#[cfg(feature = "dummy-hash")]
+impl<F: FieldExt> HashInstructions<F> for Hash2Chip<F> {
+    type Config = Hash2Config;
+
+    fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 3],
+        instance: Column<Instance>,
+    ) -> Hash2Config {
+        Hash2Chip::configure(meta, advice, instance)
+    }
+
+    fn construct(config: Hash2Config) -> Self {
+        Hash2Chip::construct(config)
+    }
+
+    type Shared = ();
+
+    fn assign_shared(&self, _layouter: impl Layouter<F>, _tagged: bool) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn hash_pair(
+        &self,
+        layouter: impl Layouter<F>,
+        _shared: &(),
+        left: AssignedCell<F, F>,
+        right: AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.hash2(layouter, left, right)
+    }
+}
+
+// The Poseidon chip allocates its own columns, so the Merkle advice and instance are not shared.
+#[cfg(feature = "poseidon")]
+impl<F: FieldExt, S: Spec<F, 3, 2>> HashInstructions<F> for PoseidonChip<F, S, 3, 2, 2> {
+    type Config = PoseidonConfig<F, 3, 2, 2>;
+
+    fn configure(
+        meta: &mut ConstraintSystem<F>,
+        _advice: [Column<Advice>; 3],
+        _instance: Column<Instance>,
+    ) -> PoseidonConfig<F, 3, 2, 2> {
+        PoseidonChip::<F, S, 3, 2, 2>::configure(meta)
+    }
+
+    fn construct(config: PoseidonConfig<F, 3, 2, 2>) -> Self {
+        PoseidonChip::construct(config)
+    }
+
+    // The initial state for two-word messages; three-word tagged messages don't fit one
+    // absorption, so they have none.
+    type Shared = Option<InitialState<F, 3>>;
+
+    fn assign_shared(
+        &self,
+        layouter: impl Layouter<F>,
+        tagged: bool,
+    ) -> Result<Option<InitialState<F, 3>>, Error> {
+        if tagged {
+            return Ok(None);
+        }
+        self.initial_state::<2>(layouter).map(Some)
+    }
+
+    fn hash_pair(
+        &self,
+        layouter: impl Layouter<F>,
+        shared: &Option<InitialState<F, 3>>,
+        left: AssignedCell<F, F>,
+        right: AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        match shared {
+            Some(initial) => self.hash_from(layouter, initial, &[left, right]),
+            None => self.hash(layouter, &[left, right]),
+        }
+    }
+
+    fn hash_tagged(
+        &self,
+        layouter: impl Layouter<F>,
+        _shared: &Option<InitialState<F, 3>>,
+        tag: AssignedCell<F, F>,
+        left: AssignedCell<F, F>,
+        right: AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.hash_message::<3>(layouter, &[tag, left, right])
+    }
+}
+
+// Selects what each layer hashes. `Legacy` hashes (left, right) as before; `Level` hashes
+// (level, left, right), so a digest from one layer can't be passed off as a node of another,
+// in particular an internal node can't be presented as a leaf.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DomainSeparation {
+    #[default]
+    Legacy,
+    Level,
+}
+
+#[derive(Debug, Clone)]
+pub struct MerkleConfig<C> {
+    pub advice: [Column<Advice>; 3],
+    // Enables the swap gate, which also checks the index bit is boolean.
+    pub swap_selector: Selector,
+    pub instance: Column<Instance>,
+    pub hash_config: C,
+}
+
+pub struct MerkleChip<F: FieldExt, H: HashInstructions<F>> {
+    config: MerkleConfig<H::Config>,
+    separation: DomainSeparation,
+    _marker: PhantomData<(F, H)>,
+}
+
+// Written out because a derive would require `H: Clone`, which the Poseidon chip is not.
+impl<F: FieldExt, H: HashInstructions<F>> Clone for MerkleChip<F, H> {
+    fn clone(&self) -> Self {
+        Self {
+            config: self.config.clone(),
+            separation: self.separation,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<F: FieldExt, H: HashInstructions<F>> MerkleChip<F, H> {
+    pub fn construct(config: MerkleConfig<H::Config>) -> Self {
+        Self {
+            config,
+            separation: DomainSeparation::Legacy,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn with_separation(mut self, separation: DomainSeparation) -> Self {
+        self.separation = separation;
+        self
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 3],
+        instance: Column<Instance>,
+    ) -> MerkleConfig<H::Config> {
+        let col_a = advice[0];
+        let col_b = advice[1];
+        let col_c = advice[2];
+        let swap_selector = meta.selector();
+        meta.enable_equality(col_a);
+        meta.enable_equality(col_b);
+        meta.enable_equality(col_c);
+        meta.enable_equality(instance);
+
+        // Enforces that if the swap bit is on, l=b and r=a. Otherwise, l=a and r=b. Also enforces
+        // that the bit c is either a 0 or 1; both always apply to the same row, so they share a
+        // selector. The swap constraint comes first so failures keep their constraint index.
+        meta.create_gate("swap", |meta| {
+            let s = meta.query_selector(swap_selector);
+            let a = meta.query_advice(col_a, Rotation::cur());
+            let b = meta.query_advice(col_b, Rotation::cur());
+            let c = meta.query_advice(col_c, Rotation::cur());
+            let l = meta.query_advice(col_a, Rotation::next());
+            let r = meta.query_advice(col_b, Rotation::next());
+            vec![
+                s.clone()
+                    * (c.clone() * Expression::Constant(F::from(2)) * (b.clone() - a.clone())
+                        - (l - a.clone())
+                        - (b.clone() - r)),
+                s * c.clone() * (Expression::Constant(F::from(1)) - c),
+            ]
+        });
+
+        MerkleConfig {
+            advice: [col_a, col_b, col_c],
+            swap_selector,
+            instance,
+            hash_config: H::configure(meta, [col_a, col_b, col_c], instance),
+        }
+    }
+
+    pub fn load_private(
+        &self,
+        mut layouter: impl Layouter<F>,
+        input: Value<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        layouter.assign_region(
+            || "load private",
+            |mut region| {
+                region.assign_advice(|| "private input", self.config.advice[0], 0, || input)
+            },
+        )
+    }
+
+    // Needs a constants column, which the Poseidon chip enables.
+    pub fn load_constant(
+        &self,
+        mut layouter: impl Layouter<F>,
+        constant: F,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        layouter.assign_region(
+            || "load constant",
+            |mut region| {
+                region.assign_advice_from_constant(
+                    || "constant value",
+                    self.config.advice[0],
+                    0,
+                    constant,
+                )
+            },
+        )
+    }
+
+    pub fn expose_public(
+        &self,
+        mut layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
+        row: usize,
+    ) -> Result<(), Error> {
+        layouter.constrain_instance(cell.cell(), self.config.instance, row)
+    }
+
+    pub fn constrain_constant(
+        &self,
+        mut layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
+        constant: F,
+    ) -> Result<(), Error> {
+        layouter.assign_region(
+            || "constrain constant",
+            |mut region| {
+                let copy = cell.copy_advice(|| "copy", &mut region, self.config.advice[0], 0)?;
+                region.constrain_constant(copy.cell(), constant)
+            },
+        )
+    }
+
+    pub fn constrain_equal(
+        &self,
+        mut layouter: impl Layouter<F>,
+        a: &AssignedCell<F, F>,
+        b: &AssignedCell<F, F>,
+    ) -> Result<(), Error> {
+        layouter.assign_region(
+            || "constrain equal",
+            |mut region| region.constrain_equal(a.cell(), b.cell()),
+        )
+    }
+
+    // Proves a single layer. The hash's shared cells are assigned for this layer alone, so prefer
+    // `merkle_prove` for whole paths.
+    pub fn merkle_prove_layer(
+        &self,
+        mut layouter: impl Layouter<F>,
+        digest: &AssignedCell<F, F>,
+        element: Value<F>,
+        index: Value<F>,
+        level: usize,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let hash_chip = H::construct(self.config.hash_config.clone());
+        let shared = hash_chip.assign_shared(layouter.namespace(|| "shared"), self.tagged())?;
+        let (digest, _, _) =
+            self.prove_layer(layouter, &hash_chip, &shared, digest, element, index, level)?;
+        Ok(digest)
+    }
+
+    fn tagged(&self) -> bool {
+        self.separation == DomainSeparation::Level
+    }
+
+    // Returns the layer's digest and its assigned index bit and sibling.
+    #[allow(clippy::too_many_arguments)]
+    fn prove_layer(
+        &self,
+        mut layouter: impl Layouter<F>,
+        hash_chip: &H,
+        shared: &H::Shared,
+        digest: &AssignedCell<F, F>,
+        element: Value<F>,
+        index: Value<F>,
+        level: usize,
+    ) -> Result<(AssignedCell<F, F>, AssignedCell<F, F>, AssignedCell<F, F>), Error> {
+        let (left, right, tag, index_cell, element_cell) = layouter.assign_region(
+            || format!("layer {}", level),
+            |mut region| {
+                // Row 0
+                digest.copy_advice(|| "digest", &mut region, self.config.advice[0], 0)?;
+                let element_cell =
+                    region.assign_advice(|| "element", self.config.advice[1], 0, || element)?;
+                let index_cell =
+                    region.assign_advice(|| "index", self.config.advice[2], 0, || index)?;
+                self.config.swap_selector.enable(&mut region, 0)?;
+
+                // Row 1
+                let digest_value = digest.value().map(|x| x.to_owned());
+                let (mut l, mut r) = (digest_value, element);
+                index.map(|x| {
+                    (l, r) = if x == F::zero() { (l, r) } else { (r, l) };
+                });
+                let left = region.assign_advice(|| "left", self.config.advice[0], 1, || l)?;
+                let right = region.assign_advice(|| "right", self.config.advice[1], 1, || r)?;
+                let tag = match self.separation {
+                    DomainSeparation::Legacy => None,
+                    DomainSeparation::Level => Some(region.assign_advice_from_constant(
+                        || "level",
+                        self.config.advice[2],
+                        1,
+                        F::from(level as u64),
+                    )?),
+                };
+
+                Ok((left, right, tag, index_cell, element_cell))
+            },
+        )?;
+
+        let digest = match tag {
+            None => hash_chip.hash_pair(layouter.namespace(|| "hash"), shared, left, right)?,
+            Some(tag) => {
+                hash_chip.hash_tagged(layouter.namespace(|| "hash"), shared, tag, left, right)?
+            }
+        };
+        Ok((digest, index_cell, element_cell))
+    }
+
+    pub fn merkle_prove(
+        &self,
+        mut layouter: impl Layouter<F>,
+        leaf: &AssignedCell<F, F>,
+        elements: &[Value<F>],
+        indices: &[Value<F>],
+    ) -> Result<MerkleOutput<F>, error::Error> {
+        if elements.len() != indices.len() {
+            return Err(error::Error::LengthMismatch {
+                elements: elements.len(),
+                indices: indices.len(),
+            });
+        }
+        if elements.is_empty() {
+            return Err(error::Error::EmptyPath);
+        }
+
+        let hash_chip = H::construct(self.config.hash_config.clone());
+        let shared = hash_chip.assign_shared(layouter.namespace(|| "shared"), self.tagged())?;
+        let mut leaf_or_digest = leaf.clone();
+        let mut index_cells = Vec::with_capacity(indices.len());
+        let mut element_cells = Vec::with_capacity(elements.len());
+        for (i, (element, index)) in elements.iter().zip(indices.iter()).enumerate() {
+            let (digest, index_cell, element_cell) = self.prove_layer(
+                layouter.namespace(|| format!("merkle_prove_layer_{}", i)),
+                &hash_chip,
+                &shared,
+                &leaf_or_digest,
+                *element,
+                *index,
+                i,
+            )?;
+            leaf_or_digest = digest;
+            index_cells.push(index_cell);
+            element_cells.push(element_cell);
+        }
+        Ok(MerkleOutput::new(leaf_or_digest)
+            .with_indices(index_cells)
+            .with_elements(element_cells))
+    }
+}
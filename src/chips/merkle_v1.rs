@@ -4,12 +4,19 @@ use std::marker::PhantomData;
 #[derive(Debug, Clone)]
 pub struct MerkleTreeV1Config {
     pub advice: [Column<Advice>; 3],
-    pub bool_selector: Selector,
     pub swap_selector: Selector,
     pub hash_selector: Selector,
     pub instance: Column<Instance>,
 }
 
+// The value entering a layer: the leaf witness on the first layer, and the digest of the
+// previous layer on every layer after that.
+#[derive(Debug, Clone, Copy)]
+pub enum LayerInput<'a, F: FieldExt> {
+    Leaf(Value<F>),
+    Digest(&'a AssignedCell<F, F>),
+}
+
 #[derive(Debug, Clone)]
 pub struct MerkleTreeV1Chip<F: FieldExt> {
     config: MerkleTreeV1Config,
@@ -32,21 +39,14 @@ impl<F: FieldExt> MerkleTreeV1Chip<F> {
         let col_a = advice[0];
         let col_b = advice[1];
         let col_c = advice[2];
-        let bool_selector = meta.selector();
         let swap_selector = meta.selector();
         let hash_selector = meta.selector();
         meta.enable_equality(col_a);
         meta.enable_equality(col_c);
         meta.enable_equality(instance);
 
-        // Enforces that c is either a 0 or 1.
-        meta.create_gate("bool", |meta| {
-            let s = meta.query_selector(bool_selector);
-            let c = meta.query_advice(col_c, Rotation::cur());
-            vec![s * c.clone() * (Expression::Constant(F::from(1)) - c.clone())]
-        });
-
-        // Enforces that if the swap bit is on, l=b and r=a. Otherwise, l=a and r=b.
+        // Enforces that if the swap bit is on, l=b and r=a. Otherwise, l=a and r=b. The second
+        // constraint enforces that c is either a 0 or 1.
         meta.create_gate("swap", |meta| {
             let s = meta.query_selector(swap_selector);
             let a = meta.query_advice(col_a, Rotation::cur());
@@ -55,9 +55,11 @@ impl<F: FieldExt> MerkleTreeV1Chip<F> {
             let l = meta.query_advice(col_a, Rotation::next());
             let r = meta.query_advice(col_b, Rotation::next());
             vec![
-                s * (c * Expression::Constant(F::from(2)) * (b.clone() - a.clone())
-                    - (l - a.clone())
-                    - (b.clone() - r)),
+                s.clone()
+                    * (c.clone() * Expression::Constant(F::from(2)) * (b.clone() - a.clone())
+                        - (l - a.clone())
+                        - (b.clone() - r)),
+                s * c.clone() * (Expression::Constant(F::from(1)) - c),
             ]
         });
 
@@ -72,7 +74,6 @@ impl<F: FieldExt> MerkleTreeV1Chip<F> {
 
         MerkleTreeV1Config {
             advice: [col_a, col_b, col_c],
-            bool_selector,
             swap_selector,
             hash_selector,
             instance,
@@ -82,40 +83,37 @@ impl<F: FieldExt> MerkleTreeV1Chip<F> {
     pub fn assign(
         &self,
         mut layouter: impl Layouter<F>,
-        leaf: Value<F>,
+        input: LayerInput<'_, F>,
         path: Value<F>,
         bit: Value<F>,
-        prev_digest: Option<&AssignedCell<F, F>>,
         layer_idx: usize,
     ) -> Result<AssignedCell<F, F>, Error> {
         layouter.assign_region(
             || format!("layer {}", layer_idx),
             |mut region| {
                 // Row 0: | Leaf | Path | Bit |
-                // Enabled Selectors: Bool, Swap
-                if layer_idx == 0 {
-                    region.assign_advice(|| "leaf", self.config.advice[0], 0, || leaf)?;
-                } else {
-                    prev_digest.unwrap().copy_advice(
-                        || "leaf_cell",
-                        &mut region,
-                        self.config.advice[0],
-                        0,
-                    )?;
-                }
+                // Enabled Selectors: Swap
+                let new = match input {
+                    LayerInput::Leaf(leaf) => {
+                        region.assign_advice(|| "leaf", self.config.advice[0], 0, || leaf)?;
+                        leaf
+                    }
+                    LayerInput::Digest(prev_digest) => {
+                        prev_digest.copy_advice(
+                            || "leaf_cell",
+                            &mut region,
+                            self.config.advice[0],
+                            0,
+                        )?;
+                        prev_digest.value().map(|x| x.to_owned())
+                    }
+                };
                 region.assign_advice(|| "path", self.config.advice[1], 0, || path)?;
                 region.assign_advice(|| "bit", self.config.advice[2], 0, || bit)?;
-                self.config.bool_selector.enable(&mut region, 0)?;
                 self.config.swap_selector.enable(&mut region, 0)?;
 
                 // Row 1: | InputLeft | InputRight | Digest |
                 // Enabled Selectors: Hash
-                let new: Value<F>;
-                if layer_idx == 0 {
-                    new = leaf
-                } else {
-                    new = prev_digest.unwrap().value().map(|x| x.to_owned())
-                };
                 let mut input_l = new;
                 let mut input_r = path;
                 bit.map(|bit| {
@@ -0,0 +1,263 @@
+/*
+Verifies a namespaced Merkle tree path (see `nmt`) in-circuit. Each layer takes the current node
+(min, max, digest), a witnessed sibling and a path bit, and lays out:
+
+    | bit  | n_min | n_max | s_min | s_max |
+    | gap  | p_min | p_max | n_c   | s_c   |
+    | left | right | span  |       |       |
+
+with bit boolean, (left, right) the two commitments in path order, (p_min, p_max) the parent's
+range, gap = right.min - left.max and span = s_max - s_min. The sibling's namespaces, gap and
+span are range checked to 64 bits by packing their bytes with LeafEncodingChip, which makes both
+non-negative: the children are in namespace order and the sibling's range is well formed. The
+leaf's namespace is range checked the same way, so every min and max along the path is a u64.
+*/
+
+use super::leaf_encoding::{LeafEncodingChip, LeafEncodingConfig};
+use super::poseidon::{PoseidonChip, PoseidonConfig};
+use crate::nmt::NmtNode;
+use halo2_gadgets::poseidon::primitives::P128Pow5T3;
+use halo2_proofs::{circuit::*, pasta::Fp, plonk::*, poly::Rotation};
+
+#[derive(Debug, Clone)]
+pub struct NmtConfig {
+    pub advice: [Column<Advice>; 5],
+    pub instance: Column<Instance>,
+    pub layer_selector: Selector,
+    pub hash_config: PoseidonConfig<Fp, 3, 2, 2>,
+    pub encoding_config: LeafEncodingConfig,
+}
+
+// An assigned node, with its namespaces also kept as integers to witness the range checks.
+#[derive(Debug, Clone)]
+pub struct NmtNodeCells {
+    pub min: AssignedCell<Fp, Fp>,
+    pub max: AssignedCell<Fp, Fp>,
+    pub digest: AssignedCell<Fp, Fp>,
+    min_value: Value<u64>,
+    max_value: Value<u64>,
+}
+
+pub struct NmtChip {
+    config: NmtConfig,
+    hasher: PoseidonChip<Fp, P128Pow5T3, 3, 2, 2>,
+    encoding: LeafEncodingChip<Fp>,
+}
+
+impl NmtChip {
+    pub fn construct(config: NmtConfig) -> Self {
+        Self {
+            hasher: PoseidonChip::construct(config.hash_config.clone()),
+            encoding: LeafEncodingChip::construct(config.encoding_config.clone()),
+            config,
+        }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<Fp>,
+        advice: [Column<Advice>; 5],
+        instance: Column<Instance>,
+    ) -> NmtConfig {
+        let layer_selector = meta.selector();
+        for column in advice.iter() {
+            meta.enable_equality(*column);
+        }
+        meta.enable_equality(instance);
+
+        meta.create_gate("nmt layer", |meta| {
+            let s = meta.query_selector(layer_selector);
+            let cell = |meta: &mut VirtualCells<Fp>, column: usize, row: i32| {
+                meta.query_advice(advice[column], Rotation(row))
+            };
+            let bit = cell(meta, 0, 0);
+            let (n_min, n_max) = (cell(meta, 1, 0), cell(meta, 2, 0));
+            let (s_min, s_max) = (cell(meta, 3, 0), cell(meta, 4, 0));
+            let (gap, p_min, p_max) = (cell(meta, 0, 1), cell(meta, 1, 1), cell(meta, 2, 1));
+            let (n_c, s_c) = (cell(meta, 3, 1), cell(meta, 4, 1));
+            let (left, right, span) = (cell(meta, 0, 2), cell(meta, 1, 2), cell(meta, 2, 2));
+            let one = Expression::Constant(Fp::one());
+
+            // With bit = 0 the node is the left child, with bit = 1 the right one.
+            let node_left_gap = s_min.clone() - n_max.clone();
+            let node_right_gap = n_min.clone() - s_max.clone();
+            vec![
+                s.clone() * bit.clone() * (one - bit.clone()),
+                s.clone()
+                    * (gap
+                        - node_left_gap.clone()
+                        - bit.clone() * (node_right_gap - node_left_gap)),
+                s.clone() * (p_min - n_min.clone() - bit.clone() * (s_min.clone() - n_min)),
+                s.clone() * (p_max - s_max.clone() - bit.clone() * (n_max - s_max.clone())),
+                s.clone() * (left - n_c.clone() - bit.clone() * (s_c.clone() - n_c.clone())),
+                s.clone() * (right - s_c.clone() - bit * (n_c - s_c)),
+                s * (span - (s_max - s_min)),
+            ]
+        });
+
+        NmtConfig {
+            advice,
+            instance,
+            layer_selector,
+            hash_config: PoseidonChip::<Fp, P128Pow5T3, 3, 2, 2>::configure(meta),
+            encoding_config: LeafEncodingChip::configure(meta, [advice[0], advice[1]], instance),
+        }
+    }
+
+    pub fn load_table(&self, layouter: impl Layouter<Fp>) -> Result<(), Error> {
+        self.encoding.load_table(layouter)
+    }
+
+    pub fn expose_public(
+        &self,
+        mut layouter: impl Layouter<Fp>,
+        cell: &AssignedCell<Fp, Fp>,
+        row: usize,
+    ) -> Result<(), Error> {
+        layouter.constrain_instance(cell.cell(), self.config.instance, row)
+    }
+
+    // Assigns a u64 through its 8 big-endian bytes, which range checks it.
+    fn load_u64(
+        &self,
+        layouter: impl Layouter<Fp>,
+        value: Value<u64>,
+    ) -> Result<AssignedCell<Fp, Fp>, Error> {
+        let bytes: Vec<Value<u8>> = (0..8).map(|i| value.map(|v| v.to_be_bytes()[i])).collect();
+        self.encoding.pack(layouter, &bytes)
+    }
+
+    fn range_check(
+        &self,
+        mut layouter: impl Layouter<Fp>,
+        cell: &AssignedCell<Fp, Fp>,
+        value: Value<u64>,
+    ) -> Result<(), Error> {
+        let packed = self.load_u64(layouter.namespace(|| "pack"), value)?;
+        layouter.assign_region(
+            || "range check",
+            |mut region| region.constrain_equal(packed.cell(), cell.cell()),
+        )
+    }
+
+    pub fn leaf(
+        &self,
+        mut layouter: impl Layouter<Fp>,
+        namespace: Value<u64>,
+        data: Value<Fp>,
+    ) -> Result<NmtNodeCells, Error> {
+        let ns = self.load_u64(layouter.namespace(|| "namespace"), namespace)?;
+        let data = self
+            .hasher
+            .load_private(layouter.namespace(|| "data"), data)?;
+        let digest = self
+            .hasher
+            .hash_message::<1>(layouter.namespace(|| "leaf digest"), &[data])?;
+        Ok(NmtNodeCells {
+            min: ns.clone(),
+            max: ns,
+            digest,
+            min_value: namespace,
+            max_value: namespace,
+        })
+    }
+
+    fn commitment(
+        &self,
+        layouter: impl Layouter<Fp>,
+        node: &NmtNodeCells,
+    ) -> Result<AssignedCell<Fp, Fp>, Error> {
+        self.hasher.hash_message::<3>(
+            layouter,
+            &[node.min.clone(), node.max.clone(), node.digest.clone()],
+        )
+    }
+
+    // The parent of `node` and its sibling; `bit` is 1 when `node` is the right child.
+    pub fn parent(
+        &self,
+        mut layouter: impl Layouter<Fp>,
+        node: &NmtNodeCells,
+        sibling: Value<NmtNode>,
+        bit: Value<bool>,
+    ) -> Result<NmtNodeCells, Error> {
+        let s_min_value = sibling.map(|s| s.min);
+        let s_max_value = sibling.map(|s| s.max);
+        let sibling = NmtNodeCells {
+            min: self.load_u64(layouter.namespace(|| "sibling min"), s_min_value)?,
+            max: self.load_u64(layouter.namespace(|| "sibling max"), s_max_value)?,
+            digest: self.hasher.load_private(
+                layouter.namespace(|| "sibling digest"),
+                sibling.map(|s| s.digest),
+            )?,
+            min_value: s_min_value,
+            max_value: s_max_value,
+        };
+        let n_c = self.commitment(layouter.namespace(|| "node commitment"), node)?;
+        let s_c = self.commitment(layouter.namespace(|| "sibling commitment"), &sibling)?;
+
+        let field = |value: Value<u64>| value.map(Fp::from);
+        let gap_value = bit
+            .zip(node.min_value.zip(node.max_value))
+            .zip(s_min_value.zip(s_max_value))
+            .map(|((bit, (n_min, n_max)), (s_min, s_max))| {
+                if bit {
+                    n_min.wrapping_sub(s_max)
+                } else {
+                    s_min.wrapping_sub(n_max)
+                }
+            });
+        let span_value = s_max_value
+            .zip(s_min_value)
+            .map(|(max, min)| max.wrapping_sub(min));
+        let pick = |bit: Value<bool>, if_left: Value<u64>, if_right: Value<u64>| {
+            bit.zip(if_left.zip(if_right))
+                .map(|(bit, (l, r))| if bit { r } else { l })
+        };
+        let p_min_value = pick(bit, node.min_value, s_min_value);
+        let p_max_value = pick(bit, s_max_value, node.max_value);
+
+        let (gap, span, p_min, p_max, left, right) = layouter.assign_region(
+            || "nmt layer",
+            |mut region| {
+                let a = &self.config.advice;
+                self.config.layer_selector.enable(&mut region, 0)?;
+
+                // Row 0: | bit | n_min | n_max | s_min | s_max |
+                region.assign_advice(|| "bit", a[0], 0, || bit.map(|b| Fp::from(b as u64)))?;
+                node.min.copy_advice(|| "n_min", &mut region, a[1], 0)?;
+                node.max.copy_advice(|| "n_max", &mut region, a[2], 0)?;
+                sibling.min.copy_advice(|| "s_min", &mut region, a[3], 0)?;
+                sibling.max.copy_advice(|| "s_max", &mut region, a[4], 0)?;
+
+                // Row 1: | gap | p_min | p_max | n_c | s_c |
+                let gap = region.assign_advice(|| "gap", a[0], 1, || field(gap_value))?;
+                let p_min = region.assign_advice(|| "p_min", a[1], 1, || field(p_min_value))?;
+                let p_max = region.assign_advice(|| "p_max", a[2], 1, || field(p_max_value))?;
+                n_c.copy_advice(|| "n_c", &mut region, a[3], 1)?;
+                s_c.copy_advice(|| "s_c", &mut region, a[4], 1)?;
+
+                // Row 2: | left | right | span |
+                let (l, r) = (n_c.value().copied(), s_c.value().copied());
+                let left_value = bit.zip(l.zip(r)).map(|(b, (l, r))| if b { r } else { l });
+                let right_value = bit.zip(l.zip(r)).map(|(b, (l, r))| if b { l } else { r });
+                let left = region.assign_advice(|| "left", a[0], 2, || left_value)?;
+                let right = region.assign_advice(|| "right", a[1], 2, || right_value)?;
+                let span = region.assign_advice(|| "span", a[2], 2, || field(span_value))?;
+                Ok((gap, span, p_min, p_max, left, right))
+            },
+        )?;
+        self.range_check(layouter.namespace(|| "gap"), &gap, gap_value)?;
+        self.range_check(layouter.namespace(|| "span"), &span, span_value)?;
+
+        let digest = self
+            .hasher
+            .hash_message::<2>(layouter.namespace(|| "inner digest"), &[left, right])?;
+        Ok(NmtNodeCells {
+            min: p_min,
+            max: p_max,
+            digest,
+            min_value: p_min_value,
+            max_value: p_max_value,
+        })
+    }
+}
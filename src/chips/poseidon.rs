@@ -3,96 +3,134 @@ An easy-to-use implementation of the Poseidon Hash in the form of a Halo2 Chip.
 is already implemented in halo2_gadgets, there is no wrapper chip that makes it easy to use in other circuits.
 */
 
-use halo2_gadgets::poseidon::{primitives::*, Hash, Pow5Chip, Pow5Config};
-use halo2_proofs::{circuit::*, pasta::Fp, plonk::*};
+use halo2_gadgets::poseidon::{
+    primitives::*, Hash, PoseidonInstructions, Pow5Chip, Pow5Config, StateWord,
+};
+#[cfg(feature = "unstable-t5")]
+use halo2_proofs::arithmetic::Field;
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::*,
+    pasta::{Fp, Fq},
+    plonk::*,
+};
 use std::marker::PhantomData;
 
 #[derive(Debug, Clone)]
 
-pub struct PoseidonConfig<const WIDTH: usize, const RATE: usize, const L: usize> {
-    inputs: Vec<Column<Advice>>,
+pub struct PoseidonConfig<F: FieldExt, const WIDTH: usize, const RATE: usize, const L: usize> {
+    inputs: [Column<Advice>; WIDTH],
     instance: Column<Instance>,
-    pow5_config: Pow5Config<Fp, WIDTH, RATE>,
+    pow5_config: Pow5Config<F, WIDTH, RATE>,
+}
+
+// The initial sponge state for ConstantLength<M> messages (zero rate words, the capacity word
+// encoding M), assigned once and copied into every permutation that starts from it.
+#[derive(Debug, Clone)]
+pub struct InitialState<F: FieldExt, const WIDTH: usize> {
+    len: usize,
+    words: [AssignedCell<F, F>; WIDTH],
 }
 
 #[derive(Debug, Clone)]
 
 pub struct PoseidonChip<
-    S: Spec<Fp, WIDTH, RATE>,
+    F: FieldExt,
+    S: Spec<F, WIDTH, RATE>,
     const WIDTH: usize,
     const RATE: usize,
     const L: usize,
 > {
-    config: PoseidonConfig<WIDTH, RATE, L>,
+    config: PoseidonConfig<F, WIDTH, RATE, L>,
     _marker: PhantomData<S>,
 }
 
-impl<S: Spec<Fp, WIDTH, RATE>, const WIDTH: usize, const RATE: usize, const L: usize>
-    PoseidonChip<S, WIDTH, RATE, L>
+impl<
+        F: FieldExt,
+        S: Spec<F, WIDTH, RATE>,
+        const WIDTH: usize,
+        const RATE: usize,
+        const L: usize,
+    > PoseidonChip<F, S, WIDTH, RATE, L>
 {
-    pub fn construct(config: PoseidonConfig<WIDTH, RATE, L>) -> Self {
+    pub fn construct(config: PoseidonConfig<F, WIDTH, RATE, L>) -> Self {
         Self {
             config,
             _marker: PhantomData,
         }
     }
 
-    pub fn configure(meta: &mut ConstraintSystem<Fp>) -> PoseidonConfig<WIDTH, RATE, L> {
-        let state = (0..WIDTH).map(|_| meta.advice_column()).collect::<Vec<_>>();
+    // Allocates an instance column of its own for `expose_public`. Circuits that also configure a
+    // Merkle or encoding chip expose through that chip's column instead, which they create first,
+    // so this one comes second and is left empty: their instances end with an empty vector.
+    pub fn configure(meta: &mut ConstraintSystem<F>) -> PoseidonConfig<F, WIDTH, RATE, L> {
+        let state = [(); WIDTH].map(|_| meta.advice_column());
         let partial_sbox = meta.advice_column();
-        let rc_a = (0..WIDTH).map(|_| meta.fixed_column()).collect::<Vec<_>>();
-        let rc_b = (0..WIDTH).map(|_| meta.fixed_column()).collect::<Vec<_>>();
+        let rc_a = [(); WIDTH].map(|_| meta.fixed_column());
+        let rc_b = [(); WIDTH].map(|_| meta.fixed_column());
         let instance = meta.instance_column();
-        for i in 0..WIDTH {
-            meta.enable_equality(state[i]);
+        for column in state.iter() {
+            meta.enable_equality(*column);
         }
         meta.enable_equality(instance);
         meta.enable_constant(rc_b[0]);
 
-        let pow5_config = Pow5Chip::configure::<S>(
-            meta,
-            state.clone().try_into().unwrap(),
-            partial_sbox.try_into().unwrap(),
-            rc_a.try_into().unwrap(),
-            rc_b.try_into().unwrap(),
-        );
+        let pow5_config = Pow5Chip::configure::<S>(meta, state, partial_sbox, rc_a, rc_b);
 
         PoseidonConfig {
-            inputs: state.clone().try_into().unwrap(),
+            inputs: state,
             instance,
             pow5_config: pow5_config,
         }
     }
 
+    // Messages longer than the state width have no column to be loaded into.
+    fn input_column(&self, i: usize) -> Result<Column<Advice>, Error> {
+        self.config.inputs.get(i).copied().ok_or(Error::Synthesis)
+    }
+
+    pub fn load_private(
+        &self,
+        mut layouter: impl Layouter<F>,
+        input: Value<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        layouter.assign_region(
+            || "load private",
+            |mut region| {
+                region.assign_advice(|| "private input", self.input_column(0)?, 0, || input)
+            },
+        )
+    }
+
     pub fn load_private_inputs(
         &self,
-        mut layouter: impl Layouter<Fp>,
-        inputs: [Value<Fp>; L],
-    ) -> Result<[AssignedCell<Fp, Fp>; L], Error> {
+        mut layouter: impl Layouter<F>,
+        inputs: [Value<F>; L],
+    ) -> Result<[AssignedCell<F, F>; L], Error> {
         layouter.assign_region(
             || "load private inputs",
-            |mut region| -> Result<[AssignedCell<Fp, Fp>; L], Error> {
+            |mut region| -> Result<[AssignedCell<F, F>; L], Error> {
                 let result = inputs
                     .iter()
                     .enumerate()
                     .map(|(i, x)| {
                         region.assign_advice(
                             || "private input",
-                            self.config.inputs[i],
+                            self.input_column(i)?,
                             0,
                             || x.to_owned(),
                         )
                     })
-                    .collect::<Result<Vec<AssignedCell<Fp, Fp>>, Error>>();
-                Ok(result?.try_into().unwrap())
+                    .collect::<Result<Vec<AssignedCell<F, F>>, Error>>();
+                result?.try_into().map_err(|_| Error::Synthesis)
             },
         )
     }
 
     pub fn expose_public(
         &self,
-        mut layouter: impl Layouter<Fp>,
-        cell: &AssignedCell<Fp, Fp>,
+        mut layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
         row: usize,
     ) -> Result<(), Error> {
         layouter.constrain_instance(cell.cell(), self.config.instance, row)
@@ -100,13 +138,22 @@ impl<S: Spec<Fp, WIDTH, RATE>, const WIDTH: usize, const RATE: usize, const L: u
 
     pub fn hash(
         &self,
-        mut layouter: impl Layouter<Fp>,
-        words: &[AssignedCell<Fp, Fp>; L],
-    ) -> Result<AssignedCell<Fp, Fp>, Error> {
+        layouter: impl Layouter<F>,
+        words: &[AssignedCell<F, F>; L],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.hash_message::<L>(layouter, words)
+    }
+
+    // Hashes a message whose length differs from the chip's `L`, reusing the same Pow5 columns.
+    pub fn hash_message<const M: usize>(
+        &self,
+        mut layouter: impl Layouter<F>,
+        words: &[AssignedCell<F, F>; M],
+    ) -> Result<AssignedCell<F, F>, Error> {
         let pow5_chip = Pow5Chip::construct(self.config.pow5_config.clone());
         let word_cells = layouter.assign_region(
             || "load words",
-            |mut region| -> Result<[AssignedCell<Fp, Fp>; L], Error> {
+            |mut region| -> Result<[AssignedCell<F, F>; M], Error> {
                 let result = words
                     .iter()
                     .enumerate()
@@ -114,19 +161,170 @@ impl<S: Spec<Fp, WIDTH, RATE>, const WIDTH: usize, const RATE: usize, const L: u
                         word.copy_advice(
                             || format!("word {}", i),
                             &mut region,
-                            self.config.inputs[i],
+                            self.input_column(i)?,
                             0,
                         )
                     })
-                    .collect::<Result<Vec<AssignedCell<Fp, Fp>>, Error>>();
-                Ok(result?.try_into().unwrap())
+                    .collect::<Result<Vec<AssignedCell<F, F>>, Error>>();
+                result?.try_into().map_err(|_| Error::Synthesis)
             },
         )?;
 
-        let hasher = Hash::<_, _, S, ConstantLength<L>, WIDTH, RATE>::init(
+        let hasher = Hash::<_, _, S, ConstantLength<M>, WIDTH, RATE>::init(
             pow5_chip,
             layouter.namespace(|| "hasher"),
         )?;
         hasher.hash(layouter.namespace(|| "hash"), word_cells)
     }
+
+    // Assigns the initial state for ConstantLength<M> messages once, for `hash_from`.
+    pub fn initial_state<const M: usize>(
+        &self,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<InitialState<F, WIDTH>, Error> {
+        if M == 0 || M > RATE {
+            return Err(Error::Synthesis);
+        }
+        let capacity = <ConstantLength<M> as Domain<F, RATE>>::initial_capacity_element();
+        let words = layouter.assign_region(
+            || "initial state",
+            |mut region| -> Result<[AssignedCell<F, F>; WIDTH], Error> {
+                let result = (0..WIDTH)
+                    .map(|i| {
+                        let value = if i == RATE { capacity } else { F::zero() };
+                        region.assign_advice_from_constant(
+                            || format!("state {}", i),
+                            self.input_column(i)?,
+                            0,
+                            value,
+                        )
+                    })
+                    .collect::<Result<Vec<AssignedCell<F, F>>, Error>>();
+                result?.try_into().map_err(|_| Error::Synthesis)
+            },
+        )?;
+        Ok(InitialState { len: M, words })
+    }
+
+    // Hashes a message of at most RATE words from a shared initial state. The words go straight
+    // into the permutation's first row, so each hash adds only permutation rows, where
+    // `hash_message` also assigns the initial state and absorbs the words in regions of their
+    // own. The digest is the same.
+    pub fn hash_from<const M: usize>(
+        &self,
+        mut layouter: impl Layouter<F>,
+        initial: &InitialState<F, WIDTH>,
+        words: &[AssignedCell<F, F>; M],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        if initial.len != M {
+            return Err(Error::Synthesis);
+        }
+        let pow5_chip = Pow5Chip::construct(self.config.pow5_config.clone());
+        let state: [StateWord<F>; WIDTH] = initial
+            .words
+            .iter()
+            .enumerate()
+            .map(|(i, word)| words.get(i).unwrap_or(word).clone().into())
+            .collect::<Vec<StateWord<F>>>()
+            .try_into()
+            .map_err(|_| Error::Synthesis)?;
+        let state = <Pow5Chip<F, WIDTH, RATE> as PoseidonInstructions<F, S, WIDTH, RATE>>::permute(
+            &pow5_chip,
+            &mut layouter.namespace(|| "permute"),
+            &state,
+        )?;
+        let [output, ..] = state;
+        Ok(output.into())
+    }
+
+    // Hashes many messages of the same length, assigning the initial state once for all of them.
+    pub fn hash_batch<const M: usize>(
+        &self,
+        mut layouter: impl Layouter<F>,
+        messages: &[[AssignedCell<F, F>; M]],
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        let initial = self.initial_state::<M>(layouter.namespace(|| "initial state"))?;
+        messages
+            .iter()
+            .enumerate()
+            .map(|(i, words)| {
+                self.hash_from(
+                    layouter.namespace(|| format!("hash {}", i)),
+                    &initial,
+                    words,
+                )
+            })
+            .collect()
+    }
+
+    // Folds any number of words into one digest: acc = len, then acc = H(acc, word) per word.
+    // Matches `encoding::compress` natively.
+    pub fn hash_chain(
+        &self,
+        mut layouter: impl Layouter<F>,
+        words: &[AssignedCell<F, F>],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let mut acc = layouter.assign_region(
+            || "chain length",
+            |mut region| {
+                region.assign_advice_from_constant(
+                    || "length",
+                    self.input_column(0)?,
+                    0,
+                    F::from(words.len() as u64),
+                )
+            },
+        )?;
+        for (i, word) in words.iter().enumerate() {
+            acc = self.hash_message::<2>(
+                layouter.namespace(|| format!("chain {}", i)),
+                &[acc, word.clone()],
+            )?;
+        }
+        Ok(acc)
+    }
+}
+
+// The width-3, rate-2 Poseidon parameters used for Merkle hashing over each supported field, so
+// MerkleTreeV3 can be instantiated over any field with an implementation.
+pub trait PoseidonSpecFor: FieldExt {
+    type Spec: Spec<Self, 3, 2>;
+}
+
+impl PoseidonSpecFor for Fp {
+    type Spec = P128Pow5T3;
+}
+
+// The Vesta base field, the other half of the pasta cycle, so a circuit over Fq can check
+// membership in the same kind of tree as the Fp circuits whose proofs it recursively verifies.
+impl PoseidonSpecFor for Fq {
+    type Spec = P128Pow5T3;
+}
+
+// Width-5, rate-4 Poseidon over the Pallas base field, for nodes with four children. There is no
+// standard T5 instantiation over pasta, so the constants come from the reference Grain LFSR through
+// `Spec`'s default `constants`, with R_F = 8 and R_P = 60: circomlib's round counts for t = 5, four
+// more partial rounds than P128Pow5T3 has at t = 3. Nobody has analysed these parameters, so the
+// type is only built with the `unstable-t5` feature.
+#[cfg(feature = "unstable-t5")]
+#[derive(Debug, Clone, Copy)]
+pub struct P128Pow5T5;
+
+#[cfg(feature = "unstable-t5")]
+impl Spec<Fp, 5, 4> for P128Pow5T5 {
+    fn full_rounds() -> usize {
+        8
+    }
+
+    fn partial_rounds() -> usize {
+        60
+    }
+
+    fn sbox(val: Fp) -> Fp {
+        val.pow_vartime(&[5])
+    }
+
+    fn secure_mds() -> usize {
+        0
+    }
 }
@@ -0,0 +1,87 @@
+/*
+The keyed derivation the application circuits share: PRF_k(x) = Poseidon(k, x, domain), a
+ConstantLength<3> message whose last word is the chip's domain tag, loaded as a constant so a prover
+can't choose it. Allowlist nullifiers and stealth keys are both PRF outputs under different
+domains, so one can never be passed off as the other, and a new application gets its own tag rather
+than its own message layout.
+
+The stealth tag is 0, the word StealthClaimCircuit always hashed its secret and nonce with, so
+existing stealth keys are unchanged. Nullifiers are tagged 1 rather than hashed as the two-word
+Poseidon(secret, scope) they used to be, which is exactly a Legacy Merkle node over the same words:
+as a three-word message a nullifier has a different capacity word and can't be mistaken for one.
+*/
+
+use super::merkle_v3::{MerkleTreeV3Chip, MerkleTreeV3Config};
+use super::poseidon::PoseidonChip;
+use halo2_gadgets::poseidon::primitives::{
+    self as poseidon, ConstantLength, P128Pow5T3 as OrchardNullifier,
+};
+use halo2_proofs::{circuit::*, pasta::Fp, plonk::*};
+
+pub type PrfConfig = MerkleTreeV3Config;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrfDomain {
+    // One-off stealth keys, keyed by the master secret with a nonce as input.
+    Stealth,
+    // Allowlist nullifiers, keyed by the identity secret with the scope as input.
+    Nullifier,
+}
+
+impl PrfDomain {
+    // The word hashed after the key and input.
+    pub fn tag(self) -> Fp {
+        match self {
+            PrfDomain::Stealth => Fp::zero(),
+            PrfDomain::Nullifier => Fp::one(),
+        }
+    }
+}
+
+// The native PRF, matching `PrfChip::prf`.
+pub fn prf(domain: PrfDomain, key: Fp, x: Fp) -> Fp {
+    poseidon::Hash::<_, OrchardNullifier, ConstantLength<3>, 3, 2>::init().hash([
+        key,
+        x,
+        domain.tag(),
+    ])
+}
+
+#[derive(Debug, Clone)]
+pub struct PrfChip {
+    config: PrfConfig,
+    domain: PrfDomain,
+}
+
+impl PrfChip {
+    pub fn construct(config: PrfConfig, domain: PrfDomain) -> Self {
+        Self { config, domain }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<Fp>,
+        advice: [Column<Advice>; 3],
+        instance: Column<Instance>,
+    ) -> PrfConfig {
+        MerkleTreeV3Chip::<Fp>::configure(meta, advice, instance)
+    }
+
+    pub fn domain(&self) -> PrfDomain {
+        self.domain
+    }
+
+    pub fn prf(
+        &self,
+        mut layouter: impl Layouter<Fp>,
+        key: &AssignedCell<Fp, Fp>,
+        x: &AssignedCell<Fp, Fp>,
+    ) -> Result<AssignedCell<Fp, Fp>, Error> {
+        let poseidon_chip = PoseidonChip::<Fp, OrchardNullifier, 3, 2, 2>::construct(
+            self.config.hash_config.clone(),
+        );
+        let tag = MerkleTreeV3Chip::<Fp>::construct(self.config.clone())
+            .load_constant(layouter.namespace(|| "domain"), self.domain.tag())?;
+        poseidon_chip
+            .hash_message::<3>(layouter.namespace(|| "prf"), &[key.clone(), x.clone(), tag])
+    }
+}
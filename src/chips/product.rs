@@ -0,0 +1,100 @@
+/*
+A grand product over a challenge: prod = (gamma - v_1) * ... * (gamma - v_n). Two lists have equal
+products at a random gamma only if they are the same multiset, except with probability n / |F|, so
+comparing products checks that one list is a permutation of the other. gamma must be fixed after
+the values are, e.g. by hashing commitments to them.
+
+    | value | gamma | acc |
+*/
+
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*, poly::Rotation};
+use std::marker::PhantomData;
+
+#[derive(Debug, Clone)]
+pub struct GrandProductConfig {
+    pub advice: [Column<Advice>; 3],
+    pub init_selector: Selector,
+    pub product_selector: Selector,
+}
+
+#[derive(Debug, Clone)]
+pub struct GrandProductChip<F: FieldExt> {
+    config: GrandProductConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: FieldExt> GrandProductChip<F> {
+    pub fn construct(config: GrandProductConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 3],
+    ) -> GrandProductConfig {
+        let [col_value, col_gamma, col_acc] = advice;
+        let init_selector = meta.selector();
+        let product_selector = meta.selector();
+        for column in advice.iter() {
+            meta.enable_equality(*column);
+        }
+
+        // Enforces that the running product starts at one.
+        meta.create_gate("product init", |meta| {
+            let s = meta.query_selector(init_selector);
+            let acc = meta.query_advice(col_acc, Rotation::cur());
+            vec![s * (acc - Expression::Constant(F::one()))]
+        });
+
+        // Enforces acc = acc_prev * (gamma - value).
+        meta.create_gate("product", |meta| {
+            let s = meta.query_selector(product_selector);
+            let value = meta.query_advice(col_value, Rotation::cur());
+            let gamma = meta.query_advice(col_gamma, Rotation::cur());
+            let acc = meta.query_advice(col_acc, Rotation::cur());
+            let acc_prev = meta.query_advice(col_acc, Rotation::prev());
+            vec![s * (acc - acc_prev * (gamma - value))]
+        });
+
+        GrandProductConfig {
+            advice,
+            init_selector,
+            product_selector,
+        }
+    }
+
+    pub fn product(
+        &self,
+        mut layouter: impl Layouter<F>,
+        gamma: &AssignedCell<F, F>,
+        values: &[AssignedCell<F, F>],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let [col_value, col_gamma, col_acc] = self.config.advice;
+        layouter.assign_region(
+            || "grand product",
+            |mut region| {
+                // Row 0: | - | - | 1 |
+                // Enabled Selectors: Init
+                let mut acc_value = Value::known(F::one());
+                let mut acc = region.assign_advice(|| "acc", col_acc, 0, || acc_value)?;
+                self.config.init_selector.enable(&mut region, 0)?;
+
+                // Row i: | Value | Gamma | Acc |
+                // Enabled Selectors: Product
+                let gamma_value = gamma.value().map(|x| x.to_owned());
+                for (i, value) in values.iter().enumerate() {
+                    let row = i + 1;
+                    value.copy_advice(|| "value", &mut region, col_value, row)?;
+                    gamma.copy_advice(|| "gamma", &mut region, col_gamma, row)?;
+                    acc_value = acc_value * (gamma_value - value.value().map(|x| x.to_owned()));
+                    acc = region.assign_advice(|| "acc", col_acc, row, || acc_value)?;
+                    self.config.product_selector.enable(&mut region, row)?;
+                }
+                Ok(acc)
+            },
+        )
+    }
+}
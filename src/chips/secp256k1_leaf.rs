@@ -0,0 +1,79 @@
+/*
+Turns a secp256k1 public key into a tree leaf inside the circuit, so Ethereum keys can be set
+members without a trusted party hashing them beforehand. The uncompressed x || y encoding is split
+into four 16-byte limbs, each range checked and packed by LeafEncodingChip, and the Secp256k1 domain
+tag and the limbs are folded into one leaf with PoseidonChip::hash_chain, matching the key's
+`ToLeaf`.
+
+The limbs are only range checked: nothing here checks that (x, y) lies on the curve or verifies an
+ECDSA signature, both of which need secp256k1 base field arithmetic emulated over Fp.
+*/
+
+use super::leaf_encoding::{LeafEncodingChip, LeafEncodingConfig};
+use super::merkle_v3::{MerkleTreeV3Chip, MerkleTreeV3Config};
+use super::poseidon::PoseidonChip;
+use crate::encoding::{LeafDomain, SECP256K1_LIMB_BYTES};
+use halo2_gadgets::poseidon::primitives::P128Pow5T3;
+use halo2_proofs::{circuit::*, pasta::Fp, plonk::*};
+
+#[derive(Debug, Clone)]
+pub struct Secp256k1LeafConfig {
+    pub encoding: LeafEncodingConfig,
+    // Its Poseidon config hashes the limbs and its advice loads the domain tag.
+    pub merkle: MerkleTreeV3Config,
+}
+
+#[derive(Debug, Clone)]
+pub struct Secp256k1LeafChip {
+    config: Secp256k1LeafConfig,
+}
+
+impl Secp256k1LeafChip {
+    pub fn construct(config: Secp256k1LeafConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<Fp>,
+        advice: [Column<Advice>; 3],
+        instance: Column<Instance>,
+    ) -> Secp256k1LeafConfig {
+        Secp256k1LeafConfig {
+            encoding: LeafEncodingChip::configure(meta, [advice[0], advice[1]], instance),
+            merkle: MerkleTreeV3Chip::<Fp>::configure(meta, advice, instance),
+        }
+    }
+
+    pub fn load_table(&self, layouter: impl Layouter<Fp>) -> Result<(), Error> {
+        LeafEncodingChip::construct(self.config.encoding.clone()).load_table(layouter)
+    }
+
+    pub fn expose_public(
+        &self,
+        layouter: impl Layouter<Fp>,
+        cell: &AssignedCell<Fp, Fp>,
+        row: usize,
+    ) -> Result<(), Error> {
+        LeafEncodingChip::construct(self.config.encoding.clone()).expose_public(layouter, cell, row)
+    }
+
+    pub fn leaf(
+        &self,
+        mut layouter: impl Layouter<Fp>,
+        key: &[Value<u8>; 64],
+    ) -> Result<AssignedCell<Fp, Fp>, Error> {
+        let encoding_chip = LeafEncodingChip::construct(self.config.encoding.clone());
+        let poseidon_chip = PoseidonChip::<Fp, P128Pow5T3, 3, 2, 2>::construct(
+            self.config.merkle.hash_config.clone(),
+        );
+        let merkle_chip = MerkleTreeV3Chip::<Fp>::construct(self.config.merkle.clone());
+        let mut words = vec![merkle_chip.load_constant(
+            layouter.namespace(|| "domain tag"),
+            Fp::from(LeafDomain::Secp256k1.tag() as u64),
+        )?];
+        for (i, limb) in key.chunks(SECP256K1_LIMB_BYTES).enumerate() {
+            words.push(encoding_chip.pack(layouter.namespace(|| format!("limb {}", i)), limb)?);
+        }
+        poseidon_chip.hash_chain(layouter.namespace(|| "compress limbs"), &words)
+    }
+}
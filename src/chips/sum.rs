@@ -0,0 +1,87 @@
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*, poly::Rotation};
+use std::marker::PhantomData;
+
+#[derive(Debug, Clone)]
+pub struct SumConfig {
+    pub advice: [Column<Advice>; 2],
+    pub init_selector: Selector,
+    pub sum_selector: Selector,
+}
+
+// Adds up assigned cells with a running sum. The caller is responsible for range checking the
+// summands so the total can't wrap around the modulus.
+#[derive(Debug, Clone)]
+pub struct SumChip<F: FieldExt> {
+    config: SumConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: FieldExt> SumChip<F> {
+    pub fn construct(config: SumConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn configure(meta: &mut ConstraintSystem<F>, advice: [Column<Advice>; 2]) -> SumConfig {
+        let col_value = advice[0];
+        let col_acc = advice[1];
+        let init_selector = meta.selector();
+        let sum_selector = meta.selector();
+        meta.enable_equality(col_value);
+        meta.enable_equality(col_acc);
+
+        // Enforces that the running sum starts at zero.
+        meta.create_gate("sum init", |meta| {
+            let s = meta.query_selector(init_selector);
+            let acc = meta.query_advice(col_acc, Rotation::cur());
+            vec![s * acc]
+        });
+
+        // Enforces acc = acc_prev + value.
+        meta.create_gate("sum", |meta| {
+            let s = meta.query_selector(sum_selector);
+            let value = meta.query_advice(col_value, Rotation::cur());
+            let acc = meta.query_advice(col_acc, Rotation::cur());
+            let acc_prev = meta.query_advice(col_acc, Rotation::prev());
+            vec![s * (acc - acc_prev - value)]
+        });
+
+        SumConfig {
+            advice: [col_value, col_acc],
+            init_selector,
+            sum_selector,
+        }
+    }
+
+    pub fn sum(
+        &self,
+        mut layouter: impl Layouter<F>,
+        values: &[AssignedCell<F, F>],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        layouter.assign_region(
+            || "sum",
+            |mut region| {
+                // Row 0: | - | 0 |
+                // Enabled Selectors: Init
+                let mut acc_value = Value::known(F::zero());
+                let mut acc =
+                    region.assign_advice(|| "acc", self.config.advice[1], 0, || acc_value)?;
+                self.config.init_selector.enable(&mut region, 0)?;
+
+                // Row i: | Value | Acc |
+                // Enabled Selectors: Sum
+                for (i, value) in values.iter().enumerate() {
+                    let row = i + 1;
+                    value.copy_advice(|| "value", &mut region, self.config.advice[0], row)?;
+                    acc_value = acc_value + value.value().map(|x| x.to_owned());
+                    acc =
+                        region.assign_advice(|| "acc", self.config.advice[1], row, || acc_value)?;
+                    self.config.sum_selector.enable(&mut region, row)?;
+                }
+                Ok(acc)
+            },
+        )
+    }
+}
@@ -0,0 +1,135 @@
+/*
+Membership for any depth up to the circuit's maximum. The path is always MAX layers long, and each
+layer carries an enable bit: an enabled layer hashes as in MerkleTreeV3Chip, a disabled one passes
+the digest below it through unchanged, so the enabled prefix is a path of that depth and the rest is
+padding. Trees of every depth up to MAX then share one circuit and one verifying key.
+
+Per layer, after the hash, CondHashChip's select region picks between the hashed layer and the
+digest below it. The layer is ordered by the swap gate first, so the pass-through value is the
+digest rather than the left child. Both branches are constrained; a disabled layer still hashes its
+(ignored) sibling, so the cost is that of a MAX-deep path whatever the depth.
+*/
+
+use super::cond_hash::{CondHashChip, CondHashConfig};
+use super::merkle_v3::{DomainSeparation, MerkleTreeV3Chip, MerkleTreeV3Config};
+use super::poseidon::{PoseidonChip, PoseidonConfig, PoseidonSpecFor};
+use super::MerkleOutput;
+use crate::error;
+use halo2_proofs::{circuit::*, pasta::Fp, plonk::*};
+
+type LayerHash<F> = PoseidonChip<F, <F as PoseidonSpecFor>::Spec, 3, 2, 2>;
+
+#[derive(Debug, Clone)]
+pub struct VariableDepthConfig<F: PoseidonSpecFor = Fp> {
+    pub merkle: MerkleTreeV3Config<F>,
+    pub cond_hash: CondHashConfig<PoseidonConfig<F, 3, 2, 2>>,
+}
+
+#[derive(Clone)]
+pub struct VariableDepthChip<F: PoseidonSpecFor = Fp> {
+    config: VariableDepthConfig<F>,
+    separation: DomainSeparation,
+}
+
+impl<F: PoseidonSpecFor> VariableDepthChip<F> {
+    pub fn construct(config: VariableDepthConfig<F>) -> Self {
+        Self {
+            config,
+            separation: DomainSeparation::Legacy,
+        }
+    }
+
+    pub fn with_separation(mut self, separation: DomainSeparation) -> Self {
+        self.separation = separation;
+        self
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 3],
+        instance: Column<Instance>,
+    ) -> VariableDepthConfig<F> {
+        let merkle = MerkleTreeV3Chip::<F>::configure(meta, advice, instance);
+        let cond_hash =
+            CondHashChip::<F, LayerHash<F>>::configure(meta, advice, merkle.hash_config.clone());
+        VariableDepthConfig { merkle, cond_hash }
+    }
+
+    fn merkle_chip(&self) -> MerkleTreeV3Chip<F> {
+        MerkleTreeV3Chip::<F>::construct(self.config.merkle.clone())
+            .with_separation(self.separation)
+    }
+
+    pub fn load_private(
+        &self,
+        layouter: impl Layouter<F>,
+        input: Value<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.merkle_chip().load_private(layouter, input)
+    }
+
+    pub fn expose_public(
+        &self,
+        layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
+        row: usize,
+    ) -> Result<(), Error> {
+        self.merkle_chip().expose_public(layouter, cell, row)
+    }
+
+    // Hashes `leaf` up all MAX = `elements.len()` layers, keeping those whose enable bit is set.
+    // Returns the root with the assigned enable bits, leaf layer first.
+    pub fn merkle_prove(
+        &self,
+        mut layouter: impl Layouter<F>,
+        leaf: &AssignedCell<F, F>,
+        elements: &[Value<F>],
+        indices: &[Value<F>],
+        enables: &[Value<F>],
+    ) -> Result<(MerkleOutput<F>, Vec<AssignedCell<F, F>>), error::Error> {
+        if elements.len() != indices.len() {
+            return Err(error::Error::LengthMismatch {
+                elements: elements.len(),
+                indices: indices.len(),
+            });
+        }
+        if enables.len() != elements.len() {
+            return Err(error::Error::DepthMismatch {
+                expected: elements.len(),
+                actual: enables.len(),
+            });
+        }
+        if elements.is_empty() {
+            return Err(error::Error::EmptyPath);
+        }
+
+        let merkle_chip = self.merkle_chip();
+        let cond_hash_chip =
+            CondHashChip::<F, LayerHash<F>>::construct(self.config.cond_hash.clone());
+        let mut digest = leaf.clone();
+        let mut enable_cells = Vec::with_capacity(enables.len());
+        for (level, ((element, index), enable)) in elements
+            .iter()
+            .zip(indices.iter())
+            .zip(enables.iter())
+            .enumerate()
+        {
+            let hashed = merkle_chip.merkle_prove_layer(
+                layouter.namespace(|| format!("layer {}", level)),
+                &digest,
+                *element,
+                *index,
+                level,
+            )?;
+            let (out, enable_cell) = cond_hash_chip.select(
+                layouter.namespace(|| format!("select {}", level)),
+                *enable,
+                &digest,
+                &hashed,
+            )?;
+            digest = out;
+            enable_cells.push(enable_cell);
+        }
+        Ok((MerkleOutput::new(digest), enable_cells))
+    }
+}
@@ -0,0 +1,136 @@
+/*
+Checks not_before <= epoch <= not_after for u64 epochs, on top of ByteTableChip's range checks. One
+region holds the window and its two margins:
+
+    | not_before | epoch | not_after |
+    | lower      | upper |           |
+
+with lower = epoch - not_before and upper = not_after - epoch. The epoch, not_before and both
+margins are range checked to 64 bits. A negative margin wraps to within 2^64 of the modulus and
+fails its check, and with epoch and lower bounded not_after = epoch + upper can't wrap either, so it
+needs no check of its own.
+*/
+
+use super::byte_table::{ByteTableChip, ByteTableConfig, TableWidth};
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*, poly::Rotation};
+use std::marker::PhantomData;
+
+pub const EPOCH_BITS: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct WindowConfig {
+    pub table: ByteTableConfig,
+    pub window_selector: Selector,
+}
+
+#[derive(Debug, Clone)]
+pub struct WindowChip<F: FieldExt> {
+    config: WindowConfig,
+    _marker: PhantomData<F>,
+}
+
+impl<F: FieldExt> WindowChip<F> {
+    pub fn construct(config: WindowConfig) -> Self {
+        Self {
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn configure(
+        meta: &mut ConstraintSystem<F>,
+        advice: [Column<Advice>; 3],
+        instance: Column<Instance>,
+    ) -> WindowConfig {
+        let table = ByteTableChip::configure(meta, advice, instance, TableWidth::Bits8);
+        let window_selector = meta.selector();
+        let [col_a, col_b, col_c] = advice;
+
+        // Enforces lower = epoch - not_before and upper = not_after - epoch.
+        meta.create_gate("window", |meta| {
+            let s = meta.query_selector(window_selector);
+            let not_before = meta.query_advice(col_a, Rotation::cur());
+            let epoch = meta.query_advice(col_b, Rotation::cur());
+            let not_after = meta.query_advice(col_c, Rotation::cur());
+            let lower = meta.query_advice(col_a, Rotation::next());
+            let upper = meta.query_advice(col_b, Rotation::next());
+            vec![
+                s.clone() * (lower - (epoch.clone() - not_before)),
+                s * (upper - (not_after - epoch)),
+            ]
+        });
+
+        WindowConfig {
+            table,
+            window_selector,
+        }
+    }
+
+    fn table_chip(&self) -> ByteTableChip<F> {
+        ByteTableChip::construct(self.config.table.clone())
+    }
+
+    pub fn load_table(&self, layouter: impl Layouter<F>) -> Result<(), Error> {
+        self.table_chip().load_table(layouter)
+    }
+
+    pub fn load_private(
+        &self,
+        layouter: impl Layouter<F>,
+        value: Value<F>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        self.table_chip().load_private(layouter, value)
+    }
+
+    pub fn expose_public(
+        &self,
+        layouter: impl Layouter<F>,
+        cell: &AssignedCell<F, F>,
+        row: usize,
+    ) -> Result<(), Error> {
+        self.table_chip().expose_public(layouter, cell, row)
+    }
+
+    // Constrains not_before <= epoch <= not_after as 64-bit integers.
+    pub fn check(
+        &self,
+        mut layouter: impl Layouter<F>,
+        not_before: &AssignedCell<F, F>,
+        epoch: &AssignedCell<F, F>,
+        not_after: &AssignedCell<F, F>,
+    ) -> Result<(), Error> {
+        let [col_a, col_b, col_c] = self.config.table.advice;
+        let (lower, upper) = layouter.assign_region(
+            || "window",
+            |mut region| {
+                self.config.window_selector.enable(&mut region, 0)?;
+                not_before.copy_advice(|| "not before", &mut region, col_a, 0)?;
+                epoch.copy_advice(|| "epoch", &mut region, col_b, 0)?;
+                not_after.copy_advice(|| "not after", &mut region, col_c, 0)?;
+                let lower = region.assign_advice(
+                    || "lower",
+                    col_a,
+                    1,
+                    || epoch.value().zip(not_before.value()).map(|(e, b)| *e - *b),
+                )?;
+                let upper = region.assign_advice(
+                    || "upper",
+                    col_b,
+                    1,
+                    || not_after.value().zip(epoch.value()).map(|(a, e)| *a - *e),
+                )?;
+                Ok((lower, upper))
+            },
+        )?;
+        let table_chip = self.table_chip();
+        for (name, cell) in [
+            ("epoch", epoch),
+            ("not before", not_before),
+            ("lower", &lower),
+            ("upper", &upper),
+        ] {
+            table_chip.range_check(layouter.namespace(|| name), cell, EPOCH_BITS)?;
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,51 @@
+#[cfg(feature = "poseidon")]
+pub mod allowlist;
+pub mod byte_table;
+#[cfg(feature = "poseidon")]
+pub mod chunk_inclusion;
+#[cfg(feature = "poseidon")]
+pub mod cond_hash;
+#[cfg(feature = "poseidon")]
+pub mod dual_root;
+#[cfg(feature = "poseidon")]
+pub mod epoch_membership;
+#[cfg(feature = "poseidon")]
+pub mod forest;
+#[cfg(feature = "poseidon")]
+pub mod full_tree;
+#[cfg(feature = "dummy-hash")]
 pub mod hash_1;
+#[cfg(feature = "dummy-hash")]
 pub mod hash_2;
+#[cfg(feature = "poseidon")]
+pub mod index_diff;
+#[cfg(feature = "poseidon")]
+pub mod intersection;
+pub mod leaf_encoding;
+#[cfg(all(feature = "poseidon", feature = "dummy-hash"))]
+pub mod merkle;
+#[cfg(feature = "dummy-hash")]
 pub mod merkle_v1;
+#[cfg(feature = "dummy-hash")]
 pub mod merkle_v2;
+#[cfg(feature = "poseidon")]
+pub mod merkle_v3;
+#[cfg(feature = "poseidon")]
+pub mod nmt;
+#[cfg(feature = "poseidon")]
+pub mod orchard;
+#[cfg(feature = "poseidon")]
 pub mod poseidon;
+#[cfg(feature = "poseidon")]
+pub mod reserves;
+#[cfg(feature = "poseidon")]
+pub mod secp256k1_leaf;
+#[cfg(feature = "poseidon")]
+pub mod smt_deletion;
+pub mod sorted_nullifiers;
+#[cfg(feature = "poseidon")]
+pub mod stealth;
+#[cfg(feature = "poseidon")]
+pub mod tree_equality;
+#[cfg(feature = "poseidon")]
+pub mod variable_depth;
@@ -0,0 +1,195 @@
+/*
+The allowlist gate, packaged as one circuit: a private identity secret is committed to as the leaf
+commitment = Poseidon(secret), the commitment is proven to be in the allowlist tree, and the public
+nullifier = PRF_secret(scope) (see PrfChip) lets a contract reject a second use of the same identity
+for the same action without learning which identity it was. Both hashes run on the MerkleTreeV3
+chip's Poseidon config; the one- and three-input messages have different capacity values, so a
+commitment can never equal a nullifier, and the two-input Legacy nodes of the tree can never equal
+either.
+
+Instance rows follow `AllowlistCircuit::layout`: the root, the nullifier, then the scope.
+*/
+
+use super::super::chips::merkle::DomainSeparation;
+use super::super::chips::merkle_v3::{MerkleTreeV3Chip, MerkleTreeV3Config};
+use super::super::chips::poseidon::PoseidonChip;
+use super::super::chips::prf::{prf, PrfChip, PrfDomain};
+use crate::error::{self, validate_path};
+use crate::instance::{InstanceLayout, PublicInputs};
+use crate::tree::MerklePath;
+use halo2_gadgets::poseidon::primitives::{
+    self as poseidon, ConstantLength, P128Pow5T3 as OrchardNullifier,
+};
+use halo2_proofs::{circuit::*, pasta::Fp, plonk::*};
+
+// The leaf an identity is registered under.
+pub fn identity_commitment(secret: Fp) -> Fp {
+    poseidon::Hash::<_, OrchardNullifier, ConstantLength<1>, 3, 2>::init().hash([secret])
+}
+
+// The nullifier of an identity for the action identified by `scope`.
+pub fn nullifier(secret: Fp, scope: Fp) -> Fp {
+    prf(PrfDomain::Nullifier, secret, scope)
+}
+
+#[derive(Default)]
+pub struct AllowlistCircuit {
+    pub secret: Value<Fp>,
+    pub elements: Vec<Value<Fp>>,
+    pub indices: Vec<Value<Fp>>,
+    pub scope: Value<Fp>,
+    pub separation: DomainSeparation,
+}
+
+impl AllowlistCircuit {
+    // `path` is the path of `identity_commitment(secret)` in the allowlist tree.
+    pub fn new(secret: Fp, path: &MerklePath, scope: Fp) -> Result<Self, error::Error> {
+        if path.leaf != identity_commitment(secret) {
+            return Err(error::Error::LeafMismatch);
+        }
+        let elements: Vec<Value<Fp>> = path.elements.iter().map(|x| Value::known(*x)).collect();
+        let indices: Vec<Value<Fp>> = path.indices.iter().map(|x| Value::known(*x)).collect();
+        validate_path(elements.len(), &elements, &indices)?;
+        Ok(Self {
+            secret: Value::known(secret),
+            elements,
+            indices,
+            scope: Value::known(scope),
+            separation: DomainSeparation::Legacy,
+        })
+    }
+
+    pub fn with_separation(mut self, separation: DomainSeparation) -> Self {
+        self.separation = separation;
+        self
+    }
+
+    pub fn layout() -> InstanceLayout {
+        InstanceLayout::merkle(false, true)
+            .with_nullifier()
+            .with_scope()
+    }
+
+    // The instance columns a verifier supplies, in `layout` order.
+    pub fn instances(root: Fp, nullifier: Fp, scope: Fp) -> Result<Vec<Vec<Fp>>, error::Error> {
+        let inputs = PublicInputs {
+            root: Some(root),
+            nullifier: Some(nullifier),
+            scope: Some(scope),
+            ..PublicInputs::default()
+        };
+        Ok(vec![Self::layout().column(&inputs)?, vec![]])
+    }
+}
+
+impl Circuit<Fp> for AllowlistCircuit {
+    type Config = MerkleTreeV3Config;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            secret: Value::unknown(),
+            elements: vec![Value::unknown(); self.elements.len()],
+            indices: vec![Value::unknown(); self.indices.len()],
+            scope: Value::unknown(),
+            separation: self.separation,
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let col_a = meta.advice_column();
+        let col_b = meta.advice_column();
+        let col_c = meta.advice_column();
+        let instance = meta.instance_column();
+        MerkleTreeV3Chip::<Fp>::configure(meta, [col_a, col_b, col_c], instance)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let poseidon_chip =
+            PoseidonChip::<Fp, OrchardNullifier, 3, 2, 2>::construct(config.hash_config.clone());
+        let prf_chip = PrfChip::construct(config.clone(), PrfDomain::Nullifier);
+        let chip = MerkleTreeV3Chip::<Fp>::construct(config).with_separation(self.separation);
+        let layout = Self::layout();
+
+        let secret = chip.load_private(layouter.namespace(|| "load secret"), self.secret)?;
+        let scope = chip.load_private(layouter.namespace(|| "load scope"), self.scope)?;
+        let commitment = poseidon_chip
+            .hash_message::<1>(layouter.namespace(|| "commitment"), &[secret.clone()])?;
+        let nullifier = prf_chip.prf(layouter.namespace(|| "nullifier"), &secret, &scope)?;
+        let root = chip.merkle_prove(
+            layouter.namespace(|| "merkle_prove"),
+            &commitment,
+            &self.elements,
+            &self.indices,
+        )?;
+
+        let rows = [
+            (layout.root, &root.cell),
+            (layout.nullifier, &nullifier),
+            (layout.scope, &scope),
+        ];
+        for (row, cell) in rows {
+            let row = row.ok_or(Error::Synthesis)?;
+            chip.expose_public(layouter.namespace(|| format!("row {}", row)), cell, row)?;
+        }
+        Ok(())
+    }
+}
+
+mod tests {
+    use super::{identity_commitment, nullifier, AllowlistCircuit};
+    use crate::chips::merkle::DomainSeparation;
+    use crate::chips::prf::{prf, PrfDomain};
+    use crate::coverage::{assert_mock_satisfied, mock_verify};
+    use crate::tree::{hash_pair, MerkleTree};
+    use halo2_gadgets::poseidon::primitives::{self as poseidon, ConstantLength, P128Pow5T3};
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let secrets: Vec<Fp> = (0..5u64).map(|i| Fp::from(1_000_003 * i + 17)).collect();
+        let commitments: Vec<Fp> = secrets.iter().map(|s| identity_commitment(*s)).collect();
+        let tree = MerkleTree::new(3, &commitments, DomainSeparation::Legacy).unwrap();
+        let scope = Fp::from(2024);
+
+        // Nullifiers are the tagged PRF, not the Legacy node hash over the same two words.
+        assert_eq!(
+            nullifier(secrets[3], scope),
+            poseidon::Hash::<_, P128Pow5T3, ConstantLength<3>, 3, 2>::init().hash([
+                secrets[3],
+                scope,
+                Fp::one()
+            ])
+        );
+        assert_ne!(
+            prf(PrfDomain::Nullifier, secrets[3], scope),
+            hash_pair(DomainSeparation::Legacy, 0, secrets[3], scope)
+        );
+
+        let circuit = AllowlistCircuit::new(secrets[3], &tree.path(3).unwrap(), scope).unwrap();
+        let instances =
+            AllowlistCircuit::instances(tree.root(), nullifier(secrets[3], scope), scope).unwrap();
+        assert_mock_satisfied(10, &circuit, instances);
+
+        // The nullifier is bound to both the identity and the scope.
+        for (secret, claimed_scope) in [(secrets[2], scope), (secrets[3], scope + Fp::one())] {
+            let instances =
+                AllowlistCircuit::instances(tree.root(), nullifier(secret, claimed_scope), scope)
+                    .unwrap();
+            assert!(mock_verify(10, &circuit, instances).unwrap().is_err());
+        }
+        let instances = AllowlistCircuit::instances(
+            tree.root(),
+            nullifier(secrets[3], scope),
+            scope + Fp::one(),
+        )
+        .unwrap();
+        assert!(mock_verify(10, &circuit, instances).unwrap().is_err());
+
+        assert!(AllowlistCircuit::new(secrets[2], &tree.path(3).unwrap(), scope).is_err());
+    }
+}
@@ -0,0 +1,187 @@
+/*
+Chunk inclusion for verifiable storage: proves a private data chunk, hashed in-circuit from its
+bytes, is the leaf at a public position under a file's Merkle root. A storage node answering a
+retrieval challenge can show it holds the chunk without revealing it; a client that downloaded the
+chunk checks `chunk_leaf` natively instead.
+
+The pipeline is the native `chunk_leaf` step by step: every byte is range checked and packed 31 at a
+time by LeafEncodingChip, the Chunk domain tag, the byte length (fixed by the circuit's shape) and
+the packed pieces are folded into the leaf with PoseidonChip::hash_chain, and the leaf is hashed up
+the path with MerkleTreeV3Chip. The chain and the path share one Poseidon config.
+
+Instance rows: the root, then the bits of the chunk's index (leaf layer first).
+*/
+
+use super::super::chips::leaf_encoding::{LeafEncodingChip, LeafEncodingConfig, PACKED_BYTES};
+use super::super::chips::merkle_v3::{DomainSeparation, MerkleTreeV3Chip, MerkleTreeV3Config};
+use super::super::chips::poseidon::PoseidonChip;
+use crate::encoding::{chunk_leaf, index_bits, LeafDomain};
+use crate::error::{self, validate_path};
+use crate::tree::MerklePath;
+use halo2_gadgets::poseidon::primitives::P128Pow5T3;
+use halo2_proofs::{circuit::*, pasta::Fp, plonk::*};
+
+pub const ROOT_ROW: usize = 0;
+const FIRST_INDEX_ROW: usize = 1;
+
+#[derive(Debug, Clone)]
+pub struct ChunkInclusionConfig {
+    pub merkle: MerkleTreeV3Config,
+    pub encoding: LeafEncodingConfig,
+}
+
+#[derive(Default)]
+pub struct ChunkInclusionCircuit {
+    pub chunk: Vec<Value<u8>>,
+    pub elements: Vec<Value<Fp>>,
+    pub indices: Vec<Value<Fp>>,
+    pub separation: DomainSeparation,
+}
+
+impl ChunkInclusionCircuit {
+    // `path` is the chunk's path in the file tree; its leaf must be `chunk_leaf(chunk)`.
+    pub fn new(
+        chunk: &[u8],
+        path: &MerklePath,
+        separation: DomainSeparation,
+    ) -> Result<Self, error::Error> {
+        if chunk_leaf(chunk) != path.leaf {
+            return Err(error::Error::LeafMismatch);
+        }
+        let elements: Vec<Value<Fp>> = path.elements.iter().map(|x| Value::known(*x)).collect();
+        let indices: Vec<Value<Fp>> = path.indices.iter().map(|x| Value::known(*x)).collect();
+        validate_path(elements.len(), &elements, &indices)?;
+        Ok(Self {
+            chunk: chunk.iter().map(|byte| Value::known(*byte)).collect(),
+            elements,
+            indices,
+            separation,
+        })
+    }
+
+    pub fn instances(root: Fp, index: u64, depth: usize) -> Result<Vec<Vec<Fp>>, error::Error> {
+        let mut column = vec![root];
+        column.extend(index_bits::<Fp>(index, depth)?);
+        Ok(vec![column, vec![]])
+    }
+}
+
+impl Circuit<Fp> for ChunkInclusionCircuit {
+    type Config = ChunkInclusionConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            chunk: vec![Value::unknown(); self.chunk.len()],
+            elements: vec![Value::unknown(); self.elements.len()],
+            indices: vec![Value::unknown(); self.indices.len()],
+            separation: self.separation,
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let col_a = meta.advice_column();
+        let col_b = meta.advice_column();
+        let col_c = meta.advice_column();
+        let instance = meta.instance_column();
+        ChunkInclusionConfig {
+            merkle: MerkleTreeV3Chip::<Fp>::configure(meta, [col_a, col_b, col_c], instance),
+            encoding: LeafEncodingChip::configure(meta, [col_a, col_b], instance),
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let encoding_chip = LeafEncodingChip::construct(config.encoding);
+        let poseidon_chip =
+            PoseidonChip::<Fp, P128Pow5T3, 3, 2, 2>::construct(config.merkle.hash_config.clone());
+        let merkle_chip =
+            MerkleTreeV3Chip::<Fp>::construct(config.merkle).with_separation(self.separation);
+
+        encoding_chip.load_table(layouter.namespace(|| "byte table"))?;
+        let mut words = vec![
+            merkle_chip.load_constant(
+                layouter.namespace(|| "domain tag"),
+                Fp::from(LeafDomain::Chunk.tag() as u64),
+            )?,
+            merkle_chip.load_constant(
+                layouter.namespace(|| "chunk length"),
+                Fp::from(self.chunk.len() as u64),
+            )?,
+        ];
+        for (i, piece) in self.chunk.chunks(PACKED_BYTES).enumerate() {
+            words.push(encoding_chip.pack(layouter.namespace(|| format!("piece {}", i)), piece)?);
+        }
+        let leaf = poseidon_chip.hash_chain(layouter.namespace(|| "chunk leaf"), &words)?;
+
+        let root = merkle_chip.merkle_prove(
+            layouter.namespace(|| "path"),
+            &leaf,
+            &self.elements,
+            &self.indices,
+        )?;
+        for (level, index) in root.indices.iter().enumerate() {
+            merkle_chip.expose_public(
+                layouter.namespace(|| format!("index bit {}", level)),
+                index,
+                FIRST_INDEX_ROW + level,
+            )?;
+        }
+        merkle_chip.expose_public(layouter.namespace(|| "root"), &root.cell, ROOT_ROW)
+    }
+}
+
+mod tests {
+    use super::ChunkInclusionCircuit;
+    use crate::chips::merkle::DomainSeparation;
+    use crate::coverage::{assert_mock_satisfied, mock_verify};
+    use crate::encoding::chunk_leaf;
+    use crate::error::Error;
+    use crate::tree::MerkleTree;
+    use halo2_proofs::{circuit::Value, pasta::Fp};
+
+    #[test]
+    fn test() {
+        let file: Vec<u8> = (0..200u32).map(|i| (i * 7 + 3) as u8).collect();
+        let chunks: Vec<&[u8]> = file.chunks(64).collect();
+        let leaves: Vec<Fp> = chunks.iter().map(|chunk| chunk_leaf(chunk)).collect();
+        let separation = DomainSeparation::Level;
+        let tree = MerkleTree::new(3, &leaves, separation).unwrap();
+
+        let path = tree.path(1).unwrap();
+        let circuit = ChunkInclusionCircuit::new(chunks[1], &path, separation).unwrap();
+        let instances = ChunkInclusionCircuit::instances(tree.root(), 1, 3).unwrap();
+        assert_mock_satisfied(10, &circuit, instances.clone());
+
+        // The last, shorter chunk has a circuit of its own length.
+        let path = tree.path(3).unwrap();
+        let circuit = ChunkInclusionCircuit::new(chunks[3], &path, separation).unwrap();
+        assert_mock_satisfied(
+            10,
+            &circuit,
+            ChunkInclusionCircuit::instances(tree.root(), 3, 3).unwrap(),
+        );
+
+        // A changed byte, or the right chunk at another position, fails.
+        let path = tree.path(1).unwrap();
+        let mut tampered = ChunkInclusionCircuit::new(chunks[1], &path, separation).unwrap();
+        tampered.chunk[10] = Value::known(0);
+        assert!(mock_verify(10, &tampered, instances).unwrap().is_err());
+        assert!(mock_verify(
+            10,
+            &circuit,
+            ChunkInclusionCircuit::instances(tree.root(), 2, 3).unwrap()
+        )
+        .unwrap()
+        .is_err());
+
+        assert_ne!(chunk_leaf(&[0, 1]), chunk_leaf(&[1]));
+        assert!(matches!(
+            ChunkInclusionCircuit::new(chunks[0], &path, separation),
+            Err(Error::LeafMismatch)
+        ));
+    }
+}
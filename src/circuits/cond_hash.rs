@@ -0,0 +1,170 @@
+/*
+Folds a list of inputs into an accumulator with CondHashChip: starting from `start`, each step with
+its enable bit set replaces the accumulator with Poseidon(acc, input), and each step with it clear
+leaves the accumulator alone. This is how a variable number of MMR peaks is bagged under one
+circuit, with the enable bits saying which of the padded slots hold peaks.
+
+Instance rows: the start, the result, then one enable bit per step.
+*/
+
+use super::super::chips::cond_hash::{CondHashChip, CondHashConfig};
+use super::super::chips::poseidon::{PoseidonChip, PoseidonConfig};
+use crate::error;
+use halo2_gadgets::poseidon::primitives::{
+    self as poseidon, ConstantLength, P128Pow5T3 as OrchardNullifier,
+};
+use halo2_proofs::{circuit::*, pasta::Fp, plonk::*};
+
+pub const START_ROW: usize = 0;
+pub const RESULT_ROW: usize = 1;
+const FIRST_ENABLE_ROW: usize = 2;
+
+type StepHash = PoseidonChip<Fp, OrchardNullifier, 3, 2, 2>;
+
+// The native fold, matching the circuit's result.
+pub fn cond_fold(start: Fp, inputs: &[Fp], enables: &[bool]) -> Fp {
+    inputs
+        .iter()
+        .zip(enables)
+        .fold(start, |acc, (input, enable)| {
+            if *enable {
+                poseidon::Hash::<_, OrchardNullifier, ConstantLength<2>, 3, 2>::init()
+                    .hash([acc, *input])
+            } else {
+                acc
+            }
+        })
+}
+
+#[derive(Debug, Clone)]
+pub struct CondHashCircuitConfig {
+    pub cond_hash: CondHashConfig<PoseidonConfig<Fp, 3, 2, 2>>,
+    pub instance: Column<Instance>,
+}
+
+#[derive(Default)]
+pub struct CondHashCircuit {
+    pub start: Value<Fp>,
+    pub inputs: Vec<Value<Fp>>,
+    pub enables: Vec<Value<Fp>>,
+}
+
+impl CondHashCircuit {
+    pub fn new(start: Fp, inputs: &[Fp], enables: &[bool]) -> Result<Self, error::Error> {
+        if inputs.len() != enables.len() {
+            return Err(error::Error::DepthMismatch {
+                expected: inputs.len(),
+                actual: enables.len(),
+            });
+        }
+        Ok(Self {
+            start: Value::known(start),
+            inputs: inputs.iter().map(|x| Value::known(*x)).collect(),
+            enables: enables
+                .iter()
+                .map(|enable| Value::known(Fp::from(*enable as u64)))
+                .collect(),
+        })
+    }
+
+    pub fn instances(start: Fp, result: Fp, enables: &[bool]) -> Vec<Vec<Fp>> {
+        let mut column = vec![start, result];
+        column.extend(enables.iter().map(|enable| Fp::from(*enable as u64)));
+        vec![column, vec![]]
+    }
+}
+
+impl Circuit<Fp> for CondHashCircuit {
+    type Config = CondHashCircuitConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            start: Value::unknown(),
+            inputs: vec![Value::unknown(); self.inputs.len()],
+            enables: vec![Value::unknown(); self.enables.len()],
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let advice = [(); 3].map(|_| meta.advice_column());
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+        let hash_config = StepHash::configure(meta);
+        CondHashCircuitConfig {
+            cond_hash: CondHashChip::<Fp, StepHash>::configure(meta, advice, hash_config),
+            instance,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let poseidon_chip = StepHash::construct(config.cond_hash.hash_config.clone());
+        let chip = CondHashChip::<Fp, StepHash>::construct(config.cond_hash);
+
+        let start = poseidon_chip.load_private(layouter.namespace(|| "start"), self.start)?;
+        let mut acc = start.clone();
+        for (step, (input, enable)) in self.inputs.iter().zip(self.enables.iter()).enumerate() {
+            let input = poseidon_chip
+                .load_private(layouter.namespace(|| format!("input {}", step)), *input)?;
+            let (out, enable) = chip.hash_or_pass(
+                layouter.namespace(|| format!("step {}", step)),
+                *enable,
+                &acc,
+                &input,
+            )?;
+            layouter.constrain_instance(enable.cell(), config.instance, FIRST_ENABLE_ROW + step)?;
+            acc = out;
+        }
+        layouter.constrain_instance(start.cell(), config.instance, START_ROW)?;
+        layouter.constrain_instance(acc.cell(), config.instance, RESULT_ROW)
+    }
+}
+
+mod tests {
+    use super::{cond_fold, CondHashCircuit};
+    use crate::coverage::{assert_mock_satisfied, mock_verify};
+    use halo2_proofs::{circuit::Value, pasta::Fp};
+
+    #[test]
+    fn test() {
+        let start = Fp::from(3);
+        let inputs: Vec<Fp> = (10..14u64).map(Fp::from).collect();
+        let enables = [true, false, true, false];
+        let result = cond_fold(start, &inputs, &enables);
+        assert_eq!(
+            result,
+            cond_fold(start, &[inputs[0], inputs[2]], &[true, true])
+        );
+
+        let circuit = CondHashCircuit::new(start, &inputs, &enables).unwrap();
+        assert_mock_satisfied(
+            10,
+            &circuit,
+            CondHashCircuit::instances(start, result, &enables),
+        );
+
+        // A disabled step passes the accumulator through, so claiming it hashed fails, as does
+        // skipping an enabled one.
+        for claimed in [[true; 4], [false, false, true, false]] {
+            assert!(mock_verify(
+                10,
+                &circuit,
+                CondHashCircuit::instances(start, cond_fold(start, &inputs, &claimed), &claimed)
+            )
+            .unwrap()
+            .is_err());
+        }
+
+        // An enable bit of 2 is rejected even with the output its select would give.
+        let mut circuit = CondHashCircuit::new(start, &inputs[..1], &[true]).unwrap();
+        let two = Fp::from(2);
+        circuit.enables[0] = Value::known(two);
+        let hashed = cond_fold(start, &inputs[..1], &[true]);
+        let instances = vec![vec![start, start + two * (hashed - start), two], vec![]];
+        assert!(mock_verify(10, &circuit, instances).unwrap().is_err());
+    }
+}
@@ -0,0 +1,210 @@
+/*
+Dual-root statement: one private element is a member of the allowlist tree under R1 and not a member
+of the blocklist under R2, in a single proof. The allowlist is a MerkleTree whose leaves are the
+elements themselves; the blocklist is an IndexedTree, so non-membership is a path under R2 to the
+element's low leaf Poseidon(low, next) together with low < element < next.
+
+The ordering uses ComparatorChip on the shared advice columns: the three values are split into
+range checked limbs and checked strictly increasing, which also bounds the element below 2^254, the
+range an IndexedTree holds. Both paths are proven with MerkleTreeV3Chip under the same domain
+separation.
+
+Instance rows: the allowlist root, then the blocklist root.
+*/
+
+use super::super::chips::comparator::{ComparatorChip, ComparatorConfig};
+use super::super::chips::merkle_v3::{DomainSeparation, MerkleTreeV3Chip, MerkleTreeV3Config};
+use super::super::chips::poseidon::PoseidonChip;
+use crate::error::{self, validate_path};
+use crate::indexed::IndexedTree;
+use crate::tree::MerklePath;
+use halo2_gadgets::poseidon::primitives::P128Pow5T3;
+use halo2_proofs::{circuit::*, pasta::Fp, plonk::*};
+
+pub const ALLOW_ROOT_ROW: usize = 0;
+pub const BLOCK_ROOT_ROW: usize = 1;
+
+#[derive(Debug, Clone)]
+pub struct DualRootConfig {
+    pub merkle: MerkleTreeV3Config,
+    pub comparator: ComparatorConfig,
+}
+
+#[derive(Default)]
+pub struct DualRootCircuit {
+    pub element: Value<Fp>,
+    pub allow_elements: Vec<Value<Fp>>,
+    pub allow_indices: Vec<Value<Fp>>,
+    pub low: Value<Fp>,
+    pub next: Value<Fp>,
+    pub block_elements: Vec<Value<Fp>>,
+    pub block_indices: Vec<Value<Fp>>,
+    pub separation: DomainSeparation,
+}
+
+fn known(values: &[Fp]) -> Vec<Value<Fp>> {
+    values.iter().map(|x| Value::known(*x)).collect()
+}
+
+impl DualRootCircuit {
+    // `allow_path` is the element's path in the allowlist; fails with `Blocked` if the blocklist
+    // holds it. Both trees must use the blocklist's separation.
+    pub fn new(allow_path: &MerklePath, blocklist: &IndexedTree) -> Result<Self, error::Error> {
+        let low = blocklist.low_leaf(allow_path.leaf)?;
+        let circuit = Self {
+            element: Value::known(allow_path.leaf),
+            allow_elements: known(&allow_path.elements),
+            allow_indices: known(&allow_path.indices),
+            low: Value::known(low.value),
+            next: Value::known(low.next),
+            block_elements: known(&low.path.elements),
+            block_indices: known(&low.path.indices),
+            separation: blocklist.separation(),
+        };
+        validate_path(
+            circuit.allow_elements.len(),
+            &circuit.allow_elements,
+            &circuit.allow_indices,
+        )?;
+        validate_path(
+            circuit.block_elements.len(),
+            &circuit.block_elements,
+            &circuit.block_indices,
+        )?;
+        Ok(circuit)
+    }
+
+    pub fn instances(allow_root: Fp, block_root: Fp) -> Vec<Vec<Fp>> {
+        vec![vec![allow_root, block_root], vec![]]
+    }
+}
+
+impl Circuit<Fp> for DualRootCircuit {
+    type Config = DualRootConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            element: Value::unknown(),
+            allow_elements: vec![Value::unknown(); self.allow_elements.len()],
+            allow_indices: vec![Value::unknown(); self.allow_indices.len()],
+            low: Value::unknown(),
+            next: Value::unknown(),
+            block_elements: vec![Value::unknown(); self.block_elements.len()],
+            block_indices: vec![Value::unknown(); self.block_indices.len()],
+            separation: self.separation,
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let col_a = meta.advice_column();
+        let col_b = meta.advice_column();
+        let col_c = meta.advice_column();
+        let instance = meta.instance_column();
+        DualRootConfig {
+            merkle: MerkleTreeV3Chip::<Fp>::configure(meta, [col_a, col_b, col_c], instance),
+            comparator: ComparatorChip::configure(meta, [col_a, col_b, col_c], instance),
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let poseidon_chip =
+            PoseidonChip::<Fp, P128Pow5T3, 3, 2, 2>::construct(config.merkle.hash_config.clone());
+        let comparator_chip = ComparatorChip::construct(config.comparator);
+        let chip =
+            MerkleTreeV3Chip::<Fp>::construct(config.merkle).with_separation(self.separation);
+
+        comparator_chip.load_table(layouter.namespace(|| "range table"))?;
+        let element = chip.load_private(layouter.namespace(|| "element"), self.element)?;
+        let low = chip.load_private(layouter.namespace(|| "low"), self.low)?;
+        let next = chip.load_private(layouter.namespace(|| "next"), self.next)?;
+
+        let allow_root = chip.merkle_prove(
+            layouter.namespace(|| "allowlist path"),
+            &element,
+            &self.allow_elements,
+            &self.allow_indices,
+        )?;
+
+        let low_leaf = poseidon_chip.hash(
+            layouter.namespace(|| "low leaf"),
+            &[low.clone(), next.clone()],
+        )?;
+        let block_root = chip.merkle_prove(
+            layouter.namespace(|| "blocklist path"),
+            &low_leaf,
+            &self.block_elements,
+            &self.block_indices,
+        )?;
+        comparator_chip.assert_strictly_increasing(
+            layouter.namespace(|| "low < element < next"),
+            &[low, element, next],
+        )?;
+
+        chip.expose_public(
+            layouter.namespace(|| "allowlist root"),
+            &allow_root.cell,
+            ALLOW_ROOT_ROW,
+        )?;
+        chip.expose_public(
+            layouter.namespace(|| "blocklist root"),
+            &block_root.cell,
+            BLOCK_ROOT_ROW,
+        )
+    }
+}
+
+mod tests {
+    use super::DualRootCircuit;
+    use crate::chips::merkle::DomainSeparation;
+    use crate::coverage::{assert_mock_satisfied, mock_verify};
+    use crate::error::Error;
+    use crate::indexed::IndexedTree;
+    use crate::tree::MerkleTree;
+    use halo2_proofs::{circuit::Value, pasta::Fp};
+
+    #[test]
+    fn test() {
+        let separation = DomainSeparation::Level;
+        let allowed: Vec<Fp> = [12u64, 25, 33, 47].iter().map(|x| Fp::from(*x)).collect();
+        let blocked: Vec<Fp> = [25u64, 30, 50].iter().map(|x| Fp::from(*x)).collect();
+        let allowlist = MerkleTree::new(3, &allowed, separation).unwrap();
+        let blocklist = IndexedTree::new(3, &blocked, separation).unwrap();
+        let instances = DualRootCircuit::instances(allowlist.root(), blocklist.root());
+
+        // 12 sits below every blocked value, 33 between two of them.
+        for index in [0, 2] {
+            let circuit =
+                DualRootCircuit::new(&allowlist.path(index).unwrap(), &blocklist).unwrap();
+            assert_mock_satisfied(11, &circuit, instances.clone());
+        }
+
+        // A blocked element has no low leaf, and its own leaf (25, 30) can't stand in.
+        let path = allowlist.path(1).unwrap();
+        assert!(matches!(
+            DualRootCircuit::new(&path, &blocklist),
+            Err(Error::Blocked)
+        ));
+        let mut circuit = DualRootCircuit::new(&allowlist.path(0).unwrap(), &blocklist).unwrap();
+        let low = blocklist.low_leaf(Fp::from(26)).unwrap();
+        circuit.element = Value::known(path.leaf);
+        circuit.allow_elements = path.elements.iter().map(|x| Value::known(*x)).collect();
+        circuit.allow_indices = path.indices.iter().map(|x| Value::known(*x)).collect();
+        circuit.low = Value::known(low.value);
+        circuit.next = Value::known(low.next);
+        circuit.block_elements = low.path.elements.iter().map(|x| Value::known(*x)).collect();
+        circuit.block_indices = low.path.indices.iter().map(|x| Value::known(*x)).collect();
+        assert!(mock_verify(11, &circuit, instances.clone())
+            .unwrap()
+            .is_err());
+
+        // The roots are bound: swapping them fails.
+        let circuit = DualRootCircuit::new(&allowlist.path(2).unwrap(), &blocklist).unwrap();
+        let swapped = DualRootCircuit::instances(blocklist.root(), allowlist.root());
+        assert!(mock_verify(11, &circuit, swapped).unwrap().is_err());
+    }
+}
@@ -0,0 +1,220 @@
+/*
+Epoch-bound membership, for credentials that expire: the leaf commits to a value together with its
+validity window, leaf = Poseidon(value, not_before, not_after), and besides the path the circuit
+checks the public current epoch against the window with WindowChip. The value and the window stay
+private; the verifier learns only that some leaf under the root is valid at that epoch.
+
+Epochs are u64 (block heights, days, ...) and the window is inclusive at both ends. Revoking a
+credential early still means updating the tree.
+
+Instance rows: the root, then the epoch.
+*/
+
+use super::super::chips::merkle_v3::{DomainSeparation, MerkleTreeV3Chip, MerkleTreeV3Config};
+use super::super::chips::poseidon::PoseidonChip;
+use super::super::chips::window::{WindowChip, WindowConfig};
+use crate::error::{self, validate_path};
+use crate::tree::MerklePath;
+use halo2_gadgets::poseidon::primitives::{
+    self as poseidon, ConstantLength, P128Pow5T3 as OrchardNullifier,
+};
+use halo2_proofs::{circuit::*, pasta::Fp, plonk::*};
+
+pub const ROOT_ROW: usize = 0;
+pub const EPOCH_ROW: usize = 1;
+
+pub fn epoch_leaf(value: Fp, not_before: u64, not_after: u64) -> Fp {
+    poseidon::Hash::<_, OrchardNullifier, ConstantLength<3>, 3, 2>::init().hash([
+        value,
+        Fp::from(not_before),
+        Fp::from(not_after),
+    ])
+}
+
+#[derive(Debug, Clone)]
+pub struct EpochMembershipConfig {
+    pub merkle: MerkleTreeV3Config,
+    pub window: WindowConfig,
+}
+
+#[derive(Default)]
+pub struct EpochMembershipCircuit {
+    pub value: Value<Fp>,
+    pub not_before: Value<Fp>,
+    pub not_after: Value<Fp>,
+    pub epoch: Value<Fp>,
+    pub elements: Vec<Value<Fp>>,
+    pub indices: Vec<Value<Fp>>,
+    pub separation: DomainSeparation,
+}
+
+impl EpochMembershipCircuit {
+    // `path` is the path of `epoch_leaf(value, not_before, not_after)`.
+    pub fn new(
+        value: Fp,
+        not_before: u64,
+        not_after: u64,
+        epoch: u64,
+        path: &MerklePath,
+    ) -> Result<Self, error::Error> {
+        if path.leaf != epoch_leaf(value, not_before, not_after) {
+            return Err(error::Error::LeafMismatch);
+        }
+        if epoch < not_before || epoch > not_after {
+            return Err(error::Error::OutsideWindow {
+                epoch,
+                from: not_before,
+                to: not_after,
+            });
+        }
+        let elements: Vec<Value<Fp>> = path.elements.iter().map(|x| Value::known(*x)).collect();
+        let indices: Vec<Value<Fp>> = path.indices.iter().map(|x| Value::known(*x)).collect();
+        validate_path(elements.len(), &elements, &indices)?;
+        Ok(Self {
+            value: Value::known(value),
+            not_before: Value::known(Fp::from(not_before)),
+            not_after: Value::known(Fp::from(not_after)),
+            epoch: Value::known(Fp::from(epoch)),
+            elements,
+            indices,
+            separation: DomainSeparation::Legacy,
+        })
+    }
+
+    pub fn with_separation(mut self, separation: DomainSeparation) -> Self {
+        self.separation = separation;
+        self
+    }
+
+    pub fn instances(root: Fp, epoch: u64) -> Vec<Vec<Fp>> {
+        vec![vec![root, Fp::from(epoch)], vec![]]
+    }
+}
+
+impl Circuit<Fp> for EpochMembershipCircuit {
+    type Config = EpochMembershipConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            value: Value::unknown(),
+            not_before: Value::unknown(),
+            not_after: Value::unknown(),
+            epoch: Value::unknown(),
+            elements: vec![Value::unknown(); self.elements.len()],
+            indices: vec![Value::unknown(); self.indices.len()],
+            separation: self.separation,
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let col_a = meta.advice_column();
+        let col_b = meta.advice_column();
+        let col_c = meta.advice_column();
+        let instance = meta.instance_column();
+        EpochMembershipConfig {
+            merkle: MerkleTreeV3Chip::<Fp>::configure(meta, [col_a, col_b, col_c], instance),
+            window: WindowChip::configure(meta, [col_a, col_b, col_c], instance),
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let poseidon_chip = PoseidonChip::<Fp, OrchardNullifier, 3, 2, 2>::construct(
+            config.merkle.hash_config.clone(),
+        );
+        let window_chip = WindowChip::construct(config.window);
+        let chip =
+            MerkleTreeV3Chip::<Fp>::construct(config.merkle).with_separation(self.separation);
+
+        window_chip.load_table(layouter.namespace(|| "range table"))?;
+        let value = chip.load_private(layouter.namespace(|| "value"), self.value)?;
+        let not_before = chip.load_private(layouter.namespace(|| "not before"), self.not_before)?;
+        let not_after = chip.load_private(layouter.namespace(|| "not after"), self.not_after)?;
+        let epoch = chip.load_private(layouter.namespace(|| "epoch"), self.epoch)?;
+
+        let leaf = poseidon_chip.hash_message::<3>(
+            layouter.namespace(|| "leaf"),
+            &[value, not_before.clone(), not_after.clone()],
+        )?;
+        window_chip.check(
+            layouter.namespace(|| "window"),
+            &not_before,
+            &epoch,
+            &not_after,
+        )?;
+        let root = chip.merkle_prove(
+            layouter.namespace(|| "path"),
+            &leaf,
+            &self.elements,
+            &self.indices,
+        )?;
+
+        chip.expose_public(layouter.namespace(|| "root"), &root.cell, ROOT_ROW)?;
+        chip.expose_public(layouter.namespace(|| "epoch"), &epoch, EPOCH_ROW)
+    }
+}
+
+mod tests {
+    use super::{epoch_leaf, EpochMembershipCircuit};
+    use crate::chips::merkle::DomainSeparation;
+    use crate::coverage::{assert_mock_satisfied, mock_verify};
+    use crate::error::Error;
+    use crate::tree::MerkleTree;
+    use halo2_proofs::{circuit::Value, pasta::Fp};
+
+    #[test]
+    fn test() {
+        let windows = [(100u64, 200u64), (150, 150), (0, u64::MAX)];
+        let leaves: Vec<Fp> = windows
+            .iter()
+            .enumerate()
+            .map(|(i, (from, to))| epoch_leaf(Fp::from(i as u64 + 7), *from, *to))
+            .collect();
+        let tree = MerkleTree::new(3, &leaves, DomainSeparation::Level).unwrap();
+        let path = tree.path(0).unwrap();
+        let value = Fp::from(7);
+
+        // Both ends of the window are inclusive.
+        for epoch in [100, 150, 200] {
+            let circuit = EpochMembershipCircuit::new(value, 100, 200, epoch, &path)
+                .unwrap()
+                .with_separation(DomainSeparation::Level);
+            let instances = EpochMembershipCircuit::instances(tree.root(), epoch);
+            assert_mock_satisfied(10, &circuit, instances);
+        }
+        let path = tree.path(2).unwrap();
+        let circuit = EpochMembershipCircuit::new(Fp::from(9), 0, u64::MAX, u64::MAX, &path)
+            .unwrap()
+            .with_separation(DomainSeparation::Level);
+        let instances = EpochMembershipCircuit::instances(tree.root(), u64::MAX);
+        assert_mock_satisfied(10, &circuit, instances);
+
+        // An epoch on either side of the window fails in-circuit, even with a consistent witness.
+        let path = tree.path(0).unwrap();
+        for epoch in [99, 201] {
+            let mut circuit = EpochMembershipCircuit::new(value, 100, 200, 150, &path)
+                .unwrap()
+                .with_separation(DomainSeparation::Level);
+            circuit.epoch = Value::known(Fp::from(epoch));
+            let instances = EpochMembershipCircuit::instances(tree.root(), epoch);
+            assert!(mock_verify(10, &circuit, instances).unwrap().is_err());
+            assert!(matches!(
+                EpochMembershipCircuit::new(value, 100, 200, epoch, &path),
+                Err(Error::OutsideWindow {
+                    from: 100,
+                    to: 200,
+                    ..
+                })
+            ));
+        }
+
+        assert!(matches!(
+            EpochMembershipCircuit::new(value, 100, 201, 150, &path),
+            Err(Error::LeafMismatch)
+        ));
+    }
+}
@@ -0,0 +1,197 @@
+/*
+Forest membership: proves that a private leaf is in one of N trees whose roots are public, without
+necessarily revealing which. The path is evaluated once with MerkleTreeV3Chip and its root is bound
+to the root picked out by a one-hot selector (see ForestChip).
+
+Instance rows: the N roots, then, if `public_selector` is set, the N selector bits.
+*/
+
+use super::super::chips::forest::{ForestChip, ForestConfig};
+use super::super::chips::merkle_v3::{MerkleTreeV3Chip, MerkleTreeV3Config};
+use crate::error::{self, validate_path};
+use crate::tree::MerklePath;
+use halo2_proofs::{circuit::*, pasta::Fp, plonk::*};
+
+#[derive(Debug, Clone)]
+pub struct ForestCircuitConfig {
+    pub merkle: MerkleTreeV3Config,
+    pub forest: ForestConfig,
+}
+
+#[derive(Default)]
+pub struct ForestCircuit {
+    pub leaf: Value<Fp>,
+    pub elements: Vec<Value<Fp>>,
+    pub indices: Vec<Value<Fp>>,
+    pub tree: Value<usize>,
+    pub trees: usize,
+    pub public_selector: bool,
+}
+
+impl ForestCircuit {
+    // Proves that `leaf` sits at `path` in tree number `tree` of a forest of `trees` trees.
+    pub fn new(
+        leaf: Fp,
+        path: &MerklePath,
+        tree: usize,
+        trees: usize,
+    ) -> Result<Self, error::Error> {
+        if tree >= trees {
+            return Err(error::Error::IndexOutOfRange {
+                index: tree,
+                leaves: trees,
+            });
+        }
+        let elements: Vec<Value<Fp>> = path.elements.iter().map(|x| Value::known(*x)).collect();
+        let indices: Vec<Value<Fp>> = path.indices.iter().map(|x| Value::known(*x)).collect();
+        validate_path(elements.len(), &elements, &indices)?;
+        Ok(Self {
+            leaf: Value::known(leaf),
+            elements,
+            indices,
+            tree: Value::known(tree),
+            trees,
+            public_selector: false,
+        })
+    }
+
+    // Also exposes which tree the leaf is in.
+    pub fn with_public_selector(mut self) -> Self {
+        self.public_selector = true;
+        self
+    }
+
+    // The instance columns for `roots`; `tree` is required iff the selector is public.
+    pub fn instances(roots: &[Fp], tree: Option<usize>) -> Result<Vec<Vec<Fp>>, error::Error> {
+        let mut column = roots.to_vec();
+        if let Some(tree) = tree {
+            if tree >= roots.len() {
+                return Err(error::Error::IndexOutOfRange {
+                    index: tree,
+                    leaves: roots.len(),
+                });
+            }
+            column.extend((0..roots.len()).map(|j| Fp::from((j == tree) as u64)));
+        }
+        Ok(vec![column, vec![]])
+    }
+}
+
+impl Circuit<Fp> for ForestCircuit {
+    type Config = ForestCircuitConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            leaf: Value::unknown(),
+            elements: vec![Value::unknown(); self.elements.len()],
+            indices: vec![Value::unknown(); self.indices.len()],
+            tree: Value::unknown(),
+            trees: self.trees,
+            public_selector: self.public_selector,
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let col_a = meta.advice_column();
+        let col_b = meta.advice_column();
+        let col_c = meta.advice_column();
+        let col_d = meta.advice_column();
+        let instance = meta.instance_column();
+        ForestCircuitConfig {
+            merkle: MerkleTreeV3Chip::<Fp>::configure(meta, [col_a, col_b, col_c], instance),
+            forest: ForestChip::configure(meta, [col_a, col_b, col_c, col_d], instance),
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let merkle_chip = MerkleTreeV3Chip::<Fp>::construct(config.merkle);
+        let forest_chip = ForestChip::construct(config.forest);
+
+        let leaf_cell = merkle_chip.load_private(layouter.namespace(|| "load leaf"), self.leaf)?;
+        let root = merkle_chip.merkle_prove(
+            layouter.namespace(|| "merkle_prove"),
+            &leaf_cell,
+            &self.elements,
+            &self.indices,
+        )?;
+
+        let bits: Vec<Value<Fp>> = (0..self.trees)
+            .map(|j| self.tree.map(|tree| Fp::from((j == tree) as u64)))
+            .collect();
+        let selection = forest_chip.select(layouter.namespace(|| "select root"), &bits, 0)?;
+        forest_chip.constrain_equal(
+            layouter.namespace(|| "bind root"),
+            &root.cell,
+            &selection.root,
+        )?;
+        if self.public_selector {
+            for (j, bit) in selection.bits.iter().enumerate() {
+                forest_chip.expose_public(
+                    layouter.namespace(|| format!("selector {}", j)),
+                    bit,
+                    self.trees + j,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+mod tests {
+    use super::ForestCircuit;
+    use crate::chips::merkle::DomainSeparation;
+    use crate::coverage::{assert_mock_satisfied, mock_verify};
+    use crate::tree::MerkleTree;
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let forest: Vec<MerkleTree> = (0..3u64)
+            .map(|t| {
+                let leaves: Vec<Fp> = (0..8u64).map(|i| Fp::from(100 * t + i)).collect();
+                MerkleTree::new(3, &leaves, DomainSeparation::Legacy).unwrap()
+            })
+            .collect();
+        let roots: Vec<Fp> = forest.iter().map(|tree| tree.root()).collect();
+
+        let leaf = Fp::from(205);
+        let path = forest[2].path(5).unwrap();
+        let circuit = ForestCircuit::new(leaf, &path, 2, 3).unwrap();
+        assert_mock_satisfied(
+            10,
+            &circuit,
+            ForestCircuit::instances(&roots, None).unwrap(),
+        );
+
+        // The leaf is in none of the published trees once its root is swapped out.
+        let mut other = roots.clone();
+        other[2] = roots[0];
+        assert!(mock_verify(
+            10,
+            &circuit,
+            ForestCircuit::instances(&other, None).unwrap()
+        )
+        .unwrap()
+        .is_err());
+
+        // Claiming the wrong tree fails, whether in the witness or in the public selector.
+        let wrong = ForestCircuit::new(leaf, &path, 1, 3).unwrap();
+        assert!(
+            mock_verify(10, &wrong, ForestCircuit::instances(&roots, None).unwrap())
+                .unwrap()
+                .is_err()
+        );
+
+        let circuit = circuit.with_public_selector();
+        let instances = ForestCircuit::instances(&roots, Some(2)).unwrap();
+        assert_mock_satisfied(10, &circuit, instances);
+        let instances = ForestCircuit::instances(&roots, Some(0)).unwrap();
+        assert!(mock_verify(10, &circuit, instances).unwrap().is_err());
+        assert!(ForestCircuit::instances(&roots, Some(3)).is_err());
+    }
+}
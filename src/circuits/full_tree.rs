@@ -0,0 +1,194 @@
+/*
+Full-tree root: all 2^depth leaves are private witnesses and the public root is proven to be their
+Merkle root, e.g. to commit to a whole small set at once. Every internal node is hashed with the
+same Poseidon config; under Legacy separation the nodes share one assigned initial state through
+`hash_from`, under Level each layer hashes (level, left, right) with one constant tag per layer,
+loaded by the MerkleTreeV3Chip the config belongs to.
+
+The cost is 2^depth - 1 Poseidon hashes, so depth is capped at MAX_DEPTH (1023 hashes, k = 17).
+Leaves past those supplied are zero, so the root matches `MerkleTree::new(depth, leaves, ..)`.
+
+Instance rows: the root.
+*/
+
+use super::super::chips::merkle_v3::{DomainSeparation, MerkleTreeV3Chip, MerkleTreeV3Config};
+use super::super::chips::poseidon::PoseidonChip;
+use crate::error;
+use halo2_gadgets::poseidon::primitives::P128Pow5T3 as OrchardNullifier;
+use halo2_proofs::{circuit::*, pasta::Fp, plonk::*};
+
+pub const MAX_DEPTH: usize = 10;
+pub const ROOT_ROW: usize = 0;
+
+#[derive(Default)]
+pub struct FullTreeCircuit {
+    pub leaves: Vec<Value<Fp>>,
+    pub separation: DomainSeparation,
+}
+
+impl FullTreeCircuit {
+    pub fn new(
+        depth: usize,
+        leaves: &[Fp],
+        separation: DomainSeparation,
+    ) -> Result<Self, error::Error> {
+        if depth == 0 {
+            return Err(error::Error::EmptyPath);
+        }
+        if depth > MAX_DEPTH {
+            return Err(error::Error::DepthTooLarge {
+                max: MAX_DEPTH,
+                depth,
+            });
+        }
+        let capacity = 1 << depth;
+        if leaves.len() > capacity {
+            return Err(error::Error::TooManyLeaves {
+                capacity,
+                leaves: leaves.len(),
+            });
+        }
+        let mut padded: Vec<Value<Fp>> = leaves.iter().map(|x| Value::known(*x)).collect();
+        padded.resize(capacity, Value::known(Fp::zero()));
+        Ok(Self {
+            leaves: padded,
+            separation,
+        })
+    }
+
+    pub fn instances(root: Fp) -> Vec<Vec<Fp>> {
+        vec![vec![root], vec![]]
+    }
+
+    // Hashes a full leaf layer up to its root. Public so that circuits which constrain the leaves
+    // further can commit to them the same way.
+    pub fn root(
+        config: &MerkleTreeV3Config,
+        mut layouter: impl Layouter<Fp>,
+        separation: DomainSeparation,
+        leaves: &[AssignedCell<Fp, Fp>],
+    ) -> Result<AssignedCell<Fp, Fp>, Error> {
+        if leaves.len() < 2 || !leaves.len().is_power_of_two() {
+            return Err(Error::Synthesis);
+        }
+        let merkle_chip = MerkleTreeV3Chip::<Fp>::construct(config.clone());
+        let chip =
+            PoseidonChip::<Fp, OrchardNullifier, 3, 2, 2>::construct(config.hash_config.clone());
+        let initial = match separation {
+            DomainSeparation::Legacy => {
+                Some(chip.initial_state::<2>(layouter.namespace(|| "initial state"))?)
+            }
+            DomainSeparation::Level => None,
+        };
+        let mut layer = leaves.to_vec();
+        let mut level = 0u64;
+        while layer.len() > 1 {
+            let tag = match separation {
+                DomainSeparation::Legacy => None,
+                DomainSeparation::Level => Some(merkle_chip.load_constant(
+                    layouter.namespace(|| format!("level {}", level)),
+                    Fp::from(level),
+                )?),
+            };
+            layer = layer
+                .chunks(2)
+                .enumerate()
+                .map(|(i, pair)| {
+                    let layouter = layouter.namespace(|| format!("node {} {}", level, i));
+                    match (&initial, &tag) {
+                        (Some(initial), _) => {
+                            chip.hash_from(layouter, initial, &[pair[0].clone(), pair[1].clone()])
+                        }
+                        (None, Some(tag)) => chip.hash_message::<3>(
+                            layouter,
+                            &[tag.clone(), pair[0].clone(), pair[1].clone()],
+                        ),
+                        (None, None) => Err(Error::Synthesis),
+                    }
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            level += 1;
+        }
+        layer.pop().ok_or(Error::Synthesis)
+    }
+}
+
+impl Circuit<Fp> for FullTreeCircuit {
+    type Config = MerkleTreeV3Config;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            leaves: vec![Value::unknown(); self.leaves.len()],
+            separation: self.separation,
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let col_a = meta.advice_column();
+        let col_b = meta.advice_column();
+        let col_c = meta.advice_column();
+        let instance = meta.instance_column();
+        MerkleTreeV3Chip::<Fp>::configure(meta, [col_a, col_b, col_c], instance)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let chip = MerkleTreeV3Chip::<Fp>::construct(config.clone());
+        let leaves = self
+            .leaves
+            .iter()
+            .enumerate()
+            .map(|(i, leaf)| chip.load_private(layouter.namespace(|| format!("leaf {}", i)), *leaf))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let root = Self::root(
+            &config,
+            layouter.namespace(|| "tree"),
+            self.separation,
+            &leaves,
+        )?;
+        chip.expose_public(layouter.namespace(|| "root"), &root, ROOT_ROW)
+    }
+}
+
+mod tests {
+    use super::FullTreeCircuit;
+    use crate::chips::merkle::DomainSeparation;
+    use crate::coverage::{assert_mock_satisfied, mock_verify};
+    use crate::error::Error;
+    use crate::tree::MerkleTree;
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let leaves: Vec<Fp> = (1..=6u64).map(Fp::from).collect();
+        for separation in [DomainSeparation::Legacy, DomainSeparation::Level] {
+            let root = MerkleTree::new(3, &leaves, separation).unwrap().root();
+            let circuit = FullTreeCircuit::new(3, &leaves, separation).unwrap();
+            assert_mock_satisfied(10, &circuit, FullTreeCircuit::instances(root));
+
+            let mut changed = leaves.clone();
+            changed[5] = Fp::from(7);
+            let circuit = FullTreeCircuit::new(3, &changed, separation).unwrap();
+            assert!(mock_verify(10, &circuit, FullTreeCircuit::instances(root))
+                .unwrap()
+                .is_err());
+        }
+
+        assert!(matches!(
+            FullTreeCircuit::new(2, &leaves, DomainSeparation::Legacy),
+            Err(Error::TooManyLeaves { capacity: 4, .. })
+        ));
+        assert!(matches!(
+            FullTreeCircuit::new(11, &leaves, DomainSeparation::Legacy),
+            Err(Error::DepthTooLarge { max: 10, depth: 11 })
+        ));
+        assert!(matches!(
+            FullTreeCircuit::new(0, &[], DomainSeparation::Legacy),
+            Err(Error::EmptyPath)
+        ));
+    }
+}
@@ -3,7 +3,7 @@ use super::super::chips::hash_1::{Hash1Chip, Hash1Config};
 use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*};
 
 #[derive(Default)]
-struct Hash1Circuit<F> {
+pub struct Hash1Circuit<F> {
     pub a: Value<F>,
 }
 
@@ -36,7 +36,8 @@ impl<F: FieldExt> Circuit<F> for Hash1Circuit<F> {
 
 mod tests {
     use super::Hash1Circuit;
-    use halo2_proofs::{circuit::Value, dev::MockProver, pasta::Fp};
+    use crate::coverage::mock_verify;
+    use halo2_proofs::{circuit::Value, pasta::Fp};
 
     #[test]
     fn test() {
@@ -44,7 +45,9 @@ mod tests {
         let a = Value::known(Fp::from(2));
         let public_inputs = vec![Fp::from(4)];
         let circuit = Hash1Circuit { a };
-        let prover = MockProver::run(k, &circuit, vec![public_inputs.clone()]).unwrap();
-        assert_eq!(prover.verify(), Ok(()));
+        assert_eq!(
+            mock_verify(k, &circuit, vec![public_inputs.clone()]).unwrap(),
+            Ok(())
+        );
     }
 }
@@ -3,7 +3,7 @@ use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*, poly::Rotation};
 use std::marker::PhantomData;
 
 #[derive(Default)]
-struct Hash2Circuit<F> {
+pub struct Hash2Circuit<F> {
     pub a: Value<F>,
     pub b: Value<F>,
 }
@@ -39,7 +39,8 @@ impl<F: FieldExt> Circuit<F> for Hash2Circuit<F> {
 
 mod tests {
     use super::Hash2Circuit;
-    use halo2_proofs::{circuit::Value, dev::MockProver, pasta::Fp};
+    use crate::coverage::mock_verify;
+    use halo2_proofs::{circuit::Value, pasta::Fp};
 
     #[test]
     fn test() {
@@ -48,7 +49,9 @@ mod tests {
         let b = Value::known(Fp::from(7));
         let public_inputs = vec![Fp::from(9)];
         let circuit = Hash2Circuit { a, b };
-        let prover = MockProver::run(k, &circuit, vec![public_inputs.clone()]).unwrap();
-        assert_eq!(prover.verify(), Ok(()));
+        assert_eq!(
+            mock_verify(k, &circuit, vec![public_inputs.clone()]).unwrap(),
+            Ok(())
+        );
     }
 }
@@ -0,0 +1,185 @@
+/*
+Single-index diff: proves two roots R1 and R2, e.g. the same tree in consecutive epochs, hold the
+same leaf at every position except a public index i. A light client that already trusts R1 can then
+accept R2 as a single-slot change without re-verifying the whole tree.
+
+Both roots are computed with MerkleTreeV3Chip from a private leaf each (the old and the new value at
+i) along one path: the siblings of the two evaluations are constrained equal and the index bits of
+both are bound to the same instance rows, so the subtrees hanging off the path, and with them every
+other leaf, are shared. The two leaves may be equal, in which case R1 = R2.
+
+Instance rows: R1, R2, then the bits of i (leaf layer first).
+*/
+
+use super::super::chips::merkle_v3::{DomainSeparation, MerkleTreeV3Chip, MerkleTreeV3Config};
+use crate::encoding::index_bits;
+use crate::error::{self, validate_path};
+use crate::tree::MerklePath;
+use halo2_proofs::{circuit::*, pasta::Fp, plonk::*};
+
+pub const OLD_ROOT_ROW: usize = 0;
+pub const NEW_ROOT_ROW: usize = 1;
+const FIRST_INDEX_ROW: usize = 2;
+
+#[derive(Default)]
+pub struct IndexDiffCircuit {
+    pub old_leaf: Value<Fp>,
+    pub new_leaf: Value<Fp>,
+    pub elements: Vec<Value<Fp>>,
+    pub indices: Vec<Value<Fp>>,
+    pub separation: DomainSeparation,
+}
+
+impl IndexDiffCircuit {
+    // `path` is the path of index i under R1; `new_leaf` is the value at i under R2.
+    pub fn new(
+        path: &MerklePath,
+        new_leaf: Fp,
+        separation: DomainSeparation,
+    ) -> Result<Self, error::Error> {
+        let elements: Vec<Value<Fp>> = path.elements.iter().map(|x| Value::known(*x)).collect();
+        let indices: Vec<Value<Fp>> = path.indices.iter().map(|x| Value::known(*x)).collect();
+        validate_path(elements.len(), &elements, &indices)?;
+        Ok(Self {
+            old_leaf: Value::known(path.leaf),
+            new_leaf: Value::known(new_leaf),
+            elements,
+            indices,
+            separation,
+        })
+    }
+
+    pub fn instances(
+        old_root: Fp,
+        new_root: Fp,
+        index: u64,
+        depth: usize,
+    ) -> Result<Vec<Vec<Fp>>, error::Error> {
+        let mut column = vec![old_root, new_root];
+        column.extend(index_bits::<Fp>(index, depth)?);
+        Ok(vec![column, vec![]])
+    }
+}
+
+impl Circuit<Fp> for IndexDiffCircuit {
+    type Config = MerkleTreeV3Config;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            old_leaf: Value::unknown(),
+            new_leaf: Value::unknown(),
+            elements: vec![Value::unknown(); self.elements.len()],
+            indices: vec![Value::unknown(); self.indices.len()],
+            separation: self.separation,
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let col_a = meta.advice_column();
+        let col_b = meta.advice_column();
+        let col_c = meta.advice_column();
+        let instance = meta.instance_column();
+        MerkleTreeV3Chip::<Fp>::configure(meta, [col_a, col_b, col_c], instance)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let chip = MerkleTreeV3Chip::<Fp>::construct(config).with_separation(self.separation);
+
+        let old_leaf = chip.load_private(layouter.namespace(|| "old leaf"), self.old_leaf)?;
+        let new_leaf = chip.load_private(layouter.namespace(|| "new leaf"), self.new_leaf)?;
+        let old_root = chip.merkle_prove(
+            layouter.namespace(|| "old path"),
+            &old_leaf,
+            &self.elements,
+            &self.indices,
+        )?;
+        let new_root = chip.merkle_prove(
+            layouter.namespace(|| "new path"),
+            &new_leaf,
+            &self.elements,
+            &self.indices,
+        )?;
+
+        for (level, (old, new)) in old_root
+            .elements
+            .iter()
+            .zip(new_root.elements.iter())
+            .enumerate()
+        {
+            chip.constrain_equal(
+                layouter.namespace(|| format!("sibling {}", level)),
+                old,
+                new,
+            )?;
+        }
+        for (level, (old, new)) in old_root
+            .indices
+            .iter()
+            .zip(new_root.indices.iter())
+            .enumerate()
+        {
+            chip.expose_public(
+                layouter.namespace(|| format!("old index bit {}", level)),
+                old,
+                FIRST_INDEX_ROW + level,
+            )?;
+            chip.expose_public(
+                layouter.namespace(|| format!("new index bit {}", level)),
+                new,
+                FIRST_INDEX_ROW + level,
+            )?;
+        }
+        chip.expose_public(
+            layouter.namespace(|| "old root"),
+            &old_root.cell,
+            OLD_ROOT_ROW,
+        )?;
+        chip.expose_public(
+            layouter.namespace(|| "new root"),
+            &new_root.cell,
+            NEW_ROOT_ROW,
+        )
+    }
+}
+
+mod tests {
+    use super::IndexDiffCircuit;
+    use crate::chips::merkle::DomainSeparation;
+    use crate::coverage::{assert_mock_satisfied, mock_verify};
+    use crate::tree::MerkleTree;
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let leaves: Vec<Fp> = (1..=6u64).map(Fp::from).collect();
+        for separation in [DomainSeparation::Legacy, DomainSeparation::Level] {
+            let epoch_1 = MerkleTree::new(3, &leaves, separation).unwrap();
+            let index = 2u64;
+            let mut epoch_2 = epoch_1.clone();
+            epoch_2.update(index as usize, Fp::from(42)).unwrap();
+
+            let path = epoch_1.path(index as usize).unwrap();
+            let circuit = IndexDiffCircuit::new(&path, Fp::from(42), separation).unwrap();
+            let instances =
+                IndexDiffCircuit::instances(epoch_1.root(), epoch_2.root(), index, 3).unwrap();
+            assert_mock_satisfied(10, &circuit, instances);
+
+            // The change can't be attributed to another index.
+            let instances =
+                IndexDiffCircuit::instances(epoch_1.root(), epoch_2.root(), 3, 3).unwrap();
+            assert!(mock_verify(10, &circuit, instances).unwrap().is_err());
+
+            // A second changed slot makes R2 unreachable from one path.
+            let mut two_slots = epoch_2.clone();
+            two_slots.update(5, Fp::from(43)).unwrap();
+            let instances =
+                IndexDiffCircuit::instances(epoch_1.root(), two_slots.root(), index, 3).unwrap();
+            assert!(mock_verify(10, &circuit, instances).unwrap().is_err());
+        }
+    }
+}
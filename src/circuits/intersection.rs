@@ -0,0 +1,132 @@
+/*
+Common-member proof: proves that one private leaf is a member of both tree A and tree B, e.g. that
+a user is on two allowlists, without revealing the leaf or its positions. The leaf is loaded once
+and the same cell starts both MerkleTreeV3 paths, so the two memberships can't be about different
+leaves. The trees may have different depths.
+
+Instance rows: root A, then root B.
+*/
+
+use super::super::chips::merkle_v3::{MerkleTreeV3Chip, MerkleTreeV3Config};
+use crate::error::{self, validate_path};
+use crate::tree::MerklePath;
+use halo2_proofs::{circuit::*, pasta::Fp, plonk::*};
+
+pub const ROOT_A_ROW: usize = 0;
+pub const ROOT_B_ROW: usize = 1;
+
+#[derive(Default)]
+pub struct IntersectionCircuit {
+    pub leaf: Value<Fp>,
+    pub elements: [Vec<Value<Fp>>; 2],
+    pub indices: [Vec<Value<Fp>>; 2],
+}
+
+impl IntersectionCircuit {
+    // `a` and `b` are the paths of the same leaf in tree A and tree B.
+    pub fn new(a: &MerklePath, b: &MerklePath) -> Result<Self, error::Error> {
+        if a.leaf != b.leaf {
+            return Err(error::Error::LeafMismatch);
+        }
+        let [elements_a, elements_b]: [Vec<Value<Fp>>; 2] =
+            [a, b].map(|path| path.elements.iter().map(|x| Value::known(*x)).collect());
+        let [indices_a, indices_b]: [Vec<Value<Fp>>; 2] =
+            [a, b].map(|path| path.indices.iter().map(|x| Value::known(*x)).collect());
+        validate_path(a.elements.len(), &elements_a, &indices_a)?;
+        validate_path(b.elements.len(), &elements_b, &indices_b)?;
+        Ok(Self {
+            leaf: Value::known(a.leaf),
+            elements: [elements_a, elements_b],
+            indices: [indices_a, indices_b],
+        })
+    }
+
+    pub fn instances(root_a: Fp, root_b: Fp) -> Vec<Vec<Fp>> {
+        vec![vec![root_a, root_b], vec![]]
+    }
+}
+
+impl Circuit<Fp> for IntersectionCircuit {
+    type Config = MerkleTreeV3Config;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            leaf: Value::unknown(),
+            elements: self
+                .elements
+                .clone()
+                .map(|path| vec![Value::unknown(); path.len()]),
+            indices: self
+                .indices
+                .clone()
+                .map(|path| vec![Value::unknown(); path.len()]),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let col_a = meta.advice_column();
+        let col_b = meta.advice_column();
+        let col_c = meta.advice_column();
+        let instance = meta.instance_column();
+        MerkleTreeV3Chip::<Fp>::configure(meta, [col_a, col_b, col_c], instance)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let chip = MerkleTreeV3Chip::<Fp>::construct(config);
+        let leaf = chip.load_private(layouter.namespace(|| "load leaf"), self.leaf)?;
+        for (tree, row) in [ROOT_A_ROW, ROOT_B_ROW].into_iter().enumerate() {
+            let root = chip.merkle_prove(
+                layouter.namespace(|| format!("path {}", tree)),
+                &leaf,
+                &self.elements[tree],
+                &self.indices[tree],
+            )?;
+            chip.expose_public(
+                layouter.namespace(|| format!("root {}", tree)),
+                &root.cell,
+                row,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+mod tests {
+    use super::IntersectionCircuit;
+    use crate::chips::merkle::DomainSeparation;
+    use crate::coverage::{assert_mock_satisfied, mock_verify};
+    use crate::tree::MerkleTree;
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let list_a: Vec<Fp> = [3u64, 14, 15, 92, 65]
+            .iter()
+            .map(|x| Fp::from(*x))
+            .collect();
+        let list_b: Vec<Fp> = [27u64, 18, 92, 81, 82, 84, 59, 45, 23]
+            .iter()
+            .map(|x| Fp::from(*x))
+            .collect();
+        let tree_a = MerkleTree::new(3, &list_a, DomainSeparation::Legacy).unwrap();
+        let tree_b = MerkleTree::new(4, &list_b, DomainSeparation::Legacy).unwrap();
+
+        let circuit =
+            IntersectionCircuit::new(&tree_a.path(3).unwrap(), &tree_b.path(2).unwrap()).unwrap();
+        let instances = IntersectionCircuit::instances(tree_a.root(), tree_b.root());
+        assert_mock_satisfied(11, &circuit, instances);
+
+        let instances = IntersectionCircuit::instances(tree_a.root(), tree_a.root());
+        assert!(mock_verify(11, &circuit, instances).unwrap().is_err());
+
+        // 14 is only in A, so there is no pair of paths for it.
+        assert!(
+            IntersectionCircuit::new(&tree_a.path(1).unwrap(), &tree_b.path(1).unwrap()).is_err()
+        );
+    }
+}
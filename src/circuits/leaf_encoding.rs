@@ -0,0 +1,147 @@
+use super::super::chips::leaf_encoding::{
+    LeafEncodingChip, LeafEncodingConfig, HASH_BYTES, PACKED_BYTES,
+};
+use crate::error;
+use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*};
+use std::marker::PhantomData;
+
+pub struct LeafEncodingCircuit<F> {
+    pub bytes: Vec<Value<u8>>,
+    _marker: PhantomData<F>,
+}
+
+impl<F: FieldExt> LeafEncodingCircuit<F> {
+    pub fn new(bytes: &[u8]) -> Result<Self, error::Error> {
+        if bytes.len() > PACKED_BYTES {
+            return Err(error::Error::EncodingTooLong {
+                max: PACKED_BYTES,
+                len: bytes.len(),
+            });
+        }
+        Ok(Self {
+            bytes: bytes.iter().map(|b| Value::known(*b)).collect(),
+            _marker: PhantomData,
+        })
+    }
+
+    // A circuit exposing `hash_to_leaf(hash)`, with all 32 bytes of the hash packed. It is only
+    // satisfied for a digest below p.
+    pub fn from_hash(hash: &[u8; HASH_BYTES]) -> Self {
+        Self {
+            bytes: hash.iter().map(|b| Value::known(*b)).collect(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<F: FieldExt> Circuit<F> for LeafEncodingCircuit<F> {
+    type Config = LeafEncodingConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            bytes: vec![Value::unknown(); self.bytes.len()],
+            _marker: PhantomData,
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let col_byte = meta.advice_column();
+        let col_acc = meta.advice_column();
+        let instance = meta.instance_column();
+        LeafEncodingChip::configure(meta, [col_byte, col_acc], instance)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let chip = LeafEncodingChip::construct(config);
+        chip.load_table(layouter.namespace(|| "byte table"))?;
+        let leaf = match <&[Value<u8>; HASH_BYTES]>::try_from(self.bytes.as_slice()) {
+            Ok(hash) => chip.pack_hash(layouter.namespace(|| "pack hash"), hash)?,
+            Err(_) => chip.pack(layouter.namespace(|| "pack leaf"), &self.bytes)?,
+        };
+        chip.expose_public(layouter.namespace(|| "public leaf"), &leaf, 0)
+    }
+}
+
+mod tests {
+    use super::LeafEncodingCircuit;
+    use crate::chips::leaf_encoding::hash_to_leaf;
+    use crate::coverage::{assert_mock_satisfied, mock_verify};
+    use crate::encoding::{encode_address, encode_str, encode_u128, str_chunks};
+    use crate::error;
+    use halo2_proofs::pasta::{group::ff::PrimeField, Fp};
+
+    #[test]
+    fn test() {
+        let mut hash = [0u8; 32];
+        for (i, byte) in hash.iter_mut().enumerate() {
+            *byte = (i as u8).wrapping_mul(37).wrapping_add(255);
+        }
+        // A top byte below 0x40 keeps the digest below p.
+        hash[0] &= 0x3f;
+        let leaf: Fp = hash_to_leaf(&hash).unwrap();
+
+        let circuit = LeafEncodingCircuit::<Fp>::from_hash(&hash);
+        assert_mock_satisfied(9, &circuit, vec![vec![leaf]]);
+
+        // Every byte counts, the last one included.
+        let mut last = hash;
+        last[31] ^= 1;
+        assert_ne!(hash_to_leaf::<Fp>(&last).unwrap(), leaf);
+        assert!(mock_verify(9, &circuit, vec![vec![leaf + Fp::one()]])
+            .unwrap()
+            .is_err());
+
+        // p - 1 is the largest digest with an encoding.
+        let mut max = (-Fp::one()).to_repr();
+        max.reverse();
+        assert_eq!(hash_to_leaf::<Fp>(&max).unwrap(), -Fp::one());
+        let circuit = LeafEncodingCircuit::<Fp>::from_hash(&max);
+        assert_mock_satisfied(9, &circuit, vec![vec![-Fp::one()]]);
+
+        // h and h + p can't share a leaf: h + p is refused natively, and in-circuit it packs to h
+        // but fails the canonical check.
+        let mut small = [0u8; 32];
+        small[31] = 5;
+        let mut shifted = max;
+        shifted[31] += 6;
+        assert_eq!(hash_to_leaf::<Fp>(&small).unwrap(), Fp::from(5));
+        assert!(matches!(
+            hash_to_leaf::<Fp>(&shifted),
+            Err(error::Error::NonCanonical)
+        ));
+        let circuit = LeafEncodingCircuit::<Fp>::from_hash(&small);
+        assert_mock_satisfied(9, &circuit, vec![vec![Fp::from(5)]]);
+        let circuit = LeafEncodingCircuit::<Fp>::from_hash(&shifted);
+        assert!(mock_verify(9, &circuit, vec![vec![Fp::from(5)]])
+            .unwrap()
+            .is_err());
+    }
+
+    #[test]
+    fn test_external_types() {
+        let address = [0xabu8; 20];
+        let circuit = LeafEncodingCircuit::<Fp>::new(&address).unwrap();
+        let leaf: Fp = encode_address(&address);
+        assert_mock_satisfied(9, &circuit, vec![vec![leaf]]);
+
+        let amount = 1_000_000_000_000_000_000u128;
+        let circuit = LeafEncodingCircuit::<Fp>::new(&amount.to_be_bytes()).unwrap();
+        let leaf: Fp = encode_u128(amount);
+        assert_mock_satisfied(9, &circuit, vec![vec![leaf]]);
+
+        let text = "a string that is longer than thirty-one bytes";
+        let chunks: Vec<Fp> = encode_str(text);
+        assert_eq!(chunks[0], Fp::from(text.len() as u64));
+        for (chunk, leaf) in str_chunks(text).zip(chunks.iter().skip(1)) {
+            let circuit = LeafEncodingCircuit::<Fp>::new(chunk).unwrap();
+            assert_mock_satisfied(9, &circuit, vec![vec![*leaf]]);
+        }
+
+        assert!(LeafEncodingCircuit::<Fp>::new(&[0u8; 32]).is_err());
+    }
+}
@@ -0,0 +1,162 @@
+/*
+One membership circuit whose hash is picked at runtime. halo2's `configure` can't see the circuit,
+so both backends are configured on the same advice and instance columns and `hasher` decides which
+one synthesize drives. Only the chosen backend's selectors are enabled, so each hasher still gets
+its own keys, but services handle every tree type through the same circuit type and instance shape.
+*/
+
+use super::super::chips::merkle::{HashInstructions, MerkleChip};
+use super::super::chips::merkle_v2::{MerkleTreeV2Chip, MerkleTreeV2Config};
+use super::super::chips::merkle_v3::{DomainSeparation, MerkleTreeV3Chip, MerkleTreeV3Config};
+use crate::error::{self, validate_path};
+use crate::instance::InstanceLayout;
+use halo2_proofs::{circuit::*, pasta::Fp, plonk::*};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Hasher {
+    // The a + b placeholder hash of MerkleTreeV2.
+    Dummy,
+    #[default]
+    Poseidon,
+}
+
+#[derive(Debug, Clone)]
+pub struct MerkleTreeConfig {
+    pub dummy: MerkleTreeV2Config,
+    pub poseidon: MerkleTreeV3Config,
+}
+
+#[derive(Default)]
+pub struct MerkleTreeCircuit {
+    pub hasher: Hasher,
+    pub leaf: Value<Fp>,
+    pub elements: Vec<Value<Fp>>,
+    pub indices: Vec<Value<Fp>>,
+    pub separation: DomainSeparation,
+    pub expose_leaf: bool,
+}
+
+impl MerkleTreeCircuit {
+    pub fn from_values(
+        hasher: Hasher,
+        leaf: Value<Fp>,
+        elements: Vec<Value<Fp>>,
+        indices: Vec<Value<Fp>>,
+        depth: usize,
+    ) -> Result<Self, error::Error> {
+        validate_path(depth, &elements, &indices)?;
+        Ok(Self {
+            hasher,
+            leaf,
+            elements,
+            indices,
+            separation: DomainSeparation::Legacy,
+            expose_leaf: true,
+        })
+    }
+
+    pub fn with_separation(mut self, separation: DomainSeparation) -> Self {
+        self.separation = separation;
+        self
+    }
+
+    pub fn layout(&self) -> InstanceLayout {
+        InstanceLayout::merkle(self.expose_leaf, true)
+    }
+
+    fn prove<H: HashInstructions<Fp>>(
+        &self,
+        chip: MerkleChip<Fp, H>,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let chip = chip.with_separation(self.separation);
+        let layout = self.layout();
+        let leaf_cell = chip.load_private(layouter.namespace(|| "load leaf"), self.leaf)?;
+        if let Some(row) = layout.leaf {
+            chip.expose_public(layouter.namespace(|| "public leaf"), &leaf_cell, row)?;
+        }
+        let root = chip.merkle_prove(
+            layouter.namespace(|| "merkle_prove"),
+            &leaf_cell,
+            &self.elements,
+            &self.indices,
+        )?;
+        if let Some(row) = layout.root {
+            chip.expose_public(layouter.namespace(|| "public root"), &root.cell, row)?;
+        }
+        Ok(())
+    }
+}
+
+impl Circuit<Fp> for MerkleTreeCircuit {
+    type Config = MerkleTreeConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            hasher: self.hasher,
+            leaf: Value::unknown(),
+            elements: vec![Value::unknown(); self.elements.len()],
+            indices: vec![Value::unknown(); self.indices.len()],
+            separation: self.separation,
+            expose_leaf: self.expose_leaf,
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let col_a = meta.advice_column();
+        let col_b = meta.advice_column();
+        let col_c = meta.advice_column();
+        let instance = meta.instance_column();
+        MerkleTreeConfig {
+            dummy: MerkleTreeV2Chip::configure(meta, [col_a, col_b, col_c], instance),
+            poseidon: MerkleTreeV3Chip::<Fp>::configure(meta, [col_a, col_b, col_c], instance),
+        }
+    }
+
+    fn synthesize(&self, config: Self::Config, layouter: impl Layouter<Fp>) -> Result<(), Error> {
+        match self.hasher {
+            Hasher::Dummy => self.prove(MerkleTreeV2Chip::construct(config.dummy), layouter),
+            Hasher::Poseidon => {
+                self.prove(MerkleTreeV3Chip::<Fp>::construct(config.poseidon), layouter)
+            }
+        }
+    }
+}
+
+mod tests {
+    use super::{Hasher, MerkleTreeCircuit};
+    use crate::chips::merkle_v3::DomainSeparation;
+    use crate::coverage::{assert_mock_satisfied, mock_verify};
+    use crate::tree::MerkleTree;
+    use halo2_proofs::{circuit::Value, pasta::Fp};
+
+    #[test]
+    fn test() {
+        let leaves: Vec<Fp> = (0..12u64).map(Fp::from).collect();
+        let tree = MerkleTree::new(4, &leaves, DomainSeparation::Legacy).unwrap();
+        let path = tree.path(5).unwrap();
+        let elements: Vec<Value<Fp>> = path.elements.iter().map(|x| Value::known(*x)).collect();
+        let indices: Vec<Value<Fp>> = path.indices.iter().map(|x| Value::known(*x)).collect();
+        // The dummy hash is a + b, so the root is the leaf plus every sibling.
+        let dummy_root = path.elements.iter().fold(path.leaf, |acc, x| acc + x);
+
+        for (hasher, root) in [(Hasher::Poseidon, tree.root()), (Hasher::Dummy, dummy_root)] {
+            let circuit = MerkleTreeCircuit::from_values(
+                hasher,
+                Value::known(path.leaf),
+                elements.clone(),
+                indices.clone(),
+                4,
+            )
+            .unwrap();
+            let public_input = vec![path.leaf, root];
+            assert_mock_satisfied(10, &circuit, vec![public_input.clone(), vec![]]);
+
+            let wrong_input = vec![path.leaf, root + Fp::one()];
+            assert!(mock_verify(10, &circuit, vec![wrong_input, vec![]])
+                .unwrap()
+                .is_err());
+        }
+    }
+}
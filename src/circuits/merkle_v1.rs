@@ -1,19 +1,68 @@
-use super::super::chips::merkle_v1::{MerkleTreeV1Chip, MerkleTreeV1Config};
+use super::super::chips::merkle_v1::{LayerInput, MerkleTreeV1Chip, MerkleTreeV1Config};
+use crate::encoding::{index_bits, ToLeaf};
+use crate::error::{self, validate_path};
+use crate::instance::{dummy_membership, InstanceLayout, PublicInputs, PublicInstances};
 use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*};
 
 #[derive(Default)]
-struct MerkleTreeV1Circuit<F> {
+pub struct MerkleTreeV1Circuit<F> {
     pub leaf: Value<F>,
     pub path_elements: Vec<Value<F>>,
     pub path_indices: Vec<Value<F>>,
 }
 
+impl<F: FieldExt> MerkleTreeV1Circuit<F> {
+    pub fn new(leaf: impl ToLeaf<F>, siblings: &[F], index: u64) -> Result<Self, error::Error> {
+        let indices = index_bits(index, siblings.len())?;
+        Self::from_values(
+            Value::known(leaf.to_leaf()),
+            siblings.iter().map(|x| Value::known(*x)).collect(),
+            indices.into_iter().map(Value::known).collect(),
+            siblings.len(),
+        )
+    }
+
+    pub fn from_values(
+        leaf: Value<F>,
+        path_elements: Vec<Value<F>>,
+        path_indices: Vec<Value<F>>,
+        depth: usize,
+    ) -> Result<Self, error::Error> {
+        validate_path(depth, &path_elements, &path_indices)?;
+        Ok(Self {
+            leaf,
+            path_elements,
+            path_indices,
+        })
+    }
+
+    // V1 only exposes the root.
+    pub fn layout(&self) -> InstanceLayout {
+        InstanceLayout::merkle(false, true)
+    }
+}
+
+impl<F: FieldExt> PublicInstances<F> for MerkleTreeV1Circuit<F> {
+    fn instance_layout(&self) -> InstanceLayout {
+        self.layout()
+    }
+
+    fn public_inputs(&self) -> PublicInputs<F> {
+        dummy_membership(self.leaf, &self.path_elements)
+    }
+}
+
 impl<F: FieldExt> Circuit<F> for MerkleTreeV1Circuit<F> {
     type Config = MerkleTreeV1Config;
     type FloorPlanner = SimpleFloorPlanner;
 
+    // Keygen lays the circuit out from this, so the depth has to survive.
     fn without_witnesses(&self) -> Self {
-        Self::default()
+        Self {
+            leaf: Value::unknown(),
+            path_elements: vec![Value::unknown(); self.path_elements.len()],
+            path_indices: vec![Value::unknown(); self.path_indices.len()],
+        }
     }
 
     fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
@@ -30,27 +79,36 @@ impl<F: FieldExt> Circuit<F> for MerkleTreeV1Circuit<F> {
         mut layouter: impl Layouter<F>,
     ) -> Result<(), Error> {
         let chip = MerkleTreeV1Chip::construct(config);
-        let mut digest = chip.assign(
-            layouter.namespace(|| "first row"),
-            self.leaf,
-            self.path_elements[0],
-            self.path_indices[0],
-            None,
-            0,
+        validate_path(
+            self.path_elements.len(),
+            &self.path_elements,
+            &self.path_indices,
         )?;
 
-        for i in 1..self.path_elements.len() {
-            digest = chip.assign(
-                layouter.namespace(|| "next row"),
-                self.leaf,
-                self.path_elements[i],
-                self.path_indices[i],
-                Some(&digest),
-                i as usize,
-            )?;
+        let mut digest: Option<AssignedCell<F, F>> = None;
+        for (i, (element, index)) in self
+            .path_elements
+            .iter()
+            .zip(self.path_indices.iter())
+            .enumerate()
+        {
+            let input = match &digest {
+                None => LayerInput::Leaf(self.leaf),
+                Some(prev_digest) => LayerInput::Digest(prev_digest),
+            };
+            digest = Some(chip.assign(
+                layouter.namespace(|| format!("layer {}", i)),
+                input,
+                *element,
+                *index,
+                i,
+            )?);
         }
+        let digest = digest.ok_or(error::Error::EmptyPath)?;
 
-        chip.expose_public(layouter.namespace(|| "root"), &digest, 0)?;
+        if let Some(row) = self.layout().root {
+            chip.expose_public(layouter.namespace(|| "root"), &digest, row)?;
+        }
 
         Ok(())
     }
@@ -58,7 +116,14 @@ impl<F: FieldExt> Circuit<F> for MerkleTreeV1Circuit<F> {
 
 mod tests {
     use super::MerkleTreeV1Circuit;
-    use halo2_proofs::{circuit::Value, dev::MockProver, pasta::Fp};
+    use crate::coverage::assert_mock_satisfied;
+    use crate::proving::keygen;
+    use halo2_proofs::{
+        circuit::Value,
+        pasta::{EqAffine, Fp},
+        plonk::Circuit,
+        poly::commitment::Params,
+    };
 
     #[test]
     fn test() {
@@ -74,7 +139,13 @@ mod tests {
         };
 
         let public_input = vec![digest];
-        let prover = MockProver::run(4, &circuit, vec![public_input.clone()]).unwrap();
-        prover.assert_satisfied();
+        assert_mock_satisfied(4, &circuit, vec![public_input.clone()]);
+
+        let blank = circuit.without_witnesses();
+        assert_eq!(
+            (blank.path_elements.len(), blank.path_indices.len()),
+            (2, 2)
+        );
+        assert!(keygen(&Params::<EqAffine>::new(4), &circuit).is_ok());
     }
 }
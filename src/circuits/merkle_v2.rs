@@ -1,11 +1,83 @@
+use super::super::chips::hash_2::Hash2Chip;
 use super::super::chips::merkle_v2::{MerkleTreeV2Chip, MerkleTreeV2Config};
+use crate::encoding::{index_bits, ToLeaf};
+use crate::error::{self, validate_path};
+use crate::instance::{
+    dummy_membership, known, InstanceLayout, LeafExposure, PublicInputs, PublicInstances,
+};
 use halo2_proofs::{arithmetic::FieldExt, circuit::*, plonk::*};
 
 #[derive(Default)]
-struct MerkleTreeV2Circuit<F> {
+pub struct MerkleTreeV2Circuit<F> {
     pub leaf: Value<F>,
     pub elements: Vec<Value<F>>,
     pub indices: Vec<Value<F>>,
+    pub leaf_exposure: LeafExposure,
+    // The blinding factor of a committed leaf, whose commitment is the placeholder hash
+    // leaf + blinding.
+    pub blinding: Value<F>,
+}
+
+impl<F: FieldExt> MerkleTreeV2Circuit<F> {
+    pub fn new(leaf: impl ToLeaf<F>, siblings: &[F], index: u64) -> Result<Self, error::Error> {
+        let indices = index_bits(index, siblings.len())?;
+        Self::from_values(
+            Value::known(leaf.to_leaf()),
+            siblings.iter().map(|x| Value::known(*x)).collect(),
+            indices.into_iter().map(Value::known).collect(),
+            siblings.len(),
+        )
+    }
+
+    pub fn from_values(
+        leaf: Value<F>,
+        elements: Vec<Value<F>>,
+        indices: Vec<Value<F>>,
+        depth: usize,
+    ) -> Result<Self, error::Error> {
+        validate_path(depth, &elements, &indices)?;
+        Ok(Self {
+            leaf,
+            elements,
+            indices,
+            leaf_exposure: LeafExposure::Instance,
+            blinding: Value::unknown(),
+        })
+    }
+
+    // Exposes leaf + blinding in the leaf's place.
+    pub fn with_commitment(mut self, blinding: F) -> Self {
+        self.leaf_exposure = LeafExposure::Commitment;
+        self.blinding = Value::known(blinding);
+        self
+    }
+
+    pub fn with_private_leaf(mut self) -> Self {
+        self.leaf_exposure = LeafExposure::Private;
+        self
+    }
+
+    pub fn layout(&self) -> InstanceLayout {
+        InstanceLayout::membership(self.leaf_exposure, true)
+    }
+}
+
+impl<F: FieldExt> PublicInstances<F> for MerkleTreeV2Circuit<F> {
+    fn instance_layout(&self) -> InstanceLayout {
+        self.layout()
+    }
+
+    fn public_inputs(&self) -> PublicInputs<F> {
+        let inputs = dummy_membership(self.leaf, &self.elements);
+        let commitment = inputs
+            .leaf
+            .zip(known(self.blinding))
+            .map(|(leaf, blinding)| leaf + blinding);
+        PublicInputs {
+            commitment,
+            ..inputs
+        }
+    }
 }
 
 impl<F: FieldExt> Circuit<F> for MerkleTreeV2Circuit<F> {
@@ -13,7 +85,13 @@ impl<F: FieldExt> Circuit<F> for MerkleTreeV2Circuit<F> {
     type FloorPlanner = SimpleFloorPlanner;
 
     fn without_witnesses(&self) -> Self {
-        Self::default()
+        Self {
+            leaf: Value::unknown(),
+            elements: vec![Value::unknown(); self.elements.len()],
+            indices: vec![Value::unknown(); self.indices.len()],
+            leaf_exposure: self.leaf_exposure,
+            blinding: Value::unknown(),
+        }
     }
 
     fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
@@ -29,23 +107,42 @@ impl<F: FieldExt> Circuit<F> for MerkleTreeV2Circuit<F> {
         config: Self::Config,
         mut layouter: impl Layouter<F>,
     ) -> Result<(), Error> {
+        let hash_chip = Hash2Chip::construct(config.hash_config.clone());
         let chip = MerkleTreeV2Chip::construct(config);
+        let layout = self.layout();
         let leaf_cell = chip.load_private(layouter.namespace(|| "load leaf"), self.leaf)?;
-        chip.expose_public(layouter.namespace(|| "public leaf"), &leaf_cell, 0);
-        let digest = chip.merkle_prove(
+        if let Some(row) = layout.leaf {
+            chip.expose_public(layouter.namespace(|| "public leaf"), &leaf_cell, row)?;
+        }
+        if let Some(row) = layout.commitment {
+            let blinding =
+                chip.load_private(layouter.namespace(|| "load blinding"), self.blinding)?;
+            let commitment = hash_chip.hash2(
+                layouter.namespace(|| "commitment"),
+                leaf_cell.clone(),
+                blinding,
+            )?;
+            chip.expose_public(layouter.namespace(|| "public commitment"), &commitment, row)?;
+        }
+        let root = chip.merkle_prove(
             layouter.namespace(|| "merkle_prove"),
             &leaf_cell,
             &self.elements,
             &self.indices,
         )?;
-        chip.expose_public(layouter.namespace(|| "public root"), &digest, 1)?;
+        if let Some(row) = layout.root {
+            chip.expose_public(layouter.namespace(|| "public root"), &root.cell, row)?;
+        }
         Ok(())
     }
 }
 
 mod tests {
     use super::MerkleTreeV2Circuit;
-    use halo2_proofs::{circuit::Value, dev::MockProver, pasta::Fp};
+    use crate::coverage::{assert_mock_satisfied, mock_verify};
+    use crate::error::Error;
+    use crate::instance::{InstanceBuilder, LeafExposure, PublicInputs};
+    use halo2_proofs::{circuit::Value, pasta::Fp};
 
     #[test]
     fn test() {
@@ -68,10 +165,92 @@ mod tests {
             leaf: leaf_fp,
             elements: elements_fp,
             indices: indices_fp,
+            leaf_exposure: LeafExposure::Instance,
+            blinding: Value::unknown(),
         };
 
         let public_input = vec![Fp::from(leaf), Fp::from(digest)];
-        let prover = MockProver::run(10, &circuit, vec![public_input.clone()]).unwrap();
-        prover.assert_satisfied();
+        assert_mock_satisfied(10, &circuit, vec![public_input.clone()]);
+    }
+
+    #[test]
+    fn test_leaf_exposure() {
+        let leaf = Value::known(Fp::from(99));
+        let elements: Vec<Value<Fp>> = [1u64, 5, 6]
+            .iter()
+            .map(|x| Value::known(Fp::from(*x)))
+            .collect();
+        let indices = vec![Value::known(Fp::zero()); 3];
+        let root = Fp::from(111);
+        let circuit = || {
+            MerkleTreeV2Circuit::from_values(leaf, elements.clone(), indices.clone(), 3).unwrap()
+        };
+
+        // The commitment takes the leaf's row, and only it is public.
+        let committed = circuit().with_commitment(Fp::from(1000));
+        let instances = InstanceBuilder::for_circuit(&committed).unwrap();
+        assert_eq!(instances, vec![vec![Fp::from(1099), root]]);
+        assert_eq!(
+            committed
+                .layout()
+                .column(&PublicInputs::committed(Fp::from(1099), root))
+                .unwrap(),
+            instances[0]
+        );
+        assert_mock_satisfied(10, &committed, instances);
+        for wrong in [Fp::from(99), Fp::from(1100)] {
+            assert!(mock_verify(10, &committed, vec![vec![wrong, root]])
+                .unwrap()
+                .is_err());
+        }
+
+        let private = circuit().with_private_leaf();
+        assert_eq!(private.leaf_exposure, LeafExposure::Private);
+        let instances = InstanceBuilder::for_circuit(&private).unwrap();
+        assert_eq!(instances, vec![vec![root]]);
+        assert_mock_satisfied(10, &private, instances);
+    }
+
+    #[test]
+    fn test_invalid_path() {
+        let leaf = Value::known(Fp::from(99));
+        let elements = vec![Value::known(Fp::from(1)), Value::known(Fp::from(5))];
+
+        let short = vec![Value::known(Fp::from(0))];
+        let result = MerkleTreeV2Circuit::from_values(leaf, elements.clone(), short, 2);
+        assert!(matches!(
+            result,
+            Err(Error::LengthMismatch {
+                elements: 2,
+                indices: 1
+            })
+        ));
+
+        let indices = vec![Value::known(Fp::from(0)), Value::known(Fp::from(1))];
+        let result = MerkleTreeV2Circuit::from_values(leaf, elements.clone(), indices, 3);
+        assert!(matches!(
+            result,
+            Err(Error::DepthMismatch {
+                expected: 3,
+                actual: 2
+            })
+        ));
+
+        let indices = vec![Value::known(Fp::from(0)), Value::known(Fp::from(2))];
+        let result = MerkleTreeV2Circuit::from_values(leaf, elements, indices, 2);
+        assert!(matches!(result, Err(Error::InvalidIndex { layer: 1 })));
+    }
+
+    #[test]
+    fn test_empty_path() {
+        let circuit = MerkleTreeV2Circuit {
+            leaf: Value::known(Fp::from(99)),
+            elements: vec![],
+            indices: vec![],
+            leaf_exposure: LeafExposure::Instance,
+            blinding: Value::unknown(),
+        };
+        let public_input = vec![Fp::from(99), Fp::from(99)];
+        assert!(mock_verify(10, &circuit, vec![public_input]).is_err());
     }
 }
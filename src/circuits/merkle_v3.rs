@@ -0,0 +1,497 @@
+use super::super::chips::merkle_v3::{
+    DomainSeparation, MerkleTreeV3Chip, MerkleTreeV3Config, RootExposure,
+};
+use super::super::chips::poseidon::{PoseidonChip, PoseidonSpecFor};
+use crate::encoding::{index_bits, ToLeaf};
+use crate::error::{self, validate_path};
+use crate::instance::{known, InstanceLayout, LeafExposure, PublicInputs, PublicInstances};
+use crate::tree::{blinded_leaf, compute_root, leaf_commitment, BLINDED_LEAF_TAG, COMMITMENT_TAG};
+use halo2_proofs::{circuit::*, pasta::Fp, plonk::*};
+
+#[derive(Default)]
+pub struct MerkleTreeV3Circuit<F = Fp> {
+    pub leaf: Value<F>,
+    pub elements: Vec<Value<F>>,
+    pub indices: Vec<Value<F>>,
+    pub separation: DomainSeparation,
+    pub root: RootExposure<F>,
+    pub leaf_exposure: LeafExposure,
+    // The blinding factor of a committed leaf, committed to as `leaf_commitment(leaf, blinding)`.
+    pub blinding: Value<F>,
+    // When set, the leaf is derived in-circuit as `blinded_leaf(secret, salt)`, ignoring `leaf`.
+    pub preimage: Option<[Value<F>; 2]>,
+}
+
+impl<F: PoseidonSpecFor> MerkleTreeV3Circuit<F> {
+    pub fn new(leaf: impl ToLeaf<F>, siblings: &[F], index: u64) -> Result<Self, error::Error> {
+        let indices = index_bits(index, siblings.len())?;
+        Self::from_values(
+            Value::known(leaf.to_leaf()),
+            siblings.iter().map(|x| Value::known(*x)).collect(),
+            indices.into_iter().map(Value::known).collect(),
+            siblings.len(),
+        )
+    }
+
+    pub fn from_values(
+        leaf: Value<F>,
+        elements: Vec<Value<F>>,
+        indices: Vec<Value<F>>,
+        depth: usize,
+    ) -> Result<Self, error::Error> {
+        validate_path(depth, &elements, &indices)?;
+        Ok(Self {
+            leaf,
+            elements,
+            indices,
+            separation: DomainSeparation::Legacy,
+            root: RootExposure::Instance,
+            leaf_exposure: LeafExposure::Instance,
+            blinding: Value::unknown(),
+            preimage: None,
+        })
+    }
+
+    // Membership of the blinded leaf H(tag, secret, salt), proving knowledge of its preimage. The
+    // leaf stays private unless `leaf_exposure` is set again.
+    pub fn blinded(secret: F, salt: F, siblings: &[F], index: u64) -> Result<Self, error::Error> {
+        let mut circuit = Self::new(blinded_leaf(secret, salt), siblings, index)?;
+        circuit.preimage = Some([Value::known(secret), Value::known(salt)]);
+        circuit.leaf_exposure = LeafExposure::Private;
+        Ok(circuit)
+    }
+
+    // Exposes H(tag, leaf, blinding) in the leaf's place.
+    pub fn with_commitment(mut self, blinding: F) -> Self {
+        self.leaf_exposure = LeafExposure::Commitment;
+        self.blinding = Value::known(blinding);
+        self
+    }
+
+    pub fn with_private_leaf(mut self) -> Self {
+        self.leaf_exposure = LeafExposure::Private;
+        self
+    }
+
+    pub fn layout(&self) -> InstanceLayout {
+        InstanceLayout::membership(self.leaf_exposure, self.root == RootExposure::Instance)
+    }
+
+    pub fn with_fixed_root(mut self, root: F) -> Self {
+        self.root = RootExposure::Constant(root);
+        self
+    }
+
+    pub fn with_separation(mut self, separation: DomainSeparation) -> Self {
+        self.separation = separation;
+        self
+    }
+}
+
+impl<F: PoseidonSpecFor> PublicInstances<F> for MerkleTreeV3Circuit<F> {
+    fn instance_layout(&self) -> InstanceLayout {
+        self.layout()
+    }
+
+    // A blinded leaf is rehashed from its preimage, as the circuit does, and the root is the
+    // native root of the witnessed path.
+    fn public_inputs(&self) -> PublicInputs<F> {
+        let leaf = match self.preimage {
+            Some([secret, salt]) => known(secret)
+                .zip(known(salt))
+                .map(|(secret, salt)| blinded_leaf(secret, salt)),
+            None => known(self.leaf),
+        };
+        let elements: Option<Vec<F>> = self.elements.iter().map(|x| known(*x)).collect();
+        let indices: Option<Vec<F>> = self.indices.iter().map(|x| known(*x)).collect();
+        let root = match (leaf, elements, indices) {
+            (Some(leaf), Some(elements), Some(indices)) => {
+                Some(compute_root(self.separation, leaf, &elements, &indices))
+            }
+            _ => None,
+        };
+        let commitment = leaf
+            .zip(known(self.blinding))
+            .map(|(leaf, blinding)| leaf_commitment(leaf, blinding));
+        PublicInputs {
+            leaf,
+            root,
+            commitment,
+            ..PublicInputs::default()
+        }
+    }
+
+    fn instance_columns(&self) -> usize {
+        2
+    }
+}
+
+impl<F: PoseidonSpecFor> Circuit<F> for MerkleTreeV3Circuit<F> {
+    type Config = MerkleTreeV3Config<F>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            leaf: Value::unknown(),
+            elements: vec![Value::unknown(); self.elements.len()],
+            indices: vec![Value::unknown(); self.indices.len()],
+            separation: self.separation,
+            root: self.root,
+            leaf_exposure: self.leaf_exposure,
+            blinding: Value::unknown(),
+            preimage: self.preimage.map(|_| [Value::unknown(); 2]),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let col_a = meta.advice_column();
+        let col_b = meta.advice_column();
+        let col_c = meta.advice_column();
+        let instance = meta.instance_column();
+        MerkleTreeV3Chip::<F>::configure(meta, [col_a, col_b, col_c], instance)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let poseidon_chip =
+            PoseidonChip::<F, F::Spec, 3, 2, 2>::construct(config.hash_config.clone());
+        let chip = MerkleTreeV3Chip::<F>::construct(config).with_separation(self.separation);
+        let layout = self.layout();
+        let leaf_cell = match self.preimage {
+            None => chip.load_private(layouter.namespace(|| "load leaf"), self.leaf)?,
+            Some([secret, salt]) => {
+                let secret = chip.load_private(layouter.namespace(|| "load secret"), secret)?;
+                let salt = chip.load_private(layouter.namespace(|| "load salt"), salt)?;
+                let tag = chip.load_constant(
+                    layouter.namespace(|| "blinded leaf tag"),
+                    F::from(BLINDED_LEAF_TAG),
+                )?;
+                poseidon_chip.hash_message::<3>(
+                    layouter.namespace(|| "blinded leaf"),
+                    &[tag, secret, salt],
+                )?
+            }
+        };
+        if let Some(row) = layout.leaf {
+            chip.expose_public(layouter.namespace(|| "public leaf"), &leaf_cell, row)?;
+        }
+        if let Some(row) = layout.commitment {
+            let blinding =
+                chip.load_private(layouter.namespace(|| "load blinding"), self.blinding)?;
+            let tag = chip.load_constant(
+                layouter.namespace(|| "commitment tag"),
+                F::from(COMMITMENT_TAG),
+            )?;
+            let commitment = poseidon_chip.hash_message::<3>(
+                layouter.namespace(|| "commitment"),
+                &[tag, leaf_cell.clone(), blinding],
+            )?;
+            chip.expose_public(layouter.namespace(|| "public commitment"), &commitment, row)?;
+        }
+        let root = chip.merkle_prove(
+            layouter.namespace(|| "merkle_prove"),
+            &leaf_cell,
+            &self.elements,
+            &self.indices,
+        )?;
+        if let RootExposure::Constant(fixed_root) = self.root {
+            chip.constrain_constant(layouter.namespace(|| "fixed root"), &root.cell, fixed_root)?;
+        }
+        if let Some(row) = layout.root {
+            chip.expose_public(layouter.namespace(|| "public root"), &root.cell, row)?;
+        }
+        Ok(())
+    }
+}
+
+mod tests {
+    use super::{DomainSeparation, MerkleTreeV3Circuit, RootExposure};
+    use crate::analysis::analyze;
+    use crate::circuits::poseidon::PoseidonCircuit;
+    use crate::coverage::{assert_mock_satisfied, mock_verify};
+    use crate::error::Error;
+    use crate::instance::{InstanceBuilder, LeafExposure, PublicInputs};
+    use crate::tree::{blinded_leaf, compute_root, hash_pair, leaf_commitment, MerkleTree};
+    use halo2_gadgets::poseidon::primitives::P128Pow5T3 as OrchardNullifier;
+    use halo2_proofs::{
+        circuit::Value,
+        pasta::{Fp, Fq},
+    };
+
+    #[test]
+    fn test() {
+        let leaf = 99u64;
+        let elements = vec![1u64, 5u64, 6u64, 9u64, 9u64];
+        let indices = vec![0u64, 0u64, 0u64, 0u64, 0u64];
+        let field = |values: &[u64]| -> Vec<Fp> { values.iter().map(|x| Fp::from(*x)).collect() };
+        let digest = compute_root(
+            DomainSeparation::Legacy,
+            Fp::from(leaf),
+            &field(&elements),
+            &field(&indices),
+        );
+
+        let leaf_fp = Value::known(Fp::from(leaf));
+        let elements_fp: Vec<Value<Fp>> = elements
+            .iter()
+            .map(|x| Value::known(Fp::from(x.to_owned())))
+            .collect();
+        let indices_fp: Vec<Value<Fp>> = indices
+            .iter()
+            .map(|x| Value::known(Fp::from(x.to_owned())))
+            .collect();
+
+        let circuit = MerkleTreeV3Circuit {
+            leaf: leaf_fp,
+            elements: elements_fp,
+            indices: indices_fp,
+            separation: DomainSeparation::Legacy,
+            root: RootExposure::Instance,
+            leaf_exposure: LeafExposure::Instance,
+            blinding: Value::unknown(),
+            preimage: None,
+        };
+
+        let correct_public_input = vec![Fp::from(leaf), digest];
+        assert_mock_satisfied(
+            10,
+            &circuit,
+            vec![correct_public_input.clone(), correct_public_input.clone()],
+        );
+
+        let wrong_public_input = vec![Fp::from(leaf), Fp::from(432058235)];
+        let result = mock_verify(
+            10,
+            &circuit,
+            vec![wrong_public_input.clone(), wrong_public_input.clone()],
+        )
+        .unwrap();
+        match result {
+            Ok(res) => panic!("shouldve not proved correctly but did"),
+            Err(error) => true,
+        };
+    }
+
+    #[test]
+    fn test_level_separation() {
+        let leaves: Vec<Fp> = (0..20u64).map(Fp::from).collect();
+        let tree = MerkleTree::new(5, &leaves, DomainSeparation::Level).unwrap();
+        let path = tree.path(13).unwrap();
+
+        let circuit = MerkleTreeV3Circuit {
+            leaf: Value::known(path.leaf),
+            elements: path.elements.iter().map(|x| Value::known(*x)).collect(),
+            indices: path.indices.iter().map(|x| Value::known(*x)).collect(),
+            separation: DomainSeparation::Level,
+            root: RootExposure::Instance,
+            leaf_exposure: LeafExposure::Instance,
+            blinding: Value::unknown(),
+            preimage: None,
+        };
+
+        let public_input = vec![path.leaf, tree.root()];
+        assert_mock_satisfied(11, &circuit, vec![public_input.clone(), public_input]);
+
+        let legacy_root = path.root(DomainSeparation::Legacy);
+        let legacy_input = vec![path.leaf, legacy_root];
+        assert!(
+            mock_verify(11, &circuit, vec![legacy_input.clone(), legacy_input])
+                .unwrap()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_fixed_root() {
+        let leaves: Vec<Fp> = (0..20u64).map(Fp::from).collect();
+        let tree = MerkleTree::new(5, &leaves, DomainSeparation::Legacy).unwrap();
+        let path = tree.path(6).unwrap();
+        let elements: Vec<Value<Fp>> = path.elements.iter().map(|x| Value::known(*x)).collect();
+        let indices: Vec<Value<Fp>> = path.indices.iter().map(|x| Value::known(*x)).collect();
+
+        let circuit = MerkleTreeV3Circuit::from_values(
+            Value::known(path.leaf),
+            elements.clone(),
+            indices.clone(),
+            5,
+        )
+        .unwrap()
+        .with_fixed_root(tree.root());
+        let public_input = vec![path.leaf];
+        assert_mock_satisfied(
+            10,
+            &circuit,
+            vec![public_input.clone(), public_input.clone()],
+        );
+
+        let other_tree = MerkleTree::new(5, &leaves[..10], DomainSeparation::Legacy).unwrap();
+        let circuit =
+            MerkleTreeV3Circuit::from_values(Value::known(path.leaf), elements, indices, 5)
+                .unwrap()
+                .with_fixed_root(other_tree.root());
+        assert!(
+            mock_verify(10, &circuit, vec![public_input.clone(), public_input])
+                .unwrap()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_new_from_index() {
+        let leaves: Vec<Fp> = (100..120u64).map(Fp::from).collect();
+        let tree = MerkleTree::new(5, &leaves, DomainSeparation::Legacy).unwrap();
+        let path = tree.path(13).unwrap();
+
+        let circuit = MerkleTreeV3Circuit::new(Fp::from(113), &path.elements, 13).unwrap();
+        let public_input = vec![Fp::from(113), tree.root()];
+        assert_mock_satisfied(10, &circuit, vec![public_input.clone(), public_input]);
+
+        assert!(MerkleTreeV3Circuit::new(Fp::from(113), &path.elements, 32).is_err());
+    }
+
+    #[test]
+    fn test_blinded_leaf() {
+        let (secret, salt) = (Fp::from(0xdead), Fp::from(0xbeef));
+        let mut leaves: Vec<Fp> = (0..20u64).map(Fp::from).collect();
+        leaves[9] = blinded_leaf(secret, salt);
+        // The tag keeps a blinded leaf apart from a node over the same two words.
+        assert_ne!(
+            leaves[9],
+            hash_pair(DomainSeparation::Legacy, 0, secret, salt)
+        );
+        let tree = MerkleTree::new(5, &leaves, DomainSeparation::Legacy).unwrap();
+        let path = tree.path(9).unwrap();
+
+        let circuit = MerkleTreeV3Circuit::blinded(secret, salt, &path.elements, 9).unwrap();
+        let public_input = vec![tree.root()];
+        assert_mock_satisfied(10, &circuit, vec![public_input.clone(), public_input]);
+
+        // Knowing the leaf alone is not enough: the witnessed leaf is ignored in this mode.
+        let mut circuit =
+            MerkleTreeV3Circuit::blinded(secret, salt + Fp::one(), &path.elements, 9).unwrap();
+        circuit.leaf = Value::known(path.leaf);
+        let public_input = vec![tree.root()];
+        assert!(
+            mock_verify(10, &circuit, vec![public_input.clone(), public_input])
+                .unwrap()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_leaf_commitment() {
+        let leaves: Vec<Fp> = (0..20u64).map(Fp::from).collect();
+        let tree = MerkleTree::new(5, &leaves, DomainSeparation::Legacy).unwrap();
+        let path = tree.path(6).unwrap();
+        let blinding = Fp::from(0xb11d);
+        let commitment = leaf_commitment(path.leaf, blinding);
+        assert_ne!(commitment, blinded_leaf(path.leaf, blinding));
+        assert_ne!(
+            commitment,
+            hash_pair(DomainSeparation::Legacy, 0, path.leaf, blinding)
+        );
+
+        let circuit = MerkleTreeV3Circuit::new(path.leaf, &path.elements, 6)
+            .unwrap()
+            .with_commitment(blinding);
+        assert_eq!(circuit.layout().commitment, Some(0));
+        let instances = InstanceBuilder::for_circuit(&circuit).unwrap();
+        assert_eq!(instances, vec![vec![commitment, tree.root()], vec![]]);
+        assert_mock_satisfied(10, &circuit, instances);
+
+        // The bare leaf doesn't open the commitment row.
+        let column = circuit
+            .layout()
+            .column(&PublicInputs::committed(path.leaf, tree.root()))
+            .unwrap();
+        assert!(mock_verify(10, &circuit, vec![column, vec![]])
+            .unwrap()
+            .is_err());
+
+        let circuit = MerkleTreeV3Circuit::new(path.leaf, &path.elements, 6)
+            .unwrap()
+            .with_private_leaf();
+        assert_eq!(circuit.leaf_exposure, LeafExposure::Private);
+        let instances = InstanceBuilder::for_circuit(&circuit).unwrap();
+        assert_eq!(instances, vec![vec![tree.root()], vec![]]);
+        assert_mock_satisfied(10, &circuit, instances);
+    }
+
+    #[test]
+    fn test_vesta() {
+        let leaves: Vec<Fq> = (0..20u64).map(Fq::from).collect();
+        let tree = MerkleTree::new(5, &leaves, DomainSeparation::Legacy).unwrap();
+        let path = tree.path(11).unwrap();
+
+        let circuit = MerkleTreeV3Circuit::<Fq>::new(path.leaf, &path.elements, 11).unwrap();
+        let public_input = vec![path.leaf, tree.root()];
+        assert_mock_satisfied(10, &circuit, vec![public_input.clone(), public_input]);
+
+        let wrong_input = vec![path.leaf, tree.root() + Fq::one()];
+        assert!(
+            mock_verify(10, &circuit, vec![wrong_input.clone(), wrong_input])
+                .unwrap()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_layer_rows() {
+        let rows = |depth: usize| {
+            let path = vec![Value::known(Fp::zero()); depth];
+            let circuit = MerkleTreeV3Circuit::from_values(
+                Value::known(Fp::zero()),
+                path.clone(),
+                path,
+                depth,
+            )
+            .unwrap();
+            analyze(&circuit).unwrap().rows
+        };
+        // A layer reuses the path's initial sponge state, so it costs its own two rows and one
+        // permutation: fewer than a standalone hash, which also loads, initializes and absorbs.
+        let standalone = PoseidonCircuit::<OrchardNullifier, 3, 2, 2>::new(
+            [Value::unknown(); 2],
+            Value::unknown(),
+        );
+        assert!(rows(6) - rows(5) < analyze(&standalone).unwrap().rows);
+    }
+
+    #[test]
+    fn test_instance_builder() {
+        let leaves: Vec<Fp> = (0..20u64).map(Fp::from).collect();
+        let tree = MerkleTree::new(5, &leaves, DomainSeparation::Level).unwrap();
+        let path = tree.path(13).unwrap();
+        let circuit = MerkleTreeV3Circuit::new(Fp::from(13), &path.elements, 13)
+            .unwrap()
+            .with_separation(DomainSeparation::Level);
+        let instances = InstanceBuilder::for_circuit(&circuit).unwrap();
+        assert_eq!(instances, vec![vec![Fp::from(13), tree.root()], vec![]]);
+        assert_mock_satisfied(11, &circuit, instances);
+
+        // A blinded leaf is private, and a fixed root is not an instance at all.
+        let (secret, salt) = (Fp::from(3), Fp::from(4));
+        let circuit = MerkleTreeV3Circuit::blinded(secret, salt, &path.elements, 13)
+            .unwrap()
+            .with_fixed_root(Fp::from(5));
+        assert_eq!(
+            InstanceBuilder::for_circuit(&circuit).unwrap(),
+            vec![vec![], vec![]]
+        );
+
+        let unknown = MerkleTreeV3Circuit::<Fp>::from_values(
+            Value::unknown(),
+            vec![Value::unknown(); 5],
+            vec![Value::unknown(); 5],
+            5,
+        )
+        .unwrap();
+        assert!(matches!(
+            InstanceBuilder::for_circuit(&unknown),
+            Err(Error::MissingInstance("leaf"))
+        ));
+    }
+}
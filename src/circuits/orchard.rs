@@ -0,0 +1,342 @@
+/*
+Orchard's note commitment tree anchor check: a private cmx at a private position hashes up a
+depth-32 path to the public anchor, with MerkleCRH^Orchard as the node hash. That is SinsemillaHash
+in the "z.cash:Orchard-MerkleCRH" domain over the 10-bit little-endian altitude (0 for the leaf
+layer) followed by the 255-bit encodings of the left and right children. Empty leaves hold the
+uncommitted value 2.
+
+The in-circuit hash is halo2_gadgets' Sinsemilla Merkle chip, the one Orchard's action circuit
+uses, over the pallas base field, which is this crate's Fp. The native side (`merkle_crh`,
+`empty_roots`, `OrchardTree`) uses the same Sinsemilla primitives, so anchors computed here match
+zcashd's and the orchard crate's.
+*/
+
+use crate::error::Error as CrateError;
+use halo2_gadgets::{
+    ecc::FixedPoints,
+    sinsemilla::{
+        chip::{SinsemillaChip, SinsemillaConfig},
+        merkle::{
+            chip::{MerkleChip, MerkleConfig},
+            MerklePath,
+        },
+        primitives::{self as sinsemilla, HashDomain},
+        CommitDomains, HashDomains,
+    },
+    utilities::lookup_range_check::LookupRangeCheckConfig,
+};
+use halo2_proofs::{
+    circuit::*,
+    pasta::{group::ff::PrimeField, group::Curve, pallas, Fp},
+    plonk::*,
+};
+use std::sync::OnceLock;
+
+pub const ORCHARD_DEPTH: usize = 32;
+pub const MERKLE_CRH_PERSONALIZATION: &str = "z.cash:Orchard-MerkleCRH";
+// Layer-0 value of every position no note has been appended to.
+pub const UNCOMMITTED: u64 = 2;
+
+// The only Sinsemilla domain the anchor check hashes in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MerkleCrhDomain;
+
+impl HashDomains<pallas::Affine> for MerkleCrhDomain {
+    #[allow(non_snake_case)]
+    fn Q(&self) -> pallas::Affine {
+        HashDomain::new(MERKLE_CRH_PERSONALIZATION).Q().to_affine()
+    }
+}
+
+// The Sinsemilla chip is generic over commitment domains and fixed bases for the commitments it
+// can also compute. Membership never commits, so these are empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoFixedBases;
+
+impl FixedPoints<pallas::Affine> for NoFixedBases {
+    type FullScalar = NoFixedBases;
+    type ShortScalar = NoFixedBases;
+    type Base = NoFixedBases;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoCommitDomains;
+
+impl CommitDomains<pallas::Affine, NoFixedBases, MerkleCrhDomain> for NoCommitDomains {
+    fn r(&self) -> NoFixedBases {
+        NoFixedBases
+    }
+
+    fn hash_domain(&self) -> MerkleCrhDomain {
+        MerkleCrhDomain
+    }
+}
+
+fn le_bits(value: &Fp) -> impl Iterator<Item = bool> {
+    let bytes = value.to_repr();
+    (0..255).map(move |i| (bytes[i / 8] >> (i % 8)) & 1 == 1)
+}
+
+// MerkleCRH^Orchard(altitude, left, right), with ⊥ mapped to 0 as in the orchard crate.
+pub fn merkle_crh(altitude: u8, left: Fp, right: Fp) -> Fp {
+    static DOMAIN: OnceLock<HashDomain> = OnceLock::new();
+    let domain = DOMAIN.get_or_init(|| HashDomain::new(MERKLE_CRH_PERSONALIZATION));
+    let altitude = (0..10).map(|i| (altitude >> i) & 1 == 1);
+    Option::from(domain.hash(altitude.chain(le_bits(&left)).chain(le_bits(&right))))
+        .unwrap_or(Fp::zero())
+}
+
+// empty_roots()[altitude] is the root of an empty subtree of that height; the last one is the
+// anchor of the empty Orchard tree.
+pub fn empty_roots() -> &'static [Fp; ORCHARD_DEPTH + 1] {
+    static ROOTS: OnceLock<[Fp; ORCHARD_DEPTH + 1]> = OnceLock::new();
+    ROOTS.get_or_init(|| {
+        let mut roots = [Fp::from(UNCOMMITTED); ORCHARD_DEPTH + 1];
+        for altitude in 1..=ORCHARD_DEPTH {
+            roots[altitude] =
+                merkle_crh(altitude as u8 - 1, roots[altitude - 1], roots[altitude - 1]);
+        }
+        roots
+    })
+}
+
+// The anchor a path leads to, siblings leaf layer first.
+pub fn anchor(cmx: Fp, position: u32, path: &[Fp; ORCHARD_DEPTH]) -> Fp {
+    path.iter()
+        .enumerate()
+        .fold(cmx, |node, (altitude, sibling)| {
+            if (position >> altitude) & 1 == 0 {
+                merkle_crh(altitude as u8, node, *sibling)
+            } else {
+                merkle_crh(altitude as u8, *sibling, node)
+            }
+        })
+}
+
+// The note commitment tree after the given cmx values were appended, in order.
+#[derive(Debug, Clone)]
+pub struct OrchardTree {
+    // layers[0] holds the cmx values and layers[ORCHARD_DEPTH] the anchor.
+    layers: Vec<Vec<Fp>>,
+}
+
+impl OrchardTree {
+    pub fn new(commitments: &[Fp]) -> Result<Self, CrateError> {
+        if commitments.len() as u64 > 1 << ORCHARD_DEPTH {
+            return Err(CrateError::TooManyLeaves {
+                capacity: 1 << ORCHARD_DEPTH,
+                leaves: commitments.len(),
+            });
+        }
+        let mut layers = vec![commitments.to_vec()];
+        for altitude in 0..ORCHARD_DEPTH {
+            let next = layers[altitude]
+                .chunks(2)
+                .map(|pair| {
+                    let right = pair.get(1).copied().unwrap_or(empty_roots()[altitude]);
+                    merkle_crh(altitude as u8, pair[0], right)
+                })
+                .collect();
+            layers.push(next);
+        }
+        Ok(Self { layers })
+    }
+
+    pub fn commitments(&self) -> &[Fp] {
+        &self.layers[0]
+    }
+
+    pub fn anchor(&self) -> Fp {
+        self.layers[ORCHARD_DEPTH]
+            .first()
+            .copied()
+            .unwrap_or(empty_roots()[ORCHARD_DEPTH])
+    }
+
+    // The authentication path of the note at `position`, as the circuit takes it.
+    pub fn path(&self, position: u32) -> Result<[Fp; ORCHARD_DEPTH], CrateError> {
+        let index = position as usize;
+        if index >= self.layers[0].len() {
+            return Err(CrateError::IndexOutOfRange {
+                index,
+                leaves: self.layers[0].len(),
+            });
+        }
+        let mut path = [Fp::zero(); ORCHARD_DEPTH];
+        for (altitude, sibling) in path.iter_mut().enumerate() {
+            *sibling = self.layers[altitude]
+                .get((index >> altitude) ^ 1)
+                .copied()
+                .unwrap_or(empty_roots()[altitude]);
+        }
+        Ok(path)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OrchardAnchorConfig {
+    advice: Column<Advice>,
+    instance: Column<Instance>,
+    sinsemilla: SinsemillaConfig<MerkleCrhDomain, NoCommitDomains, NoFixedBases>,
+    merkle: MerkleConfig<MerkleCrhDomain, NoCommitDomains, NoFixedBases>,
+}
+
+// Proves that cmx is in the Orchard tree with the public anchor (instance row 0).
+#[derive(Debug, Clone, Default)]
+pub struct OrchardAnchorCircuit {
+    pub cmx: Value<Fp>,
+    pub position: Value<u32>,
+    pub path: Value<[Fp; ORCHARD_DEPTH]>,
+}
+
+impl OrchardAnchorCircuit {
+    pub fn new(cmx: Fp, position: u32, path: [Fp; ORCHARD_DEPTH]) -> Self {
+        Self {
+            cmx: Value::known(cmx),
+            position: Value::known(position),
+            path: Value::known(path),
+        }
+    }
+
+    pub fn layout(&self) -> crate::instance::InstanceLayout {
+        crate::instance::InstanceLayout::merkle(false, true)
+    }
+}
+
+impl Circuit<Fp> for OrchardAnchorCircuit {
+    type Config = OrchardAnchorConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::default()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let advices: Vec<Column<Advice>> = (0..7).map(|_| meta.advice_column()).collect();
+        for advice in advices.iter() {
+            meta.enable_equality(*advice);
+        }
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
+        let fixed_y_q = meta.fixed_column();
+        meta.enable_constant(fixed_y_q);
+
+        let table_idx = meta.lookup_table_column();
+        let lookup = (
+            table_idx,
+            meta.lookup_table_column(),
+            meta.lookup_table_column(),
+        );
+        let range_check = LookupRangeCheckConfig::configure(meta, advices[5], table_idx);
+        let sinsemilla = SinsemillaChip::configure(
+            meta,
+            advices[..5].try_into().unwrap(),
+            advices[6],
+            fixed_y_q,
+            lookup,
+            range_check,
+        );
+        let merkle = MerkleChip::configure(meta, sinsemilla.clone());
+        OrchardAnchorConfig {
+            advice: advices[0],
+            instance,
+            sinsemilla,
+            merkle,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        SinsemillaChip::load(config.sinsemilla.clone(), &mut layouter)?;
+        let cmx = layouter.assign_region(
+            || "load cmx",
+            |mut region| region.assign_advice(|| "cmx", config.advice, 0, || self.cmx),
+        )?;
+        let path = MerklePath::<
+            pallas::Affine,
+            _,
+            ORCHARD_DEPTH,
+            { sinsemilla::K },
+            { sinsemilla::C },
+            1,
+        >::construct(
+            [MerkleChip::construct(config.merkle.clone())],
+            MerkleCrhDomain,
+            self.position,
+            self.path,
+        );
+        let anchor = path.calculate_root(layouter.namespace(|| "anchor"), cmx)?;
+        layouter.constrain_instance(anchor.cell(), config.instance, 0)
+    }
+}
+
+mod tests {
+    use super::{anchor, empty_roots, OrchardAnchorCircuit, OrchardTree, ORCHARD_DEPTH};
+    use crate::coverage::mock_verify;
+    use crate::serialization::to_bytes_le;
+    use halo2_proofs::pasta::Fp;
+
+    fn hex(value: &Fp) -> String {
+        to_bytes_le(value)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    #[test]
+    fn test() {
+        // The anchor of the empty Orchard tree, as zcashd reports it (little-endian hex).
+        assert_eq!(
+            hex(&empty_roots()[ORCHARD_DEPTH]),
+            "ae2935f1dfd8a24aed7c70df7de3a668eb7a49b1319880dde2bbd9031ae5d82f"
+        );
+        assert_eq!(
+            OrchardTree::new(&[]).unwrap().anchor(),
+            empty_roots()[ORCHARD_DEPTH]
+        );
+
+        let commitments: Vec<Fp> = (10..15u64).map(Fp::from).collect();
+        let tree = OrchardTree::new(&commitments).unwrap();
+        let path = tree.path(3).unwrap();
+        assert_eq!(anchor(commitments[3], 3, &path), tree.anchor());
+
+        // Non-empty anchors and siblings from a second Sinsemilla implementation, written from
+        // the protocol spec over pasta_curves' hash_to_curve rather than halo2_gadgets, which
+        // reproduces the empty anchor above. They are not the zcash-test-vectors
+        // orchard_merkle_tree cases, which are not vendored here.
+        assert_eq!(
+            hex(&OrchardTree::new(&commitments[..1]).unwrap().anchor()),
+            "5c5dbbc37a484324cbedd854cb92d79c3084bad663f7a5da600443cb2c311619"
+        );
+        assert_eq!(
+            hex(&tree.anchor()),
+            "2cef2acf00fac4aff381127a17ea2bceae8a1a1fde9be59d2022b70b62368715"
+        );
+        assert_eq!(path[0], Fp::from(12));
+        assert_eq!(
+            path[1..4].iter().map(hex).collect::<Vec<_>>(),
+            vec![
+                "63c78bbc718bc846faf9486eda79f5e0f0019755964ed7d525bcb7cfda313722",
+                "d809dee703a493c83279010ade3079445a7aa32b74c50253cb73d9d33e16c13f",
+                "2111fc397753e5fd50ec74816df27d6ada7ed2a9ac3816aab2573c8fac794204",
+            ]
+        );
+        assert!(tree.path(5).is_err());
+
+        let k = 12;
+        let circuit = OrchardAnchorCircuit::new(commitments[3], 3, path);
+        assert_eq!(
+            mock_verify(k, &circuit, vec![vec![tree.anchor()]]).unwrap(),
+            Ok(())
+        );
+
+        // The right cmx at the wrong position does not reach the anchor.
+        let circuit = OrchardAnchorCircuit::new(commitments[3], 2, path);
+        assert!(mock_verify(k, &circuit, vec![vec![tree.anchor()]])
+            .unwrap()
+            .is_err());
+    }
+}
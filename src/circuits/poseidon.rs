@@ -4,25 +4,37 @@ is already implemented in halo2_gadgets, there is no wrapper chip that makes it
 */
 
 use super::super::chips::poseidon::{PoseidonChip, PoseidonConfig};
-use halo2_gadgets::poseidon::{primitives::*};
+use halo2_gadgets::poseidon::primitives::*;
 use halo2_proofs::{circuit::*, pasta::Fp, plonk::*};
 use std::marker::PhantomData;
 
-struct PoseidonCircuit<
+pub struct PoseidonCircuit<
     S: Spec<Fp, WIDTH, RATE>,
     const WIDTH: usize,
     const RATE: usize,
     const L: usize,
 > {
-    message: [Value<Fp>; L],
-    output: Value<Fp>,
+    pub message: [Value<Fp>; L],
+    pub output: Value<Fp>,
     _spec: PhantomData<S>,
 }
 
+impl<S: Spec<Fp, WIDTH, RATE>, const WIDTH: usize, const RATE: usize, const L: usize>
+    PoseidonCircuit<S, WIDTH, RATE, L>
+{
+    pub fn new(message: [Value<Fp>; L], output: Value<Fp>) -> Self {
+        Self {
+            message,
+            output,
+            _spec: PhantomData,
+        }
+    }
+}
+
 impl<S: Spec<Fp, WIDTH, RATE>, const WIDTH: usize, const RATE: usize, const L: usize> Circuit<Fp>
     for PoseidonCircuit<S, WIDTH, RATE, L>
 {
-    type Config = PoseidonConfig<WIDTH, RATE, L>;
+    type Config = PoseidonConfig<Fp, WIDTH, RATE, L>;
     type FloorPlanner = SimpleFloorPlanner;
 
     fn without_witnesses(&self) -> Self {
@@ -37,16 +49,16 @@ impl<S: Spec<Fp, WIDTH, RATE>, const WIDTH: usize, const RATE: usize, const L: u
         }
     }
 
-    fn configure(meta: &mut ConstraintSystem<Fp>) -> PoseidonConfig<WIDTH, RATE, L> {
-        PoseidonChip::<S, WIDTH, RATE, L>::configure(meta)
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> PoseidonConfig<Fp, WIDTH, RATE, L> {
+        PoseidonChip::<Fp, S, WIDTH, RATE, L>::configure(meta)
     }
 
     fn synthesize(
         &self,
-        config: PoseidonConfig<WIDTH, RATE, L>,
+        config: PoseidonConfig<Fp, WIDTH, RATE, L>,
         mut layouter: impl Layouter<Fp>,
     ) -> Result<(), Error> {
-        let poseidon_chip = PoseidonChip::<S, WIDTH, RATE, L>::construct(config);
+        let poseidon_chip = PoseidonChip::<Fp, S, WIDTH, RATE, L>::construct(config);
         let message_cells = poseidon_chip
             .load_private_inputs(layouter.namespace(|| "load private inputs"), self.message)?;
         let result = poseidon_chip.hash(layouter.namespace(|| "poseidon chip"), &message_cells)?;
@@ -55,15 +67,197 @@ impl<S: Spec<Fp, WIDTH, RATE>, const WIDTH: usize, const RATE: usize, const L: u
     }
 }
 
+// Folds a variable number of private words with PoseidonChip::hash_chain and exposes the digest.
+#[derive(Default)]
+pub struct HashChainCircuit {
+    pub words: Vec<Value<Fp>>,
+}
+
+impl Circuit<Fp> for HashChainCircuit {
+    type Config = PoseidonConfig<Fp, 3, 2, 2>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            words: vec![Value::unknown(); self.words.len()],
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        PoseidonChip::<Fp, P128Pow5T3, 3, 2, 2>::configure(meta)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let poseidon_chip = PoseidonChip::<Fp, P128Pow5T3, 3, 2, 2>::construct(config);
+        let words = self
+            .words
+            .iter()
+            .enumerate()
+            .map(|(i, word)| {
+                poseidon_chip.load_private(layouter.namespace(|| format!("load word {}", i)), *word)
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let result = poseidon_chip.hash_chain(layouter.namespace(|| "hash chain"), &words)?;
+        poseidon_chip.expose_public(layouter.namespace(|| "expose result"), &result, 0)
+    }
+}
+
+// Hashes a batch of private pairs and exposes every digest, one instance row each. With `shared`
+// set the hashes start from one shared initial state (`PoseidonChip::hash_batch`), otherwise each
+// goes through `hash_message`; the digests are the same, only the rows differ.
+#[derive(Default)]
+pub struct BatchHashCircuit {
+    pub pairs: Vec<[Value<Fp>; 2]>,
+    pub shared: bool,
+}
+
+impl Circuit<Fp> for BatchHashCircuit {
+    type Config = PoseidonConfig<Fp, 3, 2, 2>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            pairs: vec![[Value::unknown(); 2]; self.pairs.len()],
+            shared: self.shared,
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        PoseidonChip::<Fp, P128Pow5T3, 3, 2, 2>::configure(meta)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let poseidon_chip = PoseidonChip::<Fp, P128Pow5T3, 3, 2, 2>::construct(config);
+        let pairs = self
+            .pairs
+            .iter()
+            .enumerate()
+            .map(|(i, pair)| {
+                poseidon_chip
+                    .load_private_inputs(layouter.namespace(|| format!("pair {}", i)), *pair)
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let digests = if self.shared {
+            poseidon_chip.hash_batch(layouter.namespace(|| "batch"), &pairs)?
+        } else {
+            pairs
+                .iter()
+                .enumerate()
+                .map(|(i, pair)| {
+                    poseidon_chip.hash(layouter.namespace(|| format!("hash {}", i)), pair)
+                })
+                .collect::<Result<Vec<_>, Error>>()?
+        };
+        for (i, digest) in digests.iter().enumerate() {
+            poseidon_chip.expose_public(
+                layouter.namespace(|| format!("digest {}", i)),
+                digest,
+                i,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// BatchHashCircuit without the input vector: pair i is generated by `pairs(i)` while it is being
+// loaded, then hashed from the shared initial state and exposed. Only the loading is streamed; the
+// circuit holds no pairs, but the prover still records every assigned cell in its witness columns,
+// so proving memory grows with the batch as before. `pairs` is called again on every synthesis pass
+// (keygen, then proving), so it should be cheap and pure.
+// The digests are the same as BatchHashCircuit's; the layout is not, as each pair's load and hash
+// regions are interleaved.
+pub struct StreamingBatchCircuit<G> {
+    pub len: usize,
+    // None without witnesses.
+    pub pairs: Option<G>,
+}
+
+impl<G: Fn(usize) -> [Fp; 2]> StreamingBatchCircuit<G> {
+    pub fn new(len: usize, pairs: G) -> Self {
+        Self {
+            len,
+            pairs: Some(pairs),
+        }
+    }
+
+    // The digests to expose, computed natively one pair at a time.
+    pub fn digests(&self) -> Vec<Fp> {
+        let pairs = match &self.pairs {
+            Some(pairs) => pairs,
+            None => return Vec::new(),
+        };
+        (0..self.len)
+            .map(|i| Hash::<_, P128Pow5T3, ConstantLength<2>, 3, 2>::init().hash(pairs(i)))
+            .collect()
+    }
+}
+
+impl<G: Fn(usize) -> [Fp; 2]> Circuit<Fp> for StreamingBatchCircuit<G> {
+    type Config = PoseidonConfig<Fp, 3, 2, 2>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            len: self.len,
+            pairs: None,
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        PoseidonChip::<Fp, P128Pow5T3, 3, 2, 2>::configure(meta)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let poseidon_chip = PoseidonChip::<Fp, P128Pow5T3, 3, 2, 2>::construct(config);
+        let initial = poseidon_chip.initial_state::<2>(layouter.namespace(|| "initial state"))?;
+        for i in 0..self.len {
+            let pair = match &self.pairs {
+                Some(pairs) => pairs(i).map(Value::known),
+                None => [Value::unknown(); 2],
+            };
+            let pair = poseidon_chip
+                .load_private_inputs(layouter.namespace(|| format!("pair {}", i)), pair)?;
+            let digest = poseidon_chip.hash_from(
+                layouter.namespace(|| format!("hash {}", i)),
+                &initial,
+                &pair,
+            )?;
+            poseidon_chip.expose_public(
+                layouter.namespace(|| format!("digest {}", i)),
+                &digest,
+                i,
+            )?;
+        }
+        Ok(())
+    }
+}
+
 mod tests {
+    use crate::coverage::{assert_mock_satisfied, mock_verify};
     use std::marker::PhantomData;
 
-    use super::PoseidonCircuit;
+    use super::{BatchHashCircuit, HashChainCircuit, PoseidonCircuit, StreamingBatchCircuit};
+    use crate::analysis::analyze;
+    #[cfg(feature = "unstable-t5")]
+    use crate::chips::poseidon::P128Pow5T5;
+    use crate::encoding::compress;
     use halo2_gadgets::poseidon::{
         primitives::{self as poseidon, ConstantLength, P128Pow5T3 as OrchardNullifier, Spec},
         Hash,
     };
-    use halo2_proofs::{circuit::Value, dev::MockProver, pasta::Fp};
+    use halo2_proofs::{circuit::Value, pasta::Fp};
 
     #[test]
     fn test() {
@@ -78,7 +272,76 @@ mod tests {
             _spec: PhantomData,
         };
         let public_input = vec![output];
-        let prover = MockProver::run(10, &circuit, vec![public_input.clone()]).unwrap();
-        prover.assert_satisfied();
+        assert_mock_satisfied(10, &circuit, vec![public_input.clone()]);
+    }
+
+    #[cfg(feature = "unstable-t5")]
+    #[test]
+    fn test_t5() {
+        let message = [1u64, 2, 3, 4].map(Fp::from);
+        let output = poseidon::Hash::<_, P128Pow5T5, ConstantLength<4>, 5, 4>::init().hash(message);
+        let circuit = PoseidonCircuit::<P128Pow5T5, 5, 4, 4>::new(
+            message.map(Value::known),
+            Value::known(output),
+        );
+        assert_mock_satisfied(10, &circuit, vec![vec![output]]);
+        assert!(mock_verify(10, &circuit, vec![vec![output + Fp::one()]])
+            .unwrap()
+            .is_err());
+    }
+
+    #[test]
+    fn test_hash_chain() {
+        let words: Vec<Fp> = (1..=5u64).map(Fp::from).collect();
+        let circuit = HashChainCircuit {
+            words: words.iter().map(|x| Value::known(*x)).collect(),
+        };
+        let public_input = vec![compress(&words)];
+        assert_mock_satisfied(10, &circuit, vec![public_input]);
+    }
+
+    #[test]
+    fn test_batch() {
+        let pairs: Vec<[Fp; 2]> = (0..64u64)
+            .map(|i| [Fp::from(2 * i), Fp::from(2 * i + 1)])
+            .collect();
+        let digests: Vec<Fp> = pairs
+            .iter()
+            .map(|pair| {
+                poseidon::Hash::<_, OrchardNullifier, ConstantLength<2>, 3, 2>::init().hash(*pair)
+            })
+            .collect();
+        let circuit = |shared: bool| BatchHashCircuit {
+            pairs: pairs.iter().map(|pair| pair.map(Value::known)).collect(),
+            shared,
+        };
+
+        let plain = analyze(&circuit(false)).unwrap().rows;
+        let shared = analyze(&circuit(true)).unwrap().rows;
+        assert!(shared < plain);
+
+        assert_mock_satisfied(12, &circuit(true), vec![digests.clone()]);
+        let mut wrong = digests;
+        wrong[17] += Fp::one();
+        assert!(mock_verify(12, &circuit(true), vec![wrong])
+            .unwrap()
+            .is_err());
+    }
+
+    #[test]
+    fn test_streaming() {
+        let pair = |i: usize| [Fp::from(2 * i as u64), Fp::from(2 * i as u64 + 1)];
+        let circuit = StreamingBatchCircuit::new(64, pair);
+        let digests = circuit.digests();
+        let batch = BatchHashCircuit {
+            pairs: (0..64).map(|i| pair(i).map(Value::known)).collect(),
+            shared: true,
+        };
+        assert_mock_satisfied(12, &batch, vec![digests.clone()]);
+
+        assert_mock_satisfied(12, &circuit, vec![digests.clone()]);
+        let mut wrong = digests;
+        wrong[63] += Fp::one();
+        assert!(mock_verify(12, &circuit, vec![wrong]).unwrap().is_err());
     }
 }
@@ -0,0 +1,211 @@
+/*
+Proof of reserves: proves that K selected leaves of a Poseidon tree are balances whose sum equals a
+public total, without revealing the rest of the tree. Each balance is range checked to 64 bits by
+packing its 8 big-endian bytes with LeafEncodingChip, so K balances can't sum past the modulus, and
+the packed cell is used directly as the leaf of its MerkleTreeV3 path.
+
+Instance rows: the root, the total, then the path index bits of every selected leaf (leaf layer
+first). The positions are public so the verifier can check that no leaf was counted twice, which
+`instances` does.
+*/
+
+use super::super::chips::leaf_encoding::{LeafEncodingChip, LeafEncodingConfig};
+use super::super::chips::merkle_v3::{MerkleTreeV3Chip, MerkleTreeV3Config};
+use super::super::chips::sum::{SumChip, SumConfig};
+use crate::encoding::index_bits;
+use crate::error::{self, validate_path};
+use crate::tree::MerklePath;
+use halo2_proofs::{circuit::*, pasta::Fp, plonk::*};
+
+pub const ROOT_ROW: usize = 0;
+pub const TOTAL_ROW: usize = 1;
+const FIRST_INDEX_ROW: usize = 2;
+
+#[derive(Debug, Clone)]
+pub struct ReservesConfig {
+    pub merkle: MerkleTreeV3Config,
+    pub encoding: LeafEncodingConfig,
+    pub sum: SumConfig,
+}
+
+#[derive(Default)]
+pub struct ReservesCircuit {
+    pub balances: Vec<Value<u64>>,
+    pub elements: Vec<Vec<Value<Fp>>>,
+    pub indices: Vec<Vec<Value<Fp>>>,
+}
+
+impl ReservesCircuit {
+    // `paths[i]` must be the path of the leaf `Fp::from(balances[i])`.
+    pub fn new(balances: &[u64], paths: &[MerklePath]) -> Result<Self, error::Error> {
+        if balances.len() != paths.len() {
+            return Err(error::Error::BalanceCount {
+                paths: paths.len(),
+                balances: balances.len(),
+            });
+        }
+        let depth = paths.first().ok_or(error::Error::EmptyPath)?.elements.len();
+        let mut elements = Vec::with_capacity(paths.len());
+        let mut indices = Vec::with_capacity(paths.len());
+        for path in paths {
+            let path_elements: Vec<Value<Fp>> =
+                path.elements.iter().map(|x| Value::known(*x)).collect();
+            let path_indices: Vec<Value<Fp>> =
+                path.indices.iter().map(|x| Value::known(*x)).collect();
+            validate_path(depth, &path_elements, &path_indices)?;
+            elements.push(path_elements);
+            indices.push(path_indices);
+        }
+        Ok(Self {
+            balances: balances.iter().map(|x| Value::known(*x)).collect(),
+            elements,
+            indices,
+        })
+    }
+
+    // The instance columns for a proof over the leaves at `positions`.
+    pub fn instances(
+        root: Fp,
+        total: Fp,
+        positions: &[u64],
+        depth: usize,
+    ) -> Result<Vec<Vec<Fp>>, error::Error> {
+        let mut column = vec![root, total];
+        for (i, position) in positions.iter().enumerate() {
+            if positions[..i].contains(position) {
+                return Err(error::Error::DuplicateLeaf {
+                    index: *position as usize,
+                });
+            }
+            column.extend(index_bits::<Fp>(*position, depth)?);
+        }
+        Ok(vec![column, vec![]])
+    }
+}
+
+impl Circuit<Fp> for ReservesCircuit {
+    type Config = ReservesConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            balances: vec![Value::unknown(); self.balances.len()],
+            elements: self
+                .elements
+                .iter()
+                .map(|path| vec![Value::unknown(); path.len()])
+                .collect(),
+            indices: self
+                .indices
+                .iter()
+                .map(|path| vec![Value::unknown(); path.len()])
+                .collect(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let col_a = meta.advice_column();
+        let col_b = meta.advice_column();
+        let col_c = meta.advice_column();
+        let instance = meta.instance_column();
+        ReservesConfig {
+            merkle: MerkleTreeV3Chip::<Fp>::configure(meta, [col_a, col_b, col_c], instance),
+            encoding: LeafEncodingChip::configure(meta, [col_a, col_b], instance),
+            sum: SumChip::configure(meta, [col_a, col_b]),
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let merkle_chip = MerkleTreeV3Chip::<Fp>::construct(config.merkle);
+        let encoding_chip = LeafEncodingChip::construct(config.encoding);
+        let sum_chip = SumChip::construct(config.sum);
+        encoding_chip.load_table(layouter.namespace(|| "byte table"))?;
+
+        let mut amounts = Vec::with_capacity(self.balances.len());
+        for (i, ((balance, elements), indices)) in self
+            .balances
+            .iter()
+            .zip(self.elements.iter())
+            .zip(self.indices.iter())
+            .enumerate()
+        {
+            let bytes: Vec<Value<u8>> = (0..8)
+                .map(|j| balance.map(|b| b.to_be_bytes()[j]))
+                .collect();
+            let amount =
+                encoding_chip.pack(layouter.namespace(|| format!("balance {}", i)), &bytes)?;
+            let root = merkle_chip.merkle_prove(
+                layouter.namespace(|| format!("path {}", i)),
+                &amount,
+                elements,
+                indices,
+            )?;
+            merkle_chip.expose_public(
+                layouter.namespace(|| format!("root {}", i)),
+                &root.cell,
+                ROOT_ROW,
+            )?;
+            for (level, index) in root.indices.iter().enumerate() {
+                merkle_chip.expose_public(
+                    layouter.namespace(|| format!("index {} {}", i, level)),
+                    index,
+                    FIRST_INDEX_ROW + i * elements.len() + level,
+                )?;
+            }
+            amounts.push(amount);
+        }
+
+        let total = sum_chip.sum(layouter.namespace(|| "total"), &amounts)?;
+        merkle_chip.expose_public(layouter.namespace(|| "public total"), &total, TOTAL_ROW)
+    }
+}
+
+mod tests {
+    use super::ReservesCircuit;
+    use crate::chips::merkle::DomainSeparation;
+    use crate::coverage::{assert_mock_satisfied, mock_verify};
+    use crate::error::Error;
+    use crate::tree::MerkleTree;
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let balances = [50u64, 1_000, 7, u64::MAX, 0, 300, 12, 9];
+        let leaves: Vec<Fp> = balances.iter().map(|b| Fp::from(*b)).collect();
+        let tree = MerkleTree::new(3, &leaves, DomainSeparation::Legacy).unwrap();
+
+        let positions = [1u64, 3, 5];
+        let selected: Vec<u64> = positions.iter().map(|p| balances[*p as usize]).collect();
+        let paths: Vec<_> = positions
+            .iter()
+            .map(|p| tree.path(*p as usize).unwrap())
+            .collect();
+        let total = selected
+            .iter()
+            .fold(Fp::zero(), |acc, b| acc + Fp::from(*b));
+
+        let circuit = ReservesCircuit::new(&selected, &paths).unwrap();
+        assert!(matches!(
+            ReservesCircuit::new(&selected[..2], &paths),
+            Err(Error::BalanceCount {
+                paths: 3,
+                balances: 2
+            })
+        ));
+        let instances = ReservesCircuit::instances(tree.root(), total, &positions, 3).unwrap();
+        assert_mock_satisfied(11, &circuit, instances);
+
+        let instances =
+            ReservesCircuit::instances(tree.root(), total + Fp::one(), &positions, 3).unwrap();
+        assert!(mock_verify(11, &circuit, instances).unwrap().is_err());
+
+        // Claiming other positions than the ones proven fails, and repeats are rejected outright.
+        let instances = ReservesCircuit::instances(tree.root(), total, &[1, 3, 6], 3).unwrap();
+        assert!(mock_verify(11, &circuit, instances).unwrap().is_err());
+        assert!(ReservesCircuit::instances(tree.root(), total, &[1, 1, 5], 3).is_err());
+    }
+}
@@ -0,0 +1,78 @@
+use super::super::chips::secp256k1_leaf::{Secp256k1LeafChip, Secp256k1LeafConfig};
+use crate::encoding::Secp256k1PublicKey;
+use halo2_proofs::{circuit::*, pasta::Fp, plonk::*};
+
+// Derives the leaf of a private secp256k1 key and exposes it.
+pub struct Secp256k1LeafCircuit {
+    pub key: [Value<u8>; 64],
+}
+
+impl Default for Secp256k1LeafCircuit {
+    fn default() -> Self {
+        Self {
+            key: [Value::unknown(); 64],
+        }
+    }
+}
+
+impl Secp256k1LeafCircuit {
+    pub fn new(key: &Secp256k1PublicKey) -> Self {
+        Self {
+            key: key.0.map(Value::known),
+        }
+    }
+}
+
+impl Circuit<Fp> for Secp256k1LeafCircuit {
+    type Config = Secp256k1LeafConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::default()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let col_byte = meta.advice_column();
+        let col_acc = meta.advice_column();
+        let col_c = meta.advice_column();
+        let instance = meta.instance_column();
+        Secp256k1LeafChip::configure(meta, [col_byte, col_acc, col_c], instance)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let chip = Secp256k1LeafChip::construct(config);
+        chip.load_table(layouter.namespace(|| "byte table"))?;
+        let leaf = chip.leaf(layouter.namespace(|| "key leaf"), &self.key)?;
+        chip.expose_public(layouter.namespace(|| "public leaf"), &leaf, 0)
+    }
+}
+
+mod tests {
+    use super::Secp256k1LeafCircuit;
+    use crate::coverage::{assert_mock_satisfied, mock_verify};
+    use crate::encoding::{Secp256k1PublicKey, ToLeaf};
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let mut key = [0u8; 64];
+        for (i, byte) in key.iter_mut().enumerate() {
+            *byte = (i as u8).wrapping_mul(97).wrapping_add(13);
+        }
+        let key = Secp256k1PublicKey(key);
+        let leaf: Fp = key.to_leaf();
+
+        let circuit = Secp256k1LeafCircuit::new(&key);
+        assert_mock_satisfied(10, &circuit, vec![vec![leaf], vec![]]);
+
+        assert!(
+            mock_verify(10, &circuit, vec![vec![leaf + Fp::one()], vec![]])
+                .unwrap()
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,250 @@
+/*
+Deletion proof for the sparse Merkle tree: proves the transition R_old -> R_new where the leaf under
+a public key K was replaced with the empty value. The tree is SparseMerkleTree, keyed by K with
+zero as the empty leaf, so deleting a key is `SparseMerkleTree::remove(key)`.
+
+Both roots are computed with MerkleTreeV3Chip along one path: the siblings of the two evaluations
+are constrained equal, and the index bits of both are bound to the same instance rows, so the only
+thing that differs between R_old and R_new is the leaf under K.
+
+Siblings that are empty subtrees can be left out of the witness: `siblings` holds None at those
+levels, and EmptySubtreeChip takes the sibling from the empty hashes in its fixed column and flags
+it. The flags are not public, so a sibling given explicitly at an empty level is accepted exactly
+when it is the empty hash, since anything else changes both roots.
+
+Instance rows: the old root, the new root, then the bits of K (leaf layer first).
+*/
+
+use super::super::chips::empty_subtree::{EmptySubtreeChip, EmptySubtreeConfig};
+use super::super::chips::merkle_v3::{DomainSeparation, MerkleTreeV3Chip, MerkleTreeV3Config};
+use crate::encoding::index_bits;
+use crate::error::{self, validate_path};
+use crate::tree::{empty_hashes, MerklePath};
+use halo2_proofs::{circuit::*, pasta::Fp, plonk::*};
+
+pub const OLD_ROOT_ROW: usize = 0;
+pub const NEW_ROOT_ROW: usize = 1;
+const FIRST_KEY_ROW: usize = 2;
+
+#[derive(Debug, Clone)]
+pub struct SmtDeletionConfig {
+    pub merkle: MerkleTreeV3Config,
+    pub empty: EmptySubtreeConfig,
+}
+
+#[derive(Default)]
+pub struct SmtDeletionCircuit {
+    pub leaf: Value<Fp>,
+    // The sibling at each level, None where it is the empty subtree.
+    pub siblings: Vec<Value<Option<Fp>>>,
+    pub indices: Vec<Value<Fp>>,
+}
+
+impl SmtDeletionCircuit {
+    // `path` is the path of the leaf being deleted, taken before the deletion. Siblings equal to
+    // the empty hash of their level are left out.
+    pub fn new(path: &MerklePath) -> Result<Self, error::Error> {
+        let elements: Vec<Value<Fp>> = path.elements.iter().map(|x| Value::known(*x)).collect();
+        let indices: Vec<Value<Fp>> = path.indices.iter().map(|x| Value::known(*x)).collect();
+        validate_path(elements.len(), &elements, &indices)?;
+        let defaults = empty_hashes::<Fp>(DomainSeparation::Legacy, path.elements.len());
+        let siblings = path
+            .elements
+            .iter()
+            .zip(defaults.iter())
+            .map(|(element, default)| Value::known((element != default).then_some(*element)))
+            .collect();
+        Ok(Self {
+            leaf: Value::known(path.leaf),
+            siblings,
+            indices,
+        })
+    }
+
+    pub fn instances(
+        old_root: Fp,
+        new_root: Fp,
+        key: u64,
+        depth: usize,
+    ) -> Result<Vec<Vec<Fp>>, error::Error> {
+        let mut column = vec![old_root, new_root];
+        column.extend(index_bits::<Fp>(key, depth)?);
+        Ok(vec![column, vec![]])
+    }
+}
+
+impl Circuit<Fp> for SmtDeletionCircuit {
+    type Config = SmtDeletionConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            leaf: Value::unknown(),
+            siblings: vec![Value::unknown(); self.siblings.len()],
+            indices: vec![Value::unknown(); self.indices.len()],
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let col_a = meta.advice_column();
+        let col_b = meta.advice_column();
+        let col_c = meta.advice_column();
+        let instance = meta.instance_column();
+        SmtDeletionConfig {
+            merkle: MerkleTreeV3Chip::<Fp>::configure(meta, [col_a, col_b, col_c], instance),
+            empty: EmptySubtreeChip::configure(meta, [col_a, col_b]),
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let chip = MerkleTreeV3Chip::<Fp>::construct(config.merkle);
+        let empty_chip = EmptySubtreeChip::construct(
+            config.empty,
+            empty_hashes(DomainSeparation::Legacy, self.siblings.len()),
+        );
+        let elements: Vec<Value<Fp>> = self
+            .siblings
+            .iter()
+            .enumerate()
+            .map(|(level, sibling)| empty_chip.resolve(level, *sibling))
+            .collect();
+        let empty: Vec<Value<bool>> = self
+            .siblings
+            .iter()
+            .map(|sibling| sibling.map(|sibling| sibling.is_none()))
+            .collect();
+
+        let old_leaf = chip.load_private(layouter.namespace(|| "old leaf"), self.leaf)?;
+        let empty_leaf = chip.load_constant(layouter.namespace(|| "empty leaf"), Fp::zero())?;
+
+        let old_root = chip.merkle_prove(
+            layouter.namespace(|| "old path"),
+            &old_leaf,
+            &elements,
+            &self.indices,
+        )?;
+        let new_root = chip.merkle_prove(
+            layouter.namespace(|| "new path"),
+            &empty_leaf,
+            &elements,
+            &self.indices,
+        )?;
+        empty_chip.constrain_siblings(
+            layouter.namespace(|| "empty siblings"),
+            &old_root.elements,
+            &empty,
+        )?;
+
+        for (level, (old, new)) in old_root
+            .elements
+            .iter()
+            .zip(new_root.elements.iter())
+            .enumerate()
+        {
+            chip.constrain_equal(
+                layouter.namespace(|| format!("sibling {}", level)),
+                old,
+                new,
+            )?;
+        }
+        for (level, (old, new)) in old_root
+            .indices
+            .iter()
+            .zip(new_root.indices.iter())
+            .enumerate()
+        {
+            chip.expose_public(
+                layouter.namespace(|| format!("old key bit {}", level)),
+                old,
+                FIRST_KEY_ROW + level,
+            )?;
+            chip.expose_public(
+                layouter.namespace(|| format!("new key bit {}", level)),
+                new,
+                FIRST_KEY_ROW + level,
+            )?;
+        }
+        chip.expose_public(
+            layouter.namespace(|| "old root"),
+            &old_root.cell,
+            OLD_ROOT_ROW,
+        )?;
+        chip.expose_public(
+            layouter.namespace(|| "new root"),
+            &new_root.cell,
+            NEW_ROOT_ROW,
+        )
+    }
+}
+
+mod tests {
+    use super::SmtDeletionCircuit;
+    use crate::chips::merkle::DomainSeparation;
+    use crate::coverage::{assert_mock_satisfied, mock_verify};
+    use crate::smt::SparseMerkleTree;
+    use halo2_proofs::{circuit::Value, pasta::Fp};
+
+    #[test]
+    fn test() {
+        let mut tree = SparseMerkleTree::new(3, DomainSeparation::Legacy).unwrap();
+        for key in 0..6u64 {
+            tree.insert(key, Fp::from(key + 1)).unwrap();
+        }
+        let key = 4u64;
+        let mut deleted = tree.clone();
+        deleted.remove(key).unwrap();
+
+        let circuit = SmtDeletionCircuit::new(&tree.path(key).unwrap()).unwrap();
+        let instances = SmtDeletionCircuit::instances(tree.root(), deleted.root(), key, 3).unwrap();
+        assert_mock_satisfied(10, &circuit, instances.clone());
+
+        // Leaves 6 and 7 are empty, so the level 1 sibling is left out of the witness and taken
+        // from the fixed column. Leaving out a populated sibling fails.
+        let path = tree.path(key).unwrap();
+        let build = |siblings: Vec<Option<Fp>>| SmtDeletionCircuit {
+            leaf: Value::known(path.leaf),
+            siblings: siblings.into_iter().map(Value::known).collect(),
+            indices: path.indices.iter().map(|bit| Value::known(*bit)).collect(),
+        };
+        let compact = build(vec![Some(path.elements[0]), None, Some(path.elements[2])]);
+        assert_mock_satisfied(10, &compact, instances.clone());
+        let missing = build(vec![None, None, Some(path.elements[2])]);
+        assert!(mock_verify(10, &missing, instances.clone())
+            .unwrap()
+            .is_err());
+
+        // The flag is the prover's: the empty sibling may be given unflagged, and then only the
+        // roots catch a wrong default.
+        let unflagged = build(vec![
+            Some(path.elements[0]),
+            Some(path.elements[1]),
+            Some(path.elements[2]),
+        ]);
+        assert_mock_satisfied(10, &unflagged, instances.clone());
+        let wrong_default = build(vec![
+            Some(path.elements[0]),
+            Some(path.elements[1] + Fp::one()),
+            Some(path.elements[2]),
+        ]);
+        assert!(mock_verify(10, &wrong_default, instances.clone())
+            .unwrap()
+            .is_err());
+
+        // The roots can't be swapped, and the deletion can't be attributed to another key.
+        let instances = SmtDeletionCircuit::instances(deleted.root(), tree.root(), key, 3).unwrap();
+        assert!(mock_verify(10, &circuit, instances).unwrap().is_err());
+        let instances = SmtDeletionCircuit::instances(tree.root(), deleted.root(), 5, 3).unwrap();
+        assert!(mock_verify(10, &circuit, instances).unwrap().is_err());
+
+        // Replacing the leaf with anything but the empty value is not a deletion.
+        let mut replaced = tree.clone();
+        replaced.insert(key, Fp::from(42)).unwrap();
+        let instances =
+            SmtDeletionCircuit::instances(tree.root(), replaced.root(), key, 3).unwrap();
+        assert!(mock_verify(10, &circuit, instances).unwrap().is_err());
+    }
+}
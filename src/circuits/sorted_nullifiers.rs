@@ -0,0 +1,153 @@
+/*
+Sorted nullifier batch: proves the K public nullifiers revealed by a batch spend are strictly
+increasing, so the contract can reject reuse with a single cursor over its sorted nullifier set
+instead of K set lookups, and a batch can't repeat a nullifier. Batch circuits that derive their
+nullifiers in-circuit call `ComparatorChip::assert_strictly_increasing` on the derived cells the
+same way; this circuit checks the ordering alone.
+
+Nullifiers are compared as integers below 2^254 (see ComparatorChip). Order them with
+`sort_nullifiers` before building the batch.
+
+Instance rows: the nullifiers, in order.
+*/
+
+use super::super::chips::comparator::{ComparatorChip, ComparatorConfig, HI_BITS, LO_BITS};
+use crate::error;
+use crate::serialization::to_bytes_be;
+use halo2_proofs::{circuit::*, pasta::Fp, plonk::*};
+
+// Sorts nullifiers into the circuit's order, as integers.
+pub fn sort_nullifiers(nullifiers: &mut [Fp]) {
+    nullifiers.sort_by_key(to_bytes_be);
+}
+
+#[derive(Default)]
+pub struct SortedNullifiersCircuit {
+    pub nullifiers: Vec<Value<Fp>>,
+}
+
+impl SortedNullifiersCircuit {
+    pub fn new(nullifiers: &[Fp]) -> Result<Self, error::Error> {
+        let bits = HI_BITS + LO_BITS;
+        for (index, nullifier) in nullifiers.iter().enumerate() {
+            // The top two bits of the 256-bit big-endian encoding must be clear.
+            if to_bytes_be(nullifier)[0] >> (8 - (256 - bits)) != 0 {
+                return Err(error::Error::ValueTooLarge { index, bits });
+            }
+            if index > 0 && to_bytes_be(&nullifiers[index - 1]) >= to_bytes_be(nullifier) {
+                return Err(error::Error::Unsorted { index });
+            }
+        }
+        Ok(Self {
+            nullifiers: nullifiers.iter().map(|x| Value::known(*x)).collect(),
+        })
+    }
+
+    pub fn instances(nullifiers: &[Fp]) -> Vec<Vec<Fp>> {
+        vec![nullifiers.to_vec()]
+    }
+}
+
+impl Circuit<Fp> for SortedNullifiersCircuit {
+    type Config = ComparatorConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            nullifiers: vec![Value::unknown(); self.nullifiers.len()],
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let col_a = meta.advice_column();
+        let col_b = meta.advice_column();
+        let col_c = meta.advice_column();
+        let instance = meta.instance_column();
+        ComparatorChip::configure(meta, [col_a, col_b, col_c], instance)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let chip = ComparatorChip::construct(config);
+        chip.load_table(layouter.namespace(|| "range table"))?;
+        let cells = self
+            .nullifiers
+            .iter()
+            .enumerate()
+            .map(|(i, nullifier)| {
+                chip.load_private(
+                    layouter.namespace(|| format!("nullifier {}", i)),
+                    *nullifier,
+                )
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        chip.assert_strictly_increasing(layouter.namespace(|| "sorted"), &cells)?;
+        for (row, cell) in cells.iter().enumerate() {
+            chip.expose_public(
+                layouter.namespace(|| format!("public nullifier {}", row)),
+                cell,
+                row,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "poseidon")]
+mod tests {
+    use super::{sort_nullifiers, SortedNullifiersCircuit};
+    use crate::circuits::allowlist::nullifier;
+    use crate::coverage::{assert_mock_satisfied, mock_verify};
+    use crate::error::Error;
+    use halo2_proofs::{circuit::Value, pasta::Fp};
+
+    #[test]
+    fn test() {
+        let mut nullifiers: Vec<Fp> = (1..=4u64)
+            .map(|i| nullifier(Fp::from(i), Fp::from(9)))
+            .collect();
+        nullifiers.push(Fp::from(7));
+        sort_nullifiers(&mut nullifiers);
+        let circuit = SortedNullifiersCircuit::new(&nullifiers).unwrap();
+        assert_mock_satisfied(
+            10,
+            &circuit,
+            SortedNullifiersCircuit::instances(&nullifiers),
+        );
+
+        // A repeated nullifier, an unsorted batch, and a value past 2^254 all fail in-circuit.
+        let mut repeated = nullifiers.clone();
+        repeated[2] = repeated[1];
+        let mut swapped = nullifiers.clone();
+        swapped.swap(3, 4);
+        let mut large = nullifiers.clone();
+        large[4] = -Fp::one();
+        for batch in [repeated, swapped, large] {
+            let circuit = SortedNullifiersCircuit {
+                nullifiers: batch.iter().map(|x| Value::known(*x)).collect(),
+            };
+            assert!(
+                mock_verify(10, &circuit, SortedNullifiersCircuit::instances(&batch))
+                    .unwrap()
+                    .is_err()
+            );
+        }
+
+        let mut unsorted = nullifiers.clone();
+        unsorted.swap(0, 1);
+        assert!(matches!(
+            SortedNullifiersCircuit::new(&unsorted),
+            Err(Error::Unsorted { index: 1 })
+        ));
+        assert!(matches!(
+            SortedNullifiersCircuit::new(&[Fp::one(), -Fp::one()]),
+            Err(Error::ValueTooLarge {
+                index: 1,
+                bits: 254
+            })
+        ));
+    }
+}
@@ -0,0 +1,158 @@
+/*
+Stealth claim: the leaf is a registered master key mpk = Poseidon(secret), the same commitment as
+the allowlist gate, and the prover shows that a public one-off key was derived from it as
+stealth = PRF_secret(nonce) for a private nonce, together with membership of mpk under the
+root. Claims can then go to a fresh key each time without linking back to the registered one:
+without the secret and nonce, neither the leaf nor the master key can be recovered from stealth.
+
+The derivation is hash-based rather than an in-circuit scalar multiplication. It is PrfChip's PRF
+under the stealth tag, which keeps stealth keys apart from allowlist nullifiers.
+
+Instance rows: the root, then the stealth key.
+*/
+
+use super::super::chips::merkle_v3::{MerkleTreeV3Chip, MerkleTreeV3Config};
+use super::super::chips::poseidon::PoseidonChip;
+use super::super::chips::prf::{prf, PrfChip, PrfDomain};
+use super::allowlist::identity_commitment;
+use crate::error::{self, validate_path};
+use crate::tree::MerklePath;
+use halo2_gadgets::poseidon::primitives::P128Pow5T3 as OrchardNullifier;
+use halo2_proofs::{circuit::*, pasta::Fp, plonk::*};
+
+pub const ROOT_ROW: usize = 0;
+pub const STEALTH_KEY_ROW: usize = 1;
+
+pub fn stealth_key(secret: Fp, nonce: Fp) -> Fp {
+    prf(PrfDomain::Stealth, secret, nonce)
+}
+
+#[derive(Default)]
+pub struct StealthClaimCircuit {
+    pub secret: Value<Fp>,
+    pub nonce: Value<Fp>,
+    pub elements: Vec<Value<Fp>>,
+    pub indices: Vec<Value<Fp>>,
+}
+
+impl StealthClaimCircuit {
+    // `path` is the path of the master key `identity_commitment(secret)`.
+    pub fn new(secret: Fp, nonce: Fp, path: &MerklePath) -> Result<Self, error::Error> {
+        if path.leaf != identity_commitment(secret) {
+            return Err(error::Error::LeafMismatch);
+        }
+        let elements: Vec<Value<Fp>> = path.elements.iter().map(|x| Value::known(*x)).collect();
+        let indices: Vec<Value<Fp>> = path.indices.iter().map(|x| Value::known(*x)).collect();
+        validate_path(elements.len(), &elements, &indices)?;
+        Ok(Self {
+            secret: Value::known(secret),
+            nonce: Value::known(nonce),
+            elements,
+            indices,
+        })
+    }
+
+    pub fn instances(root: Fp, stealth_key: Fp) -> Vec<Vec<Fp>> {
+        vec![vec![root, stealth_key], vec![]]
+    }
+}
+
+impl Circuit<Fp> for StealthClaimCircuit {
+    type Config = MerkleTreeV3Config;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            secret: Value::unknown(),
+            nonce: Value::unknown(),
+            elements: vec![Value::unknown(); self.elements.len()],
+            indices: vec![Value::unknown(); self.indices.len()],
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let col_a = meta.advice_column();
+        let col_b = meta.advice_column();
+        let col_c = meta.advice_column();
+        let instance = meta.instance_column();
+        MerkleTreeV3Chip::<Fp>::configure(meta, [col_a, col_b, col_c], instance)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let poseidon_chip =
+            PoseidonChip::<Fp, OrchardNullifier, 3, 2, 2>::construct(config.hash_config.clone());
+        let prf_chip = PrfChip::construct(config.clone(), PrfDomain::Stealth);
+        let chip = MerkleTreeV3Chip::<Fp>::construct(config);
+
+        let secret = chip.load_private(layouter.namespace(|| "load secret"), self.secret)?;
+        let nonce = chip.load_private(layouter.namespace(|| "load nonce"), self.nonce)?;
+        let master_key = poseidon_chip
+            .hash_message::<1>(layouter.namespace(|| "master key"), &[secret.clone()])?;
+        let stealth_key = prf_chip.prf(layouter.namespace(|| "stealth key"), &secret, &nonce)?;
+        let root = chip.merkle_prove(
+            layouter.namespace(|| "merkle_prove"),
+            &master_key,
+            &self.elements,
+            &self.indices,
+        )?;
+
+        chip.expose_public(layouter.namespace(|| "public root"), &root.cell, ROOT_ROW)?;
+        chip.expose_public(
+            layouter.namespace(|| "public stealth key"),
+            &stealth_key,
+            STEALTH_KEY_ROW,
+        )
+    }
+}
+
+mod tests {
+    use super::{stealth_key, StealthClaimCircuit};
+    use crate::chips::merkle::DomainSeparation;
+    use crate::circuits::allowlist::{identity_commitment, nullifier};
+    use crate::coverage::{assert_mock_satisfied, mock_verify};
+    use crate::tree::MerkleTree;
+    use halo2_gadgets::poseidon::primitives::{
+        self as poseidon, ConstantLength, P128Pow5T3 as OrchardNullifier,
+    };
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let secrets: Vec<Fp> = (0..4u64).map(|i| Fp::from(7 * i + 5)).collect();
+        let master_keys: Vec<Fp> = secrets.iter().map(|s| identity_commitment(*s)).collect();
+        let tree = MerkleTree::new(2, &master_keys, DomainSeparation::Legacy).unwrap();
+        let path = tree.path(1).unwrap();
+
+        let nonce = Fp::from(31337);
+        let key = stealth_key(secrets[1], nonce);
+        assert_ne!(key, stealth_key(secrets[1], nonce + Fp::one()));
+        assert_ne!(key, nullifier(secrets[1], nonce));
+        // The stealth tag keeps keys derived as Poseidon(secret, nonce, 0) valid.
+        let unkeyed = poseidon::Hash::<_, OrchardNullifier, ConstantLength<3>, 3, 2>::init()
+            .hash([secrets[1], nonce, Fp::zero()]);
+        assert_eq!(key, unkeyed);
+
+        let circuit = StealthClaimCircuit::new(secrets[1], nonce, &path).unwrap();
+        assert_mock_satisfied(
+            10,
+            &circuit,
+            StealthClaimCircuit::instances(tree.root(), key),
+        );
+
+        // A key derived from another registered secret can't be claimed with this witness.
+        let other = stealth_key(secrets[2], nonce);
+        assert!(mock_verify(
+            10,
+            &circuit,
+            StealthClaimCircuit::instances(tree.root(), other)
+        )
+        .unwrap()
+        .is_err());
+
+        assert!(StealthClaimCircuit::new(secrets[2], nonce, &path).is_err());
+    }
+}
@@ -0,0 +1,202 @@
+/*
+Tree equality: proves that two roots commit to the same multiset of leaves, e.g. that a tree was
+rebalanced or re-sorted without adding, dropping or altering anything, while keeping the leaves
+private. Both trees are rebuilt in-circuit from their full leaf layers with Poseidon, then the leaf
+lists are compared with a grand product at gamma = Poseidon(root_a, root_b). The roots bind the
+leaves, so gamma is only known once both lists are fixed.
+
+The cost is 2 * (2^depth - 1) + 1 Poseidon hashes, so depth is capped at MAX_DEPTH (2047 hashes,
+k = 17). Leaves past those supplied are zero, as in MerkleTree.
+
+Instance rows: root A, then root B.
+*/
+
+use super::super::chips::poseidon::{PoseidonChip, PoseidonConfig};
+use super::super::chips::product::{GrandProductChip, GrandProductConfig};
+use crate::error;
+use halo2_gadgets::poseidon::primitives::P128Pow5T3 as OrchardNullifier;
+use halo2_proofs::{circuit::*, pasta::Fp, plonk::*};
+
+pub const ROOT_A_ROW: usize = 0;
+pub const ROOT_B_ROW: usize = 1;
+pub const MAX_DEPTH: usize = 10;
+
+#[derive(Debug, Clone)]
+pub struct TreeEqualityConfig {
+    pub poseidon: PoseidonConfig<Fp, 3, 2, 2>,
+    pub product: GrandProductConfig,
+}
+
+#[derive(Default)]
+pub struct TreeEqualityCircuit {
+    pub leaves: [Vec<Value<Fp>>; 2],
+}
+
+impl TreeEqualityCircuit {
+    pub fn new(depth: usize, a: &[Fp], b: &[Fp]) -> Result<Self, error::Error> {
+        if depth > MAX_DEPTH {
+            return Err(error::Error::DepthTooLarge {
+                max: MAX_DEPTH,
+                depth,
+            });
+        }
+        let capacity = 1 << depth;
+        let pad = |leaves: &[Fp]| -> Result<Vec<Value<Fp>>, error::Error> {
+            if leaves.len() > capacity {
+                return Err(error::Error::TooManyLeaves {
+                    capacity,
+                    leaves: leaves.len(),
+                });
+            }
+            let mut padded: Vec<Value<Fp>> = leaves.iter().map(|x| Value::known(*x)).collect();
+            padded.resize(capacity, Value::known(Fp::zero()));
+            Ok(padded)
+        };
+        Ok(Self {
+            leaves: [pad(a)?, pad(b)?],
+        })
+    }
+
+    pub fn instances(root_a: Fp, root_b: Fp) -> Vec<Vec<Fp>> {
+        vec![vec![root_a, root_b]]
+    }
+
+    fn root(
+        chip: &PoseidonChip<Fp, OrchardNullifier, 3, 2, 2>,
+        mut layouter: impl Layouter<Fp>,
+        leaves: &[AssignedCell<Fp, Fp>],
+    ) -> Result<AssignedCell<Fp, Fp>, Error> {
+        let mut layer = leaves.to_vec();
+        let mut level = 0;
+        while layer.len() > 1 {
+            layer = layer
+                .chunks(2)
+                .enumerate()
+                .map(|(i, pair)| {
+                    chip.hash(
+                        layouter.namespace(|| format!("node {} {}", level, i)),
+                        &[pair[0].clone(), pair[1].clone()],
+                    )
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            level += 1;
+        }
+        layer.pop().ok_or(Error::Synthesis)
+    }
+}
+
+impl Circuit<Fp> for TreeEqualityCircuit {
+    type Config = TreeEqualityConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            leaves: self
+                .leaves
+                .clone()
+                .map(|leaves| vec![Value::unknown(); leaves.len()]),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let col_a = meta.advice_column();
+        let col_b = meta.advice_column();
+        let col_c = meta.advice_column();
+        TreeEqualityConfig {
+            poseidon: PoseidonChip::<Fp, OrchardNullifier, 3, 2, 2>::configure(meta),
+            product: GrandProductChip::configure(meta, [col_a, col_b, col_c]),
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let poseidon_chip =
+            PoseidonChip::<Fp, OrchardNullifier, 3, 2, 2>::construct(config.poseidon);
+        let product_chip = GrandProductChip::construct(config.product);
+
+        let mut leaves = Vec::with_capacity(2);
+        let mut roots = Vec::with_capacity(2);
+        for (tree, row) in [ROOT_A_ROW, ROOT_B_ROW].into_iter().enumerate() {
+            let cells = self.leaves[tree]
+                .iter()
+                .enumerate()
+                .map(|(i, leaf)| {
+                    poseidon_chip
+                        .load_private(layouter.namespace(|| format!("leaf {} {}", tree, i)), *leaf)
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            let root = Self::root(
+                &poseidon_chip,
+                layouter.namespace(|| format!("tree {}", tree)),
+                &cells,
+            )?;
+            poseidon_chip.expose_public(
+                layouter.namespace(|| format!("root {}", tree)),
+                &root,
+                row,
+            )?;
+            leaves.push(cells);
+            roots.push(root);
+        }
+
+        let gamma = poseidon_chip.hash(
+            layouter.namespace(|| "gamma"),
+            &[roots[0].clone(), roots[1].clone()],
+        )?;
+        let product_a =
+            product_chip.product(layouter.namespace(|| "product a"), &gamma, &leaves[0])?;
+        let product_b =
+            product_chip.product(layouter.namespace(|| "product b"), &gamma, &leaves[1])?;
+        layouter.assign_region(
+            || "equal products",
+            |mut region| region.constrain_equal(product_a.cell(), product_b.cell()),
+        )
+    }
+}
+
+mod tests {
+    use super::TreeEqualityCircuit;
+    use crate::chips::merkle::DomainSeparation;
+    use crate::coverage::{assert_mock_satisfied, mock_verify};
+    use crate::error::Error;
+    use crate::tree::MerkleTree;
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let a: Vec<Fp> = [5u64, 3, 9, 3, 1].iter().map(|x| Fp::from(*x)).collect();
+        let b: Vec<Fp> = [1u64, 3, 3, 5, 9].iter().map(|x| Fp::from(*x)).collect();
+        let c: Vec<Fp> = [1u64, 3, 5, 5, 9].iter().map(|x| Fp::from(*x)).collect();
+        let root = |leaves: &[Fp]| {
+            MerkleTree::new(3, leaves, DomainSeparation::Legacy)
+                .unwrap()
+                .root()
+        };
+
+        let circuit = TreeEqualityCircuit::new(3, &a, &b).unwrap();
+        assert_mock_satisfied(
+            11,
+            &circuit,
+            TreeEqualityCircuit::instances(root(&a), root(&b)),
+        );
+
+        // Same set, different multiplicities.
+        let circuit = TreeEqualityCircuit::new(3, &a, &c).unwrap();
+        assert!(mock_verify(
+            11,
+            &circuit,
+            TreeEqualityCircuit::instances(root(&a), root(&c))
+        )
+        .unwrap()
+        .is_err());
+
+        assert!(TreeEqualityCircuit::new(2, &a, &b).is_err());
+        assert!(matches!(
+            TreeEqualityCircuit::new(64, &a, &b),
+            Err(Error::DepthTooLarge { max: 10, depth: 64 })
+        ));
+    }
+}
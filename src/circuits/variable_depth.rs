@@ -0,0 +1,176 @@
+/*
+Membership in a tree of any depth up to MAX under one verifying key, for services that host trees of
+many sizes: the path is padded to MAX layers and VariableDepthChip's enable bits switch the padding
+off. The enable bits are public, so the verifier fixes the depth it expects; `instances` builds
+them from the depth.
+
+Instance rows: the leaf, the root, then MAX enable bits (leaf layer first).
+*/
+
+use super::super::chips::merkle_v3::DomainSeparation;
+use super::super::chips::variable_depth::{VariableDepthChip, VariableDepthConfig};
+use crate::error;
+use crate::tree::MerklePath;
+use halo2_proofs::{circuit::*, pasta::Fp, plonk::*};
+
+pub const LEAF_ROW: usize = 0;
+pub const ROOT_ROW: usize = 1;
+const FIRST_ENABLE_ROW: usize = 2;
+
+pub struct VariableDepthCircuit<const MAX: usize> {
+    pub leaf: Value<Fp>,
+    pub elements: [Value<Fp>; MAX],
+    pub indices: [Value<Fp>; MAX],
+    pub enables: [Value<Fp>; MAX],
+    pub separation: DomainSeparation,
+}
+
+impl<const MAX: usize> Default for VariableDepthCircuit<MAX> {
+    fn default() -> Self {
+        Self {
+            leaf: Value::unknown(),
+            elements: [Value::unknown(); MAX],
+            indices: [Value::unknown(); MAX],
+            enables: [Value::unknown(); MAX],
+            separation: DomainSeparation::Legacy,
+        }
+    }
+}
+
+impl<const MAX: usize> VariableDepthCircuit<MAX> {
+    // Pads `path` to MAX layers with zero siblings and index bits.
+    pub fn new(path: &MerklePath, separation: DomainSeparation) -> Result<Self, error::Error> {
+        let depth = path.elements.len();
+        let enables = Self::enables(depth)?;
+        if path.indices.len() != depth {
+            return Err(error::Error::LengthMismatch {
+                elements: depth,
+                indices: path.indices.len(),
+            });
+        }
+        let pad = |values: &[Fp]| -> [Value<Fp>; MAX] {
+            let mut padded = [Value::known(Fp::zero()); MAX];
+            for (padded, value) in padded.iter_mut().zip(values) {
+                *padded = Value::known(*value);
+            }
+            padded
+        };
+        let circuit = Self {
+            leaf: Value::known(path.leaf),
+            elements: pad(&path.elements),
+            indices: pad(&path.indices),
+            enables: enables.map(Value::known),
+            separation,
+        };
+        error::validate_path(MAX, &circuit.elements, &circuit.indices)?;
+        Ok(circuit)
+    }
+
+    // One enable bit per layer: set for the first `depth`, clear for the padding.
+    pub fn enables(depth: usize) -> Result<[Fp; MAX], error::Error> {
+        if depth == 0 {
+            return Err(error::Error::EmptyPath);
+        }
+        if depth > MAX {
+            return Err(error::Error::DepthTooLarge { max: MAX, depth });
+        }
+        let mut enables = [Fp::zero(); MAX];
+        enables[..depth].fill(Fp::one());
+        Ok(enables)
+    }
+
+    pub fn instances(leaf: Fp, root: Fp, depth: usize) -> Result<Vec<Vec<Fp>>, error::Error> {
+        let mut column = vec![leaf, root];
+        column.extend(Self::enables(depth)?);
+        Ok(vec![column, vec![]])
+    }
+}
+
+impl<const MAX: usize> Circuit<Fp> for VariableDepthCircuit<MAX> {
+    type Config = VariableDepthConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            separation: self.separation,
+            ..Self::default()
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fp>) -> Self::Config {
+        let col_a = meta.advice_column();
+        let col_b = meta.advice_column();
+        let col_c = meta.advice_column();
+        let instance = meta.instance_column();
+        VariableDepthChip::configure(meta, [col_a, col_b, col_c], instance)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fp>,
+    ) -> Result<(), Error> {
+        let chip = VariableDepthChip::construct(config).with_separation(self.separation);
+        let leaf = chip.load_private(layouter.namespace(|| "leaf"), self.leaf)?;
+        let (root, enables) = chip.merkle_prove(
+            layouter.namespace(|| "path"),
+            &leaf,
+            &self.elements,
+            &self.indices,
+            &self.enables,
+        )?;
+        for (level, enable) in enables.iter().enumerate() {
+            chip.expose_public(
+                layouter.namespace(|| format!("enable {}", level)),
+                enable,
+                FIRST_ENABLE_ROW + level,
+            )?;
+        }
+        chip.expose_public(layouter.namespace(|| "leaf"), &leaf, LEAF_ROW)?;
+        chip.expose_public(layouter.namespace(|| "root"), &root.cell, ROOT_ROW)
+    }
+}
+
+mod tests {
+    use super::VariableDepthCircuit;
+    use crate::chips::merkle::DomainSeparation;
+    use crate::coverage::{assert_mock_satisfied, mock_verify};
+    use crate::error::Error;
+    use crate::tree::MerkleTree;
+    use halo2_proofs::{pasta::Fp, plonk::keygen_vk, poly::commitment::Params};
+
+    #[test]
+    fn test() {
+        let leaves: Vec<Fp> = (1..=5u64).map(Fp::from).collect();
+        let params = Params::new(10);
+        let mut vks = Vec::new();
+        for depth in [3, 5, 6] {
+            for separation in [DomainSeparation::Legacy, DomainSeparation::Level] {
+                let tree = MerkleTree::new(depth, &leaves, separation).unwrap();
+                let path = tree.path(4).unwrap();
+                let circuit = VariableDepthCircuit::<6>::new(&path, separation).unwrap();
+                let instances =
+                    VariableDepthCircuit::<6>::instances(path.leaf, tree.root(), depth).unwrap();
+                assert_mock_satisfied(10, &circuit, instances);
+                if separation == DomainSeparation::Legacy {
+                    let vk = keygen_vk(&params, &circuit).unwrap();
+                    vks.push(format!("{:?}", vk.pinned()));
+                }
+
+                // The same path can't be passed off as one of another depth.
+                let other = if depth == 6 { 5 } else { depth + 1 };
+                let instances =
+                    VariableDepthCircuit::<6>::instances(path.leaf, tree.root(), other).unwrap();
+                assert!(mock_verify(10, &circuit, instances).unwrap().is_err());
+            }
+        }
+        // One verifying key for every depth.
+        assert!(vks.windows(2).all(|pair| pair[0] == pair[1]));
+
+        let tree = MerkleTree::new(7, &leaves, DomainSeparation::Legacy).unwrap();
+        assert!(matches!(
+            VariableDepthCircuit::<6>::new(&tree.path(0).unwrap(), DomainSeparation::Legacy),
+            Err(Error::DepthTooLarge { max: 6, depth: 7 })
+        ));
+    }
+}
@@ -0,0 +1,114 @@
+/*
+The smallest encoding of a `MerklePath`, for shipping witnesses to browser provers. The index bits
+are packed into one integer, which is the leaf index, and the leaf and siblings are concatenated
+with no per-element framing:
+
+    | depth: u8 | index: u64 LE | leaf: 32 bytes LE | depth siblings: 32 bytes LE each |
+
+Bit i of the index is the index at layer i, so paths are at most 64 layers deep. Unlike the wire
+format nothing describes the hash or root; the receiver is expected to know the tree. Decoding is
+strict: index bits at or above the depth must be zero and the length must match the depth exactly.
+*/
+
+use crate::error::Error;
+use crate::serialization::{from_bytes_le_checked, to_bytes_le, CanonicalField};
+use crate::tree::MerklePath;
+use crate::wire::{index_bits, index_from_bit};
+
+pub const MAX_DEPTH: usize = 64;
+
+// The depth byte and the packed index.
+const HEADER_LEN: usize = 1 + 8;
+
+impl<F: CanonicalField> MerklePath<F> {
+    pub fn to_compact(&self) -> Result<Vec<u8>, Error> {
+        let depth = self.elements.len();
+        if depth > MAX_DEPTH {
+            return Err(Error::Malformed("path deeper than 64 layers"));
+        }
+        let index = index_bits(depth as u8, self)?
+            .iter()
+            .enumerate()
+            .fold(0u64, |acc, (layer, bit)| acc | (*bit as u64) << layer);
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN + 32 * (depth + 1));
+        bytes.push(depth as u8);
+        bytes.extend_from_slice(&index.to_le_bytes());
+        bytes.extend_from_slice(&to_bytes_le(&self.leaf));
+        for element in &self.elements {
+            bytes.extend_from_slice(&to_bytes_le(element));
+        }
+        Ok(bytes)
+    }
+
+    pub fn from_compact(bytes: &[u8]) -> Result<Self, Error> {
+        let depth = *bytes.first().ok_or(Error::Malformed("truncated"))? as usize;
+        if depth > MAX_DEPTH {
+            return Err(Error::Malformed("path deeper than 64 layers"));
+        }
+        if bytes.len() != HEADER_LEN + 32 * (depth + 1) {
+            return Err(Error::Malformed("compact path length does not match depth"));
+        }
+        let index = u64::from_le_bytes(bytes[1..HEADER_LEN].try_into().expect("index is 8 bytes"));
+        if depth < MAX_DEPTH && index >> depth != 0 {
+            return Err(Error::Malformed("index has bits above the depth"));
+        }
+
+        let mut fields = bytes[HEADER_LEN..]
+            .chunks(32)
+            .map(|chunk| from_bytes_le_checked(chunk.try_into().expect("chunks are 32 bytes")));
+        let leaf = fields.next().expect("the leaf is present")?;
+        Ok(Self {
+            leaf,
+            elements: fields.collect::<Result<Vec<F>, _>>()?,
+            indices: (0..depth)
+                .map(|layer| index_from_bit(layer, (index >> layer) & 1))
+                .collect::<Result<Vec<F>, _>>()?,
+        })
+    }
+}
+
+mod tests {
+    use crate::chips::merkle::DomainSeparation;
+    use crate::tree::{MerklePath, MerkleTree};
+    use crate::wire::{HashId, MerkleProof, Payload};
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let separation = DomainSeparation::Legacy;
+        let leaves: Vec<Fp> = (0..11u64).map(Fp::from).collect();
+        let tree = MerkleTree::new(4, &leaves, separation).unwrap();
+        let path = tree.path(10).unwrap();
+        let bytes = path.to_compact().unwrap();
+        assert_eq!(bytes.len(), 9 + 32 * 5);
+        assert_eq!(bytes[..9], [4, 10, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(MerklePath::<Fp>::from_compact(&bytes).unwrap(), path);
+
+        // Smaller than the wire payload by the index bytes and the header.
+        let wire = MerkleProof {
+            hash: HashId::Poseidon(separation),
+            arity: 2,
+            depth: 4,
+            root: tree.root(),
+            payload: Payload::Path(path.clone()),
+        };
+        assert!(bytes.len() < wire.to_bytes().unwrap().len());
+
+        let corrupt = |offset: usize, value: u8| {
+            let mut bytes = bytes.clone();
+            bytes[offset] = value;
+            MerklePath::<Fp>::from_compact(&bytes)
+        };
+        assert!(corrupt(0, 5).is_err());
+        assert!(corrupt(0, 65).is_err());
+        assert!(corrupt(1, 0x1a).is_err());
+        assert!(corrupt(9 + 31, 0xff).is_err());
+        assert!(MerklePath::<Fp>::from_compact(&bytes[..bytes.len() - 1]).is_err());
+        assert!(MerklePath::<Fp>::from_compact(&[]).is_err());
+
+        let mut invalid = path;
+        invalid.indices[2] = Fp::from(2);
+        assert!(invalid.to_compact().is_err());
+    }
+}
@@ -0,0 +1,281 @@
+/*
+Differential checks of the bn254 compatibility modes against recorded reference outputs. A vector
+file is a JSON object with a list of cases, each naming the implementation that produced it:
+
+    {
+      "cases": [
+        { "kind": "poseidon", "source": "circomlibjs", "inputs": ["0x1"], "output": "0x…" },
+        { "kind": "lean-imt", "source": "zk-kit", "leaves": ["0x1", "0x2"], "root": "0x…",
+          "proofs": [{ "leaf_index": 0, "index": 0, "siblings": ["0x2"] }] }
+      ]
+    }
+
+The kinds are `poseidon` and `nullifier` (circomlib's Poseidon and Semaphore's nullifier),
+`tornado-zero` and `tornado-tree` (Tornado's empty-subtree roots, and roots and paths of a tree of
+commitments) and `lean-imt` (a group's root and proofs). Field elements are 0x-prefixed big-endian
+hex without padding, as BigInt.toString(16) writes them.
+
+vectors/compat.json holds the vectors this crate is checked against. It is not yet the output of
+vectors/generate.mjs: it has generate.mjs's Poseidon and nullifier inputs, with the outputs the
+script's libraries were not run for computed by light-poseidon (an independent circom-compatible
+Rust implementation, which agrees with circomlibjs on the two recorded ones), zk-kit's LeanIMT roots
+for one and two leaves, and Tornado's published empty-subtree roots for levels 0 to 4. Running
+generate.mjs replaces it with the full set, which adds LeanIMT groups with an odd node to lift, all
+21 zero levels and Tornado roots and withdrawal paths. `check_vectors` runs every case and fails
+with the index of the first one this crate disagrees with.
+*/
+
+use crate::error::Error;
+use crate::semaphore::{nullifier, poseidon, LeanImt, LeanImtProof};
+use crate::tornado::{from_bytes_be, zeros, Bn254Fr, TornadoTree, LEVELS};
+use serde_json::{Map, Value};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Case {
+    Poseidon {
+        inputs: Vec<Bn254Fr>,
+        output: Bn254Fr,
+    },
+    Nullifier {
+        scope: Bn254Fr,
+        secret: Bn254Fr,
+        output: Bn254Fr,
+    },
+    TornadoZero {
+        level: usize,
+        output: Bn254Fr,
+    },
+    // Each path is the leaf index with its siblings and pathIndices, bottom up.
+    TornadoTree {
+        commitments: Vec<Bn254Fr>,
+        root: Bn254Fr,
+        paths: Vec<(usize, Vec<Bn254Fr>, Vec<u64>)>,
+    },
+    // Each proof is the leaf index with the proof the reference gave for it.
+    LeanImt {
+        leaves: Vec<Bn254Fr>,
+        root: Bn254Fr,
+        proofs: Vec<(usize, LeanImtProof)>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vector {
+    pub source: String,
+    pub case: Case,
+}
+
+fn element(value: &Value) -> Result<Bn254Fr, Error> {
+    let digits = value
+        .as_str()
+        .and_then(|s| s.strip_prefix("0x"))
+        .ok_or(Error::Malformed("expected a hex string"))?;
+    if digits.is_empty() || digits.len() > 64 || !digits.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return Err(Error::Malformed("expected at most 64 hex digits"));
+    }
+    let padded = format!("{:0>64}", digits);
+    let mut bytes = [0u8; 32];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&padded[2 * i..2 * i + 2], 16).expect("checked hex digits");
+    }
+    from_bytes_be(&bytes)
+}
+
+fn get<'a>(object: &'a Map<String, Value>, key: &'static str) -> Result<&'a Value, Error> {
+    object.get(key).ok_or(Error::Malformed(key))
+}
+
+fn elements(value: &Value) -> Result<Vec<Bn254Fr>, Error> {
+    value
+        .as_array()
+        .ok_or(Error::Malformed("expected an array"))?
+        .iter()
+        .map(element)
+        .collect()
+}
+
+fn integer(value: &Value) -> Result<u64, Error> {
+    value
+        .as_u64()
+        .ok_or(Error::Malformed("expected an integer"))
+}
+
+fn objects(value: &Value) -> Result<Vec<&Map<String, Value>>, Error> {
+    value
+        .as_array()
+        .ok_or(Error::Malformed("expected an array"))?
+        .iter()
+        .map(|item| {
+            item.as_object()
+                .ok_or(Error::Malformed("expected an object"))
+        })
+        .collect()
+}
+
+fn parse_case(object: &Map<String, Value>) -> Result<Vector, Error> {
+    let source = get(object, "source")?
+        .as_str()
+        .ok_or(Error::Malformed("expected a source name"))?
+        .to_string();
+    let case = match get(object, "kind")?.as_str() {
+        Some("poseidon") => Case::Poseidon {
+            inputs: elements(get(object, "inputs")?)?,
+            output: element(get(object, "output")?)?,
+        },
+        Some("nullifier") => Case::Nullifier {
+            scope: element(get(object, "scope")?)?,
+            secret: element(get(object, "secret")?)?,
+            output: element(get(object, "output")?)?,
+        },
+        Some("tornado-zero") => Case::TornadoZero {
+            level: integer(get(object, "level")?)? as usize,
+            output: element(get(object, "output")?)?,
+        },
+        Some("tornado-tree") => Case::TornadoTree {
+            commitments: elements(get(object, "commitments")?)?,
+            root: element(get(object, "root")?)?,
+            paths: objects(get(object, "paths")?)?
+                .into_iter()
+                .map(|path| {
+                    let indices = get(path, "indices")?
+                        .as_array()
+                        .ok_or(Error::Malformed("expected an array"))?
+                        .iter()
+                        .map(integer)
+                        .collect::<Result<_, _>>()?;
+                    Ok((
+                        integer(get(path, "leaf_index")?)? as usize,
+                        elements(get(path, "elements")?)?,
+                        indices,
+                    ))
+                })
+                .collect::<Result<_, Error>>()?,
+        },
+        Some("lean-imt") => {
+            let root = element(get(object, "root")?)?;
+            let leaves = elements(get(object, "leaves")?)?;
+            let proofs = objects(get(object, "proofs")?)?
+                .into_iter()
+                .map(|proof| {
+                    let leaf_index = integer(get(proof, "leaf_index")?)? as usize;
+                    let leaf = *leaves
+                        .get(leaf_index)
+                        .ok_or(Error::Malformed("proof for a missing leaf"))?;
+                    Ok((
+                        leaf_index,
+                        LeanImtProof {
+                            root,
+                            leaf,
+                            index: integer(get(proof, "index")?)?,
+                            siblings: elements(get(proof, "siblings")?)?,
+                        },
+                    ))
+                })
+                .collect::<Result<_, Error>>()?;
+            Case::LeanImt {
+                leaves,
+                root,
+                proofs,
+            }
+        }
+        _ => return Err(Error::Malformed("unknown vector kind")),
+    };
+    Ok(Vector { source, case })
+}
+
+pub fn parse_vectors(json: &str) -> Result<Vec<Vector>, Error> {
+    let file: Value = serde_json::from_str(json).map_err(|_| Error::Malformed("invalid JSON"))?;
+    let cases = file
+        .as_object()
+        .ok_or(Error::Malformed("expected a JSON object"))
+        .and_then(|object| get(object, "cases"))?;
+    objects(cases)?.into_iter().map(parse_case).collect()
+}
+
+impl Vector {
+    // Whether this crate reproduces the recorded output.
+    pub fn check(&self) -> Result<bool, Error> {
+        Ok(match &self.case {
+            Case::Poseidon { inputs, output } => poseidon(inputs)? == *output,
+            Case::Nullifier {
+                scope,
+                secret,
+                output,
+            } => nullifier(*scope, *secret) == *output,
+            Case::TornadoZero { level, output } => {
+                if *level > LEVELS {
+                    return Err(Error::DepthTooLarge {
+                        max: LEVELS,
+                        depth: *level,
+                    });
+                }
+                zeros()[*level] == *output
+            }
+            Case::TornadoTree {
+                commitments,
+                root,
+                paths,
+            } => {
+                let tree = TornadoTree::new(commitments)?;
+                let mut matches = tree.root() == *root;
+                for (index, elements, indices) in paths {
+                    let path = tree.path(*index)?;
+                    let indices: Vec<Bn254Fr> = indices.iter().map(|x| Bn254Fr::from(*x)).collect();
+                    matches &= path.elements == *elements && path.indices == indices;
+                }
+                matches
+            }
+            Case::LeanImt {
+                leaves,
+                root,
+                proofs,
+            } => {
+                let group = LeanImt::new(leaves)?;
+                let mut matches = group.root() == *root;
+                for (index, proof) in proofs {
+                    matches &= group.proof(*index)? == *proof && proof.verify();
+                }
+                matches
+            }
+        })
+    }
+}
+
+// Checks every vector in a vector file, returning how many there were.
+pub fn check_vectors(json: &str) -> Result<usize, Error> {
+    let vectors = parse_vectors(json)?;
+    for (case, vector) in vectors.iter().enumerate() {
+        if !vector.check()? {
+            return Err(Error::VectorMismatch { case });
+        }
+    }
+    Ok(vectors.len())
+}
+
+mod tests {
+    use super::{check_vectors, parse_vectors, Case};
+    use crate::error::Error;
+
+    const VECTORS: &str = include_str!("../vectors/compat.json");
+
+    #[test]
+    fn test() {
+        let vectors = parse_vectors(VECTORS).unwrap();
+        assert_eq!(check_vectors(VECTORS).unwrap(), vectors.len());
+        for kind in ["poseidon", "nullifier", "tornado-zero", "lean-imt"] {
+            assert!(VECTORS.contains(&format!("\"kind\": \"{}\"", kind)));
+        }
+
+        // A changed digit in a recorded output is reported for that case.
+        assert!(matches!(vectors[0].case, Case::Poseidon { .. }));
+        let tampered = VECTORS.replacen("\"0x2917", "\"0x2918", 1);
+        assert!(matches!(
+            check_vectors(&tampered),
+            Err(Error::VectorMismatch { case: 0 })
+        ));
+        assert!(matches!(
+            check_vectors(&VECTORS.replacen("\"poseidon\"", "\"poseidon3\"", 1)),
+            Err(Error::Malformed(_))
+        ));
+    }
+}
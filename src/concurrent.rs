@@ -0,0 +1,201 @@
+/*
+A native tree shared between request handlers: paths are read while appends and updates go on, and
+every read says which version of the tree it came from.
+
+    let tree = ConcurrentTree::new(MerkleTree::new(20, &leaves, separation)?);
+    // on a writer thread
+    let (index, version) = tree.append(leaf)?;
+    // on any number of reader threads
+    let snapshot = tree.path(index)?;
+    let proof = prover.prove_path(snapshot.path, snapshot.root, OsRng)?;
+
+The tree sits behind an RwLock, held only to copy a path and root out (O(depth)) or to rehash one
+path on a write, never while proving. A `Snapshot` is consistent: its path leads to its root, which
+was the tree's root at `version`. The version starts at 0 and goes up by one per append or update,
+so a client can tell which root a proof was made against and whether it is still current.
+*/
+
+use crate::chips::merkle::DomainSeparation;
+use crate::chips::poseidon::PoseidonSpecFor;
+use crate::error::Error;
+use crate::tree::{MerklePath, MerkleTree};
+use halo2_proofs::pasta::Fp;
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot<F = Fp> {
+    pub version: u64,
+    pub root: F,
+    pub path: MerklePath<F>,
+}
+
+#[derive(Debug)]
+struct Versioned<F> {
+    tree: MerkleTree<F>,
+    version: u64,
+}
+
+#[derive(Debug)]
+pub struct ConcurrentTree<F = Fp> {
+    inner: RwLock<Versioned<F>>,
+}
+
+impl<F: PoseidonSpecFor> ConcurrentTree<F> {
+    pub fn new(tree: MerkleTree<F>) -> Self {
+        Self {
+            inner: RwLock::new(Versioned { tree, version: 0 }),
+        }
+    }
+
+    // Writers check capacity and indices before touching the tree, so a panic elsewhere while the
+    // lock was held can't have left it half-updated; a poisoned lock is recovered.
+    fn read(&self) -> RwLockReadGuard<'_, Versioned<F>> {
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Versioned<F>> {
+        self.inner.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn depth(&self) -> usize {
+        self.read().tree.depth()
+    }
+
+    pub fn separation(&self) -> DomainSeparation {
+        self.read().tree.separation()
+    }
+
+    pub fn version(&self) -> u64 {
+        self.read().version
+    }
+
+    pub fn len(&self) -> usize {
+        self.read().tree.leaves().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The current root and the version it belongs to.
+    pub fn root(&self) -> (F, u64) {
+        let inner = self.read();
+        (inner.tree.root(), inner.version)
+    }
+
+    pub fn path(&self, index: usize) -> Result<Snapshot<F>, Error> {
+        let inner = self.read();
+        Ok(Snapshot {
+            version: inner.version,
+            root: inner.tree.root(),
+            path: inner.tree.path(index)?,
+        })
+    }
+
+    // Runs `f` on the tree at one version, for reads that must agree with each other, such as
+    // several paths under the same root. Writers wait until `f` returns, so keep it short.
+    pub fn read_with<R>(&self, f: impl FnOnce(&MerkleTree<F>, u64) -> R) -> R {
+        let inner = self.read();
+        f(&inner.tree, inner.version)
+    }
+
+    // A copy of the tree and its version, to hold on to without blocking writers.
+    pub fn snapshot(&self) -> (MerkleTree<F>, u64) {
+        self.read_with(|tree, version| (tree.clone(), version))
+    }
+
+    // Returns the new leaf's index and the version that includes it.
+    pub fn append(&self, leaf: F) -> Result<(usize, u64), Error> {
+        let mut inner = self.write();
+        let index = inner.tree.append(leaf)?;
+        inner.version += 1;
+        Ok((index, inner.version))
+    }
+
+    // Appends all of `leaves` or, if they don't fit, none of them. Readers see either the version
+    // before or the one after, which counts as a single version.
+    pub fn extend(&self, leaves: &[F]) -> Result<u64, Error> {
+        let mut inner = self.write();
+        let populated = inner.tree.leaves().len();
+        let capacity = inner.tree.capacity();
+        if populated + leaves.len() > capacity {
+            return Err(Error::TooManyLeaves {
+                capacity,
+                leaves: populated + leaves.len(),
+            });
+        }
+        for leaf in leaves {
+            inner.tree.append(*leaf)?;
+        }
+        inner.version += 1;
+        Ok(inner.version)
+    }
+
+    pub fn update(&self, index: usize, leaf: F) -> Result<u64, Error> {
+        let mut inner = self.write();
+        inner.tree.update(index, leaf)?;
+        inner.version += 1;
+        Ok(inner.version)
+    }
+}
+
+mod tests {
+    use super::ConcurrentTree;
+    use crate::chips::merkle::DomainSeparation;
+    use crate::error::Error;
+    use crate::tree::MerkleTree;
+    use halo2_proofs::pasta::Fp;
+    use std::thread;
+
+    #[test]
+    fn test() {
+        let separation = DomainSeparation::Level;
+        let leaves: Vec<Fp> = (0..8u64).map(Fp::from).collect();
+        let tree = ConcurrentTree::new(MerkleTree::new(6, &leaves, separation).unwrap());
+        assert_eq!(tree.version(), 0);
+
+        // Readers check every snapshot against itself while a writer appends.
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                for i in 8..40u64 {
+                    tree.append(Fp::from(i)).unwrap();
+                }
+            });
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    let mut last = 0;
+                    for index in (0..8).cycle().take(100) {
+                        let snapshot = tree.path(index).unwrap();
+                        assert_eq!(snapshot.path.root(separation), snapshot.root);
+                        assert!(snapshot.version >= last);
+                        last = snapshot.version;
+                    }
+                });
+            }
+        });
+        assert_eq!(tree.version(), 32);
+        assert_eq!(tree.len(), 40);
+        let all: Vec<Fp> = (0..40u64).map(Fp::from).collect();
+        let expected = MerkleTree::new(6, &all, separation).unwrap();
+        assert_eq!(tree.root(), (expected.root(), 32));
+
+        let snapshot = tree.path(39).unwrap();
+        assert_eq!(tree.update(39, Fp::zero()).unwrap(), 33);
+        assert_ne!(tree.root().0, snapshot.root);
+        assert_eq!(snapshot.path.root(separation), snapshot.root);
+
+        let (copy, version) = tree.snapshot();
+        assert_eq!((copy.root(), version), tree.root());
+        assert_eq!(tree.extend(&all[..24]).unwrap(), 34);
+        assert_eq!(tree.len(), 64);
+        assert!(matches!(
+            tree.extend(&all[..1]),
+            Err(Error::TooManyLeaves { capacity: 64, .. })
+        ));
+        assert!(matches!(
+            tree.append(Fp::one()),
+            Err(Error::TooManyLeaves { .. })
+        ));
+        assert_eq!(tree.version(), 34);
+    }
+}
@@ -0,0 +1,210 @@
+/*
+Everything a membership prover has to agree on before it starts, checked in one place:
+
+    let config = ProverConfig::new(12, 20, Hasher::Poseidon).with_params_path("params/k12.bin");
+    config.validate()?;
+    let params = config.params()?;
+    let (circuit, layout) = config.builder().path(&path).build()?;
+
+`validate` rejects unsupported arities, an empty tree, a k outside MIN_K..=MAX_K or too small for
+the circuit (`check_k`, which names the k it needs), and a parameters file that is missing or was
+written for another k. The k check lays out the circuit the config builds, with its separation,
+planner and leaf exposure. Without a parameters path the IPA parameters are derived from k, which
+is deterministic but slow for large k, so long-running provers should generate them once and load
+them.
+*/
+
+use crate::builder::{Hasher, MerkleCircuitBuilder};
+use crate::chips::merkle::DomainSeparation;
+use crate::error::Error;
+use crate::estimate::{check_k, CircuitParams};
+use crate::instance::LeafExposure;
+use crate::planner::Planner;
+use halo2_proofs::{pasta::EqAffine, poly::commitment::Params};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProverConfig {
+    pub k: u32,
+    // Parameters written with `Params::write`. None derives them from k.
+    pub params_path: Option<PathBuf>,
+    pub hash: Hasher,
+    pub depth: usize,
+    // Children per node. Only binary trees are implemented.
+    pub arity: usize,
+    pub separation: DomainSeparation,
+    pub planner: Planner,
+    pub leaf_exposure: LeafExposure,
+}
+
+impl ProverConfig {
+    pub fn new(k: u32, depth: usize, hash: Hasher) -> Self {
+        Self {
+            k,
+            params_path: None,
+            hash,
+            depth,
+            arity: 2,
+            separation: DomainSeparation::default(),
+            planner: Planner::default(),
+            leaf_exposure: LeafExposure::default(),
+        }
+    }
+
+    pub fn with_params_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.params_path = Some(path.into());
+        self
+    }
+
+    pub fn with_separation(mut self, separation: DomainSeparation) -> Self {
+        self.separation = separation;
+        self
+    }
+
+    pub fn with_planner(mut self, planner: Planner) -> Self {
+        self.planner = planner;
+        self
+    }
+
+    pub fn with_leaf_exposure(mut self, leaf_exposure: LeafExposure) -> Self {
+        self.leaf_exposure = leaf_exposure;
+        self
+    }
+
+    pub fn circuit_params(&self) -> CircuitParams {
+        let mut params = CircuitParams::new(self.depth, self.hash);
+        params.separation = self.separation;
+        params.arity = self.arity;
+        params.planner = self.planner;
+        params.leaf_exposure = self.leaf_exposure;
+        params
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.arity != 2 {
+            return Err(Error::UnsupportedArity(self.arity));
+        }
+        if self.depth == 0 {
+            return Err(Error::EmptyPath);
+        }
+        check_k(self.k, &self.circuit_params())?;
+        if let Some(path) = &self.params_path {
+            let k = read_k(path)?;
+            if k != self.k {
+                return Err(Error::Params(format!(
+                    "{} holds parameters for k = {}, not {}",
+                    path.display(),
+                    k,
+                    self.k
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    // Validates the config, then loads the parameters from `params_path` or derives them from k.
+    pub fn params(&self) -> Result<Params<EqAffine>, Error> {
+        self.validate()?;
+        match &self.params_path {
+            Some(path) => {
+                let mut reader = BufReader::new(open(path)?);
+                Params::read(&mut reader)
+                    .map_err(|err| Error::Params(format!("{}: {}", path.display(), err)))
+            }
+            None => Ok(Params::new(self.k)),
+        }
+    }
+
+    // A builder for circuits of this config's shape; add the witness and build.
+    pub fn builder(&self) -> MerkleCircuitBuilder {
+        MerkleCircuitBuilder::new()
+            .depth(self.depth)
+            .hasher(self.hash)
+            .separation(self.separation)
+            .planner(self.planner)
+            .leaf_exposure(self.leaf_exposure)
+    }
+}
+
+fn open(path: &Path) -> Result<File, Error> {
+    File::open(path).map_err(|err| Error::Params(format!("{}: {}", path.display(), err)))
+}
+
+// `Params::write` starts with k as a little-endian u32, so the file's k can be checked without
+// reading the 2^k generators after it.
+fn read_k(path: &Path) -> Result<u32, Error> {
+    let mut k = [0u8; 4];
+    open(path)?
+        .read_exact(&mut k)
+        .map_err(|err| Error::Params(format!("{}: {}", path.display(), err)))?;
+    Ok(u32::from_le_bytes(k))
+}
+
+mod tests {
+    use super::ProverConfig;
+    use crate::builder::{Hasher, MerkleCircuit};
+    use crate::chips::merkle::DomainSeparation;
+    use crate::error::Error;
+    use crate::instance::LeafExposure;
+    use crate::planner::Planner;
+    use halo2_proofs::{pasta::EqAffine, poly::commitment::Params};
+    use std::fs::File;
+
+    #[test]
+    fn test() {
+        let config = ProverConfig::new(10, 4, Hasher::Poseidon);
+        config.validate().unwrap();
+        assert!(config.params().is_ok());
+        let (circuit, _) = config.builder().build().unwrap();
+        assert!(matches!(circuit, MerkleCircuit::Poseidon(_)));
+
+        // The k check and the builder both see the configured shape.
+        let packed = config
+            .clone()
+            .with_separation(DomainSeparation::Level)
+            .with_planner(Planner::V1)
+            .with_leaf_exposure(LeafExposure::Private);
+        let (circuit, _) = packed.builder().build().unwrap();
+        assert!(matches!(circuit, MerkleCircuit::PackedPoseidon(_)));
+        assert_eq!(circuit.circuit_params(), packed.circuit_params());
+
+        let too_small = ProverConfig::new(4, 32, Hasher::Poseidon);
+        assert!(matches!(
+            too_small.validate(),
+            Err(Error::InsufficientK { k: 4, .. })
+        ));
+        let too_large = ProverConfig::new(40, 4, Hasher::Poseidon);
+        assert!(matches!(
+            too_large.validate(),
+            Err(Error::InvalidK { k: 40, .. })
+        ));
+        let mut quaternary = config.clone();
+        quaternary.arity = 4;
+        assert!(matches!(
+            quaternary.validate(),
+            Err(Error::UnsupportedArity(4))
+        ));
+        assert!(matches!(
+            ProverConfig::new(10, 0, Hasher::Poseidon).validate(),
+            Err(Error::EmptyPath)
+        ));
+
+        let dir = std::env::temp_dir();
+        let missing = config
+            .clone()
+            .with_params_path(dir.join("halo2-merkle-missing-params"));
+        assert!(matches!(missing.validate(), Err(Error::Params(_))));
+
+        let path = dir.join(format!("halo2-merkle-params-{}", std::process::id()));
+        let params: Params<EqAffine> = Params::new(10);
+        params.write(&mut File::create(&path).unwrap()).unwrap();
+        let loaded = config.clone().with_params_path(&path);
+        loaded.validate().unwrap();
+        assert!(loaded.params().is_ok());
+        let mismatched = ProverConfig::new(11, 4, Hasher::Poseidon).with_params_path(&path);
+        assert!(matches!(mismatched.validate(), Err(Error::Params(_))));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,248 @@
+/*
+Gate coverage of MockProver runs. A gate that no test ever makes fail can be deleted or weakened
+without any test noticing, so besides asserting outcomes the test suite records, per run, which
+gates the circuit enabled and which of them reported an unsatisfied constraint:
+
+    COVERAGE_LOG=/tmp/coverage.log cargo test
+    cargo run --bin coverage-report -- /tmp/coverage.log
+
+Runs go through `mock_verify`, a drop-in for MockProver::run followed by `verify` that appends one
+line per gate to the file named by COVERAGE_LOG (and does nothing else when it is unset), or
+`assert_mock_satisfied` for runs that must pass. The crate's own tests make every MockProver run
+through them, so the report covers every chip the suite exercises. The
+report lists every gate with the number of runs that exercised it and that failed it, and flags the
+gates with no failing run. Gates are keyed by name, so a chip's gate is covered by a negative test
+through any circuit that uses it; gates named alike in different chips share an entry.
+*/
+
+use crate::analysis::active_gates;
+use crate::diagnostics::{explain_all, quoted_after};
+use crate::error::Error;
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    dev::{CircuitGates, MockProver, VerifyFailure},
+    plonk::Circuit,
+};
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+
+pub const LOG_VAR: &str = "COVERAGE_LOG";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GateCoverage {
+    // Runs that enabled the gate on some row.
+    pub exercised: usize,
+    // Runs in which one of the gate's constraints was not satisfied.
+    pub failed: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Coverage {
+    gates: BTreeMap<String, GateCoverage>,
+}
+
+// The gate names of `C` in gate order, from halo2's listing of its gates.
+fn gate_names<F: FieldExt, C: Circuit<F>>() -> Vec<String> {
+    CircuitGates::collect::<F, C>()
+        .to_string()
+        .lines()
+        .take_while(|line| !line.starts_with("Total "))
+        .filter(|line| !line.starts_with('-') && !line.starts_with(' '))
+        .filter_map(|line| line.strip_suffix(':'))
+        .map(String::from)
+        .collect()
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The coverage of one run of `circuit` that ended in `failures`.
+    pub fn of_run<F: FieldExt, C: Circuit<F>>(
+        circuit: &C,
+        failures: &[VerifyFailure],
+    ) -> Result<Self, Error> {
+        let mut coverage = Self::new();
+        for (name, active) in gate_names::<F, C>().into_iter().zip(active_gates(circuit)?) {
+            let entry = coverage.gates.entry(name).or_default();
+            entry.exercised = entry.exercised.max(active as usize);
+        }
+        for failure in failures {
+            let raw = failure.to_string();
+            if let Some(gate) = quoted_after(&raw, "in gate ") {
+                coverage.gates.entry(gate.to_string()).or_default().failed = 1;
+            }
+        }
+        Ok(coverage)
+    }
+
+    pub fn gates(&self) -> &BTreeMap<String, GateCoverage> {
+        &self.gates
+    }
+
+    pub fn merge(&mut self, other: &Coverage) {
+        for (name, gate) in &other.gates {
+            let entry = self.gates.entry(name.clone()).or_default();
+            entry.exercised += gate.exercised;
+            entry.failed += gate.failed;
+        }
+    }
+
+    // Gates that no run made fail, i.e. without negative-test coverage.
+    pub fn without_negative(&self) -> Vec<&str> {
+        self.gates
+            .iter()
+            .filter(|(_, gate)| gate.failed == 0)
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    // One "gate<TAB>exercised<TAB>failed" line per gate.
+    pub fn to_log(&self) -> String {
+        self.gates
+            .iter()
+            .map(|(name, gate)| format!("{}\t{}\t{}\n", name, gate.exercised, gate.failed))
+            .collect()
+    }
+
+    // Sums the lines of a log written by `to_log`, e.g. by many test threads appending to one file.
+    pub fn from_log(log: &str) -> Result<Self, Error> {
+        let mut coverage = Self::new();
+        for line in log.lines().filter(|line| !line.is_empty()) {
+            let fields: Vec<&str> = line.rsplitn(3, '\t').collect();
+            let [failed, exercised, name] = fields[..] else {
+                return Err(Error::Malformed("expected gate, exercised and failed"));
+            };
+            let count = |field: &str| {
+                field
+                    .parse::<usize>()
+                    .map_err(|_| Error::Malformed("expected a run count"))
+            };
+            let entry = coverage.gates.entry(name.to_string()).or_default();
+            entry.exercised += count(exercised)?;
+            entry.failed += count(failed)?;
+        }
+        Ok(coverage)
+    }
+
+    pub fn report(&self) -> String {
+        let width = self.gates.keys().map(|name| name.len()).max().unwrap_or(0);
+        let mut report = format!("{:width$}  exercised  failed\n", "gate", width = width);
+        for (name, gate) in &self.gates {
+            let flag = if gate.failed == 0 {
+                "  no negative test"
+            } else {
+                ""
+            };
+            report += &format!(
+                "{:width$}  {:>9}  {:>6}{}\n",
+                name,
+                gate.exercised,
+                gate.failed,
+                flag,
+                width = width
+            );
+        }
+        report
+    }
+}
+
+// MockProver::run and `verify`, recording the run in the coverage log when COVERAGE_LOG is set.
+// The outer error is a synthesis error, the inner one the failures of an unsatisfied circuit.
+pub fn mock_verify<F: FieldExt, C: Circuit<F>>(
+    k: u32,
+    circuit: &C,
+    instances: Vec<Vec<F>>,
+) -> Result<Result<(), Vec<VerifyFailure>>, Error> {
+    let result = MockProver::run(k, circuit, instances)?.verify();
+    if let Some(path) = std::env::var_os(LOG_VAR) {
+        let failures = result.as_ref().err().map_or(&[][..], |failures| failures);
+        let log = Coverage::of_run(circuit, failures)?.to_log();
+        // One write per run, so that concurrent tests append whole runs.
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(log.as_bytes()))
+            .map_err(|err| Error::Params(format!("coverage log: {}", err)))?;
+    }
+    Ok(result)
+}
+
+// `mock_verify` for a run that must pass, panicking with the explained failures otherwise, like
+// `diagnostics::assert_satisfied_explained`.
+pub fn assert_mock_satisfied<F: FieldExt, C: Circuit<F>>(
+    k: u32,
+    circuit: &C,
+    instances: Vec<Vec<F>>,
+) {
+    match mock_verify(k, circuit, instances) {
+        Ok(Ok(())) => {}
+        Ok(Err(failures)) => panic!("circuit is not satisfied:\n{}", explain_all(&failures)),
+        Err(err) => panic!("synthesis failed at k = {}: {}", k, err),
+    }
+}
+
+#[cfg(feature = "dummy-hash")]
+mod tests {
+    use super::{mock_verify, Coverage};
+    use crate::circuits::merkle_v2::MerkleTreeV2Circuit;
+    use crate::error::Error;
+    use halo2_proofs::{circuit::Value, pasta::Fp};
+
+    #[test]
+    fn test() {
+        let circuit = |index: u64| {
+            let mut circuit = MerkleTreeV2Circuit::from_values(
+                Value::known(Fp::from(99)),
+                vec![Value::known(Fp::from(1)), Value::known(Fp::from(5))],
+                vec![Value::known(Fp::zero()); 2],
+                2,
+            )
+            .unwrap();
+            circuit.indices[1] = Value::known(Fp::from(index));
+            circuit
+        };
+        let instances = vec![vec![Fp::from(99), Fp::from(105)]];
+
+        let honest = circuit(1);
+        assert!(mock_verify(10, &honest, instances.clone()).unwrap().is_ok());
+        let positive = Coverage::of_run(&honest, &[]).unwrap();
+        assert_eq!(positive.gates()["swap"].exercised, 1);
+        assert_eq!(positive.gates()["hash"].exercised, 1);
+        assert_eq!(positive.without_negative(), vec!["hash", "swap"]);
+
+        // A wrong public root only breaks a copy constraint, so no gate is covered by it.
+        let failures = mock_verify(10, &honest, vec![vec![Fp::from(99), Fp::from(7)]])
+            .unwrap()
+            .unwrap_err();
+        let copy_only = Coverage::of_run(&honest, &failures).unwrap();
+        assert_eq!(copy_only.without_negative().len(), 2);
+
+        // An index of 2 fails the swap gate's boolean check.
+        let non_boolean = circuit(2);
+        let failures = mock_verify(10, &non_boolean, instances)
+            .unwrap()
+            .unwrap_err();
+        let negative = Coverage::of_run(&non_boolean, &failures).unwrap();
+        assert_eq!(negative.gates()["swap"].failed, 1);
+
+        let mut total = positive.clone();
+        total.merge(&copy_only);
+        total.merge(&negative);
+        assert_eq!(total.gates()["swap"].exercised, 3);
+        assert_eq!(total.without_negative(), vec!["hash"]);
+        let log = positive.to_log() + &copy_only.to_log() + &negative.to_log();
+        assert_eq!(Coverage::from_log(&log).unwrap(), total);
+        let report = total.report();
+        assert!(report
+            .lines()
+            .any(|line| line.starts_with("hash") && line.ends_with("no negative test")));
+        assert!(matches!(
+            Coverage::from_log("swap\t1"),
+            Err(Error::Malformed(_))
+        ));
+    }
+}
@@ -0,0 +1,135 @@
+/*
+Readable MockProver failures. halo2 reports failures by gate and region name, e.g.
+
+    Constraint 0 in gate 1 ('swap') is not satisfied in Region 9 ('layer 7') at offset 0
+
+which for a deep tree means counting regions by hand. `explain` maps the gate and region names this
+crate chooses to what went wrong, here "swap gate failed at layer 7: left/right output ≠ the
+children selected by the index bit", and keeps the raw failure on a second line. Merkle layer
+regions are named "layer N" by both MerkleChip and MerkleTreeV1Chip for this purpose.
+*/
+
+use halo2_proofs::{arithmetic::FieldExt, dev::MockProver, dev::VerifyFailure};
+
+// The text between "('" and "')" after the first occurrence of `marker`.
+pub(crate) fn quoted_after<'a>(s: &'a str, marker: &str) -> Option<&'a str> {
+    let rest = &s[s.find(marker)? + marker.len()..];
+    let start = rest.find("('")? + 2;
+    let end = start + rest[start..].find("')")?;
+    Some(&rest[start..end])
+}
+
+// The index of the failed constraint within its gate, from "Constraint N in gate ...".
+pub(crate) fn constraint_index(raw: &str) -> Option<usize> {
+    let rest = raw.strip_prefix("Constraint ")?;
+    let end = rest.find(|c: char| !c.is_ascii_digit())?;
+    rest[..end].parse().ok()
+}
+
+// The Merkle "swap" gate also holds the index bit check, as its second constraint.
+fn gate_explanation(gate: &str, constraint: Option<usize>) -> &'static str {
+    match gate {
+        "swap" if constraint == Some(1) => "the index bit is not 0 or 1",
+        "swap" => "left/right output ≠ the children selected by the index bit",
+        "hash" => "the dummy hash output ≠ its definition",
+        "init" | "sum init" | "forest init" => "the running sum does not start at zero",
+        "product init" => "the running product does not start at one",
+        "pack" => "the packed value ≠ 256 * previous + byte",
+        "sum" => "the running sum ≠ previous + value",
+        "product" => "the running product ≠ previous * (gamma - value)",
+        "forest select" => "a selector bit is not 0 or 1, or a running root/count is wrong",
+        "full round" | "partial rounds" | "pad-and-add" => "the Poseidon state is inconsistent",
+        _ => "a custom constraint is not satisfied",
+    }
+}
+
+// Where the failure happened: "at layer N" for Merkle layers, else the region name.
+fn context(raw: &str) -> String {
+    match quoted_after(raw, "Region ") {
+        Some(region) => match region
+            .strip_prefix("layer ")
+            .and_then(|n| n.parse::<usize>().ok())
+        {
+            Some(layer) => format!(" at layer {}", layer),
+            None => format!(" in region '{}'", region),
+        },
+        None => String::new(),
+    }
+}
+
+// Explains one failure from its Display output.
+pub fn explain_message(raw: &str) -> String {
+    let ctx = context(raw);
+    let summary = if let Some(gate) = quoted_after(raw, "in gate ") {
+        let explanation = gate_explanation(gate, constraint_index(raw));
+        format!("{} gate failed{}: {}", gate, ctx, explanation)
+    } else if raw.starts_with("Lookup") {
+        format!(
+            "range check failed{}: a packed byte is not in [0, 256)",
+            ctx
+        )
+    } else if raw.starts_with("Equality constraint") {
+        format!(
+            "copy constraint failed{}: a copied cell differs from its source, e.g. a public input \
+             does not match the computed value",
+            ctx
+        )
+    } else if raw.contains("to be assigned") {
+        format!(
+            "unassigned cell{}: a gate reads a cell that was never assigned",
+            ctx
+        )
+    } else {
+        format!("constraint failed{}", ctx)
+    };
+    format!("{}\n  ({})", summary, raw)
+}
+
+pub fn explain(failure: &VerifyFailure) -> String {
+    explain_message(&failure.to_string())
+}
+
+pub fn explain_all(failures: &[VerifyFailure]) -> String {
+    failures.iter().map(explain).collect::<Vec<_>>().join("\n")
+}
+
+// Like `MockProver::assert_satisfied`, but panics with the explained failures.
+pub fn assert_satisfied_explained<F: FieldExt>(prover: &MockProver<F>) {
+    if let Err(failures) = prover.verify() {
+        panic!("circuit is not satisfied:\n{}", explain_all(&failures));
+    }
+}
+
+mod tests {
+    use super::{explain_all, explain_message};
+    use crate::chips::leaf_encoding::pack_be;
+    use crate::circuits::leaf_encoding::LeafEncodingCircuit;
+    use crate::coverage::mock_verify;
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let explained = explain_message(
+            "Constraint 0 in gate 1 ('swap') is not satisfied in Region 9 ('layer 7') at offset 0",
+        );
+        assert!(explained.starts_with("swap gate failed at layer 7: left/right output"));
+        let explained = explain_message(
+            "Constraint 1 in gate 1 ('swap') is not satisfied in Region 9 ('layer 7') at offset 0",
+        );
+        assert!(explained.starts_with("swap gate failed at layer 7: the index bit is not 0 or 1"));
+        let explained = explain_message(
+            "Constraint 0 in gate 3 ('pack') is not satisfied in Region 2 ('pack') at offset 4",
+        );
+        assert!(explained.starts_with("pack gate failed in region 'pack'"));
+
+        let hash = [9u8; 32];
+        let leaf: Fp = pack_be(&hash[..31]);
+        let circuit = LeafEncodingCircuit::<Fp>::new(&hash[..31]).unwrap();
+        let explained = explain_all(
+            &mock_verify(9, &circuit, vec![vec![leaf + Fp::one()]])
+                .unwrap()
+                .unwrap_err(),
+        );
+        assert!(explained.contains("copy constraint failed"));
+    }
+}
@@ -0,0 +1,135 @@
+/*
+EIP-712 typed-data hashes for signing roots and claims off-chain, matching what a Solidity contract
+computes with `_hashTypedDataV4` (OpenZeppelin's EIP712). The message types are
+
+    MerkleRootUpdate(bytes32 oldRoot,bytes32 newRoot,uint256 epoch)
+    Claim(bytes32 leaf,bytes32 root,bytes32 nullifier)
+
+Field elements are encoded as bytes32 holding their big-endian value, i.e. `bytes32(uint256(x))`
+on the Solidity side. Sign `typed_data_hash(&domain, &struct_hash)` with the usual secp256k1 key.
+*/
+
+use crate::serialization::{to_bytes_be, CanonicalField};
+use tiny_keccak::{Hasher, Keccak};
+
+pub const DOMAIN_TYPE: &str =
+    "EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)";
+pub const ROOT_UPDATE_TYPE: &str =
+    "MerkleRootUpdate(bytes32 oldRoot,bytes32 newRoot,uint256 epoch)";
+pub const CLAIM_TYPE: &str = "Claim(bytes32 leaf,bytes32 root,bytes32 nullifier)";
+
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    hasher.update(data);
+    let mut out = [0u8; 32];
+    hasher.finalize(&mut out);
+    out
+}
+
+fn uint256(value: u64) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[24..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+// keccak256 of the type hash followed by the encoded fields, each one 32-byte word.
+fn hash_struct(type_string: &str, words: &[[u8; 32]]) -> [u8; 32] {
+    let mut data = keccak256(type_string.as_bytes()).to_vec();
+    for word in words {
+        data.extend_from_slice(word);
+    }
+    keccak256(&data)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Domain {
+    pub name: String,
+    pub version: String,
+    pub chain_id: u64,
+    pub verifying_contract: [u8; 20],
+}
+
+impl Domain {
+    pub fn separator(&self) -> [u8; 32] {
+        let mut contract = [0u8; 32];
+        contract[12..].copy_from_slice(&self.verifying_contract);
+        hash_struct(
+            DOMAIN_TYPE,
+            &[
+                keccak256(self.name.as_bytes()),
+                keccak256(self.version.as_bytes()),
+                uint256(self.chain_id),
+                contract,
+            ],
+        )
+    }
+}
+
+pub fn root_update_hash<F: CanonicalField>(old_root: &F, new_root: &F, epoch: u64) -> [u8; 32] {
+    hash_struct(
+        ROOT_UPDATE_TYPE,
+        &[to_bytes_be(old_root), to_bytes_be(new_root), uint256(epoch)],
+    )
+}
+
+pub fn claim_hash<F: CanonicalField>(leaf: &F, root: &F, nullifier: &F) -> [u8; 32] {
+    hash_struct(
+        CLAIM_TYPE,
+        &[to_bytes_be(leaf), to_bytes_be(root), to_bytes_be(nullifier)],
+    )
+}
+
+// The digest to sign: keccak256("\x19\x01" || domain separator || struct hash).
+pub fn typed_data_hash(domain: &Domain, struct_hash: &[u8; 32]) -> [u8; 32] {
+    let mut data = vec![0x19, 0x01];
+    data.extend_from_slice(&domain.separator());
+    data.extend_from_slice(struct_hash);
+    keccak256(&data)
+}
+
+mod tests {
+    use super::{claim_hash, keccak256, root_update_hash, typed_data_hash, Domain};
+    use halo2_proofs::pasta::Fp;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn test() {
+        assert_eq!(
+            hex(&keccak256(b"")),
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+        );
+
+        // The domain of the example in the EIP-712 specification.
+        let domain = Domain {
+            name: "Ether Mail".to_string(),
+            version: "1".to_string(),
+            chain_id: 1,
+            verifying_contract: [0xcc; 20],
+        };
+        assert_eq!(
+            hex(&domain.separator()),
+            "f2cee375fa42b42143804025fc449deafd50cc031ca257e0b194a650a912090f"
+        );
+
+        let (leaf, root, nullifier) = (Fp::from(1), Fp::from(2), Fp::from(3));
+        let claim = claim_hash(&leaf, &root, &nullifier);
+        assert_ne!(claim, claim_hash(&leaf, &root, &Fp::from(4)));
+        assert_ne!(
+            root_update_hash(&root, &leaf, 7),
+            root_update_hash(&root, &leaf, 8)
+        );
+        assert_ne!(
+            typed_data_hash(&domain, &claim),
+            typed_data_hash(
+                &Domain {
+                    chain_id: 5,
+                    ..domain.clone()
+                },
+                &claim
+            )
+        );
+    }
+}
@@ -0,0 +1,251 @@
+/*
+Encodings of common external values into field elements. Each one is reproducible in-circuit: the
+byte-oriented encodings pack big-endian bytes exactly like LeafEncodingChip::pack, and values that
+need several field elements are folded into one leaf with `compress`, which mirrors
+PoseidonChip::hash_chain.
+
+The `encode_*` functions are the raw encodings. `ToLeaf` adds a `LeafDomain` tag on top, so a
+number, an address, a hash and a string never share a leaf: values of at most 30 bytes carry the
+tag in their top byte, and everything else is folded with `compress` starting from the tag.
+Strings and chunks also record their byte length, so trailing or leading zero bytes still count.
+*/
+
+use crate::chips::leaf_encoding::{hash_to_leaf, pack_be, PACKED_BYTES};
+use crate::error::Error;
+#[cfg(feature = "poseidon")]
+use halo2_gadgets::poseidon::primitives::{
+    self as poseidon, ConstantLength, P128Pow5T3 as OrchardNullifier,
+};
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    pasta::{Fp, Fq},
+};
+
+// An Ethereum address, packed as its 20 big-endian bytes.
+pub fn encode_address<F: FieldExt>(address: &[u8; 20]) -> F {
+    pack_be(address)
+}
+
+// A 32-byte hash, read big-endian. A hash at or above p is `NonCanonical`.
+pub fn encode_hash<F: FieldExt>(hash: &[u8; 32]) -> Result<F, Error> {
+    hash_to_leaf(hash)
+}
+
+// A u128 amount. Identical to packing its 16 big-endian bytes.
+pub fn encode_u128<F: FieldExt>(amount: u128) -> F {
+    F::from_u128(amount)
+}
+
+pub fn str_chunks(s: &str) -> impl Iterator<Item = &[u8]> {
+    s.as_bytes().chunks(PACKED_BYTES)
+}
+
+// A UTF-8 string: its byte length, then its 31-byte chunks each packed into one field element.
+pub fn encode_str<F: FieldExt>(s: &str) -> Vec<F> {
+    let mut fields = vec![F::from(s.len() as u64)];
+    fields.extend(str_chunks(s).map(pack_be));
+    fields
+}
+
+// The type a leaf was encoded from. Its tag leads the leaf's encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeafDomain {
+    U64,
+    U128,
+    Address,
+    Hash,
+    Str,
+    Chunk,
+    Secp256k1,
+}
+
+impl LeafDomain {
+    pub fn tag(&self) -> u8 {
+        match self {
+            LeafDomain::U64 => 1,
+            LeafDomain::U128 => 2,
+            LeafDomain::Address => 3,
+            LeafDomain::Hash => 4,
+            LeafDomain::Str => 5,
+            LeafDomain::Chunk => 6,
+            LeafDomain::Secp256k1 => 7,
+        }
+    }
+}
+
+// At most 30 big-endian bytes behind the domain's tag byte: tag * 2^240 + value.
+fn tag_bytes<F: FieldExt>(domain: LeafDomain, bytes: &[u8]) -> F {
+    let mut packed = [0u8; PACKED_BYTES];
+    packed[0] = domain.tag();
+    packed[PACKED_BYTES - bytes.len()..].copy_from_slice(bytes);
+    pack_be(&packed)
+}
+
+// Folds a multi-field value into a single leaf: starting from the number of fields, each field
+// is absorbed as acc = Poseidon(acc, field).
+#[cfg(feature = "poseidon")]
+pub fn compress(fields: &[Fp]) -> Fp {
+    fields
+        .iter()
+        .fold(Fp::from(fields.len() as u64), |acc, field| {
+            poseidon::Hash::<_, OrchardNullifier, ConstantLength<2>, 3, 2>::init()
+                .hash([acc, *field])
+        })
+}
+
+// `fields` folded with `compress` behind the domain's tag.
+#[cfg(feature = "poseidon")]
+pub fn tagged_compress(domain: LeafDomain, fields: &[Fp]) -> Fp {
+    let mut tagged = vec![Fp::from(domain.tag() as u64)];
+    tagged.extend_from_slice(fields);
+    compress(&tagged)
+}
+
+// The leaf of a content-addressed data chunk: its byte length followed by its 31-byte pieces,
+// folded with `tagged_compress`. The length keeps chunks that differ only in leading zero bytes
+// apart.
+#[cfg(feature = "poseidon")]
+pub fn chunk_leaf(chunk: &[u8]) -> Fp {
+    let mut fields = vec![Fp::from(chunk.len() as u64)];
+    fields.extend(chunk.chunks(PACKED_BYTES).map(pack_be::<Fp>));
+    tagged_compress(LeafDomain::Chunk, &fields)
+}
+
+// A secp256k1 public key as its uncompressed 64-byte x || y encoding, without the 0x04 prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Secp256k1PublicKey(pub [u8; 64]);
+
+pub const SECP256K1_LIMB_BYTES: usize = 16;
+
+// The four 16-byte big-endian limbs x_hi, x_lo, y_hi, y_lo of a secp256k1 key, as packed by
+// Secp256k1LeafChip.
+pub fn secp256k1_limbs<F: FieldExt>(key: &Secp256k1PublicKey) -> Vec<F> {
+    key.0.chunks(SECP256K1_LIMB_BYTES).map(pack_be).collect()
+}
+
+// Values that can be used directly as a tree leaf.
+pub trait ToLeaf<F: FieldExt> {
+    fn to_leaf(&self) -> F;
+}
+
+impl ToLeaf<Fp> for Fp {
+    fn to_leaf(&self) -> Fp {
+        *self
+    }
+}
+
+impl ToLeaf<Fq> for Fq {
+    fn to_leaf(&self) -> Fq {
+        *self
+    }
+}
+
+impl<F: FieldExt> ToLeaf<F> for u64 {
+    fn to_leaf(&self) -> F {
+        tag_bytes(LeafDomain::U64, &self.to_be_bytes())
+    }
+}
+
+// 32-byte hashes are split into their first 31 bytes and their last byte, each packed by
+// LeafEncodingChip::pack, and tagged with `tagged_compress`. Unlike `encode_hash` this accepts
+// every digest, including the ones at or above p.
+#[cfg(feature = "poseidon")]
+impl ToLeaf<Fp> for [u8; 32] {
+    fn to_leaf(&self) -> Fp {
+        let limbs = [
+            pack_be(&self[..PACKED_BYTES]),
+            pack_be(&self[PACKED_BYTES..]),
+        ];
+        tagged_compress(LeafDomain::Hash, &limbs)
+    }
+}
+
+impl ToLeaf<Fp> for [u8; 20] {
+    fn to_leaf(&self) -> Fp {
+        tag_bytes(LeafDomain::Address, self)
+    }
+}
+
+impl ToLeaf<Fp> for u128 {
+    fn to_leaf(&self) -> Fp {
+        tag_bytes(LeafDomain::U128, &self.to_be_bytes())
+    }
+}
+
+// The key's limbs folded with `tagged_compress`, matching Secp256k1LeafChip::leaf.
+#[cfg(feature = "poseidon")]
+impl ToLeaf<Fp> for Secp256k1PublicKey {
+    fn to_leaf(&self) -> Fp {
+        tagged_compress(LeafDomain::Secp256k1, &secp256k1_limbs(self))
+    }
+}
+
+#[cfg(feature = "poseidon")]
+impl ToLeaf<Fp> for &str {
+    fn to_leaf(&self) -> Fp {
+        tagged_compress(LeafDomain::Str, &encode_str(self))
+    }
+}
+
+// The path indices of the leaf at `index`, least significant bit (the leaf's layer) first.
+pub fn index_bits<F: FieldExt>(index: u64, depth: usize) -> Result<Vec<F>, Error> {
+    if depth < 64 && index >> depth != 0 {
+        return Err(Error::IndexOutOfRange {
+            index: index as usize,
+            leaves: 1usize.checked_shl(depth as u32).unwrap_or(usize::MAX),
+        });
+    }
+    Ok((0..depth)
+        .map(|i| {
+            if i < 64 && (index >> i) & 1 == 1 {
+                F::one()
+            } else {
+                F::zero()
+            }
+        })
+        .collect())
+}
+
+#[cfg(feature = "poseidon")]
+mod tests {
+    use super::{compress, encode_str, LeafDomain, ToLeaf};
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        // The length keeps strings that differ only in zero bytes apart.
+        assert_ne!("a".to_leaf(), "\0a".to_leaf());
+        assert_ne!("a".to_leaf(), "a\0".to_leaf());
+        assert_eq!(encode_str::<Fp>("")[0], Fp::zero());
+
+        // Equal payloads of different types don't share a leaf.
+        let n = 0xabcdu64;
+        let leaves: Vec<Fp> = vec![
+            n.to_leaf(),
+            (n as u128).to_leaf(),
+            {
+                let mut address = [0u8; 20];
+                address[18..].copy_from_slice(&[0xab, 0xcd]);
+                address.to_leaf()
+            },
+            {
+                let mut hash = [0u8; 32];
+                hash[30..].copy_from_slice(&[0xab, 0xcd]);
+                hash.to_leaf()
+            },
+            "\u{ab}".to_leaf(),
+        ];
+        for (i, a) in leaves.iter().enumerate() {
+            assert_ne!(*a, Fp::from(n));
+            for b in &leaves[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+
+        // A string and a chunk with the same bytes differ only in their tag.
+        assert_ne!("abc".to_leaf(), super::chunk_leaf(b"abc"));
+        let mut fields = vec![Fp::from(LeafDomain::Str.tag() as u64)];
+        fields.extend(encode_str::<Fp>("abc"));
+        assert_eq!("abc".to_leaf(), compress(&fields));
+    }
+}
@@ -0,0 +1,265 @@
+use crate::tamper::Tamper;
+use halo2_proofs::{arithmetic::FieldExt, circuit::Value, plonk};
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    // The number of path elements and path indices differ.
+    LengthMismatch { elements: usize, indices: usize },
+    // A circuit taking one path per balance was given a different number of each.
+    BalanceCount { paths: usize, balances: usize },
+    // The path length differs from the depth the circuit was configured for.
+    DepthMismatch { expected: usize, actual: usize },
+    // The path index at the given layer is neither 0 nor 1.
+    InvalidIndex { layer: usize },
+    // The path has no layers, so there is no root to compute.
+    EmptyPath,
+    // The tree is deeper than the circuit supports.
+    DepthTooLarge { max: usize, depth: usize },
+    // More leaves were supplied than a tree of the requested depth can hold.
+    TooManyLeaves { capacity: usize, leaves: usize },
+    // The requested leaf index is not populated in the tree.
+    IndexOutOfRange { index: usize, leaves: usize },
+    // A circuit was built without specifying the tree depth.
+    MissingDepth,
+    // The instance layout has a slot for the named value but none was supplied.
+    MissingInstance(&'static str),
+    // The instances have a different number of columns than the circuit allocates.
+    InstanceColumns { expected: usize, found: usize },
+    // The public instance column is longer or shorter than the circuit's layout of it.
+    InstanceRows { expected: usize, found: usize },
+    // An instance column that the circuit allocates but leaves unused has values.
+    UnusedInstance { column: usize },
+    // More bytes were given than fit canonically into one field element.
+    EncodingTooLong { max: usize, len: usize },
+    // The same leaf was selected more than once where distinct leaves are required.
+    DuplicateLeaf { index: usize },
+    // The verifying key is not in the approved key tree.
+    UnapprovedKey,
+    // The verifying key is already approved, at `index`.
+    AlreadyApproved { index: usize },
+    // The element is in the indexed tree, so it has no non-membership proof.
+    Blocked,
+    // A value that must be increasing is not above the one before it.
+    Unsorted { index: usize },
+    // A value does not fit in the bits a circuit compares.
+    ValueTooLarge { index: usize, bits: usize },
+    // The epoch falls outside a leaf's validity window [from, to].
+    OutsideWindow { epoch: u64, from: u64, to: u64 },
+    // A value is not the canonical encoding of an element of the target field.
+    NonCanonical,
+    // Paths that must share a leaf were built for different leaves.
+    LeafMismatch,
+    // The tree arity is not implemented; only binary trees are.
+    UnsupportedArity(usize),
+    // Serialized data was written in a format version this build does not understand.
+    UnsupportedVersion(u8),
+    // Serialized data does not follow its format; the message says where.
+    Malformed(&'static str),
+    // A proof leads to a different root than the one it was checked against.
+    RootMismatch,
+    // A recorded reference vector disagrees with this crate's output.
+    VectorMismatch { case: usize },
+    // A regression fixture no longer replays; the part says what the current code changed.
+    FixtureMismatch { name: String, part: &'static str },
+    // A verifier accepted a tampered proof or instance.
+    TamperAccepted(Tamper),
+    // A block header's hash is above the target its difficulty bits encode.
+    InsufficientWork,
+    // A request to an Ethereum node failed; the message is the provider's error.
+    Rpc(String),
+    // The circuit does not fit in 2^k rows, or k is outside the range this crate proves at.
+    InvalidK { k: u32, min: u32, max: u32 },
+    // The circuit, described by `shape`, needs at least k = need to fit its rows.
+    InsufficientK { k: u32, need: u32, shape: String },
+    // A prover or verifier config does not fit the tree it was given; the message says how.
+    ConfigMismatch(&'static str),
+    // The IPA parameters are missing, unreadable or for a different k; the message says which.
+    Params(String),
+    // A configured circuit variant name that `Variant` does not know.
+    UnknownVariant(String),
+    // An error raised by halo2 itself.
+    Plonk(plonk::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::LengthMismatch { elements, indices } => {
+                write!(f, "path has {} elements but {} indices", elements, indices)
+            }
+            Error::BalanceCount { paths, balances } => {
+                write!(f, "{} paths were given for {} balances", paths, balances)
+            }
+            Error::DepthMismatch { expected, actual } => write!(
+                f,
+                "path has length {} but the circuit expects depth {}",
+                actual, expected
+            ),
+            Error::InvalidIndex { layer } => {
+                write!(f, "path index at layer {} is not 0 or 1", layer)
+            }
+            Error::EmptyPath => write!(f, "path has no layers"),
+            Error::DepthTooLarge { max, depth } => {
+                write!(f, "depth {} is above the supported maximum {}", depth, max)
+            }
+            Error::TooManyLeaves { capacity, leaves } => write!(
+                f,
+                "{} leaves do not fit in a tree with capacity {}",
+                leaves, capacity
+            ),
+            Error::IndexOutOfRange { index, leaves } => write!(
+                f,
+                "leaf index {} is out of range for a tree with {} leaves",
+                index, leaves
+            ),
+            Error::MissingDepth => write!(f, "the tree depth was not specified"),
+            Error::MissingInstance(name) => {
+                write!(f, "no value supplied for the {} instance", name)
+            }
+            Error::InstanceColumns { expected, found } => write!(
+                f,
+                "{} instance columns were given but the circuit has {}",
+                found, expected
+            ),
+            Error::InstanceRows { expected, found } => write!(
+                f,
+                "the instance column has {} rows but the circuit's layout uses {}",
+                found, expected
+            ),
+            Error::UnusedInstance { column } => {
+                write!(
+                    f,
+                    "instance column {} is unused but was given values",
+                    column
+                )
+            }
+            Error::EncodingTooLong { max, len } => write!(
+                f,
+                "{} bytes do not fit in one field element (at most {})",
+                len, max
+            ),
+            Error::DuplicateLeaf { index } => write!(f, "leaf {} was selected twice", index),
+            Error::UnapprovedKey => write!(f, "the verifying key is not approved"),
+            Error::AlreadyApproved { index } => {
+                write!(f, "the verifying key is already approved at {}", index)
+            }
+            Error::Blocked => write!(f, "the element is in the tree"),
+            Error::Unsorted { index } => {
+                write!(f, "value {} is not above the one before it", index)
+            }
+            Error::ValueTooLarge { index, bits } => {
+                write!(f, "value {} does not fit in {} bits", index, bits)
+            }
+            Error::OutsideWindow { epoch, from, to } => {
+                write!(
+                    f,
+                    "epoch {} is outside the window [{}, {}]",
+                    epoch, from, to
+                )
+            }
+            Error::NonCanonical => write!(f, "value is not a canonical field element"),
+            Error::LeafMismatch => write!(f, "the paths are for different leaves"),
+            Error::UnsupportedArity(arity) => {
+                write!(f, "trees of arity {} are not supported", arity)
+            }
+            Error::UnsupportedVersion(version) => {
+                write!(f, "format version {} is not supported", version)
+            }
+            Error::Malformed(reason) => write!(f, "malformed data: {}", reason),
+            Error::RootMismatch => write!(f, "the proof does not lead to the expected root"),
+            Error::VectorMismatch { case } => {
+                write!(f, "reference vector {} does not match", case)
+            }
+            Error::FixtureMismatch { name, part } => {
+                write!(
+                    f,
+                    "fixture {} no longer replays: its {} changed",
+                    name, part
+                )
+            }
+            Error::TamperAccepted(tamper) => {
+                write!(f, "the verifier accepted a tampered pair: {:?}", tamper)
+            }
+            Error::InsufficientWork => write!(f, "the header hash is above its target"),
+            Error::Rpc(err) => write!(f, "rpc error: {}", err),
+            Error::InvalidK { k, min, max } => {
+                write!(f, "k = {} is outside the usable range {}..={}", k, min, max)
+            }
+            Error::InsufficientK { k, need, shape } => {
+                write!(f, "k={} insufficient for {}; need k={}", k, shape, need)
+            }
+            Error::ConfigMismatch(reason) => {
+                write!(f, "the config does not match the tree: {}", reason)
+            }
+            Error::Params(reason) => write!(f, "invalid parameters: {}", reason),
+            Error::UnknownVariant(name) => write!(f, "unknown circuit variant {:?}", name),
+            Error::Plonk(err) => write!(f, "halo2 error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<halo2_merkle_core::Error> for Error {
+    fn from(err: halo2_merkle_core::Error) -> Self {
+        use halo2_merkle_core::Error as Core;
+        match err {
+            Core::LengthMismatch { elements, indices } => {
+                Error::LengthMismatch { elements, indices }
+            }
+            Core::DepthMismatch { expected, actual } => Error::DepthMismatch { expected, actual },
+            Core::InvalidIndex { layer } => Error::InvalidIndex { layer },
+            Core::NonCanonical => Error::NonCanonical,
+            Core::UnsupportedArity(arity) => Error::UnsupportedArity(arity),
+            Core::UnsupportedVersion(version) => Error::UnsupportedVersion(version),
+            Core::Malformed(reason) => Error::Malformed(reason),
+            Core::RootMismatch => Error::RootMismatch,
+        }
+    }
+}
+
+impl From<plonk::Error> for Error {
+    fn from(err: plonk::Error) -> Self {
+        Error::Plonk(err)
+    }
+}
+
+// Lets chip methods returning our error be called with `?` inside `Circuit::synthesize`.
+impl From<Error> for plonk::Error {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Plonk(err) => err,
+            _ => plonk::Error::Synthesis,
+        }
+    }
+}
+
+// Checks a Merkle path before it reaches synthesis, where a malformed path either panics
+// or only shows up as an unsatisfied constraint.
+pub fn validate_path<F: FieldExt>(
+    depth: usize,
+    elements: &[Value<F>],
+    indices: &[Value<F>],
+) -> Result<(), Error> {
+    if elements.len() != indices.len() {
+        return Err(Error::LengthMismatch {
+            elements: elements.len(),
+            indices: indices.len(),
+        });
+    }
+    if elements.len() != depth {
+        return Err(Error::DepthMismatch {
+            expected: depth,
+            actual: elements.len(),
+        });
+    }
+    for (layer, index) in indices.iter().enumerate() {
+        let mut valid = true;
+        index.map(|x| valid = x == F::zero() || x == F::one());
+        if !valid {
+            return Err(Error::InvalidIndex { layer });
+        }
+    }
+    Ok(())
+}
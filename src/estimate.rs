@@ -0,0 +1,298 @@
+/*
+Picks the smallest k a circuit fits in, by laying it out with MockProver at increasing k until no
+region, fixed assignment or instance row runs past the usable rows. This measures the actual region
+shapes rather than a formula, so it stays right as chips change. As a rough guide, a Poseidon
+layer takes a few dozen rows, so depth 32 does not fit in the k = 10 the examples use.
+
+Only the shape matters, so `estimate_k` lays out an all-zero path. `CircuitParams` describes a
+membership circuit by default; `CircuitParams::smt_deletion` describes SmtDeletionCircuit, whose
+shape is fixed apart from its depth. `check_circuit_k` checks any other circuit directly.
+*/
+
+use crate::builder::{Hasher, MerkleCircuit, MerkleCircuitBuilder};
+use crate::chips::merkle::DomainSeparation;
+use crate::circuits::smt_deletion::SmtDeletionCircuit;
+use crate::error::Error;
+use crate::instance::{InstanceBuilder, LeafExposure};
+use crate::planner::Planner;
+use crate::tree::MerklePath;
+use halo2_proofs::{
+    dev::MockProver,
+    pasta::Fp,
+    plonk::{self, Circuit},
+};
+use std::fmt;
+
+pub const MIN_K: u32 = 4;
+pub const MAX_K: u32 = 24;
+
+// Which circuit a `CircuitParams` describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CircuitKind {
+    #[default]
+    Membership,
+    // SmtDeletionCircuit: Poseidon with Legacy separation, so only the depth is read.
+    SmtDeletion,
+}
+
+// The shape of a membership or deletion circuit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitParams {
+    pub kind: CircuitKind,
+    pub depth: usize,
+    pub hasher: Hasher,
+    pub separation: DomainSeparation,
+    // Children per node. Only binary trees are implemented.
+    pub arity: usize,
+    pub planner: Planner,
+    // A committed leaf costs one more hash.
+    pub leaf_exposure: LeafExposure,
+}
+
+impl CircuitParams {
+    pub fn new(depth: usize, hasher: Hasher) -> Self {
+        Self {
+            kind: CircuitKind::Membership,
+            depth,
+            hasher,
+            separation: DomainSeparation::default(),
+            arity: 2,
+            planner: Planner::default(),
+            leaf_exposure: LeafExposure::default(),
+        }
+    }
+
+    pub fn smt_deletion(depth: usize) -> Self {
+        Self {
+            kind: CircuitKind::SmtDeletion,
+            ..Self::new(depth, Hasher::Poseidon)
+        }
+    }
+}
+
+// "depth 32 Poseidon", as errors name the shape.
+impl fmt::Display for CircuitParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.kind == CircuitKind::SmtDeletion {
+            return write!(f, "depth {} SMT deletion", self.depth);
+        }
+        let hasher = match self.hasher {
+            Hasher::Dummy => "dummy-hash",
+            Hasher::Poseidon => "Poseidon",
+        };
+        write!(f, "depth {} {}", self.depth, hasher)?;
+        if self.arity != 2 {
+            write!(f, " arity {}", self.arity)?;
+        }
+        Ok(())
+    }
+}
+
+// Whether `circuit` can be synthesized with `instances` at k.
+fn fits<C: Circuit<Fp>>(k: u32, circuit: &C, instances: Vec<Vec<Fp>>) -> Result<bool, Error> {
+    match MockProver::run(k, circuit, instances) {
+        Ok(_) => Ok(true),
+        Err(plonk::Error::NotEnoughRowsAvailable { .. }) | Err(plonk::Error::InstanceTooLarge) => {
+            Ok(false)
+        }
+        Err(err) => Err(Error::Plonk(err)),
+    }
+}
+
+// The smallest k in MIN_K..=MAX_K at which `circuit` can be synthesized with `instances`. Only the
+// layout is checked, not whether the witness satisfies the constraints.
+pub fn min_k<C: Circuit<Fp>>(circuit: &C, instances: Vec<Vec<Fp>>) -> Result<u32, Error> {
+    for k in MIN_K..=MAX_K {
+        if fits(k, circuit, instances.clone())? {
+            return Ok(k);
+        }
+    }
+    Err(Error::Plonk(plonk::Error::NotEnoughRowsAvailable {
+        current_k: MAX_K,
+    }))
+}
+
+// A circuit of the shape `params` describes, with an all-zero witness, and its instances.
+fn zero_circuit(params: &CircuitParams) -> Result<(MerkleCircuit, Vec<Vec<Fp>>), Error> {
+    if params.arity != 2 {
+        return Err(Error::UnsupportedArity(params.arity));
+    }
+    let zeros = vec![Fp::zero(); params.depth];
+    let (circuit, _) = MerkleCircuitBuilder::new()
+        .depth(params.depth)
+        .hasher(params.hasher)
+        .separation(params.separation)
+        .planner(params.planner)
+        .leaf_exposure(params.leaf_exposure)
+        .blinding(Fp::zero())
+        .leaf(Fp::zero())
+        .elements(&zeros)
+        .indices(&zeros)
+        .build()?;
+    let instances = InstanceBuilder::for_circuit(&circuit)?;
+    Ok((circuit, instances))
+}
+
+// The deletion circuit of `depth` over an all-zero path, and its instances.
+fn zero_deletion(depth: usize) -> Result<(SmtDeletionCircuit, Vec<Vec<Fp>>), Error> {
+    let zeros = vec![Fp::zero(); depth];
+    let path = MerklePath {
+        leaf: Fp::zero(),
+        elements: zeros.clone(),
+        indices: zeros,
+    };
+    let circuit = SmtDeletionCircuit::new(&path)?;
+    let instances = SmtDeletionCircuit::instances(Fp::zero(), Fp::zero(), 0, depth)?;
+    Ok((circuit, instances))
+}
+
+pub fn estimate_k(params: &CircuitParams) -> Result<u32, Error> {
+    if params.kind == CircuitKind::SmtDeletion {
+        let (circuit, instances) = zero_deletion(params.depth)?;
+        return min_k(&circuit, instances);
+    }
+    let (circuit, instances) = zero_circuit(params)?;
+    match &circuit {
+        MerkleCircuit::Dummy(circuit) => min_k(circuit, instances),
+        MerkleCircuit::Poseidon(circuit) => min_k(circuit, instances),
+        MerkleCircuit::PackedDummy(circuit) => min_k(circuit, instances),
+        MerkleCircuit::PackedPoseidon(circuit) => min_k(circuit, instances),
+    }
+}
+
+// Checks before keygen or proving that the circuit `params` describes fits in 2^k rows, which
+// halo2 would otherwise only report from inside synthesis as NotEnoughRowsAvailable. The common
+// case costs one layout at k; the smallest sufficient k is only searched for the error.
+pub fn check_k(k: u32, params: &CircuitParams) -> Result<(), Error> {
+    check_range(k)?;
+    let fits = match params.kind {
+        CircuitKind::SmtDeletion => {
+            let (circuit, instances) = zero_deletion(params.depth)?;
+            fits(k, &circuit, instances)
+        }
+        CircuitKind::Membership => {
+            let (circuit, instances) = zero_circuit(params)?;
+            match &circuit {
+                MerkleCircuit::Dummy(circuit) => fits(k, circuit, instances),
+                MerkleCircuit::Poseidon(circuit) => fits(k, circuit, instances),
+                MerkleCircuit::PackedDummy(circuit) => fits(k, circuit, instances),
+                MerkleCircuit::PackedPoseidon(circuit) => fits(k, circuit, instances),
+            }
+        }
+    }?;
+    if fits {
+        return Ok(());
+    }
+    insufficient(k, estimate_k(params), params.to_string())
+}
+
+// `check_k` for a circuit `CircuitParams` can't describe, such as the benchmarks' batch circuits.
+// `shape` names it in the error.
+pub fn check_circuit_k<C: Circuit<Fp>>(
+    k: u32,
+    circuit: &C,
+    instances: Vec<Vec<Fp>>,
+    shape: &str,
+) -> Result<(), Error> {
+    check_range(k)?;
+    if fits(k, circuit, instances.clone())? {
+        return Ok(());
+    }
+    insufficient(k, min_k(circuit, instances), shape.to_string())
+}
+
+fn check_range(k: u32) -> Result<(), Error> {
+    if !(MIN_K..=MAX_K).contains(&k) {
+        return Err(Error::InvalidK {
+            k,
+            min: MIN_K,
+            max: MAX_K,
+        });
+    }
+    Ok(())
+}
+
+// The InsufficientK error for k, given the search for the smallest k that fits.
+fn insufficient(k: u32, need: Result<u32, Error>, shape: String) -> Result<(), Error> {
+    let need = match need {
+        Ok(need) => need,
+        // Past MAX_K; the error then names a k this crate doesn't prove at.
+        Err(Error::Plonk(plonk::Error::NotEnoughRowsAvailable { .. })) => MAX_K + 1,
+        Err(err) => return Err(err),
+    };
+    Err(Error::InsufficientK { k, need, shape })
+}
+
+mod tests {
+    use super::{check_circuit_k, check_k, estimate_k, CircuitParams, MAX_K};
+    use crate::builder::Hasher;
+    use crate::chips::merkle::DomainSeparation;
+    use crate::circuits::smt_deletion::SmtDeletionCircuit;
+    use crate::error::Error;
+    use crate::instance::LeafExposure;
+    use crate::tree::MerklePath;
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let shallow = estimate_k(&CircuitParams::new(4, Hasher::Poseidon)).unwrap();
+        let deep = estimate_k(&CircuitParams::new(32, Hasher::Poseidon)).unwrap();
+        assert!(shallow <= 10);
+        assert!(deep > 10 && deep >= shallow);
+
+        let mut level = CircuitParams::new(32, Hasher::Poseidon);
+        level.separation = DomainSeparation::Level;
+        assert!(estimate_k(&level).unwrap() >= deep);
+        assert!(estimate_k(&CircuitParams::new(32, Hasher::Dummy)).unwrap() <= deep);
+
+        let mut quaternary = CircuitParams::new(4, Hasher::Poseidon);
+        quaternary.arity = 4;
+        assert!(estimate_k(&quaternary).is_err());
+
+        // A committed leaf needs at least as many rows as an exposed one.
+        let mut committed = CircuitParams::new(4, Hasher::Poseidon);
+        committed.leaf_exposure = LeafExposure::Commitment;
+        assert!(estimate_k(&committed).unwrap() >= shallow);
+
+        // Too small a k is reported with the k the shape needs, before any keygen.
+        let params = CircuitParams::new(32, Hasher::Poseidon);
+        check_k(deep, &params).unwrap();
+        let err = check_k(10, &params).unwrap_err();
+        assert!(matches!(err, Error::InsufficientK { k: 10, need, .. } if need == deep));
+        assert_eq!(
+            err.to_string(),
+            format!("k=10 insufficient for depth 32 Poseidon; need k={}", deep)
+        );
+        assert!(matches!(
+            check_k(MAX_K + 1, &params),
+            Err(Error::InvalidK { .. })
+        ));
+
+        // The deletion circuit evaluates the path twice, so it needs more rows than membership.
+        let deletion = CircuitParams::smt_deletion(32);
+        let need = estimate_k(&deletion).unwrap();
+        assert!(need >= deep);
+        check_k(need, &deletion).unwrap();
+        let err = check_k(10, &deletion).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "k=10 insufficient for depth 32 SMT deletion; need k={}",
+                need
+            )
+        );
+        // Any other circuit is checked as it is, under the name the caller gives it.
+        let path = MerklePath {
+            leaf: Fp::zero(),
+            elements: vec![Fp::zero(); 32],
+            indices: vec![Fp::zero(); 32],
+        };
+        let circuit = SmtDeletionCircuit::new(&path).unwrap();
+        let instances = SmtDeletionCircuit::instances(Fp::zero(), Fp::zero(), 0, 32).unwrap();
+        check_circuit_k(need, &circuit, instances.clone(), "deletion").unwrap();
+        assert!(matches!(
+            check_circuit_k(10, &circuit, instances, "deletion"),
+            Err(Error::InsufficientK { k: 10, need: n, shape }) if n == need && shape == "deletion"
+        ));
+    }
+}
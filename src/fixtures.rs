@@ -0,0 +1,296 @@
+/*
+Regression fixtures for the membership variants. A fixture pins, for one variant and one witness
+path, the native root the path leads to and the instance columns the variant derives from it.
+Replaying it checks that the current code derives the same root and instances and that MockProver
+still accepts the witness against them, so a refactor of the chips (such as moving a variant onto
+the unified MerkleChip) shows whether it changed what the circuits prove.
+
+Fixtures are committed in fixtures/membership.txt, one per line:
+
+    v3/leaf-6: leaf=0x… elements=0x…,0x… indices=0,1,1,0 root=0x… instances=0x…,0x…;
+
+The variant name comes before the slash, field elements are written by `serialization::to_hex`,
+and instance columns are separated by semicolons, so `…;` ends with the empty column the Poseidon
+chip allocates. `check_fixtures` fails when a case of `cases()` is missing from the file or differs
+from its pinned line, so neither a regression nor an unpinned case gets past the test. With
+UPDATE_FIXTURES set it records those cases instead; commit the diff.
+*/
+
+use crate::chips::merkle::DomainSeparation;
+use crate::error::Error;
+use crate::serialization::{from_hex, to_hex};
+use crate::tree::{MerklePath, MerkleTree};
+use crate::variant::Variant;
+use halo2_proofs::pasta::Fp;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Enough rows for every variant at the depth of `cases()`.
+pub const K: u32 = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fixture {
+    pub name: String,
+    pub witness: MerklePath,
+    pub root: Fp,
+    pub instances: Vec<Vec<Fp>>,
+}
+
+fn hex_list(values: &[Fp]) -> String {
+    values.iter().map(to_hex).collect::<Vec<_>>().join(",")
+}
+
+fn parse_list(list: &str) -> Result<Vec<Fp>, Error> {
+    list.split(',')
+        .filter(|value| !value.is_empty())
+        .map(from_hex)
+        .collect()
+}
+
+impl Fixture {
+    // Records what the current code derives for `witness` under the variant `name` starts with.
+    pub fn record(name: &str, witness: MerklePath) -> Result<Self, Error> {
+        let circuit = variant_of(name)?.circuit(witness.elements.len())?;
+        Ok(Self {
+            name: name.to_string(),
+            root: witness.root(DomainSeparation::Legacy),
+            instances: circuit.instances(&witness)?,
+            witness,
+        })
+    }
+
+    pub fn variant(&self) -> Result<Variant, Error> {
+        variant_of(&self.name)
+    }
+
+    // Checks the fixture against the current code, failing with the first part that changed.
+    pub fn replay(&self) -> Result<(), Error> {
+        let mismatch = |part| Error::FixtureMismatch {
+            name: self.name.clone(),
+            part,
+        };
+        if self.witness.root(DomainSeparation::Legacy) != self.root {
+            return Err(mismatch("root"));
+        }
+        let circuit = self.variant()?.circuit(self.witness.elements.len())?;
+        if circuit.instances(&self.witness)? != self.instances {
+            return Err(mismatch("instances"));
+        }
+        if !circuit.mock_accepts(K, &self.witness, &self.instances)? {
+            return Err(mismatch("constraints"));
+        }
+        Ok(())
+    }
+
+    pub fn to_line(&self) -> String {
+        let indices: Vec<&str> = self
+            .witness
+            .indices
+            .iter()
+            .map(|index| if *index == Fp::one() { "1" } else { "0" })
+            .collect();
+        let instances: Vec<String> = self
+            .instances
+            .iter()
+            .map(|column| hex_list(column))
+            .collect();
+        format!(
+            "{}: leaf={} elements={} indices={} root={} instances={}",
+            self.name,
+            to_hex(&self.witness.leaf),
+            hex_list(&self.witness.elements),
+            indices.join(","),
+            to_hex(&self.root),
+            instances.join(";")
+        )
+    }
+
+    pub fn parse(line: &str) -> Result<Self, Error> {
+        let (name, fields) = line
+            .split_once(": ")
+            .ok_or(Error::Malformed("expected a fixture name"))?;
+        let mut values = fields.split(' ').map(|field| field.split_once('='));
+        let mut next = |key: &'static str| match values.next() {
+            Some(Some((found, value))) if found == key => Ok(value),
+            _ => Err(Error::Malformed(key)),
+        };
+        let leaf = from_hex(next("leaf")?)?;
+        let elements = parse_list(next("elements")?)?;
+        let indices = next("indices")?
+            .split(',')
+            .filter(|index| !index.is_empty())
+            .map(|index| match index {
+                "0" => Ok(Fp::zero()),
+                "1" => Ok(Fp::one()),
+                _ => Err(Error::Malformed("expected path indices of 0 or 1")),
+            })
+            .collect::<Result<_, _>>()?;
+        let root = from_hex(next("root")?)?;
+        let instances = next("instances")?
+            .split(';')
+            .map(parse_list)
+            .collect::<Result<_, _>>()?;
+        if values.next().is_some() {
+            return Err(Error::Malformed("unexpected field after instances"));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            witness: MerklePath {
+                leaf,
+                elements,
+                indices,
+            },
+            root,
+            instances,
+        })
+    }
+}
+
+fn variant_of(name: &str) -> Result<Variant, Error> {
+    let (variant, _) = name
+        .split_once('/')
+        .ok_or(Error::Malformed("expected a variant/case name"))?;
+    variant.parse()
+}
+
+// The pinned cases: paths of a depth-4 tree with leaves 1..=11 through each variant, and for the
+// dummy hash also a path it computes by hand.
+pub fn cases() -> Result<Vec<Fixture>, Error> {
+    let leaves: Vec<Fp> = (1..=11u64).map(Fp::from).collect();
+    let tree = MerkleTree::new(4, &leaves, DomainSeparation::Legacy)?;
+    // The dummy hash's root is the leaf plus its siblings: 3 + 4 * 5.
+    let dummy = MerklePath {
+        leaf: Fp::from(3),
+        elements: vec![Fp::from(5); 4],
+        indices: tree.path(6)?.indices,
+    };
+    let mut cases = vec![
+        Fixture::record("v2/dummy", dummy)?,
+        Fixture::record("v2/leaf-6", tree.path(6)?)?,
+    ];
+    for index in [0, 6, 10] {
+        cases.push(Fixture::record(
+            &format!("v3/leaf-{}", index),
+            tree.path(index)?,
+        )?);
+    }
+    cases.push(Fixture::record("smt-deletion/leaf-6", tree.path(6)?)?);
+    Ok(cases)
+}
+
+pub fn fixtures_path() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/membership.txt")
+}
+
+// The fixtures in `text`, skipping blank lines and # comments.
+pub fn parse_fixtures(text: &str) -> Result<Vec<Fixture>, Error> {
+    text.lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(Fixture::parse)
+        .collect()
+}
+
+// The fixtures in the file at `path`, which must exist.
+pub fn load_fixtures(path: &Path) -> Result<Vec<Fixture>, Error> {
+    let text = fs::read_to_string(path)
+        .map_err(|err| Error::Params(format!("{}: {}", path.display(), err)))?;
+    parse_fixtures(&text)
+}
+
+// Replays every fixture in the file at `path`, returning how many there were.
+pub fn replay_fixtures(path: &Path) -> Result<usize, Error> {
+    let fixtures = load_fixtures(path)?;
+    for fixture in &fixtures {
+        fixture.replay()?;
+    }
+    Ok(fixtures.len())
+}
+
+// Checks that every case is pinned in the file at `path` as the current code records it. With
+// UPDATE_FIXTURES set, missing and differing cases are written instead, returning how many were.
+pub fn check_fixtures(path: &Path) -> Result<usize, String> {
+    let contents = fs::read_to_string(path).unwrap_or_default();
+    let mut lines: Vec<String> = contents.lines().map(String::from).collect();
+    let update = std::env::var_os("UPDATE_FIXTURES").is_some();
+    let mut written = 0;
+    for case in cases().map_err(|err| err.to_string())? {
+        let line = case.to_line();
+        let prefix = format!("{}: ", case.name);
+        let pinned = lines.iter().position(|pinned| pinned.starts_with(&prefix));
+        match pinned {
+            Some(i) if lines[i] == line => continue,
+            _ if !update => {
+                return Err(format!(
+                    "fixture {} is {} in {}:\n  actual: {}\nre-run with UPDATE_FIXTURES=1 if this is intended",
+                    case.name,
+                    if pinned.is_some() { "different" } else { "missing" },
+                    path.display(),
+                    line
+                ))
+            }
+            Some(i) => lines[i] = line,
+            None => lines.push(line),
+        }
+        written += 1;
+    }
+    if written > 0 {
+        fs::write(path, lines.join("\n") + "\n").map_err(|err| err.to_string())?;
+    }
+    Ok(written)
+}
+
+mod tests {
+    use super::{
+        cases, check_fixtures, fixtures_path, load_fixtures, parse_fixtures, replay_fixtures,
+        Fixture,
+    };
+    use crate::error::Error;
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let path = fixtures_path();
+        if let Err(message) = check_fixtures(&path) {
+            panic!("{}", message);
+        }
+        let fixtures = load_fixtures(&path).unwrap();
+        assert_eq!(replay_fixtures(&path).unwrap(), fixtures.len());
+        let cases = cases().unwrap();
+        for case in &cases {
+            assert!(fixtures.contains(case), "{} is not pinned", case.name);
+        }
+        assert_eq!(cases[0].instances, vec![vec![Fp::from(3), Fp::from(23)]]);
+        for fixture in &fixtures {
+            assert_eq!(&Fixture::parse(&fixture.to_line()).unwrap(), fixture);
+        }
+
+        // Each part of a fixture is checked against the current code.
+        let mut moved = cases[2].clone();
+        moved.root += Fp::one();
+        assert!(matches!(
+            moved.replay(),
+            Err(Error::FixtureMismatch { part: "root", .. })
+        ));
+        let mut moved = cases[2].clone();
+        moved.instances[0][0] += Fp::one();
+        assert!(matches!(
+            moved.replay(),
+            Err(Error::FixtureMismatch {
+                part: "instances",
+                ..
+            })
+        ));
+
+        assert!(parse_fixtures("# comment\n\n").unwrap().is_empty());
+        assert!(matches!(
+            load_fixtures(&path.with_file_name("missing.txt")),
+            Err(Error::Params(_))
+        ));
+        assert!(matches!(
+            Fixture::parse("v3/leaf-0: leaf=0x1 root=0x2"),
+            Err(Error::Malformed("elements"))
+        ));
+        let unnamed = Fixture::parse(&cases[0].to_line().replacen('/', "-", 1)).unwrap();
+        assert!(matches!(unnamed.replay(), Err(Error::Malformed(_))));
+    }
+}
@@ -0,0 +1,135 @@
+/*
+Experimental, native-only support for the 64-bit Goldilocks field p = 2^64 - 2^32 + 1 used by
+STARK-style provers. halo2's `FieldExt` assumes a ~255-bit field with a large 2-adic subgroup, so
+Goldilocks values cannot go through the chips; this module only covers witness generation: field
+arithmetic and packing leaves into several limbs. Leaves are split into 7-byte big-endian limbs,
+which are always below p, so the encoding is canonical like LeafEncodingChip's 31-byte packing.
+*/
+
+use std::ops::{Add, Mul, Neg, Sub};
+
+pub const MODULUS: u64 = 0xffff_ffff_0000_0001;
+
+// Bytes per limb: 7 bytes are below 2^56 < p.
+pub const LIMB_BYTES: usize = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Goldilocks(u64);
+
+impl Goldilocks {
+    pub const ZERO: Self = Self(0);
+    pub const ONE: Self = Self(1);
+
+    pub fn new(value: u64) -> Self {
+        Self(value % MODULUS)
+    }
+
+    // The canonical representative in [0, p).
+    pub fn to_canonical_u64(self) -> u64 {
+        self.0
+    }
+
+    fn reduce(value: u128) -> Self {
+        Self((value % MODULUS as u128) as u64)
+    }
+
+    pub fn pow(self, mut exp: u64) -> Self {
+        let mut base = self;
+        let mut acc = Self::ONE;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                acc = acc * base;
+            }
+            base = base * base;
+            exp >>= 1;
+        }
+        acc
+    }
+
+    // Fermat inversion; `None` for zero.
+    pub fn invert(self) -> Option<Self> {
+        if self.0 == 0 {
+            None
+        } else {
+            Some(self.pow(MODULUS - 2))
+        }
+    }
+}
+
+impl From<u64> for Goldilocks {
+    fn from(value: u64) -> Self {
+        Self::new(value)
+    }
+}
+
+impl Add for Goldilocks {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::reduce(self.0 as u128 + rhs.0 as u128)
+    }
+}
+
+impl Sub for Goldilocks {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::reduce(self.0 as u128 + MODULUS as u128 - rhs.0 as u128)
+    }
+}
+
+impl Neg for Goldilocks {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::ZERO - self
+    }
+}
+
+impl Mul for Goldilocks {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self::reduce(self.0 as u128 * rhs.0 as u128)
+    }
+}
+
+// Packs bytes into 7-byte big-endian limbs, the last limb holding the remainder.
+pub fn pack_limbs(bytes: &[u8]) -> Vec<Goldilocks> {
+    bytes
+        .chunks(LIMB_BYTES)
+        .map(|chunk| {
+            Goldilocks(
+                chunk
+                    .iter()
+                    .fold(0u64, |acc, byte| (acc << 8) | *byte as u64),
+            )
+        })
+        .collect()
+}
+
+// A 32-byte hash as five limbs, which unlike `hash_to_leaf` accepts any digest.
+pub fn hash_to_limbs(hash: &[u8; 32]) -> Vec<Goldilocks> {
+    pack_limbs(hash)
+}
+
+mod tests {
+    use super::{pack_limbs, Goldilocks, MODULUS};
+
+    #[test]
+    fn test() {
+        let a = Goldilocks::new(MODULUS - 1);
+        assert_eq!(a + Goldilocks::ONE, Goldilocks::ZERO);
+        assert_eq!(-Goldilocks::ONE, a);
+        assert_eq!(a * a, Goldilocks::ONE);
+        assert_eq!(Goldilocks::ZERO - Goldilocks::ONE, a);
+        let b = Goldilocks::from(123456789);
+        assert_eq!(b * b.invert().unwrap(), Goldilocks::ONE);
+        assert_eq!(Goldilocks::ZERO.invert(), None);
+
+        let limbs = pack_limbs(&[0xff; 32]);
+        assert_eq!(limbs.len(), 5);
+        assert_eq!(limbs[0].to_canonical_u64(), (1 << 56) - 1);
+        assert_eq!(limbs[4].to_canonical_u64(), (1 << 32) - 1);
+    }
+}
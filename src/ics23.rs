@@ -0,0 +1,368 @@
+/*
+Cosmos ICS-23 existence proofs, verified natively, with the IAVL proof spec. A leaf hashes
+`prefix | len(key) | key | len(H(value)) | H(value)` with protobuf varint lengths, and each inner op
+hashes `prefix | child | suffix`, where IAVL's prefix carries the node's height, size and version
+and the sibling sits in the prefix (child on the right) or suffix (child on the left). Only the
+operations IAVL uses are modelled: SHA-256 or no hashing, and varint or no length prefix.
+
+Under the IAVL spec every op's prefix is also parsed as ics23's `validateIavlOps` does: it must open
+with the height, size and version as zigzag varints, none negative and the height at least the
+op's layer. A leaf prefix ends there; an inner prefix has one more byte (the child's length, child
+on the left) or 34 (the sibling and both lengths, child on the right). Without this, a proof can
+reshape a node's header into sibling bytes and prove a key the store never held.
+
+This is the native verifier only, and the ICS-23 chip is not built yet: it needs a SHA-256 chip
+with assigned digests and variable-length byte gadgets for the prefixes, which the crate does not
+have, so ICS-23 proofs cannot yet be checked in halo2.
+*/
+
+use crate::error::Error;
+use sha2::{Digest, Sha256};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashOp {
+    NoHash,
+    Sha256,
+}
+
+impl HashOp {
+    fn apply(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            HashOp::NoHash => data.to_vec(),
+            HashOp::Sha256 => Sha256::digest(data).to_vec(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LengthOp {
+    NoPrefix,
+    // The protobuf unsigned varint of the length.
+    VarProto,
+}
+
+fn varint(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+// Reads a zigzag varint from the front of `bytes`, as Go's binary.ReadVarint does.
+fn read_zigzag(bytes: &mut &[u8]) -> Result<i64, Error> {
+    let mut value = 0u64;
+    for (i, byte) in bytes.iter().enumerate().take(10) {
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            *bytes = &bytes[i + 1..];
+            return Ok((value >> 1) as i64 ^ -((value & 1) as i64));
+        }
+    }
+    Err(Error::Malformed("IAVL prefix has a truncated varint"))
+}
+
+// ics23's validateIavlOps: `layer` is 0 for the leaf op and i + 1 for the i-th inner op.
+fn check_iavl_prefix(prefix: &[u8], layer: usize) -> Result<(), Error> {
+    let mut rest = prefix;
+    let height = read_zigzag(&mut rest)?;
+    let size = read_zigzag(&mut rest)?;
+    let version = read_zigzag(&mut rest)?;
+    if height < 0 || size < 0 || version < 0 {
+        return Err(Error::Malformed("IAVL prefix has a negative value"));
+    }
+    if (height as u64) < layer as u64 {
+        return Err(Error::Malformed("IAVL height is below the op's layer"));
+    }
+    let extra = if layer == 0 { &[0][..] } else { &[1, 34][..] };
+    if !extra.contains(&rest.len()) {
+        return Err(Error::Malformed("IAVL prefix has the wrong length"));
+    }
+    Ok(())
+}
+
+impl LengthOp {
+    fn apply(self, data: &[u8], out: &mut Vec<u8>) {
+        if self == LengthOp::VarProto {
+            varint(data.len() as u64, out);
+        }
+        out.extend_from_slice(data);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeafOp {
+    pub hash: HashOp,
+    pub prehash_key: HashOp,
+    pub prehash_value: HashOp,
+    pub length: LengthOp,
+    pub prefix: Vec<u8>,
+}
+
+impl LeafOp {
+    pub fn apply(&self, key: &[u8], value: &[u8]) -> Result<Vec<u8>, Error> {
+        if key.is_empty() || value.is_empty() {
+            return Err(Error::Malformed("leaf key and value must not be empty"));
+        }
+        let mut data = self.prefix.clone();
+        self.length.apply(&self.prehash_key.apply(key), &mut data);
+        self.length
+            .apply(&self.prehash_value.apply(value), &mut data);
+        Ok(self.hash.apply(&data))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InnerOp {
+    pub hash: HashOp,
+    pub prefix: Vec<u8>,
+    pub suffix: Vec<u8>,
+}
+
+impl InnerOp {
+    pub fn apply(&self, child: &[u8]) -> Vec<u8> {
+        self.hash
+            .apply(&[self.prefix.as_slice(), child, self.suffix.as_slice()].concat())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InnerSpec {
+    pub children: usize,
+    pub child_size: usize,
+    pub min_prefix_length: usize,
+    pub max_prefix_length: usize,
+    pub hash: HashOp,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofSpec {
+    pub leaf_spec: LeafOp,
+    pub inner_spec: InnerSpec,
+    // Zero means unbounded.
+    pub max_depth: usize,
+    pub min_depth: usize,
+}
+
+// The spec Cosmos SDK stores use for IAVL trees.
+pub fn iavl_spec() -> ProofSpec {
+    ProofSpec {
+        leaf_spec: LeafOp {
+            hash: HashOp::Sha256,
+            prehash_key: HashOp::NoHash,
+            prehash_value: HashOp::Sha256,
+            length: LengthOp::VarProto,
+            prefix: vec![0],
+        },
+        inner_spec: InnerSpec {
+            children: 2,
+            child_size: 33,
+            min_prefix_length: 4,
+            max_prefix_length: 12,
+            hash: HashOp::Sha256,
+        },
+        max_depth: 0,
+        min_depth: 0,
+    }
+}
+
+impl ProofSpec {
+    // Whether this is the IAVL spec, ignoring the depth bounds, as ics23's SpecEquals does.
+    fn is_iavl(&self) -> bool {
+        let iavl = iavl_spec();
+        self.leaf_spec == iavl.leaf_spec && self.inner_spec == iavl.inner_spec
+    }
+
+    fn check_leaf(&self, leaf: &LeafOp) -> Result<(), Error> {
+        let spec = &self.leaf_spec;
+        if (leaf.hash, leaf.prehash_key, leaf.prehash_value, leaf.length)
+            != (spec.hash, spec.prehash_key, spec.prehash_value, spec.length)
+        {
+            return Err(Error::Malformed("leaf op does not match the spec"));
+        }
+        if !leaf.prefix.starts_with(&spec.prefix) {
+            return Err(Error::Malformed(
+                "leaf prefix does not start with the spec's",
+            ));
+        }
+        if self.is_iavl() {
+            check_iavl_prefix(&leaf.prefix, 0)?;
+        }
+        Ok(())
+    }
+
+    fn check_inner(&self, inner: &InnerOp, layer: usize) -> Result<(), Error> {
+        let spec = &self.inner_spec;
+        if inner.hash != spec.hash {
+            return Err(Error::Malformed("inner op hash does not match the spec"));
+        }
+        // An inner prefix readable as a leaf prefix would let a leaf pose as an inner node.
+        if inner.prefix.starts_with(&self.leaf_spec.prefix) && !self.leaf_spec.prefix.is_empty() {
+            return Err(Error::Malformed("inner prefix starts with the leaf prefix"));
+        }
+        let max_prefix = spec.max_prefix_length + (spec.children - 1) * spec.child_size;
+        if inner.prefix.len() < spec.min_prefix_length || inner.prefix.len() > max_prefix {
+            return Err(Error::Malformed("inner prefix length is out of range"));
+        }
+        if inner.suffix.len() % spec.child_size != 0 {
+            return Err(Error::Malformed(
+                "inner suffix is not a whole number of children",
+            ));
+        }
+        if self.is_iavl() {
+            check_iavl_prefix(&inner.prefix, layer)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExistenceProof {
+    pub key: Vec<u8>,
+    pub value: Vec<u8>,
+    pub leaf: LeafOp,
+    // Inner ops from the leaf up.
+    pub path: Vec<InnerOp>,
+}
+
+impl ExistenceProof {
+    pub fn calculate_root(&self) -> Result<Vec<u8>, Error> {
+        let leaf = self.leaf.apply(&self.key, &self.value)?;
+        Ok(self
+            .path
+            .iter()
+            .fold(leaf, |node, inner| inner.apply(&node)))
+    }
+
+    // Checks the proof against `spec` and that it shows `key` => `value` under `root`.
+    pub fn verify(
+        &self,
+        spec: &ProofSpec,
+        root: &[u8],
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<(), Error> {
+        spec.check_leaf(&self.leaf)?;
+        if (spec.max_depth > 0 && self.path.len() > spec.max_depth)
+            || self.path.len() < spec.min_depth
+        {
+            return Err(Error::DepthMismatch {
+                expected: spec.max_depth.max(spec.min_depth),
+                actual: self.path.len(),
+            });
+        }
+        for (i, inner) in self.path.iter().enumerate() {
+            spec.check_inner(inner, i + 1)?;
+        }
+        if self.key != key || self.value != value {
+            return Err(Error::LeafMismatch);
+        }
+        if self.calculate_root()? != root {
+            return Err(Error::RootMismatch);
+        }
+        Ok(())
+    }
+}
+
+mod tests {
+    use super::{iavl_spec, varint, ExistenceProof, HashOp, InnerOp};
+    use sha2::{Digest, Sha256};
+
+    // IAVL writes height, size and version as zigzag varints.
+    fn header(height: i64, size: i64, version: i64) -> Vec<u8> {
+        let mut out = Vec::new();
+        for value in [height, size, version] {
+            varint(((value << 1) ^ (value >> 63)) as u64, &mut out);
+        }
+        out
+    }
+
+    #[test]
+    fn test() {
+        let spec = iavl_spec();
+        let mut leaf = spec.leaf_spec.clone();
+        leaf.prefix = header(0, 1, 7);
+        let left = ExistenceProof {
+            key: b"alice".to_vec(),
+            value: b"100".to_vec(),
+            leaf: leaf.clone(),
+            path: vec![],
+        };
+        let right = ExistenceProof {
+            key: b"bob".to_vec(),
+            value: b"250".to_vec(),
+            ..left.clone()
+        };
+        let left_hash = left.calculate_root().unwrap();
+        let right_hash = right.calculate_root().unwrap();
+
+        // The leaf hash by hand: prefix, varint(5) "alice", varint(32) sha256("100").
+        let mut data = header(0, 1, 7);
+        data.push(5);
+        data.extend_from_slice(b"alice");
+        data.push(32);
+        data.extend_from_slice(&Sha256::digest(b"100"));
+        assert_eq!(left_hash, Sha256::digest(&data).to_vec());
+
+        // The parent is sha256(header | 0x20 left | 0x20 right).
+        let node = header(1, 2, 7);
+        let root = Sha256::digest(
+            [
+                node.clone(),
+                vec![32],
+                left_hash.clone(),
+                vec![32],
+                right_hash.clone(),
+            ]
+            .concat(),
+        )
+        .to_vec();
+        let left = ExistenceProof {
+            path: vec![InnerOp {
+                hash: HashOp::Sha256,
+                prefix: [node.clone(), vec![32]].concat(),
+                suffix: [vec![32], right_hash].concat(),
+            }],
+            ..left
+        };
+        let right = ExistenceProof {
+            path: vec![InnerOp {
+                hash: HashOp::Sha256,
+                prefix: [node, vec![32], left_hash, vec![32]].concat(),
+                suffix: vec![],
+            }],
+            ..right
+        };
+        assert!(left.verify(&spec, &root, b"alice", b"100").is_ok());
+        assert!(right.verify(&spec, &root, b"bob", b"250").is_ok());
+        assert!(left.verify(&spec, &root, b"alice", b"101").is_err());
+        assert!(left.verify(&spec, &[0; 32], b"alice", b"100").is_err());
+
+        // A leaf posing as an inner node, and an oversized prefix, are rejected by the spec.
+        let mut forged = left.clone();
+        forged.path[0].prefix[0] = 0;
+        assert!(forged.verify(&spec, &root, b"alice", b"100").is_err());
+        let mut long = left.clone();
+        long.path[0].prefix = vec![2; 60];
+        assert!(long.verify(&spec, &root, b"alice", b"100").is_err());
+        let mut unhashed = left.clone();
+        unhashed.leaf.prehash_value = HashOp::NoHash;
+        assert!(unhashed.verify(&spec, &root, b"alice", b"100").is_err());
+
+        // IAVL headers: an inner height below its layer, a negative size, or bytes after a leaf's
+        // header are rejected before any hashing.
+        let reheaded = |proof: &ExistenceProof, leaf: Vec<u8>, inner: Vec<u8>| {
+            let mut proof = proof.clone();
+            proof.leaf.prefix = leaf;
+            proof.path[0].prefix = [inner, vec![32]].concat();
+            proof.verify(&spec, &root, b"alice", b"100")
+        };
+        assert!(reheaded(&left, header(0, 1, 7), header(1, 2, 7)).is_ok());
+        assert!(reheaded(&left, header(0, 1, 7), header(0, 2, 7)).is_err());
+        assert!(reheaded(&left, header(0, -1, 7), header(1, 2, 7)).is_err());
+        assert!(reheaded(&left, [header(0, 1, 7), vec![0]].concat(), header(1, 2, 7)).is_err());
+        let mut truncated = left;
+        truncated.path[0].prefix = vec![0x80; 11];
+        assert!(truncated.verify(&spec, &root, b"alice", b"100").is_err());
+    }
+}
@@ -0,0 +1,201 @@
+/*
+An indexed Merkle tree, the sorted-set layout of Aztec's nullifier tree, on top of MerkleTree. Each
+leaf is Poseidon(value, next), where next is the smallest member above value, or `indexed_end()`
+for the largest. Leaf 0 is the sentinel (0, smallest member), so any nonzero element that is not a
+member lies strictly between the value and next of exactly one leaf, its low leaf, and a path to
+that leaf proves non-membership.
+
+    let blocklist = IndexedTree::new(8, &blocked, DomainSeparation::Legacy)?;
+    let low = blocklist.low_leaf(element)?;  // Err(Blocked) for members
+
+Values are ordered as integers and must lie strictly between 0 and 2^254 - 1, the range
+ComparatorChip orders in-circuit. `insert` points the low leaf at the new value and appends the new
+leaf, so leaves are in insertion order rather than sorted.
+*/
+
+use crate::chips::merkle::DomainSeparation;
+use crate::error::Error;
+use crate::serialization::to_bytes_be;
+use crate::tree::{MerklePath, MerkleTree};
+use halo2_gadgets::poseidon::primitives::{self as poseidon, ConstantLength, P128Pow5T3};
+use halo2_proofs::{arithmetic::FieldExt, pasta::Fp};
+
+const VALUE_BITS: usize = 254;
+
+// 2^254 - 1, the `next` of the largest member.
+pub fn indexed_end() -> Fp {
+    let two_128 = Fp::from_u128(u128::MAX) + Fp::one();
+    Fp::from_u128((1 << 126) - 1) * two_128 + Fp::from_u128(u128::MAX)
+}
+
+pub fn indexed_leaf(value: Fp, next: Fp) -> Fp {
+    poseidon::Hash::<_, P128Pow5T3, ConstantLength<2>, 3, 2>::init().hash([value, next])
+}
+
+// A member's leaf, or the low leaf of a non-member, with its path.
+#[derive(Debug, Clone)]
+pub struct LowLeaf {
+    pub index: usize,
+    pub value: Fp,
+    pub next: Fp,
+    pub path: MerklePath,
+}
+
+#[derive(Debug, Clone)]
+pub struct IndexedTree {
+    tree: MerkleTree,
+    // (value, next) of each leaf, in leaf order.
+    entries: Vec<(Fp, Fp)>,
+}
+
+fn check_value(index: usize, value: &Fp) -> Result<(), Error> {
+    if to_bytes_be(value) >= to_bytes_be(&indexed_end()) {
+        return Err(Error::ValueTooLarge {
+            index,
+            bits: VALUE_BITS,
+        });
+    }
+    Ok(())
+}
+
+impl IndexedTree {
+    pub fn new(depth: usize, values: &[Fp], separation: DomainSeparation) -> Result<Self, Error> {
+        for (index, value) in values.iter().enumerate() {
+            if *value == Fp::zero() {
+                return Err(Error::Malformed("zero is the sentinel value"));
+            }
+            check_value(index, value)?;
+        }
+        let mut sorted = values.to_vec();
+        sorted.sort_by_key(to_bytes_be);
+        if let Some(index) = sorted.windows(2).position(|pair| pair[0] == pair[1]) {
+            return Err(Error::DuplicateLeaf { index: index + 1 });
+        }
+
+        let mut entries = Vec::with_capacity(sorted.len() + 1);
+        let mut value = Fp::zero();
+        for next in sorted {
+            entries.push((value, next));
+            value = next;
+        }
+        entries.push((value, indexed_end()));
+        let leaves: Vec<Fp> = entries
+            .iter()
+            .map(|(value, next)| indexed_leaf(*value, *next))
+            .collect();
+        Ok(Self {
+            tree: MerkleTree::new(depth, &leaves, separation)?,
+            entries,
+        })
+    }
+
+    pub fn tree(&self) -> &MerkleTree {
+        &self.tree
+    }
+
+    pub fn root(&self) -> Fp {
+        self.tree.root()
+    }
+
+    pub fn separation(&self) -> DomainSeparation {
+        self.tree.separation()
+    }
+
+    pub fn contains(&self, value: Fp) -> bool {
+        self.entries.iter().any(|(member, _)| *member == value)
+    }
+
+    // The leaf whose value and next enclose `element`; `Blocked` if it is a member or zero.
+    pub fn low_leaf(&self, element: Fp) -> Result<LowLeaf, Error> {
+        if self.contains(element) {
+            return Err(Error::Blocked);
+        }
+        check_value(0, &element)?;
+        let key = to_bytes_be(&element);
+        let index = self
+            .entries
+            .iter()
+            .position(|(value, next)| to_bytes_be(value) < key && key < to_bytes_be(next))
+            .ok_or(Error::Malformed("no leaf encloses the element"))?;
+        let (value, next) = self.entries[index];
+        Ok(LowLeaf {
+            index,
+            value,
+            next,
+            path: self.tree.path(index)?,
+        })
+    }
+
+    // Adds `value` to the set, returning its leaf index.
+    pub fn insert(&mut self, value: Fp) -> Result<usize, Error> {
+        let low = self.low_leaf(value)?;
+        let capacity = self.tree.capacity();
+        if self.entries.len() >= capacity {
+            return Err(Error::TooManyLeaves {
+                capacity,
+                leaves: self.entries.len() + 1,
+            });
+        }
+        self.tree
+            .update(low.index, indexed_leaf(low.value, value))?;
+        self.entries[low.index].1 = value;
+        let index = self.tree.append(indexed_leaf(value, low.next))?;
+        self.entries.push((value, low.next));
+        Ok(index)
+    }
+}
+
+mod tests {
+    use super::{indexed_end, indexed_leaf, IndexedTree};
+    use crate::chips::merkle::DomainSeparation;
+    use crate::error::Error;
+    use crate::serialization::to_bytes_be;
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let blocked: Vec<Fp> = [40u64, 10, 30].iter().map(|x| Fp::from(*x)).collect();
+        let mut tree = IndexedTree::new(3, &blocked, DomainSeparation::Legacy).unwrap();
+        assert_eq!(
+            tree.tree().leaves()[0],
+            indexed_leaf(Fp::zero(), Fp::from(10))
+        );
+
+        let low = tree.low_leaf(Fp::from(35)).unwrap();
+        assert_eq!((low.value, low.next), (Fp::from(30), Fp::from(40)));
+        low.path.verify(tree.separation(), tree.root()).unwrap();
+        let last = tree.low_leaf(Fp::from(41)).unwrap();
+        assert_eq!((last.value, last.next), (Fp::from(40), indexed_end()));
+        let first = tree.low_leaf(Fp::from(1)).unwrap();
+        assert_eq!((first.index, first.next), (0, Fp::from(10)));
+        assert!(matches!(tree.low_leaf(Fp::from(30)), Err(Error::Blocked)));
+        assert!(matches!(tree.low_leaf(Fp::zero()), Err(Error::Blocked)));
+
+        // Inserting splits the low leaf's range. The leaves are those of a tree built with the
+        // value, in insertion order.
+        tree.insert(Fp::from(35)).unwrap();
+        assert!(matches!(tree.low_leaf(Fp::from(35)), Err(Error::Blocked)));
+        assert_eq!(tree.low_leaf(Fp::from(36)).unwrap().value, Fp::from(35));
+        let mut values = blocked.clone();
+        values.push(Fp::from(35));
+        let rebuilt = IndexedTree::new(3, &values, DomainSeparation::Legacy).unwrap();
+        assert_ne!(tree.root(), rebuilt.root());
+        let mut sorted_leaves = tree.tree().leaves().to_vec();
+        let mut rebuilt_leaves = rebuilt.tree().leaves().to_vec();
+        sorted_leaves.sort_by_key(to_bytes_be);
+        rebuilt_leaves.sort_by_key(to_bytes_be);
+        assert_eq!(sorted_leaves, rebuilt_leaves);
+
+        assert!(matches!(
+            IndexedTree::new(3, &[Fp::from(3), Fp::from(3)], DomainSeparation::Legacy),
+            Err(Error::DuplicateLeaf { index: 1 })
+        ));
+        assert!(matches!(
+            IndexedTree::new(3, &[-Fp::one()], DomainSeparation::Legacy),
+            Err(Error::ValueTooLarge {
+                index: 0,
+                bits: 254
+            })
+        ));
+    }
+}
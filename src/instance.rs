@@ -0,0 +1,239 @@
+use crate::error::Error;
+use halo2_proofs::{arithmetic::FieldExt, circuit::Value};
+
+// Which rows of a circuit's public instance column hold which values. Circuits derive their
+// `constrain_instance` rows from this, and verifiers build their instance vectors from the same
+// struct, so the two sides can't drift apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct InstanceLayout {
+    pub leaf: Option<usize>,
+    pub root: Option<usize>,
+    pub nullifier: Option<usize>,
+    pub index: Option<usize>,
+    pub scope: Option<usize>,
+    pub commitment: Option<usize>,
+}
+
+// The values a verifier supplies for the slots of an `InstanceLayout`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublicInputs<F> {
+    pub leaf: Option<F>,
+    pub root: Option<F>,
+    pub nullifier: Option<F>,
+    pub index: Option<F>,
+    pub scope: Option<F>,
+    pub commitment: Option<F>,
+}
+
+impl<F> Default for PublicInputs<F> {
+    fn default() -> Self {
+        Self {
+            leaf: None,
+            root: None,
+            nullifier: None,
+            index: None,
+            scope: None,
+            commitment: None,
+        }
+    }
+}
+
+impl<F> PublicInputs<F> {
+    pub fn membership(leaf: F, root: F) -> Self {
+        Self {
+            leaf: Some(leaf),
+            root: Some(root),
+            ..Self::default()
+        }
+    }
+
+    // Membership of a leaf the verifier only knows a commitment to.
+    pub fn committed(commitment: F, root: F) -> Self {
+        Self {
+            commitment: Some(commitment),
+            root: Some(root),
+            ..Self::default()
+        }
+    }
+}
+
+// How a membership circuit treats the leaf: as a public instance, through a public commitment
+// H(leaf, blinding) with a private blinding factor, or not at all. Only an exposed leaf tells the
+// verifier which member proved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LeafExposure {
+    #[default]
+    Instance,
+    Commitment,
+    Private,
+}
+
+impl InstanceLayout {
+    // The membership layout: the leaf first when it is public, followed by the root.
+    pub fn merkle(expose_leaf: bool, expose_root: bool) -> Self {
+        let leaf = if expose_leaf {
+            LeafExposure::Instance
+        } else {
+            LeafExposure::Private
+        };
+        Self::membership(leaf, expose_root)
+    }
+
+    // As `merkle`, with a committed leaf's commitment taking the leaf's row.
+    pub fn membership(leaf: LeafExposure, expose_root: bool) -> Self {
+        let mut layout = Self::default();
+        match leaf {
+            LeafExposure::Instance => layout.leaf = Some(0),
+            LeafExposure::Commitment => layout.commitment = Some(0),
+            LeafExposure::Private => {}
+        }
+        if expose_root {
+            layout.root = Some(layout.rows());
+        }
+        layout
+    }
+
+    // Appends a nullifier slot after the rows already in use.
+    pub fn with_nullifier(mut self) -> Self {
+        self.nullifier = Some(self.rows());
+        self
+    }
+
+    // Appends a leaf-index slot after the rows already in use.
+    pub fn with_index(mut self) -> Self {
+        self.index = Some(self.rows());
+        self
+    }
+
+    // Appends a slot for the scope (external nullifier) a nullifier is derived for.
+    pub fn with_scope(mut self) -> Self {
+        self.scope = Some(self.rows());
+        self
+    }
+
+    pub(crate) fn slots(&self) -> [(&'static str, Option<usize>); 6] {
+        [
+            ("leaf", self.leaf),
+            ("root", self.root),
+            ("nullifier", self.nullifier),
+            ("index", self.index),
+            ("scope", self.scope),
+            ("commitment", self.commitment),
+        ]
+    }
+
+    pub fn rows(&self) -> usize {
+        self.slots()
+            .iter()
+            .filter_map(|(_, row)| row.map(|row| row + 1))
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn column<F: FieldExt>(&self, inputs: &PublicInputs<F>) -> Result<Vec<F>, Error> {
+        let values = [
+            inputs.leaf,
+            inputs.root,
+            inputs.nullifier,
+            inputs.index,
+            inputs.scope,
+            inputs.commitment,
+        ];
+        let mut column = vec![F::zero(); self.rows()];
+        for ((name, row), value) in self.slots().iter().zip(values.iter()) {
+            if let Some(row) = row {
+                column[*row] = value.ok_or(Error::MissingInstance(name))?;
+            }
+        }
+        Ok(column)
+    }
+
+    // Checks that `instances` has `columns` columns, the first with exactly the layout's rows and
+    // the others, which chips allocate and leave unused, empty. halo2 reports a mismatch as an
+    // opaque InvalidInstances or InstanceTooLarge, or as a failure of an unrelated constraint.
+    pub fn check_shape<F>(&self, columns: usize, instances: &[Vec<F>]) -> Result<(), Error> {
+        if instances.len() != columns {
+            return Err(Error::InstanceColumns {
+                expected: columns,
+                found: instances.len(),
+            });
+        }
+        if let Some(column) = instances.first() {
+            if column.len() != self.rows() {
+                return Err(Error::InstanceRows {
+                    expected: self.rows(),
+                    found: column.len(),
+                });
+            }
+        }
+        if let Some(column) = (1..columns).find(|column| !instances[*column].is_empty()) {
+            return Err(Error::UnusedInstance { column });
+        }
+        Ok(())
+    }
+}
+
+// A circuit that exposes values according to an `InstanceLayout` and can read those values from
+// its own witness, so the instance columns come from the same place as its `constrain_instance`
+// rows rather than being assembled by hand next to it.
+pub trait PublicInstances<F> {
+    fn instance_layout(&self) -> InstanceLayout;
+
+    // The exposed values, None where the witness is unknown (e.g. a keygen circuit).
+    fn public_inputs(&self) -> PublicInputs<F>;
+
+    // The instance columns the circuit allocates. The layout describes the first; chips such as
+    // Poseidon allocate others that stay empty.
+    fn instance_columns(&self) -> usize {
+        1
+    }
+}
+
+pub struct InstanceBuilder;
+
+impl InstanceBuilder {
+    // The instance columns `circuit` is verified against, or MissingInstance if a value it exposes
+    // can't be derived from its witness.
+    pub fn for_circuit<F: FieldExt, C: PublicInstances<F>>(
+        circuit: &C,
+    ) -> Result<Vec<Vec<F>>, Error> {
+        let column = circuit.instance_layout().column(&circuit.public_inputs())?;
+        let mut columns = vec![column];
+        columns.resize(circuit.instance_columns().max(1), vec![]);
+        Ok(columns)
+    }
+
+    // Checks instances assembled elsewhere against the shape `for_circuit` would give, before
+    // they reach MockProver or the prover.
+    pub fn check<F, C: PublicInstances<F>>(circuit: &C, instances: &[Vec<F>]) -> Result<(), Error> {
+        circuit
+            .instance_layout()
+            .check_shape(circuit.instance_columns().max(1), instances)
+    }
+}
+
+pub(crate) fn known<F: Copy>(value: Value<F>) -> Option<F> {
+    let mut known = None;
+    value.map(|value| known = Some(value));
+    known
+}
+
+// The leaf and root of a path hashed with the a + b placeholder of MerkleTreeV1/V2, whose root is
+// the sum of the leaf and siblings whatever the indices.
+#[cfg(feature = "dummy-hash")]
+pub(crate) fn dummy_membership<F: FieldExt>(
+    leaf: Value<F>,
+    elements: &[Value<F>],
+) -> PublicInputs<F> {
+    let leaf = known(leaf);
+    let root = leaf.and_then(|leaf| {
+        elements.iter().try_fold(leaf, |acc, element| {
+            known(*element).map(|element| acc + element)
+        })
+    });
+    PublicInputs {
+        leaf,
+        root,
+        ..PublicInputs::default()
+    }
+}
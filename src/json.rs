@@ -0,0 +1,275 @@
+/*
+JSON interchange for proofs and public inputs, in the shape JS verifiers and block explorers
+expect: field elements are 0x-prefixed big-endian hex strings and byte strings (halo2 proofs) are
+0x-prefixed hex. A path proof looks like
+
+    {
+      "version": 1, "hash": "poseidon-level", "arity": 2, "depth": 2, "root": "0x…",
+      "path": { "leaf": "0x…", "elements": ["0x…", "0x…"], "indices": [1, 0] }
+    }
+
+with `"proof": "0x…"` in place of `"path"` for an opaque halo2 proof. Public inputs are an object
+with the populated slots of `PublicInputs` by name. Parsing applies the same checks as the binary
+wire format, and also rejects unknown keys so that typos don't silently drop a value.
+*/
+
+use crate::chips::merkle::DomainSeparation;
+use crate::error::Error;
+use crate::instance::PublicInputs;
+use crate::serialization::{from_hex, to_hex, CanonicalField};
+use crate::tree::MerklePath;
+use crate::wire::{index_bits, index_from_bit, HashId, MerkleProof, Payload, WIRE_VERSION};
+use serde_json::{json, Map, Value};
+
+fn parse(s: &str) -> Result<Map<String, Value>, Error> {
+    match serde_json::from_str(s) {
+        Ok(Value::Object(object)) => Ok(object),
+        Ok(_) => Err(Error::Malformed("expected a JSON object")),
+        Err(_) => Err(Error::Malformed("invalid JSON")),
+    }
+}
+
+fn check_keys(object: &Map<String, Value>, allowed: &[&str]) -> Result<(), Error> {
+    if object.keys().all(|key| allowed.contains(&key.as_str())) {
+        Ok(())
+    } else {
+        Err(Error::Malformed("unknown key"))
+    }
+}
+
+fn field<F: CanonicalField>(value: &Value) -> Result<F, Error> {
+    from_hex(
+        value
+            .as_str()
+            .ok_or(Error::Malformed("expected a hex string"))?,
+    )
+}
+
+fn bytes_to_hex(bytes: &[u8]) -> String {
+    let digits: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("0x{}", digits)
+}
+
+fn bytes_from_hex(value: &Value) -> Result<Vec<u8>, Error> {
+    let digits = value
+        .as_str()
+        .and_then(|s| s.strip_prefix("0x"))
+        .ok_or(Error::Malformed("expected a hex string"))?;
+    if digits.len() % 2 != 0 || !digits.is_ascii() {
+        return Err(Error::Malformed("odd-length hex string"));
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&digits[i..i + 2], 16).map_err(|_| Error::Malformed("invalid hex"))
+        })
+        .collect()
+}
+
+fn small(object: &Map<String, Value>, key: &'static str) -> Result<u8, Error> {
+    object
+        .get(key)
+        .and_then(Value::as_u64)
+        .and_then(|x| u8::try_from(x).ok())
+        .ok_or(Error::Malformed(key))
+}
+
+impl HashId {
+    pub fn name(self) -> &'static str {
+        match self {
+            HashId::Dummy => "dummy",
+            HashId::Poseidon(DomainSeparation::Legacy) => "poseidon",
+            HashId::Poseidon(DomainSeparation::Level) => "poseidon-level",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self, Error> {
+        match name {
+            "dummy" => Ok(HashId::Dummy),
+            "poseidon" => Ok(HashId::Poseidon(DomainSeparation::Legacy)),
+            "poseidon-level" => Ok(HashId::Poseidon(DomainSeparation::Level)),
+            _ => Err(Error::Malformed("unknown hash id")),
+        }
+    }
+}
+
+impl<F: CanonicalField> MerkleProof<F> {
+    pub fn to_json(&self) -> Result<String, Error> {
+        let mut object = json!({
+            "version": WIRE_VERSION,
+            "hash": self.hash.name(),
+            "arity": self.arity,
+            "depth": self.depth,
+            "root": to_hex(&self.root),
+        });
+        match &self.payload {
+            Payload::Path(path) => {
+                let indices = index_bits(self.depth, path)?;
+                object["path"] = json!({
+                    "leaf": to_hex(&path.leaf),
+                    "elements": path.elements.iter().map(to_hex).collect::<Vec<_>>(),
+                    "indices": indices,
+                });
+            }
+            Payload::Proof(proof) => object["proof"] = json!(bytes_to_hex(proof)),
+        }
+        Ok(object.to_string())
+    }
+
+    pub fn from_json(s: &str) -> Result<Self, Error> {
+        let object = parse(s)?;
+        check_keys(
+            &object,
+            &["version", "hash", "arity", "depth", "root", "path", "proof"],
+        )?;
+        let version = small(&object, "version")?;
+        if version != WIRE_VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        let hash = object
+            .get("hash")
+            .and_then(Value::as_str)
+            .ok_or(Error::Malformed("hash"))
+            .and_then(HashId::from_name)?;
+        let arity = small(&object, "arity")?;
+        let depth = small(&object, "depth")?;
+        let root = field(object.get("root").ok_or(Error::Malformed("root"))?)?;
+
+        let payload = match (object.get("path"), object.get("proof")) {
+            (Some(Value::Object(path)), None) => {
+                check_keys(path, &["leaf", "elements", "indices"])?;
+                let leaf = field(path.get("leaf").ok_or(Error::Malformed("leaf"))?)?;
+                let elements = path
+                    .get("elements")
+                    .and_then(Value::as_array)
+                    .ok_or(Error::Malformed("elements"))?
+                    .iter()
+                    .map(field)
+                    .collect::<Result<Vec<F>, _>>()?;
+                let indices = path
+                    .get("indices")
+                    .and_then(Value::as_array)
+                    .ok_or(Error::Malformed("indices"))?
+                    .iter()
+                    .enumerate()
+                    .map(|(layer, bit)| {
+                        index_from_bit(layer, bit.as_u64().ok_or(Error::InvalidIndex { layer })?)
+                    })
+                    .collect::<Result<Vec<F>, _>>()?;
+                Payload::Path(MerklePath::try_new(arity, depth, leaf, elements, indices)?)
+            }
+            (None, Some(proof)) => Payload::Proof(bytes_from_hex(proof)?),
+            _ => return Err(Error::Malformed("expected exactly one of path and proof")),
+        };
+
+        Ok(Self {
+            hash,
+            arity,
+            depth,
+            root,
+            payload,
+        })
+    }
+}
+
+impl<F: CanonicalField> PublicInputs<F> {
+    fn named(&self) -> [(&'static str, &Option<F>); 6] {
+        [
+            ("leaf", &self.leaf),
+            ("root", &self.root),
+            ("nullifier", &self.nullifier),
+            ("index", &self.index),
+            ("scope", &self.scope),
+            ("commitment", &self.commitment),
+        ]
+    }
+
+    // The populated slots, e.g. {"leaf": "0x…", "root": "0x…"}.
+    pub fn to_json(&self) -> String {
+        let object: Map<String, Value> = self
+            .named()
+            .iter()
+            .filter_map(|(name, value)| value.map(|x| (name.to_string(), json!(to_hex(&x)))))
+            .collect();
+        Value::Object(object).to_string()
+    }
+
+    pub fn from_json(s: &str) -> Result<Self, Error> {
+        let object = parse(s)?;
+        check_keys(
+            &object,
+            &["leaf", "root", "nullifier", "index", "scope", "commitment"],
+        )?;
+        let get = |name: &str| object.get(name).map(field::<F>).transpose();
+        Ok(Self {
+            leaf: get("leaf")?,
+            root: get("root")?,
+            nullifier: get("nullifier")?,
+            index: get("index")?,
+            scope: get("scope")?,
+            commitment: get("commitment")?,
+        })
+    }
+}
+
+mod tests {
+    use crate::chips::merkle::DomainSeparation;
+    use crate::instance::PublicInputs;
+    use crate::tree::MerkleTree;
+    use crate::wire::{HashId, MerkleProof, Payload};
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let inputs = PublicInputs::membership(Fp::from(0x0102), -Fp::one());
+        let json = inputs.to_json();
+        assert_eq!(
+            json,
+            format!(
+                "{{\"leaf\":\"0x{}0102\",\"root\":\"0x40000000000000000000000000000000224698fc094cf91b992d30ed00000000\"}}",
+                "0".repeat(60)
+            )
+        );
+        assert_eq!(PublicInputs::<Fp>::from_json(&json).unwrap(), inputs);
+        assert!(PublicInputs::<Fp>::from_json("{\"lief\":\"0x1\"}").is_err());
+        assert!(PublicInputs::<Fp>::from_json("{\"leaf\":1}").is_err());
+
+        let separation = DomainSeparation::Level;
+        let leaves: Vec<Fp> = (0..3u64).map(Fp::from).collect();
+        let tree = MerkleTree::new(2, &leaves, separation).unwrap();
+        let proof = MerkleProof {
+            hash: HashId::Poseidon(separation),
+            arity: 2,
+            depth: 2,
+            root: tree.root(),
+            payload: Payload::Path(tree.path(2).unwrap()),
+        };
+        let json = proof.to_json().unwrap();
+        assert_eq!(MerkleProof::<Fp>::from_json(&json).unwrap(), proof);
+
+        let opaque = MerkleProof::<Fp> {
+            payload: Payload::Proof(vec![0xab, 0x01]),
+            ..proof.clone()
+        };
+        let json = opaque.to_json().unwrap();
+        assert!(json.contains("\"proof\":\"0xab01\""));
+        assert_eq!(MerkleProof::<Fp>::from_json(&json).unwrap(), opaque);
+
+        for bad in [
+            json.replace("\"version\":1", "\"version\":2"),
+            json.replace("poseidon-level", "sha256"),
+            json.replace("0xab01", "0xab0"),
+            json.replace("\"proof\"", "\"proofs\""),
+            proof
+                .to_json()
+                .unwrap()
+                .replace("\"indices\":[0,1]", "\"indices\":[0,2]"),
+            proof
+                .to_json()
+                .unwrap()
+                .replace("\"depth\":2", "\"depth\":3"),
+        ] {
+            assert!(MerkleProof::<Fp>::from_json(&bad).is_err(), "{}", bad);
+        }
+    }
+}
@@ -1,2 +1,92 @@
+pub mod analysis;
+#[cfg(feature = "unstable-t5")]
+pub mod arity;
+#[cfg(feature = "arkworks")]
+pub mod arkworks;
+#[cfg(feature = "poseidon")]
+pub mod base64;
+#[cfg(feature = "bitcoin")]
+pub mod bitcoin;
+#[cfg(all(feature = "borsh", feature = "poseidon"))]
+pub mod borsh;
+#[cfg(all(feature = "poseidon", feature = "dummy-hash"))]
+pub mod builder;
+pub mod calldata;
 pub mod chips;
 pub mod circuits;
+#[cfg(feature = "poseidon")]
+pub mod compact;
+#[cfg(all(feature = "semaphore", feature = "json"))]
+pub mod compat;
+#[cfg(feature = "poseidon")]
+pub mod concurrent;
+#[cfg(all(feature = "poseidon", feature = "dummy-hash"))]
+pub mod config;
+pub mod coverage;
+pub mod diagnostics;
+#[cfg(feature = "eip712")]
+pub mod eip712;
+pub mod encoding;
+pub mod error;
+#[cfg(all(feature = "poseidon", feature = "dummy-hash"))]
+pub mod estimate;
+#[cfg(all(feature = "poseidon", feature = "dummy-hash"))]
+pub mod fixtures;
+#[cfg(feature = "goldilocks")]
+pub mod goldilocks;
+#[cfg(feature = "ics23")]
+pub mod ics23;
+#[cfg(feature = "poseidon")]
+pub mod indexed;
+pub mod instance;
+#[cfg(all(feature = "json", feature = "poseidon"))]
+pub mod json;
+#[cfg(all(feature = "poseidon", feature = "dummy-hash"))]
+pub mod membership;
+#[cfg(feature = "poseidon")]
+pub mod multiproof;
+#[cfg(feature = "poseidon")]
+pub mod nmt;
+#[cfg(feature = "ethers")]
+pub mod onchain;
+#[cfg(all(feature = "poseidon", feature = "dummy-hash"))]
+pub mod planner;
+pub mod prelude;
+#[cfg(all(feature = "proto", feature = "poseidon"))]
+pub mod proto;
+pub mod proving;
+pub mod report;
+#[cfg(feature = "semaphore")]
+pub mod semaphore;
+pub mod serialization;
+#[cfg(feature = "poseidon")]
+pub mod smt;
+pub mod snapshot;
+pub mod solidity;
+#[cfg(feature = "poseidon")]
+pub mod ssz;
+pub mod tamper;
+#[cfg(all(
+    feature = "poseidon",
+    feature = "dummy-hash",
+    any(test, feature = "test-utils")
+))]
+pub mod testing;
+#[cfg(feature = "tornado")]
+pub mod tornado;
+#[cfg(feature = "tracing")]
+pub mod trace;
+#[cfg(feature = "poseidon")]
+pub mod tree;
+#[cfg(all(feature = "poseidon", feature = "dummy-hash"))]
+pub mod variant;
+#[cfg(feature = "poseidon")]
+pub mod vk_tree;
+#[cfg(feature = "ethers")]
+pub mod watcher;
+#[cfg(feature = "poseidon")]
+pub mod wire;
+#[cfg(all(feature = "poseidon", feature = "dummy-hash"))]
+pub mod witness;
+#[cfg(feature = "zeroize")]
+pub mod zeroize;
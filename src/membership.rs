@@ -0,0 +1,333 @@
+/*
+Membership proofs without touching halo2: hand the prover a native tree and a `ProverConfig`, then
+ask for proofs by leaf index.
+
+    let prover = MembershipProver::new(tree, config)?;
+    let proof = prover.prove(index)?;
+
+`new` validates the config against the tree, loads the parameters and generates the proving key
+once; `prove` builds the path witness and the instance column and runs the prover. A `Proof`
+carries the leaf and root it was made for next to the proof bytes. Proofs are made with `OsRng`;
+`prove_with_rng` takes a seeded RNG for reproducible proofs. `prove_path` proves a path read from
+somewhere else, such as a `ConcurrentTree` snapshot, so the prover's own tree only fixes the shape.
+
+The other side is `MembershipVerifier`, built once from the verifying key, the parameters and the
+instance layout (`MembershipProver::verifier` hands one out) and then called per request:
+
+    verifier.verify(&proof, root, leaf)?;
+
+It tells a proof for different public inputs (`VerifyError::WrongInstances`, e.g. a stale root)
+apart from bytes that don't parse or don't verify, so a service can answer each differently. The
+check against the leaf and root the proof claims is a convenience; verification always uses the
+caller's values.
+*/
+
+use crate::builder::{Hasher, MerkleCircuit};
+use crate::config::ProverConfig;
+use crate::error::Error;
+use crate::instance::{InstanceLayout, PublicInputs};
+use crate::proving::{verify, OsRng, RngCore};
+use crate::tree::{MerklePath, MerkleTree};
+use halo2_proofs::{
+    pasta::{EqAffine, Fp},
+    plonk::{self, ProvingKey, VerifyingKey},
+    poly::commitment::Params,
+};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Proof {
+    pub leaf: Fp,
+    pub root: Fp,
+    pub bytes: Vec<u8>,
+}
+
+pub struct MembershipProver {
+    tree: MerkleTree,
+    config: ProverConfig,
+    params: Params<EqAffine>,
+    pk: ProvingKey<EqAffine>,
+    // The circuit without witnesses, for keygen and the instance layout.
+    shape: MerkleCircuit,
+    layout: InstanceLayout,
+}
+
+impl MembershipProver {
+    // The tree's domain separation overrides the config's, so k is checked for the circuit that
+    // is actually built.
+    pub fn new(tree: MerkleTree, config: ProverConfig) -> Result<Self, Error> {
+        if config.hash != Hasher::Poseidon {
+            return Err(Error::ConfigMismatch(
+                "native trees are hashed with Poseidon",
+            ));
+        }
+        if config.depth != tree.depth() {
+            return Err(Error::DepthMismatch {
+                expected: config.depth,
+                actual: tree.depth(),
+            });
+        }
+        let config = config.with_separation(tree.separation());
+        let params = config.params()?;
+        let (shape, layout) = config.builder().build()?;
+        let pk = shape.keygen(&params)?;
+        Ok(Self {
+            tree,
+            config,
+            params,
+            pk,
+            shape,
+            layout,
+        })
+    }
+
+    pub fn tree(&self) -> &MerkleTree {
+        &self.tree
+    }
+
+    pub fn config(&self) -> &ProverConfig {
+        &self.config
+    }
+
+    pub fn params(&self) -> &Params<EqAffine> {
+        &self.params
+    }
+
+    pub fn verifying_key(&self) -> &VerifyingKey<EqAffine> {
+        self.pk.get_vk()
+    }
+
+    pub fn verifier(&self) -> MembershipVerifier {
+        MembershipVerifier::new(
+            self.verifying_key().clone(),
+            self.params.clone(),
+            self.layout,
+            self.config.hash,
+        )
+    }
+
+    // The instance columns a proof for `leaf` under `root` is verified against.
+    pub fn instances(&self, leaf: Fp, root: Fp) -> Result<Vec<Vec<Fp>>, Error> {
+        self.shape.instances(&PublicInputs::membership(leaf, root))
+    }
+
+    pub fn prove(&self, leaf_index: usize) -> Result<Proof, Error> {
+        self.prove_with_rng(leaf_index, OsRng)
+    }
+
+    pub fn prove_with_rng(&self, leaf_index: usize, rng: impl RngCore) -> Result<Proof, Error> {
+        let path = self.tree.path(leaf_index)?;
+        self.prove_path(path, self.tree.root(), rng)
+    }
+
+    // Proves a path taken elsewhere, e.g. a `ConcurrentTree` snapshot, under the root it leads to.
+    // The path must have the config's depth and the tree's domain separation.
+    pub fn prove_path(
+        &self,
+        path: MerklePath,
+        root: Fp,
+        rng: impl RngCore,
+    ) -> Result<Proof, Error> {
+        if path.root(self.tree.separation()) != root {
+            return Err(Error::RootMismatch);
+        }
+        let (circuit, _) = self
+            .config
+            .builder()
+            .separation(self.tree.separation())
+            .path(&path)
+            .build()?;
+        let instances = self.instances(path.leaf, root)?;
+        let bytes = circuit.prove(&self.params, &self.pk, &instances, rng)?;
+        let leaf = path.leaf;
+        #[cfg(feature = "zeroize")]
+        {
+            let mut path = path;
+            ::zeroize::Zeroize::zeroize(&mut path);
+        }
+        Ok(Proof { leaf, root, bytes })
+    }
+}
+
+#[derive(Debug)]
+pub enum VerifyError {
+    // The proof was made for a different leaf or root than the ones it is checked against.
+    WrongInstances,
+    // The leaf or root the layout needs was not supplied, or the instances don't fit the key.
+    MalformedInstances(Error),
+    // The proof bytes could not be read as a proof for this key.
+    MalformedProof,
+    // The proof is well formed but does not verify against the given leaf and root.
+    InvalidProof,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::WrongInstances => {
+                write!(f, "the proof is for a different leaf or root")
+            }
+            VerifyError::MalformedInstances(err) => write!(f, "malformed instances: {}", err),
+            VerifyError::MalformedProof => write!(f, "the proof bytes are malformed"),
+            VerifyError::InvalidProof => write!(f, "the proof does not verify"),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+pub struct MembershipVerifier {
+    vk: VerifyingKey<EqAffine>,
+    params: Params<EqAffine>,
+    layout: InstanceLayout,
+    // Poseidon circuits have a second, empty instance column.
+    hash: Hasher,
+}
+
+impl MembershipVerifier {
+    pub fn new(
+        vk: VerifyingKey<EqAffine>,
+        params: Params<EqAffine>,
+        layout: InstanceLayout,
+        hash: Hasher,
+    ) -> Self {
+        Self {
+            vk,
+            params,
+            layout,
+            hash,
+        }
+    }
+
+    pub fn layout(&self) -> InstanceLayout {
+        self.layout
+    }
+
+    pub fn instances(&self, leaf: Fp, root: Fp) -> Result<Vec<Vec<Fp>>, Error> {
+        let column = self.layout.column(&PublicInputs::membership(leaf, root))?;
+        Ok(match self.hash {
+            Hasher::Dummy => vec![column],
+            Hasher::Poseidon => vec![column, vec![]],
+        })
+    }
+
+    pub fn verify(&self, proof: &Proof, root: Fp, leaf: Fp) -> Result<(), VerifyError> {
+        let leaf_exposed = self.layout.leaf.is_some();
+        if proof.root != root || (leaf_exposed && proof.leaf != leaf) {
+            return Err(VerifyError::WrongInstances);
+        }
+        let instances = self
+            .instances(leaf, root)
+            .map_err(VerifyError::MalformedInstances)?;
+        verify(&self.params, &self.vk, &proof.bytes, &instances).map_err(|err| match err {
+            Error::Plonk(plonk::Error::Transcript(_)) => VerifyError::MalformedProof,
+            Error::Plonk(plonk::Error::InvalidInstances)
+            | Error::Plonk(plonk::Error::InstanceTooLarge) => VerifyError::MalformedInstances(err),
+            _ => VerifyError::InvalidProof,
+        })
+    }
+}
+
+mod tests {
+    use super::{MembershipProver, VerifyError};
+    use crate::builder::Hasher;
+    use crate::chips::merkle::DomainSeparation;
+    use crate::config::ProverConfig;
+    use crate::error::Error;
+    use crate::proving::{seeded_rng, verify, TEST_SEED};
+    use crate::tree::MerkleTree;
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let leaves: Vec<Fp> = (0..11u64).map(Fp::from).collect();
+        let tree = MerkleTree::new(4, &leaves, DomainSeparation::Legacy).unwrap();
+        let config = ProverConfig::new(10, 4, Hasher::Poseidon);
+        let prover = MembershipProver::new(tree.clone(), config).unwrap();
+
+        let proof = prover.prove(5).unwrap();
+        assert_eq!(proof.leaf, Fp::from(5));
+        assert_eq!(proof.root, tree.root());
+        let instances = prover.instances(proof.leaf, proof.root).unwrap();
+        verify(
+            prover.params(),
+            prover.verifying_key(),
+            &proof.bytes,
+            &instances,
+        )
+        .unwrap();
+        let wrong = prover.instances(Fp::from(6), proof.root).unwrap();
+        assert!(verify(
+            prover.params(),
+            prover.verifying_key(),
+            &proof.bytes,
+            &wrong
+        )
+        .is_err());
+
+        let seeded =
+            |prover: &MembershipProver| prover.prove_with_rng(5, seeded_rng(TEST_SEED)).unwrap();
+        assert_eq!(seeded(&prover), seeded(&prover));
+        let path = tree.path(5).unwrap();
+        assert_eq!(
+            prover
+                .prove_path(path.clone(), tree.root(), seeded_rng(TEST_SEED))
+                .unwrap(),
+            seeded(&prover)
+        );
+        assert!(matches!(
+            prover.prove_path(path, tree.root() + Fp::one(), seeded_rng(TEST_SEED)),
+            Err(Error::RootMismatch)
+        ));
+        assert!(matches!(
+            prover.prove(11),
+            Err(Error::IndexOutOfRange { index: 11, .. })
+        ));
+
+        let verifier = prover.verifier();
+        verifier.verify(&proof, tree.root(), Fp::from(5)).unwrap();
+        assert!(matches!(
+            verifier.verify(&proof, tree.root() + Fp::one(), Fp::from(5)),
+            Err(VerifyError::WrongInstances)
+        ));
+        assert!(matches!(
+            verifier.verify(&proof, tree.root(), Fp::from(6)),
+            Err(VerifyError::WrongInstances)
+        ));
+        let mut forged = proof.clone();
+        forged.leaf = Fp::from(6);
+        assert!(matches!(
+            verifier.verify(&forged, tree.root(), Fp::from(6)),
+            Err(VerifyError::InvalidProof)
+        ));
+        let mut truncated = proof.clone();
+        truncated.bytes.truncate(10);
+        assert!(matches!(
+            verifier.verify(&truncated, tree.root(), Fp::from(5)),
+            Err(VerifyError::MalformedProof)
+        ));
+
+        let dummy = ProverConfig::new(10, 4, Hasher::Dummy);
+        assert!(matches!(
+            MembershipProver::new(tree.clone(), dummy),
+            Err(Error::ConfigMismatch(_))
+        ));
+        // A k too small for the circuit is refused before keygen, naming the k it needs.
+        assert!(matches!(
+            MembershipProver::new(tree.clone(), ProverConfig::new(6, 4, Hasher::Poseidon)),
+            Err(Error::InsufficientK { k: 6, .. })
+        ));
+        // k is checked for the tree's separation, not the config's default.
+        let level = MerkleTree::new(4, &leaves, DomainSeparation::Level).unwrap();
+        let prover = MembershipProver::new(level, ProverConfig::new(10, 4, Hasher::Poseidon));
+        assert_eq!(
+            prover.unwrap().config().circuit_params().separation,
+            DomainSeparation::Level
+        );
+        let deeper = ProverConfig::new(10, 5, Hasher::Poseidon);
+        assert!(matches!(
+            MembershipProver::new(tree, deeper),
+            Err(Error::DepthMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,301 @@
+/*
+Multiproofs: the paths of many leaves of one tree with every node they share stored once. Paths
+of nearby leaves share most of their upper siblings, so a batch of proofs is far smaller than the
+proofs one by one. Each node is stored with its position, and the per-leaf paths are rebuilt by
+looking siblings up by position, without hashing:
+
+    | version: u8 | hash: u8 | arity: u8 | depth: u8 | root: 32 bytes LE |
+    | leaf count: u32 BE | (index: u64 BE, leaf: 32 bytes LE) ... |
+    | node count: u32 BE | (level: u8, position: u64 BE, node: 32 bytes LE) ... |
+
+Level 0 nodes are leaves and level `depth - 1` nodes are the root's children. Leaves and nodes are
+sorted by position with no repeats, and the nodes must be exactly the siblings the leaves need, so
+every multiproof has a single encoding. As with single paths, the root is not checked here; rebuild
+the paths and check them against it.
+*/
+
+use crate::chips::poseidon::PoseidonSpecFor;
+use crate::error::Error;
+use crate::serialization::{to_bytes_le, CanonicalField};
+use crate::tree::{MerklePath, MerkleTree};
+use crate::wire::{index_bits, index_from_bit, HashId, Reader};
+use halo2_proofs::pasta::Fp;
+use std::collections::{BTreeMap, BTreeSet};
+
+pub const MULTIPROOF_VERSION: u8 = 1;
+
+// Positions are u64, so deeper trees are not representable.
+pub const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultiProof<F = Fp> {
+    pub hash: HashId,
+    pub depth: u8,
+    pub root: F,
+    // Leaf index to leaf.
+    pub leaves: BTreeMap<u64, F>,
+    // (level, position) to node.
+    pub nodes: BTreeMap<(u8, u64), F>,
+}
+
+// The (level, position) of every sibling on the paths of the given leaves.
+fn siblings(depth: u8, leaves: impl Iterator<Item = u64>) -> BTreeSet<(u8, u64)> {
+    leaves
+        .flat_map(|index| (0..depth).map(move |level| (level, (index >> level) ^ 1)))
+        .collect()
+}
+
+fn read_u32(reader: &mut Reader) -> Result<usize, Error> {
+    Ok(u32::from_be_bytes(reader.take(4)?.try_into().expect("took 4 bytes")) as usize)
+}
+
+fn read_u64(reader: &mut Reader) -> Result<u64, Error> {
+    Ok(u64::from_be_bytes(
+        reader.take(8)?.try_into().expect("took 8 bytes"),
+    ))
+}
+
+fn count(len: usize) -> Result<[u8; 4], Error> {
+    Ok(u32::try_from(len)
+        .map_err(|_| Error::Malformed("too many entries"))?
+        .to_be_bytes())
+}
+
+impl<F: CanonicalField> MultiProof<F> {
+    // Merges paths of a tree of the given depth. Paths must be of distinct leaves and agree on
+    // every node they share.
+    pub fn new(hash: HashId, depth: u8, root: F, paths: &[MerklePath<F>]) -> Result<Self, Error> {
+        if depth as usize > MAX_DEPTH {
+            return Err(Error::Malformed("tree deeper than 64 layers"));
+        }
+        let mut leaves = BTreeMap::new();
+        let mut nodes = BTreeMap::new();
+        for path in paths {
+            let index = index_bits(depth, path)?
+                .iter()
+                .enumerate()
+                .fold(0u64, |acc, (layer, bit)| acc | (*bit as u64) << layer);
+            if leaves.insert(index, path.leaf).is_some() {
+                return Err(Error::DuplicateLeaf {
+                    index: index as usize,
+                });
+            }
+            for (level, element) in path.elements.iter().enumerate() {
+                let position = (index >> level) ^ 1;
+                if *nodes.entry((level as u8, position)).or_insert(*element) != *element {
+                    return Err(Error::Malformed("paths disagree on a shared node"));
+                }
+            }
+        }
+        Ok(Self {
+            hash,
+            depth,
+            root,
+            leaves,
+            nodes,
+        })
+    }
+
+    // The path of every leaf, in order of leaf index.
+    pub fn paths(&self) -> Result<Vec<MerklePath<F>>, Error> {
+        self.leaves
+            .iter()
+            .map(|(index, leaf)| {
+                let elements = (0..self.depth)
+                    .map(|level| {
+                        self.nodes
+                            .get(&(level, (index >> level) ^ 1))
+                            .copied()
+                            .ok_or(Error::Malformed("missing node"))
+                    })
+                    .collect::<Result<Vec<F>, _>>()?;
+                let indices = (0..self.depth as usize)
+                    .map(|layer| index_from_bit(layer, (index >> layer) & 1))
+                    .collect::<Result<Vec<F>, _>>()?;
+                Ok(MerklePath {
+                    leaf: *leaf,
+                    elements,
+                    indices,
+                })
+            })
+            .collect()
+    }
+
+    fn check_shape(&self) -> Result<(), Error> {
+        if self.depth as usize > MAX_DEPTH {
+            return Err(Error::Malformed("tree deeper than 64 layers"));
+        }
+        if (self.depth as usize) < MAX_DEPTH
+            && self.leaves.keys().any(|index| index >> self.depth != 0)
+        {
+            return Err(Error::Malformed("leaf index beyond the tree"));
+        }
+        if !self
+            .nodes
+            .keys()
+            .copied()
+            .eq(siblings(self.depth, self.leaves.keys().copied()))
+        {
+            return Err(Error::Malformed("nodes are not the siblings of the leaves"));
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        self.check_shape()?;
+        let mut bytes = vec![MULTIPROOF_VERSION, self.hash.to_byte(), 2, self.depth];
+        bytes.extend_from_slice(&to_bytes_le(&self.root));
+        bytes.extend_from_slice(&count(self.leaves.len())?);
+        for (index, leaf) in &self.leaves {
+            bytes.extend_from_slice(&index.to_be_bytes());
+            bytes.extend_from_slice(&to_bytes_le(leaf));
+        }
+        bytes.extend_from_slice(&count(self.nodes.len())?);
+        for ((level, position), node) in &self.nodes {
+            bytes.push(*level);
+            bytes.extend_from_slice(&position.to_be_bytes());
+            bytes.extend_from_slice(&to_bytes_le(node));
+        }
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader(bytes);
+        let version = reader.byte()?;
+        if version != MULTIPROOF_VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        let hash = HashId::from_byte(reader.byte()?)?;
+        let arity = reader.byte()?;
+        if arity != 2 {
+            return Err(Error::UnsupportedArity(arity as usize));
+        }
+        let depth = reader.byte()?;
+        let root = reader.field()?;
+
+        let mut leaves = BTreeMap::new();
+        for _ in 0..read_u32(&mut reader)? {
+            let index = read_u64(&mut reader)?;
+            if leaves
+                .last_key_value()
+                .is_some_and(|(last, _)| *last >= index)
+            {
+                return Err(Error::Malformed("leaves out of order"));
+            }
+            leaves.insert(index, reader.field()?);
+        }
+        let mut nodes = BTreeMap::new();
+        for _ in 0..read_u32(&mut reader)? {
+            let key = (reader.byte()?, read_u64(&mut reader)?);
+            if nodes.last_key_value().is_some_and(|(last, _)| *last >= key) {
+                return Err(Error::Malformed("nodes out of order"));
+            }
+            nodes.insert(key, reader.field()?);
+        }
+        if !reader.0.is_empty() {
+            return Err(Error::Malformed("trailing bytes"));
+        }
+
+        let proof = Self {
+            hash,
+            depth,
+            root,
+            leaves,
+            nodes,
+        };
+        proof.check_shape()?;
+        Ok(proof)
+    }
+}
+
+impl<F: PoseidonSpecFor + CanonicalField> MerkleTree<F> {
+    pub fn multiproof(&self, indices: &[usize]) -> Result<MultiProof<F>, Error> {
+        let depth = u8::try_from(self.depth())
+            .map_err(|_| Error::Malformed("tree deeper than 64 layers"))?;
+        let paths = indices
+            .iter()
+            .map(|index| self.path(*index))
+            .collect::<Result<Vec<_>, _>>()?;
+        MultiProof::new(
+            HashId::Poseidon(self.separation()),
+            depth,
+            self.root(),
+            &paths,
+        )
+    }
+}
+
+mod tests {
+    use super::MultiProof;
+    use crate::chips::merkle::DomainSeparation;
+    use crate::tree::MerkleTree;
+    use crate::wire::{HashId, MerkleProof, Payload};
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let separation = DomainSeparation::Level;
+        let leaves: Vec<Fp> = (0..11u64).map(Fp::from).collect();
+        let tree = MerkleTree::new(4, &leaves, separation).unwrap();
+        let indices = [8, 0, 1, 2, 3];
+        let proof = tree.multiproof(&indices).unwrap();
+        // Siblings at levels 0 to 3: {0, 1, 2, 3, 9}, {0, 1, 5}, {1, 3} and {0, 1}.
+        assert_eq!(proof.nodes.len(), 12);
+        let paths = proof.paths().unwrap();
+        for (path, index) in paths.iter().zip([0, 1, 2, 3, 8]) {
+            assert_eq!(*path, tree.path(index).unwrap());
+            assert_eq!(path.root(separation), proof.root);
+        }
+
+        let bytes = proof.to_bytes().unwrap();
+        assert_eq!(bytes.len(), 36 + 4 + 5 * 40 + 4 + 12 * 41);
+        assert_eq!(MultiProof::<Fp>::from_bytes(&bytes).unwrap(), proof);
+        let single: usize = indices
+            .iter()
+            .map(|index| {
+                MerkleProof {
+                    hash: HashId::Poseidon(separation),
+                    arity: 2,
+                    depth: 4,
+                    root: tree.root(),
+                    payload: Payload::Path(tree.path(*index).unwrap()),
+                }
+                .to_bytes()
+                .unwrap()
+                .len()
+            })
+            .sum();
+        assert!(bytes.len() < single);
+
+        assert!(tree.multiproof(&[3, 3]).is_err());
+        let mut other = tree.clone();
+        other.update(10, Fp::from(99)).unwrap();
+        // Leaf 10 is under the sibling (3, 1) of leaf 1 but under no sibling of leaf 8.
+        let mixed = [tree.path(0).unwrap(), other.path(8).unwrap()];
+        assert!(MultiProof::new(HashId::Poseidon(separation), 4, tree.root(), &mixed).is_ok());
+        let mixed = [tree.path(0).unwrap(), other.path(1).unwrap()];
+        assert!(MultiProof::new(HashId::Poseidon(separation), 4, tree.root(), &mixed).is_err());
+
+        let corrupt = |offset: usize, value: u8| {
+            let mut bytes = bytes.clone();
+            bytes[offset] = value;
+            MultiProof::<Fp>::from_bytes(&bytes)
+        };
+        assert!(corrupt(0, 2).is_err());
+        assert!(corrupt(2, 4).is_err());
+        assert!(corrupt(3, 5).is_err());
+        // Leaf 0 moved after leaf 1, leaf 8 moved beyond the tree and a node's position changed.
+        assert!(corrupt(40 + 7, 2).is_err());
+        assert!(corrupt(40 + 4 * 40 + 7, 16).is_err());
+        assert!(corrupt(244 + 1 + 7, 7).is_err());
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(MultiProof::<Fp>::from_bytes(&trailing).is_err());
+
+        let mut missing = proof;
+        missing.nodes.remove(&(2, 3));
+        assert!(missing.to_bytes().is_err());
+        assert!(missing.paths().is_err());
+    }
+}
@@ -0,0 +1,207 @@
+/*
+A Celestia-style namespaced Merkle tree over Poseidon. Every node carries the smallest and largest
+namespace below it next to its digest, and children must be in namespace order (left.max <=
+right.min), so a proof of a leaf also shows that no leaf of its namespace can hide elsewhere under
+the root.
+
+    leaf digest  = H(data)
+    commitment   = H(min, max, digest)
+    inner digest = H(commitment(left), commitment(right))
+
+with each H a Poseidon ConstantLength of its own arity, so the three never collide. Namespaces are
+u64 rather than Celestia's 29 bytes so the ordering fits one range check in-circuit, and digests
+are Poseidon rather than SHA-256; the shape of the proofs is Celestia's. Unused positions are
+filled with `PADDING_NAMESPACE` leaves, which sort last.
+*/
+
+use crate::error::Error;
+use halo2_gadgets::poseidon::primitives::{self as poseidon, ConstantLength, P128Pow5T3};
+use halo2_proofs::pasta::Fp;
+use std::ops::Range;
+
+pub const PADDING_NAMESPACE: u64 = u64::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NmtNode {
+    pub min: u64,
+    pub max: u64,
+    pub digest: Fp,
+}
+
+impl NmtNode {
+    pub fn leaf(namespace: u64, data: Fp) -> Self {
+        Self {
+            min: namespace,
+            max: namespace,
+            digest: poseidon::Hash::<_, P128Pow5T3, ConstantLength<1>, 3, 2>::init().hash([data]),
+        }
+    }
+
+    pub fn commitment(&self) -> Fp {
+        poseidon::Hash::<_, P128Pow5T3, ConstantLength<3>, 3, 2>::init().hash([
+            Fp::from(self.min),
+            Fp::from(self.max),
+            self.digest,
+        ])
+    }
+
+    pub fn parent(left: &Self, right: &Self) -> Result<Self, Error> {
+        if left.min > left.max || right.min > right.max || left.max > right.min {
+            return Err(Error::Malformed("nodes are out of namespace order"));
+        }
+        Ok(Self {
+            min: left.min,
+            max: right.max,
+            digest: poseidon::Hash::<_, P128Pow5T3, ConstantLength<2>, 3, 2>::init()
+                .hash([left.commitment(), right.commitment()]),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NmtTree {
+    // layers[0] holds the (padded) leaves and the last layer the root.
+    layers: Vec<Vec<NmtNode>>,
+    len: usize,
+}
+
+impl NmtTree {
+    // `leaves` are (namespace, data) pairs, sorted by namespace.
+    pub fn new(depth: usize, leaves: &[(u64, Fp)]) -> Result<Self, Error> {
+        let capacity = 1usize << depth;
+        if leaves.len() > capacity {
+            return Err(Error::TooManyLeaves {
+                capacity,
+                leaves: leaves.len(),
+            });
+        }
+        if leaves.iter().any(|(ns, _)| *ns == PADDING_NAMESPACE) {
+            return Err(Error::Malformed("the padding namespace is reserved"));
+        }
+        let mut layer: Vec<NmtNode> = leaves
+            .iter()
+            .map(|(ns, data)| NmtNode::leaf(*ns, *data))
+            .collect();
+        layer.resize(capacity, NmtNode::leaf(PADDING_NAMESPACE, Fp::zero()));
+        let mut layers = vec![layer];
+        for level in 0..depth {
+            let next = layers[level]
+                .chunks(2)
+                .map(|pair| NmtNode::parent(&pair[0], &pair[1]))
+                .collect::<Result<Vec<_>, _>>()?;
+            layers.push(next);
+        }
+        Ok(Self {
+            layers,
+            len: leaves.len(),
+        })
+    }
+
+    pub fn depth(&self) -> usize {
+        self.layers.len() - 1
+    }
+
+    pub fn root(&self) -> NmtNode {
+        self.layers[self.depth()][0]
+    }
+
+    // The positions of the leaves in `namespace`; empty, at where they would go, if there are none.
+    pub fn namespace_range(&self, namespace: u64) -> Range<usize> {
+        let leaves = &self.layers[0][..self.len];
+        let start = leaves.partition_point(|leaf| leaf.min < namespace);
+        let end = leaves.partition_point(|leaf| leaf.min <= namespace);
+        start..end
+    }
+
+    pub fn proof(&self, index: usize, data: Fp) -> Result<NmtProof, Error> {
+        if index >= self.len {
+            return Err(Error::IndexOutOfRange {
+                index,
+                leaves: self.len,
+            });
+        }
+        let leaf = self.layers[0][index];
+        if leaf != NmtNode::leaf(leaf.min, data) {
+            return Err(Error::LeafMismatch);
+        }
+        let siblings = (0..self.depth())
+            .map(|level| self.layers[level][(index >> level) ^ 1])
+            .collect();
+        Ok(NmtProof {
+            namespace: leaf.min,
+            data,
+            index: index as u64,
+            siblings,
+        })
+    }
+}
+
+// Inclusion of `data` under `namespace` at `index`, siblings leaf layer first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NmtProof {
+    pub namespace: u64,
+    pub data: Fp,
+    pub index: u64,
+    pub siblings: Vec<NmtNode>,
+}
+
+impl NmtProof {
+    pub fn root(&self) -> Result<NmtNode, Error> {
+        self.siblings.iter().enumerate().try_fold(
+            NmtNode::leaf(self.namespace, self.data),
+            |node, (level, sibling)| {
+                if (self.index >> level) & 1 == 0 {
+                    NmtNode::parent(&node, sibling)
+                } else {
+                    NmtNode::parent(sibling, &node)
+                }
+            },
+        )
+    }
+
+    pub fn verify(&self, root: &NmtNode) -> Result<(), Error> {
+        if self.root()? != *root {
+            return Err(Error::RootMismatch);
+        }
+        Ok(())
+    }
+}
+
+mod tests {
+    use super::{NmtNode, NmtTree, PADDING_NAMESPACE};
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let leaves: Vec<(u64, Fp)> = [(1, 10), (3, 11), (3, 12), (7, 13), (9, 14)]
+            .iter()
+            .map(|(ns, data)| (*ns, Fp::from(*data)))
+            .collect();
+        let tree = NmtTree::new(3, &leaves).unwrap();
+        let root = tree.root();
+        assert_eq!((root.min, root.max), (1, PADDING_NAMESPACE));
+        for (index, (_, data)) in leaves.iter().enumerate() {
+            let proof = tree.proof(index, *data).unwrap();
+            assert!(proof.verify(&root).is_ok());
+        }
+        assert_eq!(tree.namespace_range(3), 1..3);
+        assert_eq!(tree.namespace_range(4), 3..3);
+
+        // Claiming another namespace for a leaf changes the root.
+        let mut wrong = tree.proof(1, leaves[1].1).unwrap();
+        wrong.namespace = 4;
+        assert!(wrong.verify(&root).is_err());
+        assert!(tree.proof(1, Fp::from(99)).is_err());
+        assert!(tree.proof(5, Fp::zero()).is_err());
+
+        // Unsorted leaves, the reserved namespace and overfull trees are rejected.
+        let mut unsorted = leaves.clone();
+        unsorted.swap(0, 1);
+        assert!(NmtTree::new(3, &unsorted).is_err());
+        assert!(NmtTree::new(3, &[(PADDING_NAMESPACE, Fp::one())]).is_err());
+        assert!(NmtTree::new(2, &leaves).is_err());
+        assert!(
+            NmtNode::parent(&NmtNode::leaf(2, Fp::one()), &NmtNode::leaf(1, Fp::one())).is_err()
+        );
+    }
+}
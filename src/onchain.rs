@@ -0,0 +1,216 @@
+/*
+The airdrop/allowlist pipeline over ethers-rs: collect one 32-byte word from each log an event
+filter matches (an indexed topic or a data word), encode the words as leaves, build the tree, and
+compare its root with the one a contract stores.
+
+Roots are exchanged with contracts as bytes32 holding their big-endian value, as in `eip712`.
+Providers cap the block range of one eth_getLogs call, so a large history should be fetched with
+several filters over consecutive ranges and the leaves concatenated.
+*/
+
+use crate::chips::merkle::DomainSeparation;
+use crate::encoding::{encode_address, ToLeaf};
+use crate::error::Error;
+use crate::serialization::from_bytes_be_checked;
+use crate::tree::MerkleTree;
+use ethers::providers::Middleware;
+use ethers::types::{Address, Filter, Log, TransactionRequest};
+use ethers::utils::id;
+use halo2_proofs::pasta::Fp;
+use std::collections::HashSet;
+
+// Where in a log the leaf's word is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogField {
+    // An indexed parameter; topic 0 is the event signature, so the first one is Topic(1).
+    Topic(usize),
+    // The i-th 32-byte word of the non-indexed data.
+    Data(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeafKind {
+    // The low 20 bytes of the word, encoded with `encode_address`.
+    Address,
+    // The whole word, as the `ToLeaf` hash leaf. Log words are often keccak digests, most of
+    // which are at or above p, so the word is split in two rather than read as one element.
+    Word,
+}
+
+#[derive(Debug, Clone)]
+pub struct LeafQuery {
+    pub filter: Filter,
+    pub field: LogField,
+    pub kind: LeafKind,
+    // Keep only the first occurrence of each word, e.g. for an address that claimed twice.
+    pub dedup: bool,
+}
+
+fn rpc<E: std::fmt::Display>(err: E) -> Error {
+    Error::Rpc(err.to_string())
+}
+
+pub fn log_word(log: &Log, field: LogField) -> Result<[u8; 32], Error> {
+    match field {
+        LogField::Topic(i) => log
+            .topics
+            .get(i)
+            .map(|topic| topic.0)
+            .ok_or(Error::Malformed("log has no such topic")),
+        LogField::Data(i) => log
+            .data
+            .get(32 * i..32 * (i + 1))
+            .map(|word| word.try_into().expect("words are 32 bytes"))
+            .ok_or(Error::Malformed("log data has no such word")),
+    }
+}
+
+pub fn encode_word(word: &[u8; 32], kind: LeafKind) -> Fp {
+    match kind {
+        LeafKind::Address => encode_address(word[12..].try_into().expect("addresses are 20 bytes")),
+        LeafKind::Word => word.to_leaf(),
+    }
+}
+
+impl LeafQuery {
+    // The leaves of already fetched logs, in log order. Logs removed by a reorg are skipped.
+    pub fn leaves(&self, logs: &[Log]) -> Result<Vec<Fp>, Error> {
+        let mut seen = HashSet::new();
+        let mut leaves = Vec::new();
+        for log in logs.iter().filter(|log| log.removed != Some(true)) {
+            let word = log_word(log, self.field)?;
+            if !self.dedup || seen.insert(word) {
+                leaves.push(encode_word(&word, self.kind));
+            }
+        }
+        Ok(leaves)
+    }
+
+    pub async fn fetch<M: Middleware>(&self, client: &M) -> Result<Vec<Fp>, Error> {
+        let logs = client.get_logs(&self.filter).await.map_err(rpc)?;
+        self.leaves(&logs)
+    }
+}
+
+pub async fn build_tree<M: Middleware>(
+    client: &M,
+    query: &LeafQuery,
+    depth: usize,
+    separation: DomainSeparation,
+) -> Result<MerkleTree, Error> {
+    MerkleTree::new(depth, &query.fetch(client).await?, separation)
+}
+
+// Calls a no-argument view returning bytes32, such as "merkleRoot()", and reads it as a root.
+pub async fn stored_root<M: Middleware>(
+    client: &M,
+    contract: Address,
+    getter: &str,
+) -> Result<Fp, Error> {
+    let call = TransactionRequest::new()
+        .to(contract)
+        .data(id(getter).to_vec());
+    let output = client.call(&call.into(), None).await.map_err(rpc)?;
+    let word: &[u8; 32] = output
+        .as_ref()
+        .try_into()
+        .map_err(|_| Error::Malformed("getter did not return one word"))?;
+    from_bytes_be_checked(word)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reconciliation {
+    Match,
+    Mismatch { local: Fp, onchain: Fp },
+}
+
+pub async fn reconcile<M: Middleware>(
+    client: &M,
+    tree: &MerkleTree,
+    contract: Address,
+    getter: &str,
+) -> Result<Reconciliation, Error> {
+    let onchain = stored_root(client, contract, getter).await?;
+    Ok(if onchain == tree.root() {
+        Reconciliation::Match
+    } else {
+        Reconciliation::Mismatch {
+            local: tree.root(),
+            onchain,
+        }
+    })
+}
+
+mod tests {
+    use super::{build_tree, reconcile, LeafKind, LeafQuery, LogField, Reconciliation};
+    use crate::chips::merkle::DomainSeparation;
+    use crate::encoding::encode_address;
+    use crate::serialization::to_bytes_be;
+    use ethers::providers::Provider;
+    use ethers::types::{Address, Bytes, Filter, Log, H256};
+    use futures::executor::block_on;
+
+    fn transfer(to: u8, removed: bool) -> Log {
+        let mut recipient = H256::zero();
+        recipient.0[31] = to;
+        Log {
+            topics: vec![H256::repeat_byte(0xdd), H256::zero(), recipient],
+            data: Bytes::from(vec![0; 32]),
+            removed: Some(removed),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test() {
+        let query = LeafQuery {
+            filter: Filter::new().event("Transfer(address,address,uint256)"),
+            field: LogField::Topic(2),
+            kind: LeafKind::Address,
+            dedup: true,
+        };
+        let logs = vec![
+            transfer(1, false),
+            transfer(2, false),
+            transfer(1, false),
+            transfer(3, true),
+        ];
+        let address = |byte: u8| {
+            let mut address = [0u8; 20];
+            address[19] = byte;
+            encode_address(&address)
+        };
+        assert_eq!(query.leaves(&logs).unwrap(), vec![address(1), address(2)]);
+        let all = LeafQuery {
+            dedup: false,
+            ..query.clone()
+        };
+        assert_eq!(all.leaves(&logs).unwrap().len(), 3);
+        let missing = LeafQuery {
+            field: LogField::Data(1),
+            ..query.clone()
+        };
+        assert!(missing.leaves(&logs).is_err());
+
+        // The mock provider answers requests with the responses pushed to it.
+        let (provider, mock) = Provider::mocked();
+        mock.push::<Vec<Log>, _>(logs).unwrap();
+        let tree = block_on(build_tree(&provider, &query, 2, DomainSeparation::Level)).unwrap();
+        assert_eq!(tree.leaves(), &[address(1), address(2)]);
+
+        let contract = Address::repeat_byte(0xcc);
+        mock.push::<Bytes, _>(Bytes::from(to_bytes_be(&tree.root()).to_vec()))
+            .unwrap();
+        assert_eq!(
+            block_on(reconcile(&provider, &tree, contract, "merkleRoot()")).unwrap(),
+            Reconciliation::Match
+        );
+        mock.push::<Bytes, _>(Bytes::from(vec![0; 32])).unwrap();
+        assert!(matches!(
+            block_on(reconcile(&provider, &tree, contract, "merkleRoot()")).unwrap(),
+            Reconciliation::Mismatch { .. }
+        ));
+        mock.push::<Bytes, _>(Bytes::from(vec![0; 31])).unwrap();
+        assert!(block_on(reconcile(&provider, &tree, contract, "merkleRoot()")).is_err());
+    }
+}
@@ -0,0 +1,107 @@
+/*
+Floor planner choice for the membership circuits. A Merkle path is laid out as many small regions,
+a Poseidon permutation and a swap per layer, and `SimpleFloorPlanner` places each one after the
+last region using any of its columns, so the rows a narrow region leaves free in the other columns
+stay empty. `Packed(circuit)` is the same circuit laid out with halo2's V1 planner, which measures
+every region first and then slots them into free space column by column:
+
+    let (circuit, _) = MerkleCircuitBuilder::new().depth(32).planner(Planner::V1).build()?;
+
+Only the placement changes, not the gates, so `analyze(&circuit).rows` and `min_k` compare the two
+directly. Keys depend on the placement, so a verifying key is tied to the planner it was made with.
+
+For the Legacy membership path the permutations set the height under both planners: each takes 37
+rows of the Poseidon columns, one per round row (4 full, 28 paired partial, 4 full) plus the output,
+and the two-row swaps sit beside them in the Merkle columns either way. Simple puts the one-row
+initial state first and V1 puts it last, so a depth-8 path is 8 * 37 + 1 = 297 rows with both. V1
+only saves rows where regions of different columns would otherwise wait on each other.
+*/
+
+use halo2_proofs::{
+    arithmetic::Field,
+    circuit::{floor_planner::V1, Layouter},
+    plonk::{Circuit, ConstraintSystem, Error},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Planner {
+    // halo2's SimpleFloorPlanner, which each circuit declares.
+    #[default]
+    Simple,
+    // halo2's V1 floor planner, through `Packed`.
+    V1,
+}
+
+// Any circuit, laid out with the V1 floor planner.
+pub struct Packed<C>(pub C);
+
+impl<F: Field, C: Circuit<F>> Circuit<F> for Packed<C> {
+    type Config = C::Config;
+    type FloorPlanner = V1;
+
+    fn without_witnesses(&self) -> Self {
+        Packed(self.0.without_witnesses())
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        C::configure(meta)
+    }
+
+    fn synthesize(&self, config: Self::Config, layouter: impl Layouter<F>) -> Result<(), Error> {
+        self.0.synthesize(config, layouter)
+    }
+}
+
+mod tests {
+    use super::Planner;
+    use crate::analysis::analyze;
+    use crate::builder::{MerkleCircuit, MerkleCircuitBuilder};
+    use crate::chips::merkle::DomainSeparation;
+    use crate::coverage::assert_mock_satisfied;
+    use crate::estimate::min_k;
+    use crate::instance::PublicInputs;
+    use crate::tree::MerkleTree;
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let leaves: Vec<Fp> = (0..20u64).map(Fp::from).collect();
+        let tree = MerkleTree::new(8, &leaves, DomainSeparation::Legacy).unwrap();
+        let path = tree.path(13).unwrap();
+        let inputs = PublicInputs::membership(path.leaf, tree.root());
+        let build = |planner: Planner| {
+            MerkleCircuitBuilder::new()
+                .depth(8)
+                .planner(planner)
+                .path(&path)
+                .build()
+                .unwrap()
+                .0
+        };
+
+        let simple = build(Planner::Simple);
+        let packed = build(Planner::V1);
+        let instances = simple.instances(&inputs).unwrap();
+        assert_eq!(packed.instances(&inputs).unwrap(), instances);
+        let (simple, packed) = match (simple, packed) {
+            (MerkleCircuit::Poseidon(simple), MerkleCircuit::PackedPoseidon(packed)) => {
+                (simple, packed)
+            }
+            _ => unreachable!(),
+        };
+
+        // Same cells, placed in the same 8 * 37 + 1 rows; see the note above.
+        let (simple_report, packed_report) = (analyze(&simple).unwrap(), analyze(&packed).unwrap());
+        assert_eq!(simple_report.rows, 297);
+        assert_eq!(packed_report.rows, 297);
+        assert_eq!(
+            packed_report.unconstrained.len(),
+            simple_report.unconstrained.len()
+        );
+        assert_eq!(packed_report.selectors, simple_report.selectors);
+
+        let k = min_k(&packed, instances.clone()).unwrap();
+        assert!(k <= min_k(&simple, instances.clone()).unwrap());
+        assert_mock_satisfied(k, &packed, instances);
+    }
+}
@@ -0,0 +1,162 @@
+// The types most downstream users need, importable with `use halo2_merkle_tree::prelude::*`.
+
+pub use crate::analysis::{analyze, AnalysisReport, UnconstrainedCell};
+#[cfg(all(feature = "poseidon", feature = "dummy-hash"))]
+pub use crate::builder::{Hasher, MerkleCircuit, MerkleCircuitBuilder};
+pub use crate::chips::{
+    byte_table::{ByteTableChip, ByteTableConfig, TableWidth},
+    comparator::{ComparatorChip, ComparatorConfig, Limbs},
+    cond_hash::{CondHashChip, CondHashConfig},
+    empty_subtree::{EmptySubtreeChip, EmptySubtreeConfig},
+    forest::{ForestChip, ForestConfig, Selection},
+    leaf_encoding::{LeafEncodingChip, LeafEncodingConfig},
+    merkle::{DomainSeparation, HashInstructions, MerkleChip, MerkleConfig},
+    product::{GrandProductChip, GrandProductConfig},
+    sum::{SumChip, SumConfig},
+    window::{WindowChip, WindowConfig},
+    MerkleOutput,
+};
+#[cfg(feature = "dummy-hash")]
+pub use crate::chips::{
+    hash_1::{Hash1Chip, Hash1Config},
+    hash_2::{Hash2Chip, Hash2Config},
+    merkle_v1::{LayerInput, MerkleTreeV1Chip, MerkleTreeV1Config},
+    merkle_v2::{MerkleTreeV2Chip, MerkleTreeV2Config},
+};
+#[cfg(feature = "poseidon")]
+pub use crate::chips::{
+    merkle_v3::{MerkleTreeV3Chip, MerkleTreeV3Config, RootExposure},
+    nmt::{NmtChip, NmtConfig},
+    poseidon::{InitialState, PoseidonChip, PoseidonConfig, PoseidonSpecFor},
+    prf::{prf, PrfChip, PrfConfig, PrfDomain},
+    secp256k1_leaf::{Secp256k1LeafChip, Secp256k1LeafConfig},
+    variable_depth::{VariableDepthChip, VariableDepthConfig},
+};
+#[cfg(all(feature = "poseidon", feature = "dummy-hash"))]
+pub use crate::circuits::merkle::{MerkleTreeCircuit, MerkleTreeConfig};
+#[cfg(feature = "poseidon")]
+pub use crate::circuits::{
+    allowlist::{identity_commitment, nullifier, AllowlistCircuit},
+    chunk_inclusion::{ChunkInclusionCircuit, ChunkInclusionConfig},
+    cond_hash::{cond_fold, CondHashCircuit, CondHashCircuitConfig},
+    dual_root::{DualRootCircuit, DualRootConfig},
+    epoch_membership::{epoch_leaf, EpochMembershipCircuit, EpochMembershipConfig},
+    forest::{ForestCircuit, ForestCircuitConfig},
+    full_tree::FullTreeCircuit,
+    index_diff::IndexDiffCircuit,
+    intersection::IntersectionCircuit,
+    merkle_v3::MerkleTreeV3Circuit,
+    nmt::NmtCircuit,
+    orchard::{OrchardAnchorCircuit, OrchardTree},
+    poseidon::{BatchHashCircuit, HashChainCircuit, PoseidonCircuit, StreamingBatchCircuit},
+    reserves::{ReservesCircuit, ReservesConfig},
+    secp256k1_leaf::Secp256k1LeafCircuit,
+    smt_deletion::{SmtDeletionCircuit, SmtDeletionConfig},
+    stealth::{stealth_key, StealthClaimCircuit},
+    tree_equality::{TreeEqualityCircuit, TreeEqualityConfig},
+    variable_depth::VariableDepthCircuit,
+};
+pub use crate::circuits::{
+    byte_table::ByteTableCircuit,
+    leaf_encoding::LeafEncodingCircuit,
+    sorted_nullifiers::{sort_nullifiers, SortedNullifiersCircuit},
+};
+#[cfg(feature = "dummy-hash")]
+pub use crate::circuits::{
+    hash_1::Hash1Circuit, hash_2::Hash2Circuit, merkle_v1::MerkleTreeV1Circuit,
+    merkle_v2::MerkleTreeV2Circuit,
+};
+#[cfg(feature = "poseidon")]
+pub use crate::concurrent::{ConcurrentTree, Snapshot};
+#[cfg(all(feature = "poseidon", feature = "dummy-hash"))]
+pub use crate::config::ProverConfig;
+pub use crate::coverage::{assert_mock_satisfied, mock_verify, Coverage, GateCoverage};
+pub use crate::diagnostics::{assert_satisfied_explained, explain, explain_all};
+#[cfg(feature = "poseidon")]
+pub use crate::encoding::{chunk_leaf, compress, tagged_compress};
+pub use crate::encoding::{
+    encode_address, encode_hash, encode_str, encode_u128, index_bits, LeafDomain,
+    Secp256k1PublicKey, ToLeaf,
+};
+pub use crate::error::Error;
+#[cfg(all(feature = "poseidon", feature = "dummy-hash"))]
+pub use crate::estimate::{
+    check_circuit_k, check_k, estimate_k, min_k, CircuitKind, CircuitParams, MAX_K, MIN_K,
+};
+#[cfg(all(feature = "poseidon", feature = "dummy-hash"))]
+pub use crate::fixtures::{check_fixtures, load_fixtures, replay_fixtures, Fixture};
+#[cfg(feature = "poseidon")]
+pub use crate::indexed::{indexed_end, indexed_leaf, IndexedTree, LowLeaf};
+pub use crate::instance::{
+    InstanceBuilder, InstanceLayout, LeafExposure, PublicInputs, PublicInstances,
+};
+#[cfg(all(feature = "poseidon", feature = "dummy-hash"))]
+pub use crate::membership::{MembershipProver, MembershipVerifier, Proof, VerifyError};
+#[cfg(feature = "poseidon")]
+pub use crate::multiproof::MultiProof;
+#[cfg(feature = "poseidon")]
+pub use crate::nmt::{NmtNode, NmtProof, NmtTree};
+#[cfg(all(feature = "poseidon", feature = "dummy-hash"))]
+pub use crate::planner::{Packed, Planner};
+pub use crate::proving::{keygen, prove, seeded_rng, verify, OsRng, RngCore, TEST_SEED};
+pub use crate::report::{report, ProofReport};
+pub use crate::serialization::{
+    from_bytes_be_checked, from_bytes_le_checked, from_decimal_str, from_hex,
+    from_montgomery_limbs, from_raw_le, to_bytes_be, to_bytes_le, to_decimal_string, to_hex,
+    to_montgomery_limbs, CanonicalField,
+};
+#[cfg(feature = "poseidon")]
+pub use crate::smt::SparseMerkleTree;
+pub use crate::tamper::{check_tampering, check_tampering_with, tampers, Tamper};
+#[cfg(feature = "tracing")]
+pub use crate::trace::{Traced, TracingFloorPlanner};
+#[cfg(feature = "poseidon")]
+pub use crate::tree::{
+    blinded_leaf, compute_root, empty_hashes, leaf_commitment, MerklePath, MerkleTree,
+};
+#[cfg(all(feature = "poseidon", feature = "dummy-hash"))]
+pub use crate::variant::{MembershipCircuit, MerkleMembership, SmtDeletion, Variant};
+#[cfg(feature = "poseidon")]
+pub use crate::vk_tree::{verify_approved, vk_digest, VkTree};
+#[cfg(feature = "poseidon")]
+pub use crate::wire::{HashId, MerkleProof, Payload, WIRE_VERSION};
+#[cfg(all(feature = "poseidon", feature = "dummy-hash"))]
+pub use crate::witness::{ProvingRecord, Witness, WITNESS_VERSION};
+#[cfg(feature = "zeroize")]
+pub use crate::zeroize::{prove_zeroizing, Secret};
+
+// A downstream user's whole flow, through the prelude alone.
+#[cfg(all(feature = "poseidon", feature = "dummy-hash"))]
+mod tests {
+    use super::*;
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let leaves: Vec<Fp> = ["alice", "bob", "carol"]
+            .iter()
+            .map(|name| name.to_leaf())
+            .collect();
+        let tree = MerkleTree::new(4, &leaves, DomainSeparation::Legacy).unwrap();
+        let path = tree.path(1).unwrap();
+        assert_eq!(path.indices, index_bits::<Fp>(1, 4).unwrap());
+        assert_eq!(
+            compute_root(
+                DomainSeparation::Legacy,
+                path.leaf,
+                &path.elements,
+                &path.indices
+            ),
+            tree.root()
+        );
+
+        let prover =
+            MembershipProver::new(tree.clone(), ProverConfig::new(10, 4, Hasher::Poseidon))
+                .unwrap();
+        let proof = prover.prove_with_rng(1, seeded_rng(TEST_SEED)).unwrap();
+        prover
+            .verifier()
+            .verify(&proof, tree.root(), leaves[1])
+            .unwrap();
+    }
+}
@@ -0,0 +1,318 @@
+/*
+Prost bindings for proto/merkle.proto, written out by hand so that building the crate does not need
+protoc, together with conversions to and from the crate's own types. Keep the two in sync: the
+field tags here must match the schema.
+
+Conversions into crate types are checked like the binary wire format: field elements must be
+canonical 32-byte little-endian reprs, paths must match their depth, and a TreeSnapshot's root
+must be the root of its leaves.
+*/
+
+use crate::chips::merkle::DomainSeparation;
+use crate::chips::poseidon::PoseidonSpecFor;
+use crate::error::Error;
+use crate::serialization::{from_bytes_le_checked, to_bytes_le, CanonicalField};
+use crate::tree::{self, MerkleTree};
+use crate::wire::{self, index_bits};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum HashId {
+    Unspecified = 0,
+    Dummy = 1,
+    Poseidon = 2,
+    PoseidonLevel = 3,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct MerklePath {
+    #[prost(bytes = "vec", tag = "1")]
+    pub leaf: Vec<u8>,
+    #[prost(bytes = "vec", repeated, tag = "2")]
+    pub elements: Vec<Vec<u8>>,
+    #[prost(bool, repeated, tag = "3")]
+    pub indices: Vec<bool>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct MerkleProof {
+    #[prost(uint32, tag = "1")]
+    pub version: u32,
+    #[prost(enumeration = "HashId", tag = "2")]
+    pub hash: i32,
+    #[prost(uint32, tag = "3")]
+    pub arity: u32,
+    #[prost(uint32, tag = "4")]
+    pub depth: u32,
+    #[prost(bytes = "vec", tag = "5")]
+    pub root: Vec<u8>,
+    #[prost(oneof = "merkle_proof::Payload", tags = "6, 7")]
+    pub payload: Option<merkle_proof::Payload>,
+}
+
+pub mod merkle_proof {
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Payload {
+        #[prost(message, tag = "6")]
+        Path(super::MerklePath),
+        #[prost(bytes, tag = "7")]
+        Proof(Vec<u8>),
+    }
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TreeSnapshot {
+    #[prost(enumeration = "HashId", tag = "1")]
+    pub hash: i32,
+    #[prost(uint32, tag = "2")]
+    pub depth: u32,
+    #[prost(bytes = "vec", repeated, tag = "3")]
+    pub leaves: Vec<Vec<u8>>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub root: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ProveRequest {
+    #[prost(message, optional, tag = "1")]
+    pub tree: Option<TreeSnapshot>,
+    #[prost(uint64, tag = "2")]
+    pub leaf_index: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ProveResponse {
+    #[prost(message, optional, tag = "1")]
+    pub proof: Option<MerkleProof>,
+    #[prost(bytes = "vec", repeated, tag = "2")]
+    pub instances: Vec<Vec<u8>>,
+}
+
+impl From<wire::HashId> for HashId {
+    fn from(hash: wire::HashId) -> Self {
+        match hash {
+            wire::HashId::Dummy => HashId::Dummy,
+            wire::HashId::Poseidon(DomainSeparation::Legacy) => HashId::Poseidon,
+            wire::HashId::Poseidon(DomainSeparation::Level) => HashId::PoseidonLevel,
+        }
+    }
+}
+
+impl TryFrom<i32> for wire::HashId {
+    type Error = Error;
+
+    fn try_from(hash: i32) -> Result<Self, Error> {
+        match HashId::try_from(hash) {
+            Ok(HashId::Dummy) => Ok(wire::HashId::Dummy),
+            Ok(HashId::Poseidon) => Ok(wire::HashId::Poseidon(DomainSeparation::Legacy)),
+            Ok(HashId::PoseidonLevel) => Ok(wire::HashId::Poseidon(DomainSeparation::Level)),
+            Ok(HashId::Unspecified) | Err(_) => Err(Error::Malformed("unknown hash id")),
+        }
+    }
+}
+
+fn field<F: CanonicalField>(bytes: &[u8]) -> Result<F, Error> {
+    let bytes: &[u8; 32] = bytes
+        .try_into()
+        .map_err(|_| Error::Malformed("field elements are 32 bytes"))?;
+    from_bytes_le_checked(bytes)
+}
+
+fn fields<F: CanonicalField>(values: &[Vec<u8>]) -> Result<Vec<F>, Error> {
+    values.iter().map(|bytes| field(bytes)).collect()
+}
+
+fn small(value: u32) -> Result<u8, Error> {
+    u8::try_from(value).map_err(|_| Error::Malformed("value does not fit in a byte"))
+}
+
+impl<F: CanonicalField> TryFrom<&wire::MerkleProof<F>> for MerkleProof {
+    type Error = Error;
+
+    fn try_from(proof: &wire::MerkleProof<F>) -> Result<Self, Error> {
+        let payload = match &proof.payload {
+            wire::Payload::Path(path) => merkle_proof::Payload::Path(MerklePath {
+                leaf: to_bytes_le(&path.leaf).to_vec(),
+                elements: path
+                    .elements
+                    .iter()
+                    .map(|x| to_bytes_le(x).to_vec())
+                    .collect(),
+                indices: index_bits(proof.depth, path)?
+                    .into_iter()
+                    .map(|bit| bit == 1)
+                    .collect(),
+            }),
+            wire::Payload::Proof(bytes) => merkle_proof::Payload::Proof(bytes.clone()),
+        };
+        Ok(Self {
+            version: wire::WIRE_VERSION as u32,
+            hash: HashId::from(proof.hash) as i32,
+            arity: proof.arity as u32,
+            depth: proof.depth as u32,
+            root: to_bytes_le(&proof.root).to_vec(),
+            payload: Some(payload),
+        })
+    }
+}
+
+impl<F: CanonicalField> TryFrom<&MerkleProof> for wire::MerkleProof<F> {
+    type Error = Error;
+
+    fn try_from(proof: &MerkleProof) -> Result<Self, Error> {
+        let version = small(proof.version)?;
+        if version != wire::WIRE_VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        let arity = small(proof.arity)?;
+        let depth = small(proof.depth)?;
+        let payload = match &proof.payload {
+            Some(merkle_proof::Payload::Path(path)) => {
+                let indices = path
+                    .indices
+                    .iter()
+                    .map(|bit| if *bit { F::one() } else { F::zero() })
+                    .collect();
+                wire::Payload::Path(tree::MerklePath::try_new(
+                    arity,
+                    depth,
+                    field(&path.leaf)?,
+                    fields(&path.elements)?,
+                    indices,
+                )?)
+            }
+            Some(merkle_proof::Payload::Proof(bytes)) => wire::Payload::Proof(bytes.clone()),
+            None => return Err(Error::Malformed("missing payload")),
+        };
+        Ok(Self {
+            hash: wire::HashId::try_from(proof.hash)?,
+            arity,
+            depth,
+            root: field(&proof.root)?,
+            payload,
+        })
+    }
+}
+
+impl<F: PoseidonSpecFor + CanonicalField> From<&MerkleTree<F>> for TreeSnapshot {
+    fn from(tree: &MerkleTree<F>) -> Self {
+        Self {
+            hash: HashId::from(wire::HashId::Poseidon(tree.separation())) as i32,
+            depth: tree.depth() as u32,
+            leaves: tree
+                .leaves()
+                .iter()
+                .map(|x| to_bytes_le(x).to_vec())
+                .collect(),
+            root: to_bytes_le(&tree.root()).to_vec(),
+        }
+    }
+}
+
+impl<F: PoseidonSpecFor + CanonicalField> TryFrom<&TreeSnapshot> for MerkleTree<F> {
+    type Error = Error;
+
+    fn try_from(snapshot: &TreeSnapshot) -> Result<Self, Error> {
+        let separation = match wire::HashId::try_from(snapshot.hash)? {
+            wire::HashId::Poseidon(separation) => separation,
+            // The native tree only hashes with Poseidon.
+            wire::HashId::Dummy => return Err(Error::Malformed("unsupported hash for a tree")),
+        };
+        let tree = MerkleTree::new(
+            snapshot.depth as usize,
+            &fields(&snapshot.leaves)?,
+            separation,
+        )?;
+        if tree.root() != field(&snapshot.root)? {
+            return Err(Error::Malformed("root does not match the leaves"));
+        }
+        Ok(tree)
+    }
+}
+
+impl ProveRequest {
+    // The tree to prove against and the path witness for the requested leaf.
+    pub fn witness<F: PoseidonSpecFor + CanonicalField>(
+        &self,
+    ) -> Result<(MerkleTree<F>, tree::MerklePath<F>), Error> {
+        let tree: MerkleTree<F> = self
+            .tree
+            .as_ref()
+            .ok_or(Error::Malformed("missing tree"))?
+            .try_into()?;
+        let path = tree.path(usize::try_from(self.leaf_index).unwrap_or(usize::MAX))?;
+        Ok((tree, path))
+    }
+}
+
+impl ProveResponse {
+    pub fn new<F: CanonicalField>(
+        proof: &wire::MerkleProof<F>,
+        instances: &[F],
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            proof: Some(proof.try_into()?),
+            instances: instances.iter().map(|x| to_bytes_le(x).to_vec()).collect(),
+        })
+    }
+
+    pub fn parts<F: CanonicalField>(&self) -> Result<(wire::MerkleProof<F>, Vec<F>), Error> {
+        let proof = self
+            .proof
+            .as_ref()
+            .ok_or(Error::Malformed("missing proof"))?
+            .try_into()?;
+        Ok((proof, fields(&self.instances)?))
+    }
+}
+
+mod tests {
+    use super::{ProveRequest, ProveResponse, TreeSnapshot};
+    use crate::chips::merkle::DomainSeparation;
+    use crate::tree::MerkleTree;
+    use crate::wire::{HashId, MerkleProof, Payload};
+    use halo2_proofs::pasta::Fp;
+    use prost::Message;
+
+    #[test]
+    fn test() {
+        let separation = DomainSeparation::Level;
+        let leaves: Vec<Fp> = (0..5u64).map(Fp::from).collect();
+        let tree = MerkleTree::new(3, &leaves, separation).unwrap();
+
+        let snapshot = TreeSnapshot::from(&tree);
+        let decoded = TreeSnapshot::decode(snapshot.encode_to_vec().as_slice()).unwrap();
+        assert_eq!(
+            MerkleTree::<Fp>::try_from(&decoded).unwrap().root(),
+            tree.root()
+        );
+
+        let mut tampered = snapshot.clone();
+        tampered.leaves[0] = tampered.leaves[1].clone();
+        assert!(MerkleTree::<Fp>::try_from(&tampered).is_err());
+
+        let request = ProveRequest {
+            tree: Some(snapshot),
+            leaf_index: 4,
+        };
+        let (_, path) = request.witness::<Fp>().unwrap();
+        assert_eq!(path, tree.path(4).unwrap());
+
+        let proof = MerkleProof {
+            hash: HashId::Poseidon(separation),
+            arity: 2,
+            depth: 3,
+            root: tree.root(),
+            payload: Payload::Path(path),
+        };
+        let response = ProveResponse::new(&proof, &[Fp::from(4), tree.root()]).unwrap();
+        let decoded = ProveResponse::decode(response.encode_to_vec().as_slice()).unwrap();
+        let (parsed, instances) = decoded.parts::<Fp>().unwrap();
+        assert_eq!(parsed, proof);
+        assert_eq!(instances, vec![Fp::from(4), tree.root()]);
+
+        let mut truncated = response.proof.clone().unwrap();
+        truncated.root.pop();
+        assert!(MerkleProof::<Fp>::try_from(&truncated).is_err());
+    }
+}
@@ -0,0 +1,119 @@
+/*
+Key generation, proving and verification over pasta with the IPA commitment scheme. Proving takes
+the randomness source explicitly: pass `OsRng` in production, or `seeded_rng` to get byte-identical
+proofs across runs and machines, as CI and benchmark comparisons need. IPA parameters are derived
+deterministically from k, so a seeded RNG makes the whole pipeline reproducible.
+*/
+
+use crate::error::Error;
+use halo2_proofs::{
+    pasta::{EqAffine, Fp},
+    plonk::{
+        create_proof, keygen_pk, keygen_vk, verify_proof, Circuit, ProvingKey, SingleVerifier,
+        VerifyingKey,
+    },
+    poly::commitment::Params,
+    transcript::{Blake2bRead, Blake2bWrite, Challenge255},
+};
+use rand_chacha::ChaCha20Rng;
+pub use rand_core::{OsRng, RngCore, SeedableRng};
+
+// The seed tests and benchmarks use unless they need several independent streams.
+pub const TEST_SEED: u64 = 0x6d65726b6c65;
+
+pub fn seeded_rng(seed: u64) -> ChaCha20Rng {
+    ChaCha20Rng::seed_from_u64(seed)
+}
+
+pub fn keygen<C: Circuit<Fp>>(
+    params: &Params<EqAffine>,
+    circuit: &C,
+) -> Result<ProvingKey<EqAffine>, Error> {
+    let vk = keygen_vk(params, circuit)?;
+    Ok(keygen_pk(params, vk, circuit)?)
+}
+
+pub fn prove<C: Circuit<Fp>>(
+    params: &Params<EqAffine>,
+    pk: &ProvingKey<EqAffine>,
+    circuit: C,
+    instances: &[Vec<Fp>],
+    rng: impl RngCore,
+) -> Result<Vec<u8>, Error> {
+    prove_ref(params, pk, &circuit, instances, rng)
+}
+
+// `prove` for a circuit the caller keeps, e.g. to wipe its witness afterwards.
+pub(crate) fn prove_ref<C: Circuit<Fp>>(
+    params: &Params<EqAffine>,
+    pk: &ProvingKey<EqAffine>,
+    circuit: &C,
+    instances: &[Vec<Fp>],
+    rng: impl RngCore,
+) -> Result<Vec<u8>, Error> {
+    let columns: Vec<&[Fp]> = instances.iter().map(|column| column.as_slice()).collect();
+    let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+    create_proof(
+        params,
+        pk,
+        std::slice::from_ref(circuit),
+        &[&columns],
+        rng,
+        &mut transcript,
+    )?;
+    Ok(transcript.finalize())
+}
+
+pub fn verify(
+    params: &Params<EqAffine>,
+    vk: &VerifyingKey<EqAffine>,
+    proof: &[u8],
+    instances: &[Vec<Fp>],
+) -> Result<(), Error> {
+    let columns: Vec<&[Fp]> = instances.iter().map(|column| column.as_slice()).collect();
+    let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(proof);
+    Ok(verify_proof(
+        params,
+        vk,
+        SingleVerifier::new(params),
+        &[&columns],
+        &mut transcript,
+    )?)
+}
+
+mod tests {
+    use super::{keygen, prove, seeded_rng, verify, TEST_SEED};
+    use crate::chips::leaf_encoding::pack_be;
+    use crate::circuits::leaf_encoding::LeafEncodingCircuit;
+    use halo2_proofs::{
+        pasta::{EqAffine, Fp},
+        poly::commitment::Params,
+    };
+
+    #[test]
+    fn test() {
+        let hash = [3u8; 32];
+        let leaf: Fp = pack_be(&hash[..31]);
+        let circuit = || LeafEncodingCircuit::<Fp>::new(&hash[..31]).unwrap();
+        let instances = vec![vec![leaf]];
+
+        let params: Params<EqAffine> = Params::new(9);
+        let pk = keygen(&params, &circuit()).unwrap();
+        let first = prove(&params, &pk, circuit(), &instances, seeded_rng(TEST_SEED)).unwrap();
+        let second = prove(&params, &pk, circuit(), &instances, seeded_rng(TEST_SEED)).unwrap();
+        let other = prove(
+            &params,
+            &pk,
+            circuit(),
+            &instances,
+            seeded_rng(TEST_SEED + 1),
+        )
+        .unwrap();
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+
+        verify(&params, pk.get_vk(), &first, &instances).unwrap();
+        verify(&params, pk.get_vk(), &other, &instances).unwrap();
+        assert!(verify(&params, pk.get_vk(), &first, &[vec![leaf + Fp::one()]]).is_err());
+    }
+}
@@ -0,0 +1,99 @@
+/*
+Proof size and verification cost, for capacity planning before choosing a depth or hash. `report`
+verifies a proof against its verifying key and returns the proof length, the instance rows per
+column and the verification wall time; its Display impl prints them one per line.
+
+This crate proves over pasta with the IPA commitment scheme, which has no EVM verifier, so the
+gas estimate is always `None`. It is kept for when a KZG backend exists (see the README on bn254).
+*/
+
+use crate::error::Error;
+use crate::proving::verify;
+use halo2_proofs::{
+    pasta::{EqAffine, Fp},
+    plonk::VerifyingKey,
+    poly::commitment::Params,
+};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofReport {
+    pub k: u32,
+    pub proof_bytes: usize,
+    // The number of rows supplied for each instance column.
+    pub instance_rows: Vec<usize>,
+    pub verified: bool,
+    pub verify_time: Duration,
+    pub gas: Option<u64>,
+}
+
+impl fmt::Display for ProofReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "k: {}", self.k)?;
+        writeln!(f, "proof size: {} bytes", self.proof_bytes)?;
+        writeln!(f, "instance rows: {:?}", self.instance_rows)?;
+        writeln!(f, "verified: {}", self.verified)?;
+        writeln!(f, "verify time: {:?}", self.verify_time)?;
+        match self.gas {
+            Some(gas) => write!(f, "estimated gas: {}", gas),
+            None => write!(f, "estimated gas: n/a (IPA)"),
+        }
+    }
+}
+
+// A proof that fails to verify is reported with `verified: false` rather than as an error, so the
+// timing of rejected proofs can be measured too.
+pub fn report(
+    params: &Params<EqAffine>,
+    vk: &VerifyingKey<EqAffine>,
+    proof: &[u8],
+    instances: &[Vec<Fp>],
+) -> Result<ProofReport, Error> {
+    let start = Instant::now();
+    let verified = verify(params, vk, proof, instances).is_ok();
+    let verify_time = start.elapsed();
+
+    Ok(ProofReport {
+        k: vk.get_domain().k(),
+        proof_bytes: proof.len(),
+        instance_rows: instances.iter().map(|column| column.len()).collect(),
+        verified,
+        verify_time,
+        gas: None,
+    })
+}
+
+mod tests {
+    use super::report;
+    use crate::chips::leaf_encoding::pack_be;
+    use crate::circuits::leaf_encoding::LeafEncodingCircuit;
+    use crate::proving::{keygen, prove, seeded_rng, TEST_SEED};
+    use halo2_proofs::{
+        pasta::{EqAffine, Fp},
+        poly::commitment::Params,
+    };
+
+    #[test]
+    fn test() {
+        let hash = [7u8; 32];
+        let leaf: Fp = pack_be(&hash[..31]);
+        let circuit = LeafEncodingCircuit::<Fp>::new(&hash[..31]).unwrap();
+
+        let params: Params<EqAffine> = Params::new(9);
+        let pk = keygen(&params, &circuit).unwrap();
+        let proof = prove(&params, &pk, circuit, &[vec![leaf]], seeded_rng(TEST_SEED)).unwrap();
+        let vk = pk.get_vk();
+
+        let ok = report(&params, vk, &proof, &[vec![leaf]]).unwrap();
+        assert!(ok.verified);
+        assert_eq!(ok.k, 9);
+        assert_eq!(ok.proof_bytes, proof.len());
+        assert_eq!(ok.instance_rows, vec![1]);
+        assert!(ok.gas.is_none());
+        assert!(ok.to_string().contains("proof size"));
+
+        let bad = report(&params, vk, &proof, &[vec![leaf + Fp::one()]]).unwrap();
+        assert!(!bad.verified);
+    }
+}
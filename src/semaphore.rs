@@ -0,0 +1,358 @@
+/*
+Semaphore v4's group tree and derivations, reproduced natively over the bn254 scalar field.
+
+Hashing is circomlib's Poseidon (the poseidon-lite/circomlibjs one): x^5, 8 full rounds, 56 partial
+rounds for one input and 57 for two, with the round constants and Cauchy MDS matrix drawn from the
+reference Grain LFSR, which is how circomlib generated its tables. Groups are LeanIMTs: a parent is
+Poseidon(left, right), a node without a right sibling is carried up unhashed, and the depth grows
+with the number of members, so proofs carry only the siblings that exist.
+
+An identity commitment is Poseidon(Ax, Ay) of the member's Baby Jubjub EdDSA public key and a
+nullifier is Poseidon(scope, secret) with the EdDSA secret scalar; key derivation itself is not
+reimplemented. As with `tornado`, the field is bn254 and the circuits here are over pasta, so
+groups are native only.
+*/
+
+use crate::error::Error;
+use crate::tornado::{from_bytes_be, keccak256, Bn254Fr};
+use ff::{Field, PrimeField};
+use std::collections::VecDeque;
+use std::sync::OnceLock;
+
+pub const FULL_ROUNDS: usize = 8;
+// Semaphore v4's largest supported group depth.
+pub const MAX_DEPTH: usize = 32;
+
+struct Params {
+    constants: Vec<Bn254Fr>,
+    mds: Vec<Vec<Bn254Fr>>,
+    partial_rounds: usize,
+}
+
+// The reference Grain LFSR in self-shrinking mode, seeded with the field and permutation shape.
+struct Grain(VecDeque<bool>);
+
+impl Grain {
+    fn new(width: usize, partial_rounds: usize) -> Self {
+        let mut state = VecDeque::with_capacity(80);
+        let fields = [
+            (1, 2),
+            (0, 4),
+            (Bn254Fr::NUM_BITS as usize, 12),
+            (width, 12),
+            (FULL_ROUNDS, 10),
+            (partial_rounds, 10),
+            ((1 << 30) - 1, 30),
+        ];
+        for (value, bits) in fields {
+            state.extend((0..bits).rev().map(|bit| (value >> bit) & 1 == 1));
+        }
+        let mut grain = Self(state);
+        for _ in 0..160 {
+            grain.step();
+        }
+        grain
+    }
+
+    fn step(&mut self) -> bool {
+        let s = &self.0;
+        let bit = s[62] ^ s[51] ^ s[38] ^ s[23] ^ s[13] ^ s[0];
+        self.0.pop_front();
+        self.0.push_back(bit);
+        bit
+    }
+
+    fn bit(&mut self) -> bool {
+        loop {
+            let keep = self.step();
+            let bit = self.step();
+            if keep {
+                return bit;
+            }
+        }
+    }
+
+    // NUM_BITS output bits read as a big-endian integer.
+    fn bytes(&mut self) -> [u8; 32] {
+        let skip = 256 - Bn254Fr::NUM_BITS as usize;
+        let mut bytes = [0u8; 32];
+        for pos in skip..256 {
+            if self.bit() {
+                bytes[pos / 8] |= 0x80 >> (pos % 8);
+            }
+        }
+        bytes
+    }
+}
+
+fn params(inputs: usize) -> &'static Params {
+    static PARAMS: [OnceLock<Params>; 2] = [OnceLock::new(), OnceLock::new()];
+    PARAMS[inputs - 1].get_or_init(|| {
+        let width = inputs + 1;
+        let partial_rounds = [56, 57][inputs - 1];
+        let mut grain = Grain::new(width, partial_rounds);
+        // Constants are rejection-sampled; the MDS seeds are reduced.
+        let constants = (0..(FULL_ROUNDS + partial_rounds) * width)
+            .map(|_| loop {
+                if let Ok(constant) = from_bytes_be(&grain.bytes()) {
+                    break constant;
+                }
+            })
+            .collect();
+        let seeds: Vec<Bn254Fr> = (0..2 * width)
+            .map(|_| {
+                grain.bytes().iter().fold(Bn254Fr::ZERO, |acc, byte| {
+                    acc * Bn254Fr::from(256) + Bn254Fr::from(*byte as u64)
+                })
+            })
+            .collect();
+        let mds = (0..width)
+            .map(|i| {
+                (0..width)
+                    .map(|j| (seeds[i] + seeds[width + j]).invert().unwrap())
+                    .collect()
+            })
+            .collect();
+        Params {
+            constants,
+            mds,
+            partial_rounds,
+        }
+    })
+}
+
+// circomlib's Poseidon of one or two inputs.
+pub fn poseidon(inputs: &[Bn254Fr]) -> Result<Bn254Fr, Error> {
+    if inputs.is_empty() || inputs.len() > 2 {
+        return Err(Error::UnsupportedArity(inputs.len()));
+    }
+    let params = params(inputs.len());
+    let width = inputs.len() + 1;
+    let mut state = vec![Bn254Fr::ZERO];
+    state.extend_from_slice(inputs);
+    for round in 0..FULL_ROUNDS + params.partial_rounds {
+        for (i, element) in state.iter_mut().enumerate() {
+            *element += params.constants[round * width + i];
+        }
+        let full = round < FULL_ROUNDS / 2 || round >= FULL_ROUNDS / 2 + params.partial_rounds;
+        for element in state.iter_mut().take(if full { width } else { 1 }) {
+            *element = element.square().square() * *element;
+        }
+        state = params
+            .mds
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .zip(state.iter())
+                    .fold(Bn254Fr::ZERO, |acc, (m, s)| acc + *m * s)
+            })
+            .collect();
+    }
+    Ok(state[0])
+}
+
+fn hash_pair(left: Bn254Fr, right: Bn254Fr) -> Bn254Fr {
+    poseidon(&[left, right]).expect("two inputs are supported")
+}
+
+pub fn identity_commitment(public_key: (Bn254Fr, Bn254Fr)) -> Bn254Fr {
+    hash_pair(public_key.0, public_key.1)
+}
+
+pub fn nullifier(scope: Bn254Fr, secret: Bn254Fr) -> Bn254Fr {
+    hash_pair(scope, secret)
+}
+
+// The SDK's `hash` for messages and scopes: keccak256 of the 32-byte big-endian value, shifted
+// right by 8 bits so it fits the field.
+pub fn hash_to_field(value: &[u8; 32]) -> Bn254Fr {
+    let digest = keccak256(value);
+    let mut shifted = [0u8; 32];
+    shifted[1..].copy_from_slice(&digest[..31]);
+    from_bytes_be(&shifted).expect("248-bit values are canonical")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeanImtProof {
+    pub root: Bn254Fr,
+    pub leaf: Bn254Fr,
+    // Bit i says whether the node at siblings[i]'s level is a right child.
+    pub index: u64,
+    pub siblings: Vec<Bn254Fr>,
+}
+
+impl LeanImtProof {
+    pub fn verify(&self) -> bool {
+        let root = self
+            .siblings
+            .iter()
+            .enumerate()
+            .fold(self.leaf, |node, (i, sibling)| {
+                if (self.index >> i) & 1 == 1 {
+                    hash_pair(*sibling, node)
+                } else {
+                    hash_pair(node, *sibling)
+                }
+            });
+        root == self.root
+    }
+
+    // The circuit's merkleProofLength, index and siblings zero-padded to `depth`.
+    pub fn circuit_inputs(&self, depth: usize) -> Result<(usize, u64, Vec<Bn254Fr>), Error> {
+        if self.siblings.len() > depth {
+            return Err(Error::DepthMismatch {
+                expected: depth,
+                actual: self.siblings.len(),
+            });
+        }
+        let mut siblings = self.siblings.clone();
+        siblings.resize(depth, Bn254Fr::ZERO);
+        Ok((self.siblings.len(), self.index, siblings))
+    }
+}
+
+// A Semaphore group: a LeanIMT over identity commitments.
+#[derive(Debug, Clone, Default)]
+pub struct LeanImt {
+    // layers[0] holds the leaves and the last layer the root.
+    layers: Vec<Vec<Bn254Fr>>,
+}
+
+impl LeanImt {
+    pub fn new(leaves: &[Bn254Fr]) -> Result<Self, Error> {
+        let mut tree = Self::default();
+        for leaf in leaves {
+            tree.insert(*leaf)?;
+        }
+        Ok(tree)
+    }
+
+    pub fn leaves(&self) -> &[Bn254Fr] {
+        self.layers.first().map_or(&[], |leaves| leaves)
+    }
+
+    pub fn depth(&self) -> usize {
+        self.layers.len().saturating_sub(1)
+    }
+
+    // The empty group's root is 0, as in the Semaphore contract.
+    pub fn root(&self) -> Bn254Fr {
+        self.layers.last().map_or(Bn254Fr::ZERO, |layer| layer[0])
+    }
+
+    pub fn insert(&mut self, leaf: Bn254Fr) -> Result<(), Error> {
+        let size = self.leaves().len();
+        if size >= 1 << MAX_DEPTH {
+            return Err(Error::TooManyLeaves {
+                capacity: 1 << MAX_DEPTH,
+                leaves: size + 1,
+            });
+        }
+        if self.layers.is_empty() {
+            self.layers.push(Vec::new());
+        }
+        self.layers[0].push(leaf);
+        let depth = (usize::BITS - size.leading_zeros()) as usize;
+        while self.layers.len() <= depth {
+            self.layers.push(Vec::new());
+        }
+        self.layers.truncate(depth + 1);
+        let (mut node, mut index) = (leaf, size);
+        for level in 0..depth {
+            if index & 1 == 1 {
+                node = hash_pair(self.layers[level][index - 1], node);
+            }
+            index >>= 1;
+            let layer = &mut self.layers[level + 1];
+            if index < layer.len() {
+                layer[index] = node;
+            } else {
+                layer.push(node);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn proof(&self, index: usize) -> Result<LeanImtProof, Error> {
+        let leaf = *self.leaves().get(index).ok_or(Error::IndexOutOfRange {
+            index,
+            leaves: self.leaves().len(),
+        })?;
+        let mut siblings = Vec::new();
+        let mut bits = 0u64;
+        let mut position = index;
+        for level in 0..self.depth() {
+            if let Some(sibling) = self.layers[level].get(position ^ 1) {
+                bits |= ((position & 1) as u64) << siblings.len();
+                siblings.push(*sibling);
+            }
+            position >>= 1;
+        }
+        Ok(LeanImtProof {
+            root: self.root(),
+            leaf,
+            index: bits,
+            siblings,
+        })
+    }
+}
+
+mod tests {
+    use super::{hash_to_field, identity_commitment, nullifier, poseidon, LeanImt};
+    use crate::tornado::{to_bytes_be, Bn254Fr};
+
+    fn hex(value: &Bn254Fr) -> String {
+        to_bytes_be(value)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    #[test]
+    fn test() {
+        // circomlibjs / poseidon-lite: poseidon1([1]) and poseidon2([1, 2]).
+        let one = Bn254Fr::from(1);
+        let two = Bn254Fr::from(2);
+        assert_eq!(
+            hex(&poseidon(&[one]).unwrap()),
+            "29176100eaa962bdc1fe6c654d6a3c130e96a4d1168b33848b897dc502820133"
+        );
+        assert_eq!(
+            hex(&poseidon(&[one, two]).unwrap()),
+            "115cc0f5e7d690413df64c6b9662e9cf2a3617f2743245519e19607a4417189a"
+        );
+        assert!(poseidon(&[]).is_err());
+        assert_eq!(
+            identity_commitment((one, two)),
+            poseidon(&[one, two]).unwrap()
+        );
+        assert_ne!(nullifier(one, two), nullifier(two, one));
+        assert_eq!(to_bytes_be(&hash_to_field(&[0xff; 32]))[0], 0);
+
+        let mut group = LeanImt::default();
+        assert_eq!(group.root(), Bn254Fr::from(0));
+        group.insert(one).unwrap();
+        assert_eq!((group.depth(), group.root()), (0, one));
+
+        // Five leaves: the fifth is carried up to the root level unhashed.
+        let leaves: Vec<Bn254Fr> = (1..=5u64).map(Bn254Fr::from).collect();
+        let group = LeanImt::new(&leaves).unwrap();
+        let pair = |a: u64, b: u64| poseidon(&[Bn254Fr::from(a), Bn254Fr::from(b)]).unwrap();
+        let left = poseidon(&[pair(1, 2), pair(3, 4)]).unwrap();
+        assert_eq!(group.depth(), 3);
+        assert_eq!(group.root(), poseidon(&[left, Bn254Fr::from(5)]).unwrap());
+        // Computed independently with light-poseidon's circom parameters.
+        assert_eq!(
+            hex(&group.root()),
+            "1973be9a0ac928df30c68c1698876c310c8246a3f215d33764045ec9da859b08"
+        );
+        for index in 0..leaves.len() {
+            assert!(group.proof(index).unwrap().verify());
+        }
+        let last = group.proof(4).unwrap();
+        assert_eq!((last.siblings, last.index), (vec![left], 1));
+        let (length, _, padded) = group.proof(2).unwrap().circuit_inputs(32).unwrap();
+        assert_eq!((length, padded.len()), (3, 32));
+        assert!(group.proof(5).is_err());
+    }
+}
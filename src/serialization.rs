@@ -0,0 +1,114 @@
+/*
+Endianness-explicit serialization of field elements. halo2 and pasta use a 32-byte little-endian
+repr, while JS tooling (snarkjs, circomlibjs, ethers) usually speaks big-endian bytes or decimal
+strings; mixing the two up is the usual cause of "my root doesn't match". Every decoder here is
+checked: values at or above the modulus, and decimal strings with signs, leading zeros or other
+characters, are rejected with `Error::NonCanonical` rather than silently reduced.
+
+Values exported from gnark or circom internals are often still in Montgomery form, i.e. x * 2^256
+mod p as four little-endian u64 limbs; `from_montgomery_limbs` undoes that.
+
+The conversions live in halo2_merkle_core, which is no_std; the encoders are re-exported from it
+and the decoders wrapped to return this crate's `Error`.
+*/
+
+use crate::error::Error;
+use halo2_merkle_core::field;
+pub use halo2_merkle_core::field::{
+    to_bytes_be, to_bytes_le, to_decimal_string, to_hex, to_montgomery_limbs,
+};
+use halo2_proofs::{arithmetic::FieldExt, pasta::group::ff::PrimeField};
+
+// The fields this crate works over: all have a 32-byte little-endian repr.
+pub trait CanonicalField: FieldExt + PrimeField<Repr = [u8; 32]> {}
+
+impl<F: FieldExt + PrimeField<Repr = [u8; 32]>> CanonicalField for F {}
+
+pub fn from_bytes_le_checked<F: CanonicalField>(bytes: &[u8; 32]) -> Result<F, Error> {
+    Ok(field::from_bytes_le_checked(bytes)?)
+}
+
+pub fn from_bytes_be_checked<F: CanonicalField>(bytes: &[u8; 32]) -> Result<F, Error> {
+    Ok(field::from_bytes_be_checked(bytes)?)
+}
+
+pub fn from_decimal_str<F: CanonicalField>(s: &str) -> Result<F, Error> {
+    Ok(field::from_decimal_str(s)?)
+}
+
+pub fn from_hex<F: CanonicalField>(s: &str) -> Result<F, Error> {
+    Ok(field::from_hex(s)?)
+}
+
+pub fn from_raw_le<F: CanonicalField>(bytes: &[u8; 32]) -> Result<F, Error> {
+    Ok(field::from_raw_le(bytes)?)
+}
+
+pub fn from_montgomery_limbs<F: CanonicalField>(limbs: &[u64; 4]) -> Result<F, Error> {
+    Ok(field::from_montgomery_limbs(limbs)?)
+}
+
+mod tests {
+    use super::{
+        from_bytes_be_checked, from_bytes_le_checked, from_decimal_str, from_hex,
+        from_montgomery_limbs, from_raw_le, to_bytes_be, to_bytes_le, to_decimal_string, to_hex,
+        to_montgomery_limbs,
+    };
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let x = Fp::from(0x0102);
+        assert_eq!(to_bytes_le(&x)[..2], [0x02, 0x01]);
+        assert_eq!(to_bytes_be(&x)[30..], [0x01, 0x02]);
+        assert_eq!(from_bytes_le_checked::<Fp>(&to_bytes_le(&x)).unwrap(), x);
+        assert_eq!(from_bytes_be_checked::<Fp>(&to_bytes_be(&x)).unwrap(), x);
+        assert!(from_bytes_le_checked::<Fp>(&[0xff; 32]).is_err());
+
+        let minus_one = -Fp::one();
+        let decimal =
+            "28948022309329048855892746252171976963363056481941560715954676764349967630336";
+        assert_eq!(to_decimal_string(&minus_one), decimal);
+        assert_eq!(from_decimal_str::<Fp>(decimal).unwrap(), minus_one);
+        assert_eq!(to_decimal_string(&Fp::zero()), "0");
+        assert_eq!(from_decimal_str::<Fp>("0").unwrap(), Fp::zero());
+        assert_eq!(from_decimal_str::<Fp>("258").unwrap(), x);
+
+        // The modulus itself, and a value above 2^256.
+        assert!(from_decimal_str::<Fp>(
+            "28948022309329048855892746252171976963363056481941560715954676764349967630337"
+        )
+        .is_err());
+        assert!(from_decimal_str::<Fp>(&"9".repeat(80)).is_err());
+        for bad in ["", "007", "-1", "1e3", " 1"] {
+            assert!(from_decimal_str::<Fp>(bad).is_err());
+        }
+
+        assert_eq!(to_hex(&x), format!("0x{}0102", "0".repeat(60)));
+        assert_eq!(from_hex::<Fp>(&to_hex(&minus_one)).unwrap(), minus_one);
+        assert_eq!(from_hex::<Fp>("0x102").unwrap(), x);
+        assert_eq!(from_hex::<Fp>("0xABCD").unwrap(), Fp::from(0xabcd));
+        let too_big = format!("0x{}", "f".repeat(64));
+        for bad in ["", "0x", "102", "0x1g", too_big.as_str()] {
+            assert!(from_hex::<Fp>(bad).is_err());
+        }
+
+        // The Montgomery form of 1 is R = 2^256 mod p.
+        assert_eq!(
+            to_montgomery_limbs(&Fp::one()),
+            [
+                0x34786d38fffffffd,
+                0x992c350be41914ad,
+                0xffffffffffffffff,
+                0x3fffffffffffffff
+            ]
+        );
+
+        let x = Fp::from(123456789);
+        let limbs = to_montgomery_limbs(&x);
+        assert_eq!(from_montgomery_limbs::<Fp>(&limbs).unwrap(), x);
+        assert_ne!(limbs, [123456789, 0, 0, 0]);
+        assert!(from_montgomery_limbs::<Fp>(&[u64::MAX; 4]).is_err());
+        assert_eq!(from_raw_le::<Fp>(&to_bytes_le(&x)).unwrap(), x);
+    }
+}
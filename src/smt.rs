@@ -0,0 +1,192 @@
+/*
+A sparse Merkle tree keyed by u64, the tree SmtDeletionCircuit proves deletions from. Only nodes
+that differ from the empty subtree of their level are stored, one map per level, so a depth-64 tree
+holding a few keys stores a few nodes per level rather than a populated prefix of 2^64 leaves.
+Setting a key to zero, the empty leaf, deletes it and drops the nodes that are empty again.
+
+Nodes are hashed with `hash_pair` as in MerkleTree, so both trees give the same root for the same
+leaves at the same indices, and a key's path verifies with `MerklePath::verify`. An absent key has
+a path too, whose leaf is zero.
+*/
+
+use crate::chips::merkle::DomainSeparation;
+use crate::chips::poseidon::PoseidonSpecFor;
+use crate::encoding::index_bits;
+use crate::error::Error;
+use crate::tree::{empty_hashes, hash_pair, MerklePath};
+use halo2_proofs::pasta::Fp;
+use std::collections::HashMap;
+
+// Keys are u64, so a deeper tree would have leaves no key reaches.
+pub const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct SparseMerkleTree<F = Fp> {
+    depth: usize,
+    separation: DomainSeparation,
+    // nodes[level] maps a position to its node, for nodes that are not the empty subtree.
+    nodes: Vec<HashMap<u64, F>>,
+    // empty[level] is the root of an all-zero subtree of height `level`.
+    empty: Vec<F>,
+}
+
+impl<F: PoseidonSpecFor> SparseMerkleTree<F> {
+    pub fn new(depth: usize, separation: DomainSeparation) -> Result<Self, Error> {
+        if depth > MAX_DEPTH {
+            return Err(Error::DepthTooLarge {
+                max: MAX_DEPTH,
+                depth,
+            });
+        }
+        Ok(Self {
+            depth,
+            separation,
+            nodes: vec![HashMap::new(); depth + 1],
+            empty: empty_hashes(separation, depth),
+        })
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn separation(&self) -> DomainSeparation {
+        self.separation
+    }
+
+    pub fn root(&self) -> F {
+        self.node(self.depth, 0)
+    }
+
+    // The number of keys with a nonzero leaf.
+    pub fn len(&self) -> usize {
+        self.nodes[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes[0].is_empty()
+    }
+
+    fn node(&self, level: usize, position: u64) -> F {
+        self.nodes[level]
+            .get(&position)
+            .copied()
+            .unwrap_or(self.empty[level])
+    }
+
+    fn store(&mut self, level: usize, position: u64, node: F) {
+        if node == self.empty[level] {
+            self.nodes[level].remove(&position);
+        } else {
+            self.nodes[level].insert(position, node);
+        }
+    }
+
+    fn check_key(&self, key: u64) -> Result<(), Error> {
+        if self.depth < 64 && key >> self.depth != 0 {
+            return Err(Error::IndexOutOfRange {
+                index: key as usize,
+                leaves: 1usize.checked_shl(self.depth as u32).unwrap_or(usize::MAX),
+            });
+        }
+        Ok(())
+    }
+
+    // The leaf under `key`, zero if it is absent.
+    pub fn get(&self, key: u64) -> Result<F, Error> {
+        self.check_key(key)?;
+        Ok(self.node(0, key))
+    }
+
+    // Sets the leaf under `key` and rehashes its path. Zero deletes the key.
+    pub fn insert(&mut self, key: u64, leaf: F) -> Result<(), Error> {
+        self.check_key(key)?;
+        let mut position = key;
+        let mut node = leaf;
+        for level in 0..self.depth {
+            self.store(level, position, node);
+            let sibling = self.node(level, position ^ 1);
+            node = if position & 1 == 0 {
+                hash_pair(self.separation, level, node, sibling)
+            } else {
+                hash_pair(self.separation, level, sibling, node)
+            };
+            position >>= 1;
+        }
+        self.store(self.depth, 0, node);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, key: u64) -> Result<(), Error> {
+        self.insert(key, F::zero())
+    }
+
+    pub fn path(&self, key: u64) -> Result<MerklePath<F>, Error> {
+        let indices = index_bits(key, self.depth)?;
+        let elements = (0..self.depth)
+            .map(|level| self.node(level, (key >> level) ^ 1))
+            .collect();
+        Ok(MerklePath {
+            leaf: self.node(0, key),
+            elements,
+            indices,
+        })
+    }
+}
+
+mod tests {
+    use super::SparseMerkleTree;
+    use crate::chips::merkle::DomainSeparation;
+    use crate::error::Error;
+    use crate::tree::{empty_hashes, MerkleTree};
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        // The same root as the dense tree, and nothing stored for the empty leaves.
+        let leaves: Vec<Fp> = (1..=6u64).map(Fp::from).collect();
+        let dense = MerkleTree::new(4, &leaves, DomainSeparation::Level).unwrap();
+        let mut tree = SparseMerkleTree::new(4, DomainSeparation::Level).unwrap();
+        for (key, leaf) in leaves.iter().enumerate() {
+            tree.insert(key as u64, *leaf).unwrap();
+        }
+        assert_eq!(tree.root(), dense.root());
+        assert_eq!(tree.len(), 6);
+        for key in 0..16u64 {
+            let path = tree.path(key).unwrap();
+            assert_eq!(path.leaf, tree.get(key).unwrap());
+            path.verify(DomainSeparation::Level, tree.root()).unwrap();
+        }
+        assert_eq!(tree.path(3).unwrap(), dense.path(3).unwrap());
+
+        // Deleting every key leaves the empty tree with no stored nodes.
+        for key in 0..6u64 {
+            tree.remove(key).unwrap();
+        }
+        assert!(tree.is_empty());
+        assert!(tree.nodes.iter().all(|level| level.is_empty()));
+        assert_eq!(tree.root(), empty_hashes(DomainSeparation::Level, 4)[4]);
+
+        // Keys anywhere in a depth-64 tree cost one node per level.
+        let mut wide = SparseMerkleTree::<Fp>::new(64, DomainSeparation::Legacy).unwrap();
+        wide.insert(u64::MAX, Fp::from(7)).unwrap();
+        wide.insert(1 << 40, Fp::from(8)).unwrap();
+        assert!(wide.nodes.iter().all(|level| level.len() <= 2));
+        wide.path(u64::MAX)
+            .unwrap()
+            .verify(DomainSeparation::Legacy, wide.root())
+            .unwrap();
+
+        assert!(matches!(
+            tree.insert(16, Fp::one()),
+            Err(Error::IndexOutOfRange {
+                index: 16,
+                leaves: 16
+            })
+        ));
+        assert!(matches!(
+            SparseMerkleTree::<Fp>::new(65, DomainSeparation::Legacy),
+            Err(Error::DepthTooLarge { max: 64, depth: 65 })
+        ));
+    }
+}
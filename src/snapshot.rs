@@ -0,0 +1,155 @@
+/*
+Constraint-system pinning. A `CsSnapshot` records the shape of a circuit's constraint system: gate,
+column, selector and lookup counts, the degree, and a hash of every gate polynomial as printed by
+halo2's `CircuitGates`. These determine the verifying key, so comparing them against the snapshots
+committed in snapshots/constraint_systems.txt catches accidental VK changes from refactors.
+
+`check_snapshot` fails on an entry that is missing from the file as well as on one that differs,
+so a circuit cannot go unpinned. With UPDATE_SNAPSHOTS set it records or overwrites the entry
+instead; commit the diff.
+*/
+
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    dev::CircuitGates,
+    plonk::{Circuit, ConstraintSystem},
+};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CsSnapshot {
+    pub gates: usize,
+    pub advice_columns: usize,
+    pub fixed_columns: usize,
+    pub instance_columns: usize,
+    pub selectors: usize,
+    pub lookups: usize,
+    pub degree: usize,
+    pub hash: u64,
+}
+
+impl fmt::Display for CsSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "gates={} advice={} fixed={} instance={} selectors={} lookups={} degree={} hash={:016x}",
+            self.gates,
+            self.advice_columns,
+            self.fixed_columns,
+            self.instance_columns,
+            self.selectors,
+            self.lookups,
+            self.degree,
+            self.hash
+        )
+    }
+}
+
+// FNV-1a, which unlike std's hashers is stable across Rust releases.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+pub fn snapshot<F: FieldExt, C: Circuit<F>>() -> CsSnapshot {
+    let mut cs = ConstraintSystem::<F>::default();
+    C::configure(&mut cs);
+    let gates = CircuitGates::collect::<F, C>().to_string();
+    CsSnapshot {
+        gates: cs.gates().len(),
+        advice_columns: cs.num_advice_columns(),
+        fixed_columns: cs.num_fixed_columns(),
+        instance_columns: cs.num_instance_columns(),
+        selectors: cs.num_selectors(),
+        lookups: cs.lookups().len(),
+        degree: cs.degree(),
+        hash: fnv1a(gates.as_bytes()),
+    }
+}
+
+pub fn snapshot_path() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("snapshots/constraint_systems.txt")
+}
+
+// Compares `snapshot` with the entry `name` in the file at `path`.
+pub fn check_snapshot(path: &Path, name: &str, snapshot: &CsSnapshot) -> Result<(), String> {
+    let contents = fs::read_to_string(path).unwrap_or_default();
+    let expected = snapshot.to_string();
+    let prefix = format!("{}: ", name);
+    let mut lines: Vec<String> = contents.lines().map(String::from).collect();
+    let update = std::env::var_os("UPDATE_SNAPSHOTS").is_some();
+
+    match lines.iter().position(|line| line.starts_with(&prefix)) {
+        Some(i) if lines[i][prefix.len()..] == expected => return Ok(()),
+        Some(i) if !update => {
+            return Err(format!(
+                "constraint system of {} changed, which changes its verifying key:\n  pinned: {}\n  actual: {}\nre-run with UPDATE_SNAPSHOTS=1 if this is intended",
+                name,
+                &lines[i][prefix.len()..],
+                expected
+            ))
+        }
+        None if !update => {
+            return Err(format!(
+                "constraint system of {} is not pinned in {}:\n  actual: {}\nre-run with UPDATE_SNAPSHOTS=1 to record it",
+                name,
+                path.display(),
+                expected
+            ))
+        }
+        Some(i) => lines[i] = format!("{}{}", prefix, expected),
+        None => lines.push(format!("{}{}", prefix, expected)),
+    }
+    fs::write(path, lines.join("\n") + "\n").map_err(|err| err.to_string())
+}
+
+pub fn check<F: FieldExt, C: Circuit<F>>(name: &str) -> Result<(), String> {
+    check_snapshot(&snapshot_path(), name, &snapshot::<F, C>())
+}
+
+mod tests {
+    use super::{check, check_snapshot, snapshot, CsSnapshot};
+    use crate::circuits::leaf_encoding::LeafEncodingCircuit;
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        check::<Fp, LeafEncodingCircuit<Fp>>("leaf_encoding").unwrap();
+        #[cfg(feature = "dummy-hash")]
+        {
+            use crate::circuits::{merkle_v1::MerkleTreeV1Circuit, merkle_v2::MerkleTreeV2Circuit};
+            check::<Fp, MerkleTreeV1Circuit<Fp>>("merkle_v1").unwrap();
+            check::<Fp, MerkleTreeV2Circuit<Fp>>("merkle_v2").unwrap();
+        }
+        #[cfg(feature = "poseidon")]
+        {
+            use crate::circuits::{
+                allowlist::AllowlistCircuit, forest::ForestCircuit, merkle_v3::MerkleTreeV3Circuit,
+                reserves::ReservesCircuit, smt_deletion::SmtDeletionCircuit,
+            };
+            check::<Fp, MerkleTreeV3Circuit>("merkle_v3").unwrap();
+            check::<Fp, AllowlistCircuit>("allowlist").unwrap();
+            check::<Fp, ForestCircuit>("forest").unwrap();
+            check::<Fp, ReservesCircuit>("reserves").unwrap();
+            check::<Fp, SmtDeletionCircuit>("smt_deletion").unwrap();
+        }
+
+        // A changed shape is reported against the pinned one.
+        let path = std::env::temp_dir().join("halo2_merkle_tree_snapshot_test.txt");
+        let pinned = snapshot::<Fp, LeafEncodingCircuit<Fp>>();
+        std::fs::write(&path, format!("leaf_encoding: {}\n", pinned)).unwrap();
+        let changed = CsSnapshot {
+            gates: pinned.gates + 1,
+            ..pinned
+        };
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_none() {
+            assert!(check_snapshot(&path, "leaf_encoding", &changed).is_err());
+            assert!(check_snapshot(&path, "merkle_v1", &pinned).is_err());
+        }
+        assert!(check_snapshot(&path, "leaf_encoding", &pinned).is_ok());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
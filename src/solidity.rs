@@ -0,0 +1,206 @@
+/*
+Generates a minimal Solidity consumer of membership proofs: a registry of accepted roots kept by
+the deployer, a set of spent nullifiers, and a `claim` function that checks both and calls a
+deployed verifier. The claim arguments and the instance vector follow an `InstanceLayout`, so the
+contract builds the same column as `InstanceLayout::column` does off-chain.
+
+The verifier is called with the instances as 32-byte big-endian words followed by the proof
+(`calldata::encode_calldata`), which is how snark-verifier's generated verifiers take their input;
+they revert on an invalid proof. No such verifier exists for this crate's pasta/IPA proofs yet (see
+the README), so the contract is ready for the bn254 port rather than deployable today.
+
+Every claim argument is required to be below the bn254 scalar field modulus before it is used.
+`x` and `x + p` are the same field element, so without the check one nullifier would have two
+mapping keys and could be spent twice.
+*/
+
+use crate::error::Error;
+use crate::instance::InstanceLayout;
+
+const HEADER: &str = "// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.20;
+
+";
+
+// The Solidity parameter type of each slot.
+fn param_type(name: &str) -> &'static str {
+    match name {
+        "index" => "uint256",
+        _ => "bytes32",
+    }
+}
+
+// Solidity's keywords and reserved words, none of which can name a contract.
+const KEYWORDS: &str = "abstract address after alias anonymous apply as assembly auto bool break \
+    byte bytes calldata case catch constant constructor continue contract copyof days default \
+    define delete do else emit enum error ether event external fallback false final finney fixed \
+    for function gwei hours if immutable implements import in indexed inline int interface \
+    internal is let library macro mapping match memory minutes modifier mutable new null of \
+    override partial payable pragma private promise public pure receive reference relocatable \
+    return returns revert sealed seconds sizeof static storage string struct supports switch \
+    szabo this throw true try type typedef typeof ufixed uint unchecked unicode using var view \
+    virtual weeks wei while years";
+
+// Sized types such as uint64, bytes32 and fixed128x18 are reserved as well.
+fn is_sized_type(name: &str) -> bool {
+    ["uint", "int", "bytes", "ufixed", "fixed"]
+        .iter()
+        .filter_map(|prefix| name.strip_prefix(prefix))
+        .any(|size| !size.is_empty() && size.chars().all(|c| c.is_ascii_digit() || c == 'x'))
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !KEYWORDS.split_whitespace().any(|keyword| keyword == name)
+        && !is_sized_type(name)
+}
+
+// A claim contract named `name` for circuits with the given layout, which must have root and
+// nullifier slots.
+pub fn claim_contract(name: &str, layout: &InstanceLayout) -> Result<String, Error> {
+    if !is_identifier(name) {
+        return Err(Error::Malformed(
+            "contract name is not a Solidity identifier",
+        ));
+    }
+    layout.root.ok_or(Error::MissingInstance("root"))?;
+    layout
+        .nullifier
+        .ok_or(Error::MissingInstance("nullifier"))?;
+    let mut slots: Vec<(&str, usize)> = layout
+        .slots()
+        .iter()
+        .filter_map(|(name, row)| row.map(|row| (*name, row)))
+        .collect();
+    slots.sort_by_key(|(_, row)| *row);
+
+    let rows: String = slots
+        .iter()
+        .map(|(name, row)| format!("//   row {}: {}\n", row, name))
+        .collect();
+    let params: Vec<String> = slots
+        .iter()
+        .map(|(name, _)| format!("{} {}", param_type(name), name))
+        .chain(std::iter::once("bytes calldata proof".to_string()))
+        .collect();
+    let range_checks: String = slots
+        .iter()
+        .map(|(name, _)| {
+            format!(
+                "        require(uint256({0}) < SNARK_SCALAR_FIELD, \"{0} out of field\");\n",
+                name
+            )
+        })
+        .collect();
+    let assignments: String = slots
+        .iter()
+        .map(|(name, row)| format!("        instances[{}] = uint256({});\n", row, name))
+        .collect();
+
+    Ok(format!(
+        "{header}// Generated by halo2-merkle-tree for the instance layout
+{rows}// Proofs are not bound to msg.sender; a claim that pays out should bind its recipient through
+// the circuit.
+contract {name} {{
+    uint256 internal constant SNARK_SCALAR_FIELD =
+        21888242871839275222246405745257275088548364400416034343698204186575808495617;
+
+    address public immutable verifier;
+    address public owner;
+    mapping(bytes32 => bool) public knownRoots;
+    mapping(bytes32 => bool) public spentNullifiers;
+
+    event RootAdded(bytes32 root);
+    event Claimed(bytes32 nullifier, address claimant);
+
+    constructor(address verifier_) {{
+        // A call to an address without code succeeds, which would accept every proof.
+        require(verifier_.code.length > 0, \"verifier has no code\");
+        verifier = verifier_;
+        owner = msg.sender;
+    }}
+
+    function addRoot(bytes32 root) external {{
+        require(msg.sender == owner, \"not owner\");
+        require(uint256(root) < SNARK_SCALAR_FIELD, \"root out of field\");
+        knownRoots[root] = true;
+        emit RootAdded(root);
+    }}
+
+    function claim({params}) external {{
+{range_checks}        require(knownRoots[root], \"unknown root\");
+        require(!spentNullifiers[nullifier], \"nullifier already spent\");
+        uint256[] memory instances = new uint256[]({len});
+{assignments}        (bool ok, ) = verifier.staticcall(abi.encodePacked(instances, proof));
+        require(ok, \"invalid proof\");
+        spentNullifiers[nullifier] = true;
+        emit Claimed(nullifier, msg.sender);
+    }}
+}}
+",
+        header = HEADER,
+        rows = rows,
+        name = name,
+        params = params.join(", "),
+        len = layout.rows(),
+        range_checks = range_checks,
+        assignments = assignments,
+    ))
+}
+
+mod tests {
+    use super::claim_contract;
+    use crate::instance::InstanceLayout;
+
+    #[test]
+    fn test() {
+        let layout = InstanceLayout::merkle(false, true)
+            .with_nullifier()
+            .with_scope();
+        let source = claim_contract("AirdropClaim", &layout).unwrap();
+        assert!(source.contains("contract AirdropClaim {"));
+        assert!(source.contains(
+            "function claim(bytes32 root, bytes32 nullifier, bytes32 scope, bytes calldata proof)"
+        ));
+        assert!(source.contains("new uint256[](3);"));
+        assert!(source.contains("instances[0] = uint256(root);"));
+        assert!(source.contains("instances[2] = uint256(scope);"));
+        for name in ["root", "nullifier", "scope"] {
+            assert!(source.contains(&format!(
+                "require(uint256({0}) < SNARK_SCALAR_FIELD, \"{0} out of field\");",
+                name
+            )));
+        }
+        let check = source
+            .find("require(uint256(nullifier) < SNARK_SCALAR_FIELD")
+            .unwrap();
+        assert!(check < source.find("spentNullifiers[nullifier] = true").unwrap());
+
+        let indexed = InstanceLayout::merkle(true, true)
+            .with_nullifier()
+            .with_index();
+        let source = claim_contract("Claim", &indexed).unwrap();
+        assert!(source.contains("bytes32 leaf, bytes32 root, bytes32 nullifier, uint256 index"));
+        assert!(source.contains("instances[3] = uint256(index);"));
+
+        assert!(claim_contract("Claim", &InstanceLayout::merkle(true, true)).is_err());
+        assert!(claim_contract("Claim", &InstanceLayout::default().with_nullifier()).is_err());
+        assert!(claim_contract("2Claim", &layout).is_err());
+        assert!(claim_contract("Claim; selfdestruct", &layout).is_err());
+        for keyword in [
+            "contract",
+            "mapping",
+            "uint256",
+            "bytes32",
+            "fixed128x18",
+            "this",
+        ] {
+            assert!(claim_contract(keyword, &layout).is_err(), "{}", keyword);
+        }
+        assert!(claim_contract("uint256Claim", &layout).is_ok());
+    }
+}
@@ -0,0 +1,220 @@
+/*
+SSZ encoding of `MerkleProof`, for embedding proofs in consensus-layer messages. The SSZ types are
+
+    class MerklePath(Container):
+        leaf: Bytes32
+        elements: List[Bytes32, MAX_DEPTH]
+        indices: Bitlist[MAX_DEPTH]
+
+    class MerkleProof(Container):
+        version: uint8
+        hash: uint8
+        arity: uint8
+        depth: uint8
+        root: Bytes32
+        payload: Union[MerklePath, List[uint8, MAX_PROOF_BYTES]]
+
+with field elements as their 32-byte little-endian repr and `hash` using the wire format's ids.
+SSZ has exactly one encoding per value, and decoding enforces that: offsets must be the minimal
+ones, bitlists must end in their delimiter bit, and nothing may trail the last field. Only
+serialization is provided, not hash_tree_root.
+*/
+
+use crate::error::Error;
+use crate::serialization::{from_bytes_le_checked, to_bytes_le, CanonicalField};
+use crate::tree::MerklePath;
+use crate::wire::{index_bits, index_from_bit, HashId, MerkleProof, Payload, WIRE_VERSION};
+
+pub const MAX_DEPTH: usize = 255;
+pub const MAX_PROOF_BYTES: usize = 1 << 20;
+
+const OFFSET_LEN: usize = 4;
+// version, hash, arity, depth, root and the payload offset.
+const PROOF_FIXED_LEN: usize = 4 + 32 + OFFSET_LEN;
+// leaf and the offsets of elements and indices.
+const PATH_FIXED_LEN: usize = 32 + 2 * OFFSET_LEN;
+
+fn offset(bytes: &[u8], at: usize) -> Result<usize, Error> {
+    let bytes = bytes
+        .get(at..at + OFFSET_LEN)
+        .ok_or(Error::Malformed("truncated"))?;
+    Ok(u32::from_le_bytes(bytes.try_into().expect("offsets are 4 bytes")) as usize)
+}
+
+fn field<F: CanonicalField>(bytes: &[u8]) -> Result<F, Error> {
+    from_bytes_le_checked(bytes.try_into().expect("field elements are 32 bytes"))
+}
+
+fn encode_bitlist(bits: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![0u8; bits.len() / 8 + 1];
+    for (i, bit) in bits.iter().enumerate() {
+        bytes[i / 8] |= bit << (i % 8);
+    }
+    // The delimiter bit marks the length.
+    bytes[bits.len() / 8] |= 1 << (bits.len() % 8);
+    bytes
+}
+
+fn decode_bitlist(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    let last = *bytes.last().ok_or(Error::Malformed("empty bitlist"))?;
+    if last == 0 {
+        return Err(Error::Malformed("bitlist has no delimiter bit"));
+    }
+    let len = (bytes.len() - 1) * 8 + 7 - last.leading_zeros() as usize;
+    if len > MAX_DEPTH {
+        return Err(Error::Malformed("bitlist too long"));
+    }
+    Ok((0..len).map(|i| (bytes[i / 8] >> (i % 8)) & 1).collect())
+}
+
+fn encode_path<F: CanonicalField>(depth: u8, path: &MerklePath<F>) -> Result<Vec<u8>, Error> {
+    let bits = index_bits(depth, path)?;
+    let elements_len = 32 * path.elements.len();
+    let mut bytes = Vec::with_capacity(PATH_FIXED_LEN + elements_len + bits.len() / 8 + 1);
+    bytes.extend_from_slice(&to_bytes_le(&path.leaf));
+    bytes.extend_from_slice(&(PATH_FIXED_LEN as u32).to_le_bytes());
+    bytes.extend_from_slice(&((PATH_FIXED_LEN + elements_len) as u32).to_le_bytes());
+    for element in &path.elements {
+        bytes.extend_from_slice(&to_bytes_le(element));
+    }
+    bytes.extend(encode_bitlist(&bits));
+    Ok(bytes)
+}
+
+fn decode_path<F: CanonicalField>(
+    bytes: &[u8],
+    arity: u8,
+    depth: u8,
+) -> Result<MerklePath<F>, Error> {
+    if bytes.len() < PATH_FIXED_LEN {
+        return Err(Error::Malformed("truncated"));
+    }
+    let (elements_at, indices_at) = (offset(bytes, 32)?, offset(bytes, 36)?);
+    if elements_at != PATH_FIXED_LEN || indices_at < elements_at || indices_at > bytes.len() {
+        return Err(Error::Malformed("invalid offset"));
+    }
+    let elements = &bytes[elements_at..indices_at];
+    if elements.len() % 32 != 0 || elements.len() / 32 > MAX_DEPTH {
+        return Err(Error::Malformed("invalid element list"));
+    }
+    MerklePath::try_new(
+        arity,
+        depth,
+        field(&bytes[..32])?,
+        elements
+            .chunks(32)
+            .map(field)
+            .collect::<Result<Vec<F>, _>>()?,
+        decode_bitlist(&bytes[indices_at..])?
+            .into_iter()
+            .enumerate()
+            .map(|(layer, bit)| index_from_bit(layer, bit as u64))
+            .collect::<Result<Vec<F>, _>>()?,
+    )
+}
+
+impl<F: CanonicalField> MerkleProof<F> {
+    pub fn to_ssz(&self) -> Result<Vec<u8>, Error> {
+        let (selector, value) = match &self.payload {
+            Payload::Path(path) => (0u8, encode_path(self.depth, path)?),
+            Payload::Proof(proof) => {
+                if proof.len() > MAX_PROOF_BYTES {
+                    return Err(Error::Malformed("proof too long"));
+                }
+                (1u8, proof.clone())
+            }
+        };
+        let mut bytes = Vec::with_capacity(PROOF_FIXED_LEN + 1 + value.len());
+        bytes.extend_from_slice(&[WIRE_VERSION, self.hash.to_byte(), self.arity, self.depth]);
+        bytes.extend_from_slice(&to_bytes_le(&self.root));
+        bytes.extend_from_slice(&(PROOF_FIXED_LEN as u32).to_le_bytes());
+        bytes.push(selector);
+        bytes.extend(value);
+        Ok(bytes)
+    }
+
+    pub fn from_ssz(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < PROOF_FIXED_LEN + 1 {
+            return Err(Error::Malformed("truncated"));
+        }
+        if bytes[0] != WIRE_VERSION {
+            return Err(Error::UnsupportedVersion(bytes[0]));
+        }
+        let (hash, arity, depth) = (HashId::from_byte(bytes[1])?, bytes[2], bytes[3]);
+        if offset(bytes, 36)? != PROOF_FIXED_LEN {
+            return Err(Error::Malformed("invalid offset"));
+        }
+        let value = &bytes[PROOF_FIXED_LEN + 1..];
+        let payload = match bytes[PROOF_FIXED_LEN] {
+            0 => Payload::Path(decode_path(value, arity, depth)?),
+            1 if value.len() <= MAX_PROOF_BYTES => Payload::Proof(value.to_vec()),
+            1 => return Err(Error::Malformed("proof too long")),
+            _ => return Err(Error::Malformed("unknown union selector")),
+        };
+        Ok(Self {
+            hash,
+            arity,
+            depth,
+            root: field(&bytes[4..36])?,
+            payload,
+        })
+    }
+}
+
+mod tests {
+    use super::{decode_bitlist, encode_bitlist};
+    use crate::chips::merkle::DomainSeparation;
+    use crate::tree::MerkleTree;
+    use crate::wire::{HashId, MerkleProof, Payload};
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        // The examples from the SSZ spec: [] is 0x01 and [1, 0, 1] is 0b1101.
+        assert_eq!(encode_bitlist(&[]), vec![0x01]);
+        assert_eq!(encode_bitlist(&[1, 0, 1]), vec![0x0d]);
+        assert_eq!(encode_bitlist(&[0; 8]), vec![0x00, 0x01]);
+        assert_eq!(decode_bitlist(&[0x0d]).unwrap(), vec![1, 0, 1]);
+        assert!(decode_bitlist(&[0x0d, 0x00]).is_err());
+
+        let separation = DomainSeparation::Legacy;
+        let leaves: Vec<Fp> = (0..7u64).map(Fp::from).collect();
+        let tree = MerkleTree::new(3, &leaves, separation).unwrap();
+        let proof = MerkleProof {
+            hash: HashId::Poseidon(separation),
+            arity: 2,
+            depth: 3,
+            root: tree.root(),
+            payload: Payload::Path(tree.path(6).unwrap()),
+        };
+        let bytes = proof.to_ssz().unwrap();
+        assert_eq!(bytes.len(), 40 + 1 + 40 + 3 * 32 + 1);
+        // Index 6 is the bits [0, 1, 1] followed by the delimiter.
+        assert_eq!(*bytes.last().unwrap(), 0b1110);
+        assert_eq!(MerkleProof::<Fp>::from_ssz(&bytes).unwrap(), proof);
+
+        let opaque = MerkleProof::<Fp> {
+            payload: Payload::Proof(vec![1, 2, 3]),
+            ..proof
+        };
+        let opaque_bytes = opaque.to_ssz().unwrap();
+        assert_eq!(&opaque_bytes[40..], &[1, 1, 2, 3]);
+        assert_eq!(MerkleProof::<Fp>::from_ssz(&opaque_bytes).unwrap(), opaque);
+
+        let corrupt = |offset: usize, value: u8| {
+            let mut bytes = bytes.clone();
+            bytes[offset] = value;
+            MerkleProof::<Fp>::from_ssz(&bytes)
+        };
+        assert!(corrupt(0, 2).is_err());
+        assert!(corrupt(36, 41).is_err());
+        assert!(corrupt(40, 2).is_err());
+        // The elements offset, and a bitlist that is one bit short of the depth.
+        assert!(corrupt(41 + 32, 44).is_err());
+        assert!(corrupt(bytes.len() - 1, 0b0110).is_err());
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(MerkleProof::<Fp>::from_ssz(&trailing).is_err());
+    }
+}
@@ -0,0 +1,168 @@
+/*
+Tamper rejection: given a proof and the instances it verifies against, derive a battery of
+tampered pairs and check that every one of them is rejected. `tampers` lists them in a fixed order:
+
+  - one bit flipped in every 32-byte word of the proof, each word being a point or a scalar of the
+    transcript, with the bit moving through the word so the battery isn't all low bits,
+  - the proof with its last word dropped,
+  - every instance row shifted by one, which covers an altered root or leaf,
+  - every pair of rows in a column swapped, when the two values differ.
+
+`check_tampering` runs the battery through `proving::verify`; `check_tampering_with` takes any
+verifier, so an app can run it against the key and verifier it deploys. Both first check that the
+untampered pair verifies, since otherwise every rejection is vacuous.
+*/
+
+use crate::error::Error;
+use crate::proving::verify;
+use halo2_proofs::{
+    pasta::{EqAffine, Fp},
+    plonk::VerifyingKey,
+    poly::commitment::Params,
+};
+
+pub const WORD: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tamper {
+    FlipBit { byte: usize, bit: u8 },
+    // The proof loses its last word.
+    Truncated,
+    // The instance at this row has one added to it.
+    ShiftedInstance { column: usize, row: usize },
+    SwappedRows { column: usize, a: usize, b: usize },
+}
+
+impl Tamper {
+    pub fn apply(&self, proof: &[u8], instances: &[Vec<Fp>]) -> (Vec<u8>, Vec<Vec<Fp>>) {
+        let mut proof = proof.to_vec();
+        let mut instances = instances.to_vec();
+        match *self {
+            Tamper::FlipBit { byte, bit } => proof[byte] ^= 1 << bit,
+            Tamper::Truncated => proof.truncate(proof.len().saturating_sub(WORD)),
+            Tamper::ShiftedInstance { column, row } => instances[column][row] += Fp::one(),
+            Tamper::SwappedRows { column, a, b } => instances[column].swap(a, b),
+        }
+        (proof, instances)
+    }
+}
+
+// Every tamper of a proof of `proof_len` bytes and of `instances`, in a fixed order.
+pub fn tampers(proof_len: usize, instances: &[Vec<Fp>]) -> Vec<Tamper> {
+    let mut tampers: Vec<Tamper> = (0..proof_len / WORD)
+        .map(|word| Tamper::FlipBit {
+            byte: word * WORD + word % WORD,
+            bit: (word % 8) as u8,
+        })
+        .collect();
+    if proof_len > 0 {
+        tampers.push(Tamper::Truncated);
+    }
+    for (column, values) in instances.iter().enumerate() {
+        tampers.extend((0..values.len()).map(|row| Tamper::ShiftedInstance { column, row }));
+        for a in 0..values.len() {
+            tampers.extend(
+                (a + 1..values.len())
+                    .filter(|b| values[a] != values[*b])
+                    .map(|b| Tamper::SwappedRows { column, a, b }),
+            );
+        }
+    }
+    tampers
+}
+
+// Checks that `verify` accepts the pair and rejects every tamper of it, returning how many were
+// run. Fails with the untampered pair's error, or with the first tamper that was accepted.
+pub fn check_tampering_with(
+    proof: &[u8],
+    instances: &[Vec<Fp>],
+    mut verify: impl FnMut(&[u8], &[Vec<Fp>]) -> Result<(), Error>,
+) -> Result<usize, Error> {
+    verify(proof, instances)?;
+    let tampers = tampers(proof.len(), instances);
+    for tamper in &tampers {
+        let (proof, instances) = tamper.apply(proof, instances);
+        if verify(&proof, &instances).is_ok() {
+            return Err(Error::TamperAccepted(*tamper));
+        }
+    }
+    Ok(tampers.len())
+}
+
+pub fn check_tampering(
+    params: &Params<EqAffine>,
+    vk: &VerifyingKey<EqAffine>,
+    proof: &[u8],
+    instances: &[Vec<Fp>],
+) -> Result<usize, Error> {
+    check_tampering_with(proof, instances, |proof, instances| {
+        verify(params, vk, proof, instances)
+    })
+}
+
+#[cfg(all(feature = "poseidon", feature = "dummy-hash"))]
+mod tests {
+    use super::{check_tampering, check_tampering_with, tampers, Tamper, WORD};
+    use crate::builder::Hasher;
+    use crate::chips::merkle::DomainSeparation;
+    use crate::config::ProverConfig;
+    use crate::error::Error;
+    use crate::membership::MembershipProver;
+    use crate::proving::{seeded_rng, TEST_SEED};
+    use crate::tree::MerkleTree;
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let leaves: Vec<Fp> = (0..11u64).map(Fp::from).collect();
+        let tree = MerkleTree::new(4, &leaves, DomainSeparation::Legacy).unwrap();
+        let prover =
+            MembershipProver::new(tree, ProverConfig::new(10, 4, Hasher::Poseidon)).unwrap();
+        let proof = prover.prove_with_rng(5, seeded_rng(TEST_SEED)).unwrap();
+        let instances = prover.instances(proof.leaf, proof.root).unwrap();
+
+        // Leaf and root rows of the first column, and the Poseidon chip's empty column.
+        let battery = tampers(proof.bytes.len(), &instances);
+        let words = proof.bytes.len() / WORD;
+        assert_eq!(battery.len(), words + 1 + 2 + 1);
+        assert!(battery.contains(&Tamper::SwappedRows {
+            column: 0,
+            a: 0,
+            b: 1
+        }));
+        let count = check_tampering(
+            prover.params(),
+            prover.verifying_key(),
+            &proof.bytes,
+            &instances,
+        )
+        .unwrap();
+        assert_eq!(count, battery.len());
+
+        // A proof for other instances is refused before the battery runs.
+        let wrong = prover.instances(Fp::from(6), proof.root).unwrap();
+        assert!(matches!(
+            check_tampering(
+                prover.params(),
+                prover.verifying_key(),
+                &proof.bytes,
+                &wrong
+            ),
+            Err(Error::Plonk(_))
+        ));
+
+        // A verifier that ignores the proof bytes is caught by the first bit flip.
+        let honest = instances.clone();
+        let lax = check_tampering_with(&proof.bytes, &instances, |_, instances| {
+            if instances == honest.as_slice() {
+                Ok(())
+            } else {
+                Err(Error::RootMismatch)
+            }
+        });
+        assert!(matches!(
+            lax,
+            Err(Error::TamperAccepted(Tamper::FlipBit { byte: 0, bit: 0 }))
+        ));
+    }
+}
@@ -0,0 +1,532 @@
+/*
+Reusable property-test support: proptest strategies for random trees and paths, and checks that
+MerkleTreeV3Circuit agrees with the native tree. `check_accepts` proves a random honest path and
+`check_rejects` applies one entry of the `Mutation` catalog to the witness or the public inputs,
+which the circuit must then refuse. `witnessed_root` returns the root a hash chip's `merkle_prove`
+computes, to compare with the native one. Downstream crates can enable the `test-utils` feature to
+run the same properties against their own wrappers.
+
+For a single hand-written case, `assert_membership_circuit(depth, &tree, index)` does the whole
+MockProver round trip (witness from the tree, smallest k, instances in layout order) and
+`assert_membership_rejects` does the same for one mutation, expecting failure.
+
+`check_exhaustive(depth, separation)` is the systematic counterpart for small trees: it fills a
+tree of that depth and, for every leaf position and every entry of `catalog(depth)`, compares the
+exact set of failures MockProver reports (which gate constraints, in which layer, and the instance
+rows whose copy constraints break) against `expected`.
+*/
+
+use crate::chips::merkle::{DomainSeparation, HashInstructions, MerkleChip, MerkleConfig};
+use crate::circuits::merkle_v3::{MerkleTreeV3Circuit, RootExposure};
+use crate::coverage::{assert_mock_satisfied, mock_verify};
+use crate::diagnostics::{constraint_index, quoted_after};
+use crate::error;
+use crate::estimate::min_k;
+use crate::instance::{InstanceLayout, LeafExposure};
+use crate::tree::{MerklePath, MerkleTree};
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    dev::{FailureLocation, MockProver, VerifyFailure},
+    pasta::Fp,
+    plonk::{self, Circuit, ConstraintSystem},
+};
+use proptest::prelude::*;
+use proptest::test_runner::TestCaseError;
+use std::{cell::Cell, collections::BTreeSet, marker::PhantomData};
+
+// Enough rows for a depth-6 path with `DomainSeparation::Level`.
+pub const K: u32 = 11;
+pub const MAX_DEPTH: usize = 6;
+
+#[derive(Debug, Clone)]
+pub struct TreeCase {
+    pub depth: usize,
+    pub leaves: Vec<Fp>,
+    pub index: usize,
+    pub separation: DomainSeparation,
+}
+
+impl TreeCase {
+    pub fn tree(&self) -> MerkleTree {
+        MerkleTree::new(self.depth, &self.leaves, self.separation).expect("leaves fit the depth")
+    }
+
+    pub fn path(&self) -> MerklePath {
+        self.tree().path(self.index).expect("index is populated")
+    }
+}
+
+// Ways to corrupt an honest proof. Layers count from the leaf.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mutation {
+    // The witnessed leaf no longer matches the public one.
+    Leaf,
+    Sibling(usize),
+    // The index bit is flipped, i.e. the sibling is hashed on the wrong side.
+    Index(usize),
+    // The index bit is neither 0 nor 1.
+    NonBooleanIndex(usize),
+    PublicLeaf,
+    PublicRoot,
+    // The circuit hashes with the other domain separation than the tree.
+    Separation,
+    // The siblings at this layer and the next trade places.
+    SwappedSiblings(usize),
+    // The path loses its top layer.
+    Truncated,
+    // The leaf and root are given in each other's instance rows.
+    SwappedRows,
+}
+
+// Every mutation that applies to a path of `depth` layers, in a fixed order.
+pub fn catalog(depth: usize) -> Vec<Mutation> {
+    let mut mutations = vec![Mutation::Leaf];
+    for layer in 0..depth {
+        mutations.push(Mutation::Sibling(layer));
+        mutations.push(Mutation::Index(layer));
+        mutations.push(Mutation::NonBooleanIndex(layer));
+    }
+    mutations.extend((0..depth.saturating_sub(1)).map(Mutation::SwappedSiblings));
+    mutations.extend([
+        Mutation::PublicLeaf,
+        Mutation::PublicRoot,
+        Mutation::Separation,
+        Mutation::Truncated,
+        Mutation::SwappedRows,
+    ]);
+    mutations
+}
+
+// One failure in a MockProver report.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Failure {
+    // Synthesis returned an error, so there is nothing to verify.
+    Synthesis,
+    // A gate constraint, by gate name and index within the gate, with the layer of a Merkle layer
+    // region.
+    Gate {
+        gate: String,
+        constraint: usize,
+        layer: Option<usize>,
+    },
+    // A copy constraint, e.g. a public input that differs from the computed cell. MockProver
+    // reports both ends of a broken copy: the public input by its instance row, and the computed
+    // cell, which it only locates by region offset, as None.
+    Copy {
+        row: Option<usize>,
+    },
+    // Anything else, such as a lookup or an unassigned cell, by its Display output.
+    Other(String),
+}
+
+impl Failure {
+    fn gate(gate: &str, constraint: usize, layer: usize) -> Self {
+        Failure::Gate {
+            gate: gate.to_string(),
+            constraint,
+            layer: Some(layer),
+        }
+    }
+
+    fn from_verify(failure: &VerifyFailure) -> Self {
+        match failure {
+            VerifyFailure::Permutation { location, .. } => Failure::Copy {
+                row: match location {
+                    FailureLocation::OutsideRegion { row } => Some(*row),
+                    FailureLocation::InRegion { .. } => None,
+                },
+            },
+            _ => Self::parse(&failure.to_string()),
+        }
+    }
+
+    // Classifies a failure from its Display output, as `diagnostics` does.
+    fn parse(raw: &str) -> Self {
+        match quoted_after(raw, "in gate ") {
+            Some(gate) => Failure::Gate {
+                gate: gate.to_string(),
+                constraint: constraint_index(raw).unwrap_or(usize::MAX),
+                layer: quoted_after(raw, "Region ")
+                    .and_then(|region| region.strip_prefix("layer "))
+                    .and_then(|layer| layer.parse().ok()),
+            },
+            None => Failure::Other(raw.to_string()),
+        }
+    }
+}
+
+// The failures MockProver reports for `circuit` at k = K; empty if it accepts.
+pub fn outcome<C: Circuit<Fp>>(circuit: &C, instances: Vec<Vec<Fp>>) -> BTreeSet<Failure> {
+    match mock_verify(K, circuit, instances) {
+        Ok(Ok(())) => BTreeSet::new(),
+        Ok(Err(failures)) => failures.iter().map(Failure::from_verify).collect(),
+        Err(_) => BTreeSet::from([Failure::Synthesis]),
+    }
+}
+
+// The failures `mutation` must cause on the honest `path`; `None` is the honest path itself. A
+// mutation breaks the copy into the leaf row if it changes the witnessed or public leaf, and into
+// the root row if it changes the computed or public root. A non-boolean index bit breaks both swap
+// constraints of its layer, and moves the root only if the honest bit was 0, since the chip swaps
+// the children for any nonzero bit.
+pub fn expected(path: &MerklePath, mutation: Option<Mutation>) -> BTreeSet<Failure> {
+    let layout = InstanceLayout::membership(LeafExposure::Instance, true);
+    let (leaf, root) = (layout.leaf, layout.root);
+    let (mut failures, rows) = match mutation {
+        None => (BTreeSet::new(), vec![]),
+        Some(Mutation::NonBooleanIndex(layer)) => (
+            BTreeSet::from([
+                Failure::gate("swap", 0, layer),
+                Failure::gate("swap", 1, layer),
+            ]),
+            if path.indices[layer] == Fp::zero() {
+                vec![root]
+            } else {
+                vec![]
+            },
+        ),
+        Some(Mutation::PublicLeaf) => (BTreeSet::new(), vec![leaf]),
+        Some(Mutation::Leaf | Mutation::SwappedRows) => (BTreeSet::new(), vec![leaf, root]),
+        Some(_) => (BTreeSet::new(), vec![root]),
+    };
+    for row in rows {
+        failures.insert(Failure::Copy { row });
+        failures.insert(Failure::Copy { row: None });
+    }
+    failures
+}
+
+pub fn tree_case(max_depth: usize) -> impl Strategy<Value = TreeCase> {
+    (1..=max_depth)
+        .prop_flat_map(|depth| {
+            let capacity = 1usize << depth;
+            (
+                Just(depth),
+                prop::collection::vec(any::<u64>(), 1..=capacity),
+                prop_oneof![
+                    Just(DomainSeparation::Legacy),
+                    Just(DomainSeparation::Level)
+                ],
+            )
+        })
+        .prop_flat_map(|(depth, leaves, separation)| {
+            let len = leaves.len();
+            (Just(depth), Just(leaves), 0..len, Just(separation))
+        })
+        .prop_map(|(depth, leaves, index, separation)| TreeCase {
+            depth,
+            leaves: leaves.into_iter().map(Fp::from).collect(),
+            index,
+            separation,
+        })
+}
+
+pub fn mutation(depth: usize) -> impl Strategy<Value = Mutation> {
+    prop_oneof![
+        Just(Mutation::Leaf),
+        (0..depth).prop_map(Mutation::Sibling),
+        (0..depth).prop_map(Mutation::Index),
+        (0..depth).prop_map(Mutation::NonBooleanIndex),
+        Just(Mutation::PublicLeaf),
+        Just(Mutation::PublicRoot),
+        Just(Mutation::Separation),
+    ]
+}
+
+// A tree case together with a mutation that applies to it.
+pub fn mutated_case(max_depth: usize) -> impl Strategy<Value = (TreeCase, Mutation)> {
+    tree_case(max_depth).prop_flat_map(|case| {
+        let depth = case.depth;
+        (Just(case), mutation(depth))
+    })
+}
+
+// Whether the MockProver accepts, treating synthesis errors as rejection.
+pub fn accepts<C: Circuit<Fp>>(circuit: &C, instances: Vec<Vec<Fp>>) -> bool {
+    matches!(mock_verify(K, circuit, instances), Ok(Ok(())))
+}
+
+// Builds the circuit without `from_values`, so that malformed witnesses reach synthesis.
+fn circuit(path: &MerklePath, separation: DomainSeparation) -> MerkleTreeV3Circuit {
+    MerkleTreeV3Circuit {
+        leaf: Value::known(path.leaf),
+        elements: path.elements.iter().map(|x| Value::known(*x)).collect(),
+        indices: path.indices.iter().map(|x| Value::known(*x)).collect(),
+        separation,
+        root: RootExposure::Instance,
+        leaf_exposure: LeafExposure::Instance,
+        blinding: Value::unknown(),
+        preimage: None,
+    }
+}
+
+fn instances(leaf: Fp, root: Fp) -> Vec<Vec<Fp>> {
+    vec![vec![leaf, root], vec![]]
+}
+
+pub fn check_accepts(case: &TreeCase) -> Result<(), TestCaseError> {
+    let tree = case.tree();
+    let path = case.path();
+    prop_assert_eq!(path.root(case.separation), tree.root());
+    prop_assert!(accepts(
+        &circuit(&path, case.separation),
+        instances(path.leaf, tree.root())
+    ));
+    Ok(())
+}
+
+// The circuit and instances for `path` in `tree` after applying `mutation`.
+fn mutate(
+    tree: &MerkleTree,
+    path: &MerklePath,
+    mutation: Mutation,
+) -> (MerkleTreeV3Circuit, Vec<Vec<Fp>>) {
+    let mut path = path.clone();
+    let mut separation = tree.separation();
+    let (mut public_leaf, mut public_root) = (path.leaf, tree.root());
+    match mutation {
+        Mutation::Leaf => path.leaf += Fp::one(),
+        Mutation::Sibling(layer) => path.elements[layer] += Fp::one(),
+        Mutation::Index(layer) => path.indices[layer] = Fp::one() - path.indices[layer],
+        Mutation::NonBooleanIndex(layer) => path.indices[layer] = Fp::from(2),
+        Mutation::PublicLeaf => public_leaf += Fp::one(),
+        Mutation::PublicRoot => public_root += Fp::one(),
+        Mutation::Separation => {
+            separation = match separation {
+                DomainSeparation::Legacy => DomainSeparation::Level,
+                DomainSeparation::Level => DomainSeparation::Legacy,
+            }
+        }
+        Mutation::SwappedSiblings(layer) => path.elements.swap(layer, layer + 1),
+        Mutation::Truncated => {
+            path.elements.pop();
+            path.indices.pop();
+        }
+        Mutation::SwappedRows => (public_leaf, public_root) = (public_root, public_leaf),
+    }
+    (
+        circuit(&path, separation),
+        instances(public_leaf, public_root),
+    )
+}
+
+pub fn check_rejects(case: &TreeCase, mutation: Mutation) -> Result<(), TestCaseError> {
+    let (circuit, instances) = mutate(&case.tree(), &case.path(), mutation);
+    prop_assert!(!accepts(&circuit, instances));
+    Ok(())
+}
+
+// The honest path to leaf `index` of `tree` and the smallest k its circuit fits in.
+fn membership(depth: usize, tree: &MerkleTree, index: usize) -> (MerklePath, u32) {
+    assert_eq!(depth, tree.depth(), "tree has depth {}", tree.depth());
+    let path = tree
+        .path(index)
+        .unwrap_or_else(|err| panic!("no path for leaf {}: {}", index, err));
+    let k = min_k(
+        &circuit(&path, tree.separation()),
+        instances(path.leaf, tree.root()),
+    )
+    .unwrap_or_else(|err| panic!("depth {} does not fit: {}", depth, err));
+    (path, k)
+}
+
+// Asserts that MerkleTreeV3Circuit proves membership of leaf `index` of `tree`.
+pub fn assert_membership_circuit(depth: usize, tree: &MerkleTree, index: usize) {
+    let (path, k) = membership(depth, tree, index);
+    assert_mock_satisfied(
+        k,
+        &circuit(&path, tree.separation()),
+        instances(path.leaf, tree.root()),
+    );
+}
+
+// Asserts that MerkleTreeV3Circuit refuses leaf `index` of `tree` once `mutation` is applied.
+pub fn assert_membership_rejects(
+    depth: usize,
+    tree: &MerkleTree,
+    index: usize,
+    mutation: Mutation,
+) {
+    let (path, k) = membership(depth, tree, index);
+    let (circuit, instances) = mutate(tree, &path, mutation);
+    let accepted = matches!(mock_verify(k, &circuit, instances), Ok(Ok(())));
+    assert!(!accepted, "{:?} was accepted for leaf {}", mutation, index);
+}
+
+// Checks every leaf of a full tree of `depth` against every mutation in `catalog(depth)`,
+// panicking with the first outcome that differs from `expected`.
+pub fn check_exhaustive(depth: usize, separation: DomainSeparation) {
+    let leaves: Vec<Fp> = (0..1u64 << depth).map(|i| Fp::from(1000 + i)).collect();
+    let tree = MerkleTree::new(depth, &leaves, separation).expect("leaves fit the depth");
+    for index in 0..leaves.len() {
+        let path = tree.path(index).expect("index is populated");
+        let honest = outcome(
+            &circuit(&path, separation),
+            instances(path.leaf, tree.root()),
+        );
+        assert_eq!(
+            honest,
+            expected(&path, None),
+            "honest path to leaf {}",
+            index
+        );
+        for mutation in catalog(depth) {
+            let (circuit, instances) = mutate(&tree, &path, mutation);
+            assert_eq!(
+                outcome(&circuit, instances),
+                expected(&path, Some(mutation)),
+                "{:?} on leaf {} at depth {}",
+                mutation,
+                index,
+                depth
+            );
+        }
+    }
+}
+
+// `merkle_prove` alone, recording the root it computes.
+struct RootCircuit<F: FieldExt, H: HashInstructions<F>> {
+    separation: DomainSeparation,
+    leaf: Value<F>,
+    elements: Vec<Value<F>>,
+    indices: Vec<Value<F>>,
+    root: Cell<Option<F>>,
+    _marker: PhantomData<H>,
+}
+
+impl<F: FieldExt, H: HashInstructions<F>> Circuit<F> for RootCircuit<F, H> {
+    type Config = MerkleConfig<H::Config>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            separation: self.separation,
+            leaf: Value::unknown(),
+            elements: vec![Value::unknown(); self.elements.len()],
+            indices: vec![Value::unknown(); self.indices.len()],
+            root: Cell::new(None),
+            _marker: PhantomData,
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let advice = [(); 3].map(|_| meta.advice_column());
+        let instance = meta.instance_column();
+        MerkleChip::<F, H>::configure(meta, advice, instance)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), plonk::Error> {
+        let chip = MerkleChip::<F, H>::construct(config).with_separation(self.separation);
+        let leaf = chip.load_private(layouter.namespace(|| "load leaf"), self.leaf)?;
+        let root = chip.merkle_prove(
+            layouter.namespace(|| "merkle_prove"),
+            &leaf,
+            &self.elements,
+            &self.indices,
+        )?;
+        self.root.set(root.known());
+        Ok(())
+    }
+}
+
+// Lays out `merkle_prove` on MockProver at `k` and returns the root the chip computed, for checking
+// a hash chip's `MerkleOutput` against a natively computed root.
+pub fn witnessed_root<F: FieldExt, H: HashInstructions<F>>(
+    k: u32,
+    separation: DomainSeparation,
+    leaf: F,
+    elements: &[F],
+    indices: &[F],
+) -> Result<F, error::Error> {
+    let known = |values: &[F]| values.iter().map(|x| Value::known(*x)).collect();
+    let circuit = RootCircuit::<F, H> {
+        separation,
+        leaf: Value::known(leaf),
+        elements: known(elements),
+        indices: known(indices),
+        root: Cell::new(None),
+        _marker: PhantomData,
+    };
+    let mut cs = ConstraintSystem::default();
+    RootCircuit::<F, H>::configure(&mut cs);
+    MockProver::run(k, &circuit, vec![vec![]; cs.num_instance_columns()])?;
+    circuit
+        .root
+        .get()
+        .ok_or(error::Error::Plonk(plonk::Error::Synthesis))
+}
+
+mod tests {
+    use super::{
+        assert_membership_circuit, assert_membership_rejects, check_accepts, check_exhaustive,
+        check_rejects, mutated_case, tree_case, witnessed_root, Mutation, MAX_DEPTH,
+    };
+    use crate::chips::hash_2::Hash2Chip;
+    use crate::chips::merkle::DomainSeparation;
+    use crate::chips::poseidon::PoseidonChip;
+    use crate::tree::{compute_root, MerkleTree};
+    use halo2_gadgets::poseidon::primitives::P128Pow5T3 as OrchardNullifier;
+    use halo2_proofs::pasta::Fp;
+    use proptest::prelude::*;
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(8))]
+
+        #[test]
+        fn test(case in tree_case(MAX_DEPTH), (mutated, mutation) in mutated_case(MAX_DEPTH)) {
+            check_accepts(&case)?;
+            check_rejects(&mutated, mutation)?;
+        }
+    }
+
+    #[test]
+    fn test_membership_helpers() {
+        let leaves: Vec<Fp> = (0..5u64).map(Fp::from).collect();
+        let tree = MerkleTree::new(3, &leaves, DomainSeparation::Level).unwrap();
+        assert_membership_circuit(3, &tree, 4);
+        assert_membership_rejects(3, &tree, 4, Mutation::Sibling(1));
+        assert_membership_rejects(3, &tree, 4, Mutation::PublicRoot);
+    }
+
+    #[test]
+    fn test_witnessed_root() {
+        let leaf = Fp::from(99);
+        let elements: Vec<Fp> = [1u64, 5, 6, 9, 9].map(Fp::from).to_vec();
+        let indices: Vec<Fp> = [0u64, 1, 0, 1, 1].map(Fp::from).to_vec();
+        for separation in [DomainSeparation::Legacy, DomainSeparation::Level] {
+            assert_eq!(
+                witnessed_root::<Fp, PoseidonChip<Fp, OrchardNullifier, 3, 2, 2>>(
+                    10, separation, leaf, &elements, &indices
+                )
+                .unwrap(),
+                compute_root(separation, leaf, &elements, &indices)
+            );
+        }
+        // The placeholder Hash2 is a + b, so its root is the leaf plus every sibling.
+        assert_eq!(
+            witnessed_root::<Fp, Hash2Chip<Fp>>(
+                10,
+                DomainSeparation::Legacy,
+                leaf,
+                &elements,
+                &indices
+            )
+            .unwrap(),
+            elements.iter().fold(leaf, |acc, x| acc + x)
+        );
+    }
+
+    // Every position of depth 2-4 trees. The separation alternates by depth to keep the run short
+    // while covering both hash layouts.
+    #[test]
+    fn test_exhaustive() {
+        check_exhaustive(2, DomainSeparation::Legacy);
+        check_exhaustive(3, DomainSeparation::Level);
+        check_exhaustive(4, DomainSeparation::Legacy);
+    }
+}
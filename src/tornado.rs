@@ -0,0 +1,222 @@
+/*
+Tornado Cash classic's commitment tree, reproduced natively: MiMCSponge over the bn254 scalar field
+(220 rounds, exponent 5, circomlib's constants), the zero leaf keccak256("tornado") mod p, and
+depth 20. Node hashing is Tornado's hashLeftRight: absorb the left child, permute, add the right
+child, permute and take the left output. Paths use this crate's convention, index bit 0 meaning the
+node is the left child, which is also Tornado's pathIndices.
+
+Leaves are the deposit commitments as they appear in the contract's Deposit events; the Pedersen
+commitment over Baby Jubjub is not reimplemented. MiMCSponge is defined over bn254 and the circuits
+here are over pasta, so the tree is native only.
+*/
+
+use crate::error::Error;
+use crate::tree::MerklePath;
+use ff::{Field, PrimeField};
+use std::sync::OnceLock;
+use tiny_keccak::{Hasher, Keccak};
+
+#[derive(PrimeField)]
+#[PrimeFieldModulus = "21888242871839275222246405745257275088548364400416034343698204186575808495617"]
+#[PrimeFieldGenerator = "7"]
+#[PrimeFieldReprEndianness = "little"]
+pub struct Bn254Fr([u64; 4]);
+
+pub const LEVELS: usize = 20;
+pub const ROUNDS: usize = 220;
+
+pub(crate) fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    hasher.update(data);
+    let mut out = [0u8; 32];
+    hasher.finalize(&mut out);
+    out
+}
+
+// A big-endian 32-byte value reduced mod p, as Solidity's `uint256(x) % FIELD_SIZE`.
+fn reduce(bytes: &[u8; 32]) -> Bn254Fr {
+    bytes.iter().fold(Bn254Fr::ZERO, |acc, byte| {
+        acc * Bn254Fr::from(256) + Bn254Fr::from(*byte as u64)
+    })
+}
+
+pub fn from_bytes_be(bytes: &[u8; 32]) -> Result<Bn254Fr, Error> {
+    let mut repr = *bytes;
+    repr.reverse();
+    Option::from(Bn254Fr::from_repr(Bn254FrRepr(repr))).ok_or(Error::NonCanonical)
+}
+
+pub fn to_bytes_be(value: &Bn254Fr) -> [u8; 32] {
+    let mut bytes = value.to_repr().0;
+    bytes.reverse();
+    bytes
+}
+
+// circomlib's round constants: c_i = keccak256 applied i + 1 times to "mimcsponge", mod p, with
+// the first and last constants zero.
+fn constants() -> &'static [Bn254Fr; ROUNDS] {
+    static CONSTANTS: OnceLock<[Bn254Fr; ROUNDS]> = OnceLock::new();
+    CONSTANTS.get_or_init(|| {
+        let mut constants = [Bn254Fr::ZERO; ROUNDS];
+        let mut seed = keccak256(b"mimcsponge");
+        for constant in constants.iter_mut().take(ROUNDS - 1).skip(1) {
+            seed = keccak256(&seed);
+            *constant = reduce(&seed);
+        }
+        constants
+    })
+}
+
+// The MiMC Feistel permutation with key 0, as MiMCSponge(xL, xR) in Tornado's Hasher contract.
+pub fn mimc_sponge(mut left: Bn254Fr, mut right: Bn254Fr) -> (Bn254Fr, Bn254Fr) {
+    for (round, constant) in constants().iter().enumerate() {
+        let t = left + constant;
+        let t5 = t.square().square() * t;
+        if round < ROUNDS - 1 {
+            (left, right) = (right + t5, left);
+        } else {
+            right += t5;
+        }
+    }
+    (left, right)
+}
+
+pub fn hash_left_right(left: Bn254Fr, right: Bn254Fr) -> Bn254Fr {
+    let (state, capacity) = mimc_sponge(left, Bn254Fr::ZERO);
+    mimc_sponge(state + right, capacity).0
+}
+
+// zeros[level] is the root of an empty subtree of height `level`, MerkleTreeWithHistory.zeros.
+pub fn zeros() -> &'static [Bn254Fr; LEVELS + 1] {
+    static ZEROS: OnceLock<[Bn254Fr; LEVELS + 1]> = OnceLock::new();
+    ZEROS.get_or_init(|| {
+        let mut zeros = [reduce(&keccak256(b"tornado")); LEVELS + 1];
+        for level in 1..=LEVELS {
+            zeros[level] = hash_left_right(zeros[level - 1], zeros[level - 1]);
+        }
+        zeros
+    })
+}
+
+// The tree a Tornado instance holds after the given deposits, in deposit order.
+#[derive(Debug, Clone)]
+pub struct TornadoTree {
+    // layers[0] holds the commitments and layers[LEVELS] the root.
+    layers: Vec<Vec<Bn254Fr>>,
+}
+
+impl TornadoTree {
+    pub fn new(commitments: &[Bn254Fr]) -> Result<Self, Error> {
+        if commitments.len() > 1 << LEVELS {
+            return Err(Error::TooManyLeaves {
+                capacity: 1 << LEVELS,
+                leaves: commitments.len(),
+            });
+        }
+        let mut layers = vec![commitments.to_vec()];
+        for level in 0..LEVELS {
+            let next = layers[level]
+                .chunks(2)
+                .map(|pair| {
+                    hash_left_right(pair[0], pair.get(1).copied().unwrap_or(zeros()[level]))
+                })
+                .collect();
+            layers.push(next);
+        }
+        Ok(Self { layers })
+    }
+
+    pub fn commitments(&self) -> &[Bn254Fr] {
+        &self.layers[0]
+    }
+
+    pub fn root(&self) -> Bn254Fr {
+        self.layers[LEVELS]
+            .first()
+            .copied()
+            .unwrap_or(zeros()[LEVELS])
+    }
+
+    // The pathElements/pathIndices a withdrawal proof takes for the deposit at `index`.
+    pub fn path(&self, index: usize) -> Result<MerklePath<Bn254Fr>, Error> {
+        let leaf = *self.layers[0].get(index).ok_or(Error::IndexOutOfRange {
+            index,
+            leaves: self.layers[0].len(),
+        })?;
+        let elements = (0..LEVELS)
+            .map(|level| {
+                self.layers[level]
+                    .get((index >> level) ^ 1)
+                    .copied()
+                    .unwrap_or(zeros()[level])
+            })
+            .collect();
+        let indices = (0..LEVELS)
+            .map(|level| Bn254Fr::from(((index >> level) & 1) as u64))
+            .collect();
+        Ok(MerklePath {
+            leaf,
+            elements,
+            indices,
+        })
+    }
+}
+
+pub fn compute_root(path: &MerklePath<Bn254Fr>) -> Bn254Fr {
+    path.elements
+        .iter()
+        .zip(path.indices.iter())
+        .fold(path.leaf, |node, (element, index)| {
+            if index.is_zero_vartime() {
+                hash_left_right(node, *element)
+            } else {
+                hash_left_right(*element, node)
+            }
+        })
+}
+
+mod tests {
+    use super::{compute_root, from_bytes_be, to_bytes_be, zeros, Bn254Fr, TornadoTree, LEVELS};
+
+    fn hex(value: &Bn254Fr) -> String {
+        to_bytes_be(value)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    #[test]
+    fn test() {
+        // The first zeros of Tornado's MerkleTreeWithHistory contract.
+        let published = [
+            "2fe54c60d3acabf3343a35b6eba15db4821b340f76e741e2249685ed4899af6c",
+            "256a6135777eee2fd26f54b8b7037a25439d5235caee224154186d2b8a52e31d",
+            "1151949895e82ab19924de92c40a3d6f7bcb60d92b00504b8199613683f0c200",
+            "20121ee811489ff8d61f09fb89e313f14959a0f28bb428a20dba6b0b068b3bdb",
+            "0a89ca6ffa14cc462cfedb842c30ed221a50a3d6bf022a6a57dc82ab24c157c9",
+        ];
+        for (level, expected) in published.iter().enumerate() {
+            assert_eq!(hex(&zeros()[level]), *expected);
+        }
+        assert_eq!(TornadoTree::new(&[]).unwrap().root(), zeros()[LEVELS]);
+
+        let commitments: Vec<Bn254Fr> = (1..=5u64).map(Bn254Fr::from).collect();
+        let tree = TornadoTree::new(&commitments).unwrap();
+        // From a separate MiMCSponge written from circomlib's constants, which
+        // reproduces the published zeros above.
+        assert_eq!(
+            hex(&tree.root()),
+            "1815d9b4574f8c0be87e830f58a3b6dc7850b1905a25a2bcffd634ea1a869899"
+        );
+        for index in 0..commitments.len() {
+            let path = tree.path(index).unwrap();
+            assert_eq!(path.elements.len(), LEVELS);
+            assert_eq!(compute_root(&path), tree.root());
+        }
+        assert!(tree.path(5).is_err());
+
+        let bytes = to_bytes_be(&tree.root());
+        assert_eq!(from_bytes_be(&bytes).unwrap(), tree.root());
+        assert!(from_bytes_be(&[0xff; 32]).is_err());
+    }
+}
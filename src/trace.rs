@@ -0,0 +1,229 @@
+/*
+Per-region synthesis logging. `Traced(circuit)` is the same circuit laid out through
+`TracingFloorPlanner`, which wraps the floor planner's assignment backend and emits one `tracing`
+event per region with its name, first row, rows used, cells assigned and the time spent assigning
+it. The constraint system is unchanged, so a traced circuit has the same keys and proofs:
+
+    let prover = MockProver::run(k, &Traced(circuit), instances)?;
+
+Region names carry the layer for Merkle regions ("layer 7"), so the events show directly whether
+Poseidon or the swap regions dominate a deep path.
+*/
+
+use halo2_proofs::{
+    arithmetic::Field,
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    plonk::{
+        Advice, Any, Assigned, Assignment, Circuit, Column, ConstraintSystem, Error, Fixed,
+        FloorPlanner, Instance, Selector,
+    },
+};
+use std::marker::PhantomData;
+use std::time::Instant;
+
+struct RegionStats {
+    name: String,
+    start: Instant,
+    first_row: Option<usize>,
+    last_row: usize,
+    cells: usize,
+}
+
+struct TracingAssignment<'a, CS> {
+    inner: &'a mut CS,
+    region: Option<RegionStats>,
+}
+
+impl<'a, CS> TracingAssignment<'a, CS> {
+    fn touch(&mut self, row: usize) {
+        if let Some(stats) = self.region.as_mut() {
+            stats.first_row = Some(stats.first_row.map_or(row, |first| first.min(row)));
+            stats.last_row = stats.last_row.max(row);
+            stats.cells += 1;
+        }
+    }
+}
+
+impl<'a, F: Field, CS: Assignment<F>> Assignment<F> for TracingAssignment<'a, CS> {
+    fn enter_region<NR, N>(&mut self, name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        let name: String = name_fn().into();
+        self.region = Some(RegionStats {
+            name: name.clone(),
+            start: Instant::now(),
+            first_row: None,
+            last_row: 0,
+            cells: 0,
+        });
+        self.inner.enter_region(|| name);
+    }
+
+    fn exit_region(&mut self) {
+        self.inner.exit_region();
+        if let Some(stats) = self.region.take() {
+            let rows = stats
+                .first_row
+                .map_or(0, |first| stats.last_row - first + 1);
+            tracing::debug!(
+                region = %stats.name,
+                first_row = stats.first_row.unwrap_or(0),
+                rows,
+                cells = stats.cells,
+                elapsed_us = stats.start.elapsed().as_micros() as u64,
+                "region assigned"
+            );
+        }
+    }
+
+    fn enable_selector<A, AR>(
+        &mut self,
+        annotation: A,
+        selector: &Selector,
+        row: usize,
+    ) -> Result<(), Error>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.touch(row);
+        self.inner.enable_selector(annotation, selector, row)
+    }
+
+    fn query_instance(&self, column: Column<Instance>, row: usize) -> Result<Value<F>, Error> {
+        self.inner.query_instance(column, row)
+    }
+
+    fn assign_advice<V, VR, A, AR>(
+        &mut self,
+        annotation: A,
+        column: Column<Advice>,
+        row: usize,
+        to: V,
+    ) -> Result<(), Error>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.touch(row);
+        self.inner.assign_advice(annotation, column, row, to)
+    }
+
+    fn assign_fixed<V, VR, A, AR>(
+        &mut self,
+        annotation: A,
+        column: Column<Fixed>,
+        row: usize,
+        to: V,
+    ) -> Result<(), Error>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.touch(row);
+        self.inner.assign_fixed(annotation, column, row, to)
+    }
+
+    fn copy(
+        &mut self,
+        left_column: Column<Any>,
+        left_row: usize,
+        right_column: Column<Any>,
+        right_row: usize,
+    ) -> Result<(), Error> {
+        self.inner
+            .copy(left_column, left_row, right_column, right_row)
+    }
+
+    fn fill_from_row(
+        &mut self,
+        column: Column<Fixed>,
+        row: usize,
+        to: Value<Assigned<F>>,
+    ) -> Result<(), Error> {
+        self.inner.fill_from_row(column, row, to)
+    }
+
+    fn push_namespace<NR, N>(&mut self, name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        self.inner.push_namespace(name_fn)
+    }
+
+    fn pop_namespace(&mut self, gadget_name: Option<String>) {
+        self.inner.pop_namespace(gadget_name)
+    }
+}
+
+// Lays out with `P`, logging every region.
+#[derive(Debug)]
+pub struct TracingFloorPlanner<P = SimpleFloorPlanner>(PhantomData<P>);
+
+impl<P: FloorPlanner> FloorPlanner for TracingFloorPlanner<P> {
+    fn synthesize<F: Field, CS: Assignment<F>, C: Circuit<F>>(
+        cs: &mut CS,
+        circuit: &C,
+        config: C::Config,
+        constants: Vec<Column<Fixed>>,
+    ) -> Result<(), Error> {
+        let start = Instant::now();
+        let mut tracing_cs = TracingAssignment {
+            inner: cs,
+            region: None,
+        };
+        let result = P::synthesize(&mut tracing_cs, circuit, config, constants);
+        tracing::debug!(
+            elapsed_us = start.elapsed().as_micros() as u64,
+            "synthesis finished"
+        );
+        result
+    }
+}
+
+// Any circuit, laid out through `TracingFloorPlanner`.
+pub struct Traced<C>(pub C);
+
+impl<F: Field, C: Circuit<F>> Circuit<F> for Traced<C> {
+    type Config = C::Config;
+    type FloorPlanner = TracingFloorPlanner<C::FloorPlanner>;
+
+    fn without_witnesses(&self) -> Self {
+        Traced(self.0.without_witnesses())
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        C::configure(meta)
+    }
+
+    fn synthesize(&self, config: Self::Config, layouter: impl Layouter<F>) -> Result<(), Error> {
+        self.0.synthesize(config, layouter)
+    }
+}
+
+mod tests {
+    use super::Traced;
+    use crate::chips::leaf_encoding::pack_be;
+    use crate::circuits::leaf_encoding::LeafEncodingCircuit;
+    use crate::coverage::{assert_mock_satisfied, mock_verify};
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let hash = [5u8; 32];
+        let leaf: Fp = pack_be(&hash[..31]);
+        let circuit = Traced(LeafEncodingCircuit::<Fp>::new(&hash[..31]).unwrap());
+        assert_mock_satisfied(9, &circuit, vec![vec![leaf]]);
+
+        assert!(mock_verify(9, &circuit, vec![vec![leaf + Fp::one()]])
+            .unwrap()
+            .is_err());
+    }
+}
@@ -0,0 +1,410 @@
+/*
+A native Poseidon Merkle tree matching the in-circuit hashing of MerkleTreeV3Chip, over any field
+with a `PoseidonSpecFor` implementation (Pallas by default). Only the populated prefix of every
+layer is stored; missing nodes take the value of the empty subtree at that level, so deep trees with
+few leaves stay cheap to build.
+
+With the `parallel` feature each layer's pairs are hashed on rayon's thread pool. The layers are
+still built one after another, and every node is the same `hash_pair` as without the feature, so
+the tree stays bit-compatible with the circuits; only the wall time changes.
+*/
+
+use crate::chips::merkle::DomainSeparation;
+use crate::chips::poseidon::PoseidonSpecFor;
+use crate::error::Error;
+use halo2_gadgets::poseidon::primitives::{self as poseidon, ConstantLength};
+use halo2_merkle_core::path::{fold_path, verify_path};
+use halo2_proofs::pasta::Fp;
+use std::ops::Range;
+
+pub fn hash_pair<F: PoseidonSpecFor>(
+    separation: DomainSeparation,
+    level: usize,
+    left: F,
+    right: F,
+) -> F {
+    match separation {
+        DomainSeparation::Legacy => {
+            poseidon::Hash::<_, F::Spec, ConstantLength<2>, 3, 2>::init().hash([left, right])
+        }
+        DomainSeparation::Level => poseidon::Hash::<_, F::Spec, ConstantLength<3>, 3, 2>::init()
+            .hash([F::from(level as u64), left, right]),
+    }
+}
+
+// The word a blinded leaf is hashed after. Like a Level node's level it comes first in a
+// ConstantLength<3> message, but no tree is deep enough for a level to reach it, and a Legacy node
+// is a two-word message with a different capacity word, so a blinded leaf is never a node's hash.
+pub const BLINDED_LEAF_TAG: u64 = u64::MAX;
+
+// The word a leaf commitment is hashed after, keeping it apart from nodes and blinded leaves.
+pub const COMMITMENT_TAG: u64 = u64::MAX - 1;
+
+// A leaf that hides its contents: H(tag, secret, salt), as derived by MerkleTreeV3Circuit::blinded.
+pub fn blinded_leaf<F: PoseidonSpecFor>(secret: F, salt: F) -> F {
+    poseidon::Hash::<_, F::Spec, ConstantLength<3>, 3, 2>::init().hash([
+        F::from(BLINDED_LEAF_TAG),
+        secret,
+        salt,
+    ])
+}
+
+// The public commitment H(tag, leaf, blinding) to a private leaf, matching LeafExposure::Commitment
+// on MerkleTreeV3Circuit.
+pub fn leaf_commitment<F: PoseidonSpecFor>(leaf: F, blinding: F) -> F {
+    poseidon::Hash::<_, F::Spec, ConstantLength<3>, 3, 2>::init().hash([
+        F::from(COMMITMENT_TAG),
+        leaf,
+        blinding,
+    ])
+}
+
+// empty[level] is the root of an all-zero subtree of height `level`, for levels 0..=depth.
+pub fn empty_hashes<F: PoseidonSpecFor>(separation: DomainSeparation, depth: usize) -> Vec<F> {
+    let mut empty = vec![F::zero()];
+    for level in 0..depth {
+        empty.push(hash_pair(separation, level, empty[level], empty[level]));
+    }
+    empty
+}
+
+// The number of leaves a tree of `depth` holds, saturating for depths past the word size.
+fn capacity(depth: usize) -> usize {
+    1usize.checked_shl(depth as u32).unwrap_or(usize::MAX)
+}
+
+// Hashes a layer into the one above it, pairing a trailing odd node with `empty`.
+fn hash_layer<F: PoseidonSpecFor>(
+    separation: DomainSeparation,
+    level: usize,
+    layer: &[F],
+    empty: F,
+) -> Vec<F> {
+    let hash = |pair: &[F]| {
+        let right = pair.get(1).copied().unwrap_or(empty);
+        hash_pair(separation, level, pair[0], right)
+    };
+    #[cfg(feature = "parallel")]
+    {
+        use rayon::prelude::*;
+        layer.par_chunks(2).map(hash).collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        layer.chunks(2).map(hash).collect()
+    }
+}
+
+pub fn compute_root<F: PoseidonSpecFor>(
+    separation: DomainSeparation,
+    leaf: F,
+    elements: &[F],
+    indices: &[F],
+) -> F {
+    fold_path(leaf, elements, indices, |level, left, right| {
+        hash_pair(separation, level, left, right)
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerklePath<F = Fp> {
+    pub leaf: F,
+    pub elements: Vec<F>,
+    pub indices: Vec<F>,
+}
+
+impl<F: PoseidonSpecFor> MerklePath<F> {
+    pub fn root(&self, separation: DomainSeparation) -> F {
+        compute_root(separation, self.leaf, &self.elements, &self.indices)
+    }
+
+    // Checks the path's shape and index bits as well as the root it leads to.
+    pub fn verify(&self, separation: DomainSeparation, root: F) -> Result<(), Error> {
+        Ok(verify_path(
+            root,
+            self.elements.len(),
+            self.leaf,
+            &self.elements,
+            &self.indices,
+            |level, left, right| hash_pair(separation, level, left, right),
+        )?)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MerkleTree<F = Fp> {
+    depth: usize,
+    separation: DomainSeparation,
+    // layers[0] holds the leaves and layers[depth] the root.
+    layers: Vec<Vec<F>>,
+    // empty[level] is the root of an all-zero subtree of height `level`.
+    empty: Vec<F>,
+}
+
+impl<F: PoseidonSpecFor> MerkleTree<F> {
+    pub fn new(depth: usize, leaves: &[F], separation: DomainSeparation) -> Result<Self, Error> {
+        let capacity = capacity(depth);
+        if leaves.len() > capacity {
+            return Err(Error::TooManyLeaves {
+                capacity,
+                leaves: leaves.len(),
+            });
+        }
+
+        let empty = empty_hashes(separation, depth);
+
+        let mut layers = vec![leaves.to_vec()];
+        for level in 0..depth {
+            let next = hash_layer(separation, level, &layers[level], empty[level]);
+            layers.push(next);
+        }
+
+        Ok(Self {
+            depth,
+            separation,
+            layers,
+            empty,
+        })
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn separation(&self) -> DomainSeparation {
+        self.separation
+    }
+
+    pub fn leaves(&self) -> &[F] {
+        &self.layers[0]
+    }
+
+    // How many leaves fit, populated or not.
+    pub fn capacity(&self) -> usize {
+        capacity(self.depth)
+    }
+
+    pub fn root(&self) -> F {
+        self.layers[self.depth]
+            .first()
+            .copied()
+            .unwrap_or(self.empty[self.depth])
+    }
+
+    // Replaces a populated leaf and rehashes its path. Setting it to zero deletes it.
+    pub fn update(&mut self, index: usize, leaf: F) -> Result<(), Error> {
+        let leaves = self.layers[0].len();
+        let slot = self.layers[0]
+            .get_mut(index)
+            .ok_or(Error::IndexOutOfRange { index, leaves })?;
+        *slot = leaf;
+
+        let mut position = index;
+        for level in 0..self.depth {
+            let left = self.layers[level][position & !1];
+            let right = self.layers[level]
+                .get(position | 1)
+                .copied()
+                .unwrap_or(self.empty[level]);
+            position >>= 1;
+            self.layers[level + 1][position] = hash_pair(self.separation, level, left, right);
+        }
+        Ok(())
+    }
+
+    // Adds a leaf after the populated ones and rehashes its path; returns its index.
+    pub fn append(&mut self, leaf: F) -> Result<usize, Error> {
+        let index = self.layers[0].len();
+        let capacity = self.capacity();
+        if index >= capacity {
+            return Err(Error::TooManyLeaves {
+                capacity,
+                leaves: index + 1,
+            });
+        }
+        self.layers[0].push(leaf);
+
+        let mut position = index;
+        for level in 0..self.depth {
+            let left = self.layers[level][position & !1];
+            let right = self.layers[level]
+                .get(position | 1)
+                .copied()
+                .unwrap_or(self.empty[level]);
+            position >>= 1;
+            let digest = hash_pair(self.separation, level, left, right);
+            match self.layers[level + 1].get_mut(position) {
+                Some(node) => *node = digest,
+                None => self.layers[level + 1].push(digest),
+            }
+        }
+        Ok(index)
+    }
+
+    fn sibling(&self, level: usize, position: usize) -> F {
+        self.layers[level]
+            .get(position ^ 1)
+            .copied()
+            .unwrap_or(self.empty[level])
+    }
+
+    pub fn path(&self, index: usize) -> Result<MerklePath<F>, Error> {
+        let leaf = *self.layers[0].get(index).ok_or(Error::IndexOutOfRange {
+            index,
+            leaves: self.layers[0].len(),
+        })?;
+
+        let mut elements = Vec::with_capacity(self.depth);
+        let mut indices = Vec::with_capacity(self.depth);
+        let mut position = index;
+        for level in 0..self.depth {
+            elements.push(self.sibling(level, position));
+            indices.push(F::from((position & 1) as u64));
+            position >>= 1;
+        }
+
+        Ok(MerklePath {
+            leaf,
+            elements,
+            indices,
+        })
+    }
+
+    // The populated leaves with their indices.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, F)> + '_ {
+        self.layers[0].iter().copied().enumerate()
+    }
+
+    // The populated prefix of each level, from the leaves (level 0) up to the root (level depth).
+    // Nodes past the prefix are empty subtrees, see `empty_hashes`.
+    pub fn levels(&self) -> impl ExactSizeIterator<Item = &[F]> + '_ {
+        self.layers.iter().map(Vec::as_slice)
+    }
+
+    // The paths of a range of populated leaves, in order. Consecutive paths only differ below the
+    // level where their indices' carry stops, so each one updates amortized O(1) siblings of the
+    // previous rather than looking up all `depth` levels, and nothing is rehashed, as the tree
+    // keeps every internal node. Each path is still returned as its own copy, so yielding one costs
+    // O(depth) and a range costs O(len * depth) overall.
+    pub fn proofs_for_range(&self, range: Range<usize>) -> Result<Paths<'_, F>, Error> {
+        let leaves = self.layers[0].len();
+        if range.end > leaves {
+            return Err(Error::IndexOutOfRange {
+                index: range.end - 1,
+                leaves,
+            });
+        }
+        Ok(Paths {
+            tree: self,
+            range,
+            current: None,
+        })
+    }
+}
+
+pub struct Paths<'a, F> {
+    tree: &'a MerkleTree<F>,
+    range: Range<usize>,
+    // The path most recently returned, updated in place for the next index.
+    current: Option<MerklePath<F>>,
+}
+
+impl<F: PoseidonSpecFor> Iterator for Paths<'_, F> {
+    type Item = MerklePath<F>;
+
+    fn next(&mut self) -> Option<MerklePath<F>> {
+        let index = self.range.next()?;
+        let tree = self.tree;
+        let path = match self.current.take() {
+            // index - 1 and index differ exactly in the bits up to the lowest set bit of index.
+            Some(mut path) => {
+                path.leaf = tree.layers[0][index];
+                let changed = (index.trailing_zeros() as usize + 1).min(tree.depth);
+                for level in 0..changed {
+                    let position = index >> level;
+                    path.elements[level] = tree.sibling(level, position);
+                    path.indices[level] = F::from((position & 1) as u64);
+                }
+                path
+            }
+            None => tree.path(index).ok()?,
+        };
+        self.current = Some(path.clone());
+        Some(path)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+}
+
+impl<F: PoseidonSpecFor> ExactSizeIterator for Paths<'_, F> {}
+
+mod tests {
+    use super::{DomainSeparation, MerkleTree};
+    use crate::error::Error;
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let leaves: Vec<Fp> = (0..11u64).map(Fp::from).collect();
+        for separation in [DomainSeparation::Legacy, DomainSeparation::Level] {
+            let tree = MerkleTree::new(4, &leaves, separation).unwrap();
+            for index in 0..leaves.len() {
+                let path = tree.path(index).unwrap();
+                assert_eq!(path.root(separation), tree.root());
+                path.verify(separation, tree.root()).unwrap();
+            }
+            assert!(tree.path(leaves.len()).is_err());
+            let path = tree.path(2).unwrap();
+            assert!(matches!(
+                path.verify(separation, tree.root() + Fp::one()),
+                Err(Error::RootMismatch)
+            ));
+
+            let mut updated = tree.clone();
+            let mut expected = leaves.clone();
+            for (index, leaf) in [(10, Fp::from(99)), (3, Fp::zero())] {
+                updated.update(index, leaf).unwrap();
+                expected[index] = leaf;
+                assert_eq!(
+                    updated.root(),
+                    MerkleTree::new(4, &expected, separation).unwrap().root()
+                );
+            }
+            assert!(updated.update(leaves.len(), Fp::zero()).is_err());
+        }
+        assert!(MerkleTree::new(3, &leaves, DomainSeparation::Legacy).is_err());
+
+        let mut appended = MerkleTree::new(4, &[], DomainSeparation::Level).unwrap();
+        for (index, leaf) in leaves.iter().enumerate() {
+            assert_eq!(appended.append(*leaf).unwrap(), index);
+            let expected = MerkleTree::new(4, &leaves[..=index], DomainSeparation::Level).unwrap();
+            assert_eq!(appended.root(), expected.root());
+        }
+        let tree = MerkleTree::new(5, &leaves, DomainSeparation::Legacy).unwrap();
+        let paths: Vec<_> = tree.proofs_for_range(3..11).unwrap().collect();
+        let expected: Vec<_> = (3..11).map(|index| tree.path(index).unwrap()).collect();
+        assert_eq!(paths, expected);
+        assert_eq!(tree.proofs_for_range(0..11).unwrap().len(), 11);
+        assert_eq!(tree.proofs_for_range(4..4).unwrap().count(), 0);
+        assert!(tree.proofs_for_range(5..12).is_err());
+        assert_eq!(tree.iter().nth(7), Some((7, leaves[7])));
+        let levels: Vec<usize> = tree.levels().map(|level| level.len()).collect();
+        assert_eq!(levels, [11, 6, 3, 2, 1, 1]);
+
+        let mut full = MerkleTree::new(2, &leaves[..4], DomainSeparation::Legacy).unwrap();
+        assert!(matches!(
+            full.append(Fp::one()),
+            Err(Error::TooManyLeaves { capacity: 4, .. })
+        ));
+
+        // Enough leaves for the layers to be split across threads under `parallel`.
+        let leaves: Vec<Fp> = (0..1000u64).map(Fp::from).collect();
+        let tree = MerkleTree::new(10, &leaves, DomainSeparation::Legacy).unwrap();
+        for index in [0, 511, 999] {
+            let path = tree.path(index).unwrap();
+            assert_eq!(path.root(DomainSeparation::Legacy), tree.root());
+        }
+    }
+}
@@ -0,0 +1,334 @@
+/*
+Membership circuits picked at runtime. Every variant takes a `MerklePath` witness, derives its own
+instance columns from it and proves and verifies through the same object-safe trait, so a service
+can read the variant from its configuration at startup and hold a `Box<dyn MembershipCircuit>`
+without carrying circuit types around:
+
+    let circuit = "v3".parse::<Variant>()?.circuit(depth)?;
+    let pk = circuit.keygen(&params)?;
+    let (proof, instances) = circuit.prove(&params, &pk, &path, &mut OsRng)?;
+    circuit.verify(&params, pk.get_vk(), &proof, &instances)?;
+
+The variants:
+
+    v2             MerkleTreeV2Circuit, the dummy hash
+    v3             MerkleTreeV3Circuit, Poseidon
+    smt-deletion   SmtDeletionCircuit, for the path of the key being deleted
+
+`MerkleMembership` wraps any `MerkleCircuitBuilder` shape (planner, separation, leaf exposure) for
+configurations the named variants don't cover.
+*/
+
+use crate::builder::{Hasher, MerkleCircuit, MerkleCircuitBuilder};
+use crate::chips::merkle_v3::DomainSeparation;
+use crate::circuits::smt_deletion::SmtDeletionCircuit;
+use crate::coverage::mock_verify;
+use crate::error::Error;
+use crate::estimate::{check_k, CircuitParams};
+use crate::instance::InstanceBuilder;
+use crate::proving::{keygen, prove, verify, RngCore};
+use crate::tree::{compute_root, MerklePath};
+use halo2_proofs::{
+    circuit::Value,
+    pasta::{EqAffine, Fp},
+    plonk::{ProvingKey, VerifyingKey},
+    poly::commitment::Params,
+};
+use std::fmt;
+use std::str::FromStr;
+
+pub trait MembershipCircuit: Send + Sync {
+    // The variant's configuration name.
+    fn name(&self) -> &'static str;
+
+    // The instance columns a proof for `witness` is verified against.
+    fn instances(&self, witness: &MerklePath) -> Result<Vec<Vec<Fp>>, Error>;
+
+    // Keygen runs on the circuit shape alone; no witness is needed.
+    fn keygen(&self, params: &Params<EqAffine>) -> Result<ProvingKey<EqAffine>, Error>;
+
+    // Returns the proof together with the instances it was made for.
+    fn prove(
+        &self,
+        params: &Params<EqAffine>,
+        pk: &ProvingKey<EqAffine>,
+        witness: &MerklePath,
+        rng: &mut dyn RngCore,
+    ) -> Result<(Vec<u8>, Vec<Vec<Fp>>), Error>;
+
+    fn verify(
+        &self,
+        params: &Params<EqAffine>,
+        vk: &VerifyingKey<EqAffine>,
+        proof: &[u8],
+        instances: &[Vec<Fp>],
+    ) -> Result<(), Error> {
+        verify(params, vk, proof, instances)
+    }
+
+    // Whether MockProver accepts `witness` against `instances` at k, without keygen or proving.
+    fn mock_accepts(
+        &self,
+        k: u32,
+        witness: &MerklePath,
+        instances: &[Vec<Fp>],
+    ) -> Result<bool, Error>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    V2,
+    V3,
+    SmtDeletion,
+}
+
+impl Variant {
+    // Quaternary trees get a variant once a circuit for them exists.
+    pub const ALL: [Variant; 3] = [Variant::V2, Variant::V3, Variant::SmtDeletion];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Variant::V2 => "v2",
+            Variant::V3 => "v3",
+            Variant::SmtDeletion => "smt-deletion",
+        }
+    }
+
+    pub fn circuit(&self, depth: usize) -> Result<Box<dyn MembershipCircuit>, Error> {
+        if depth == 0 {
+            return Err(Error::EmptyPath);
+        }
+        let builder = MerkleCircuitBuilder::new().depth(depth);
+        Ok(match self {
+            Variant::V2 => Box::new(MerkleMembership::new(builder.hasher(Hasher::Dummy))?),
+            Variant::V3 => Box::new(MerkleMembership::new(builder.hasher(Hasher::Poseidon))?),
+            Variant::SmtDeletion => Box::new(SmtDeletion { depth }),
+        })
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Variant {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self, Error> {
+        Variant::ALL
+            .into_iter()
+            .find(|variant| variant.name() == name)
+            .ok_or_else(|| Error::UnknownVariant(name.to_string()))
+    }
+}
+
+// A membership circuit of the shape a builder describes; the builder's witness, if any, is
+// replaced by the one passed to `prove`.
+#[derive(Debug, Clone)]
+pub struct MerkleMembership {
+    builder: MerkleCircuitBuilder,
+    name: &'static str,
+}
+
+impl MerkleMembership {
+    // Builds the shape once, so a builder missing its depth is rejected here rather than at keygen.
+    pub fn new(builder: MerkleCircuitBuilder) -> Result<Self, Error> {
+        let name = match builder.build()?.0 {
+            MerkleCircuit::Dummy(_) | MerkleCircuit::PackedDummy(_) => Variant::V2.name(),
+            MerkleCircuit::Poseidon(_) | MerkleCircuit::PackedPoseidon(_) => Variant::V3.name(),
+        };
+        Ok(Self { builder, name })
+    }
+}
+
+impl MembershipCircuit for MerkleMembership {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn instances(&self, witness: &MerklePath) -> Result<Vec<Vec<Fp>>, Error> {
+        let (circuit, _) = self.builder.clone().path(witness).build()?;
+        InstanceBuilder::for_circuit(&circuit)
+    }
+
+    fn keygen(&self, params: &Params<EqAffine>) -> Result<ProvingKey<EqAffine>, Error> {
+        let (circuit, _) = self.builder.clone().build()?;
+        circuit.keygen(params)
+    }
+
+    fn prove(
+        &self,
+        params: &Params<EqAffine>,
+        pk: &ProvingKey<EqAffine>,
+        witness: &MerklePath,
+        rng: &mut dyn RngCore,
+    ) -> Result<(Vec<u8>, Vec<Vec<Fp>>), Error> {
+        let (circuit, _) = self.builder.clone().path(witness).build()?;
+        let instances = InstanceBuilder::for_circuit(&circuit)?;
+        let proof = circuit.prove(params, pk, &instances, rng)?;
+        Ok((proof, instances))
+    }
+
+    fn mock_accepts(
+        &self,
+        k: u32,
+        witness: &MerklePath,
+        instances: &[Vec<Fp>],
+    ) -> Result<bool, Error> {
+        let (circuit, _) = self.builder.clone().path(witness).build()?;
+        Ok(circuit.mock_verify(k, instances.to_vec())?.is_ok())
+    }
+}
+
+// Deletion from the sparse tree: the witness is the path of the key before deletion, and the
+// instances are the roots before and after with the key's bits, as `SmtDeletionCircuit` exposes.
+#[derive(Debug, Clone, Copy)]
+pub struct SmtDeletion {
+    depth: usize,
+}
+
+impl SmtDeletion {
+    fn key(witness: &MerklePath) -> Result<u64, Error> {
+        if witness.indices.len() > 64 {
+            return Err(Error::Malformed("keys wider than 64 bits"));
+        }
+        witness
+            .indices
+            .iter()
+            .enumerate()
+            .try_fold(0u64, |key, (layer, bit)| {
+                if *bit == Fp::one() {
+                    Ok(key | (1 << layer))
+                } else if *bit == Fp::zero() {
+                    Ok(key)
+                } else {
+                    Err(Error::InvalidIndex { layer })
+                }
+            })
+    }
+}
+
+impl MembershipCircuit for SmtDeletion {
+    fn name(&self) -> &'static str {
+        Variant::SmtDeletion.name()
+    }
+
+    fn instances(&self, witness: &MerklePath) -> Result<Vec<Vec<Fp>>, Error> {
+        if witness.elements.len() != self.depth {
+            return Err(Error::DepthMismatch {
+                expected: self.depth,
+                actual: witness.elements.len(),
+            });
+        }
+        let key = Self::key(witness)?;
+        let old_root = witness.root(DomainSeparation::Legacy);
+        let new_root = compute_root(
+            DomainSeparation::Legacy,
+            Fp::zero(),
+            &witness.elements,
+            &witness.indices,
+        );
+        SmtDeletionCircuit::instances(old_root, new_root, key, self.depth)
+    }
+
+    fn keygen(&self, params: &Params<EqAffine>) -> Result<ProvingKey<EqAffine>, Error> {
+        check_k(params.k(), &CircuitParams::smt_deletion(self.depth))?;
+        let shape = SmtDeletionCircuit {
+            leaf: Value::unknown(),
+            siblings: vec![Value::unknown(); self.depth],
+            indices: vec![Value::unknown(); self.depth],
+        };
+        keygen(params, &shape)
+    }
+
+    fn prove(
+        &self,
+        params: &Params<EqAffine>,
+        pk: &ProvingKey<EqAffine>,
+        witness: &MerklePath,
+        rng: &mut dyn RngCore,
+    ) -> Result<(Vec<u8>, Vec<Vec<Fp>>), Error> {
+        let instances = self.instances(witness)?;
+        let circuit = SmtDeletionCircuit::new(witness)?;
+        let proof = prove(params, pk, circuit, &instances, rng)?;
+        Ok((proof, instances))
+    }
+
+    fn mock_accepts(
+        &self,
+        k: u32,
+        witness: &MerklePath,
+        instances: &[Vec<Fp>],
+    ) -> Result<bool, Error> {
+        let circuit = SmtDeletionCircuit::new(witness)?;
+        Ok(mock_verify(k, &circuit, instances.to_vec())?.is_ok())
+    }
+}
+
+mod tests {
+    use super::{MembershipCircuit, Variant};
+    use crate::chips::merkle_v3::DomainSeparation;
+    use crate::error::Error;
+    use crate::proving::{seeded_rng, TEST_SEED};
+    use crate::tree::{MerklePath, MerkleTree};
+    use halo2_proofs::{pasta::Fp, poly::commitment::Params};
+
+    #[test]
+    fn test() {
+        let leaves: Vec<Fp> = (1..=11u64).map(Fp::from).collect();
+        let tree = MerkleTree::new(4, &leaves, DomainSeparation::Legacy).unwrap();
+        let path = tree.path(6).unwrap();
+        // The dummy hash's root is the leaf plus its siblings, which any elements satisfy.
+        let dummy = MerklePath {
+            leaf: Fp::from(3),
+            elements: vec![Fp::from(5); 4],
+            indices: path.indices.clone(),
+        };
+        let params = Params::new(10);
+        let mut rng = seeded_rng(TEST_SEED);
+
+        // Selected by name, as a service would from its configuration.
+        let circuits: Vec<(Box<dyn MembershipCircuit>, &MerklePath)> = vec![
+            ("v2".parse::<Variant>().unwrap().circuit(4).unwrap(), &dummy),
+            ("v3".parse::<Variant>().unwrap().circuit(4).unwrap(), &path),
+            (Variant::SmtDeletion.circuit(4).unwrap(), &path),
+        ];
+        for (circuit, witness) in circuits.iter() {
+            let pk = circuit.keygen(&params).unwrap();
+            let (proof, instances) = circuit.prove(&params, &pk, witness, &mut rng).unwrap();
+            assert_eq!(instances, circuit.instances(witness).unwrap());
+            circuit
+                .verify(&params, pk.get_vk(), &proof, &instances)
+                .unwrap();
+            let mut wrong = instances.clone();
+            wrong[0][0] += Fp::one();
+            assert!(circuit
+                .verify(&params, pk.get_vk(), &proof, &wrong)
+                .is_err());
+        }
+        assert_eq!(circuits[0].0.name(), "v2");
+        assert_eq!(circuits[2].0.name(), "smt-deletion");
+
+        let smt = &circuits[2].0;
+        let instances = smt.instances(&path).unwrap();
+        let mut deleted = tree.clone();
+        deleted.update(6, Fp::zero()).unwrap();
+        assert_eq!(instances[0][..2], [tree.root(), deleted.root()]);
+
+        for variant in Variant::ALL {
+            assert_eq!(variant.to_string().parse::<Variant>().unwrap(), variant);
+            assert!(variant.circuit(4).is_ok());
+        }
+        assert!(matches!(
+            "v3-arity4".parse::<Variant>(),
+            Err(Error::UnknownVariant(_))
+        ));
+        assert!(matches!(
+            "v4".parse::<Variant>(),
+            Err(Error::UnknownVariant(_))
+        ));
+        assert!(matches!(Variant::V3.circuit(0), Err(Error::EmptyPath)));
+    }
+}
@@ -0,0 +1,278 @@
+/*
+A Merkle tree of approved verifying keys, for verifiers that are upgraded by governance rather than
+redeployed: the governance process publishes the root of the keys it has approved, and a proof is
+accepted if it verifies against some key with a path to that root.
+
+    let tree = VkTree::new(4, &[&vk_v1, &vk_v2], DomainSeparation::Level)?;
+    let path = tree.path(&vk_v2)?;
+    verify_approved(&params, &vk_v2, &proof, &instances, tree.root(), &path, tree.separation())?;
+
+A key's leaf is `vk_digest`: the scalar halo2 itself hashes into every proof's transcript for the
+key (`VerifyingKey::hash_into`), read back through a transcript that only records it. It commits to
+the domain, the constraint system and the fixed commitments, and it is exactly what a proof is bound
+to, so two keys share a digest only if they accept the same proofs. Approving a key appends its
+digest to the next empty slot and revoking one overwrites it with zero, both of which change the
+root. A key is approved at most once, so revoking it removes it from the tree.
+
+`VkTree::membership_circuit` is the in-circuit side: a MerkleTreeV3 membership proof with the key
+digest as its public leaf. A recursive or aggregating verifier that takes a key digest as a public
+input binds it to the approved root with this proof, without learning which other keys are in the
+tree.
+*/
+
+use crate::chips::merkle::DomainSeparation;
+use crate::circuits::merkle_v3::MerkleTreeV3Circuit;
+use crate::error::Error;
+use crate::proving::verify;
+use crate::tree::{MerklePath, MerkleTree};
+use halo2_proofs::{
+    pasta::{EqAffine, Fp},
+    plonk::{self, VerifyingKey},
+    poly::commitment::Params,
+    transcript::{Challenge255, EncodedChallenge, Transcript},
+};
+use std::io;
+
+// Fails only if halo2's `hash_into` errors or writes no scalar, as a transcript error.
+pub fn vk_digest(vk: &VerifyingKey<EqAffine>) -> Result<Fp, Error> {
+    let mut recorder = ScalarRecorder(None);
+    vk.hash_into(&mut recorder).map_err(plonk::Error::from)?;
+    recorder.0.ok_or_else(|| {
+        Error::Plonk(plonk::Error::Transcript(
+            io::ErrorKind::UnexpectedEof.into(),
+        ))
+    })
+}
+
+// A transcript that keeps the last scalar written to it; `vk_digest` only writes one.
+struct ScalarRecorder(Option<Fp>);
+
+impl Transcript<EqAffine, Challenge255<EqAffine>> for ScalarRecorder {
+    fn squeeze_challenge(&mut self) -> Challenge255<EqAffine> {
+        Challenge255::new(&[0; 64])
+    }
+
+    fn common_point(&mut self, _: EqAffine) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn common_scalar(&mut self, scalar: Fp) -> io::Result<()> {
+        self.0 = Some(scalar);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VkTree {
+    tree: MerkleTree,
+}
+
+impl VkTree {
+    pub fn new(
+        depth: usize,
+        vks: &[&VerifyingKey<EqAffine>],
+        separation: DomainSeparation,
+    ) -> Result<Self, Error> {
+        let digests = vks
+            .iter()
+            .map(|vk| vk_digest(vk))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_digests(depth, &digests, separation)
+    }
+
+    // From digests published elsewhere, e.g. by the governance process, without the keys.
+    pub fn from_digests(
+        depth: usize,
+        digests: &[Fp],
+        separation: DomainSeparation,
+    ) -> Result<Self, Error> {
+        for (index, digest) in digests.iter().enumerate() {
+            // Zero is a revoked slot, which may repeat.
+            if *digest != Fp::zero() && digests[..index].contains(digest) {
+                return Err(Error::AlreadyApproved { index });
+            }
+        }
+        Ok(Self {
+            tree: MerkleTree::new(depth, digests, separation)?,
+        })
+    }
+
+    pub fn tree(&self) -> &MerkleTree {
+        &self.tree
+    }
+
+    pub fn root(&self) -> Fp {
+        self.tree.root()
+    }
+
+    pub fn separation(&self) -> DomainSeparation {
+        self.tree.separation()
+    }
+
+    pub fn position(&self, vk: &VerifyingKey<EqAffine>) -> Result<Option<usize>, Error> {
+        let digest = vk_digest(vk)?;
+        Ok(self.tree.leaves().iter().position(|leaf| *leaf == digest))
+    }
+
+    pub fn path(&self, vk: &VerifyingKey<EqAffine>) -> Result<MerklePath, Error> {
+        let index = self.position(vk)?.ok_or(Error::UnapprovedKey)?;
+        self.tree.path(index)
+    }
+
+    // Approves another key, returning its index. A key that is already approved is refused, so that
+    // `revoke` has a single leaf to clear.
+    pub fn approve(&mut self, vk: &VerifyingKey<EqAffine>) -> Result<usize, Error> {
+        if let Some(index) = self.position(vk)? {
+            return Err(Error::AlreadyApproved { index });
+        }
+        self.tree.append(vk_digest(vk)?)
+    }
+
+    // Replaces a key's leaf with zero; its old paths no longer lead to the root.
+    pub fn revoke(&mut self, vk: &VerifyingKey<EqAffine>) -> Result<(), Error> {
+        let index = self.position(vk)?.ok_or(Error::UnapprovedKey)?;
+        self.tree.update(index, Fp::zero())
+    }
+
+    // Proves in-circuit that the key's digest, the circuit's public leaf, is approved.
+    pub fn membership_circuit(
+        &self,
+        vk: &VerifyingKey<EqAffine>,
+    ) -> Result<MerkleTreeV3Circuit, Error> {
+        let index = self.position(vk)?.ok_or(Error::UnapprovedKey)?;
+        let path = self.tree.path(index)?;
+        Ok(
+            MerkleTreeV3Circuit::new(path.leaf, &path.elements, index as u64)?
+                .with_separation(self.separation()),
+        )
+    }
+}
+
+// Verifies `proof` against `vk` after checking that `path` shows `vk` is approved under `root`.
+pub fn verify_approved(
+    params: &Params<EqAffine>,
+    vk: &VerifyingKey<EqAffine>,
+    proof: &[u8],
+    instances: &[Vec<Fp>],
+    root: Fp,
+    path: &MerklePath,
+    separation: DomainSeparation,
+) -> Result<(), Error> {
+    if path.leaf != vk_digest(vk)? {
+        return Err(Error::UnapprovedKey);
+    }
+    if path.root(separation) != root {
+        return Err(Error::RootMismatch);
+    }
+    verify(params, vk, proof, instances)
+}
+
+mod tests {
+    use super::{verify_approved, vk_digest, VkTree};
+    use crate::chips::merkle::DomainSeparation;
+    use crate::circuits::merkle_v3::MerkleTreeV3Circuit;
+    use crate::coverage::assert_mock_satisfied;
+    use crate::error::Error;
+    use crate::instance::InstanceBuilder;
+    use crate::proving::{keygen, prove, seeded_rng, TEST_SEED};
+    use crate::tree::MerkleTree;
+    use halo2_proofs::{pasta::Fp, poly::commitment::Params};
+
+    #[test]
+    fn test() {
+        let params = Params::new(10);
+        let leaves: Vec<Fp> = (1..=5u64).map(Fp::from).collect();
+        let circuit = |depth: usize| {
+            let tree = MerkleTree::new(depth, &leaves, DomainSeparation::Legacy).unwrap();
+            let path = tree.path(3).unwrap();
+            MerkleTreeV3Circuit::new(path.leaf, &path.elements, 3).unwrap()
+        };
+        let pks: Vec<_> = [3, 4, 5]
+            .iter()
+            .map(|depth| keygen(&params, &circuit(*depth)).unwrap())
+            .collect();
+        assert_eq!(
+            vk_digest(pks[0].get_vk()).unwrap(),
+            vk_digest(pks[0].get_vk()).unwrap()
+        );
+        assert_ne!(
+            vk_digest(pks[0].get_vk()).unwrap(),
+            vk_digest(pks[1].get_vk()).unwrap()
+        );
+
+        let separation = DomainSeparation::Level;
+        let mut tree = VkTree::new(3, &[pks[0].get_vk(), pks[1].get_vk()], separation).unwrap();
+        let (vk, unapproved) = (pks[1].get_vk(), pks[2].get_vk());
+
+        let approved = circuit(4);
+        let instances = InstanceBuilder::for_circuit(&approved).unwrap();
+        let proof = prove(
+            &params,
+            &pks[1],
+            approved,
+            &instances,
+            seeded_rng(TEST_SEED),
+        )
+        .unwrap();
+        let path = tree.path(vk).unwrap();
+        verify_approved(
+            &params,
+            vk,
+            &proof,
+            &instances,
+            tree.root(),
+            &path,
+            separation,
+        )
+        .unwrap();
+        assert!(matches!(
+            verify_approved(
+                &params,
+                vk,
+                &proof,
+                &instances,
+                Fp::one(),
+                &path,
+                separation
+            ),
+            Err(Error::RootMismatch)
+        ));
+        assert!(matches!(
+            verify_approved(
+                &params,
+                unapproved,
+                &proof,
+                &instances,
+                tree.root(),
+                &path,
+                separation
+            ),
+            Err(Error::UnapprovedKey)
+        ));
+        assert!(matches!(tree.path(unapproved), Err(Error::UnapprovedKey)));
+
+        // In-circuit, the key's digest is the public leaf under the approved root.
+        let membership = tree.membership_circuit(vk).unwrap();
+        let instances = InstanceBuilder::for_circuit(&membership).unwrap();
+        assert_eq!(instances[0], vec![vk_digest(vk).unwrap(), tree.root()]);
+        assert_mock_satisfied(10, &membership, instances);
+
+        // Approving a key and revoking another both move the root.
+        let root = tree.root();
+        assert_eq!(tree.approve(unapproved).unwrap(), 2);
+        assert_ne!(tree.root(), root);
+        assert!(matches!(
+            tree.approve(vk),
+            Err(Error::AlreadyApproved { index: 1 })
+        ));
+        tree.revoke(vk).unwrap();
+        assert!(matches!(tree.path(vk), Err(Error::UnapprovedKey)));
+        assert_ne!(path.root(separation), tree.root());
+        // A revoked key can be approved again, in a fresh slot.
+        assert_eq!(tree.approve(vk).unwrap(), 3);
+        assert!(matches!(
+            VkTree::new(3, &[vk, unapproved, vk], separation),
+            Err(Error::AlreadyApproved { index: 2 })
+        ));
+    }
+}
@@ -0,0 +1,378 @@
+/*
+RootWatcher keeps the window of recently accepted roots that ForestCircuit proves membership
+against in sync with a contract. It reads the contract's root update events, keeps the last
+`window` roots with the block each came from, and hands them out as the circuit's public roots.
+
+Only blocks with `confirmations` blocks on top are read. On each poll the watcher checks that the
+last block it read is still canonical; if not, it drops roots from blocks that were reorged out
+and rescans from the newest root that survived. Its state round-trips through `to_bytes` so that a
+restarted service resumes where it stopped:
+
+    | version: u8 | head: u8 (0 or 1) | head block: u64 BE | head hash: 32 bytes |
+    | root count: u32 BE |
+    | (root: 32 bytes LE, block: u64 BE, block hash: 32 bytes, log index: u64 BE) ... |
+*/
+
+use crate::error::Error;
+use crate::onchain::{log_word, LogField};
+use crate::serialization::{from_bytes_be_checked, to_bytes_le};
+use crate::wire::Reader;
+use ethers::providers::Middleware;
+use ethers::types::{Address, Filter, Log, H256};
+use futures::StreamExt;
+use halo2_proofs::pasta::Fp;
+use std::collections::VecDeque;
+use std::io;
+use std::path::Path;
+
+pub const WATCHER_VERSION: u8 = 1;
+
+#[derive(Debug, Clone)]
+pub struct WatcherConfig {
+    pub contract: Address,
+    // The root update event, e.g. "RootUpdated(bytes32)", and where the root is in its logs.
+    pub event: String,
+    pub field: LogField,
+    // How many recent roots are accepted: the `trees` of the ForestCircuit they feed.
+    pub window: usize,
+    pub confirmations: u64,
+    // Where scanning starts, e.g. the contract's deployment block.
+    pub from_block: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RootEntry {
+    pub root: Fp,
+    pub block_number: u64,
+    pub block_hash: H256,
+    pub log_index: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct RootWatcher {
+    pub config: WatcherConfig,
+    // Oldest first, at most `config.window` of them.
+    entries: VecDeque<RootEntry>,
+    // The last block read and its hash.
+    head: Option<(u64, H256)>,
+}
+
+fn rpc<E: std::fmt::Display>(err: E) -> Error {
+    Error::Rpc(err.to_string())
+}
+
+async fn block_hash<M: Middleware>(client: &M, number: u64) -> Result<H256, Error> {
+    client
+        .get_block(number)
+        .await
+        .map_err(rpc)?
+        .and_then(|block| block.hash)
+        .ok_or_else(|| Error::Rpc(format!("block {} not found", number)))
+}
+
+fn read_u64(reader: &mut Reader) -> Result<u64, Error> {
+    Ok(u64::from_be_bytes(
+        reader.take(8)?.try_into().expect("took 8 bytes"),
+    ))
+}
+
+fn read_hash(reader: &mut Reader) -> Result<H256, Error> {
+    Ok(H256::from_slice(reader.take(32)?))
+}
+
+impl RootWatcher {
+    pub fn new(config: WatcherConfig) -> Self {
+        Self {
+            config,
+            entries: VecDeque::new(),
+            head: None,
+        }
+    }
+
+    pub fn entries(&self) -> &VecDeque<RootEntry> {
+        &self.entries
+    }
+
+    pub fn latest(&self) -> Option<Fp> {
+        self.entries.back().map(|entry| entry.root)
+    }
+
+    // Exactly `window` roots, oldest first, padded at the front with zero while fewer have been
+    // seen. These are ForestCircuit's public roots.
+    pub fn roots(&self) -> Vec<Fp> {
+        let mut roots = vec![Fp::zero(); self.config.window - self.entries.len()];
+        roots.extend(self.entries.iter().map(|entry| entry.root));
+        roots
+    }
+
+    // The position of `root` in `roots()`, i.e. the `tree` to pass to ForestCircuit::new.
+    pub fn position(&self, root: &Fp) -> Option<usize> {
+        let padding = self.config.window - self.entries.len();
+        self.entries
+            .iter()
+            .rposition(|entry| entry.root == *root)
+            .map(|i| padding + i)
+    }
+
+    // Records the root updates among `logs`, which must cover every block up to `head`. Logs at
+    // or before the newest recorded one are skipped, so overlapping batches are harmless.
+    pub fn apply(&mut self, logs: &[Log], head: (u64, H256)) -> Result<usize, Error> {
+        let mut added = 0;
+        for log in logs.iter().filter(|log| log.removed != Some(true)) {
+            let entry = RootEntry {
+                root: from_bytes_be_checked(&log_word(log, self.config.field)?)?,
+                block_number: log
+                    .block_number
+                    .ok_or(Error::Malformed("log has no block number"))?
+                    .as_u64(),
+                block_hash: log
+                    .block_hash
+                    .ok_or(Error::Malformed("log has no block hash"))?,
+                log_index: log
+                    .log_index
+                    .ok_or(Error::Malformed("log has no index"))?
+                    .as_u64(),
+            };
+            if self.entries.back().is_some_and(|last| {
+                (last.block_number, last.log_index) >= (entry.block_number, entry.log_index)
+            }) {
+                continue;
+            }
+            self.entries.push_back(entry);
+            added += 1;
+        }
+        while self.entries.len() > self.config.window {
+            self.entries.pop_front();
+        }
+        self.head = Some(head);
+        Ok(added)
+    }
+
+    // Drops the roots of blocks that are no longer canonical and moves the head back to the
+    // newest surviving root, or to the start if none survived.
+    async fn rewind<M: Middleware>(&mut self, client: &M) -> Result<(), Error> {
+        let mut checked: Option<(u64, H256)> = None;
+        while let Some(entry) = self.entries.back().copied() {
+            let canonical = match checked {
+                Some((number, hash)) if number == entry.block_number => hash,
+                _ => block_hash(client, entry.block_number).await?,
+            };
+            if canonical == entry.block_hash {
+                self.head = Some((entry.block_number, entry.block_hash));
+                return Ok(());
+            }
+            checked = Some((entry.block_number, canonical));
+            self.entries.pop_back();
+        }
+        self.head = None;
+        Ok(())
+    }
+
+    // Reads the confirmed blocks since the last poll, handling a reorg first if there was one.
+    // Returns the number of new roots.
+    pub async fn poll<M: Middleware>(&mut self, client: &M) -> Result<usize, Error> {
+        let latest = client.get_block_number().await.map_err(rpc)?.as_u64();
+        let to = match latest.checked_sub(self.config.confirmations) {
+            Some(to) => to,
+            None => return Ok(0),
+        };
+        if let Some((number, hash)) = self.head {
+            if block_hash(client, number).await? != hash {
+                self.rewind(client).await?;
+            }
+        }
+        let from = self
+            .head
+            .map_or(self.config.from_block, |(number, _)| number + 1);
+        if from > to {
+            return Ok(0);
+        }
+        // The head's hash is read before the logs, so that a reorg in between shows up as a
+        // stale head on the next poll.
+        let head = (to, block_hash(client, to).await?);
+        let filter = Filter::new()
+            .address(self.config.contract)
+            .event(&self.config.event)
+            .from_block(from)
+            .to_block(to);
+        let logs = client.get_logs(&filter).await.map_err(rpc)?;
+        self.apply(&logs, head)
+    }
+
+    // Polls on every new block until the block stream ends or an error occurs, calling
+    // `on_update` after each poll that found new roots, e.g. to persist the watcher.
+    pub async fn follow<M: Middleware, U: FnMut(&Self) -> Result<(), Error>>(
+        &mut self,
+        client: &M,
+        mut on_update: U,
+    ) -> Result<(), Error> {
+        let mut blocks = client.watch_blocks().await.map_err(rpc)?;
+        while blocks.next().await.is_some() {
+            if self.poll(client).await? > 0 {
+                on_update(self)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let (flag, (number, hash)) = match self.head {
+            Some(head) => (1, head),
+            None => (0, (0, H256::zero())),
+        };
+        let count =
+            u32::try_from(self.entries.len()).map_err(|_| Error::Malformed("too many entries"))?;
+        let mut bytes = vec![WATCHER_VERSION, flag];
+        bytes.extend_from_slice(&number.to_be_bytes());
+        bytes.extend_from_slice(hash.as_bytes());
+        bytes.extend_from_slice(&count.to_be_bytes());
+        for entry in &self.entries {
+            bytes.extend_from_slice(&to_bytes_le(&entry.root));
+            bytes.extend_from_slice(&entry.block_number.to_be_bytes());
+            bytes.extend_from_slice(entry.block_hash.as_bytes());
+            bytes.extend_from_slice(&entry.log_index.to_be_bytes());
+        }
+        Ok(bytes)
+    }
+
+    // Restores a watcher saved with `to_bytes`. If the window shrank, only the newest roots are
+    // kept.
+    pub fn from_bytes(config: WatcherConfig, bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader(bytes);
+        let version = reader.byte()?;
+        if version != WATCHER_VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        let flag = reader.byte()?;
+        let head = (read_u64(&mut reader)?, read_hash(&mut reader)?);
+        let head = match flag {
+            0 if head == (0, H256::zero()) => None,
+            1 => Some(head),
+            _ => return Err(Error::Malformed("invalid head")),
+        };
+        let count = u32::from_be_bytes(reader.take(4)?.try_into().expect("took 4 bytes"));
+        let mut watcher = Self::new(config);
+        for _ in 0..count {
+            let entry = RootEntry {
+                root: reader.field()?,
+                block_number: read_u64(&mut reader)?,
+                block_hash: read_hash(&mut reader)?,
+                log_index: read_u64(&mut reader)?,
+            };
+            if watcher.entries.back().is_some_and(|last| {
+                (last.block_number, last.log_index) >= (entry.block_number, entry.log_index)
+            }) {
+                return Err(Error::Malformed("roots out of order"));
+            }
+            watcher.entries.push_back(entry);
+        }
+        if !reader.0.is_empty() {
+            return Err(Error::Malformed("trailing bytes"));
+        }
+        while watcher.entries.len() > watcher.config.window {
+            watcher.entries.pop_front();
+        }
+        watcher.head = head;
+        Ok(watcher)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let bytes = self
+            .to_bytes()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        std::fs::write(path, bytes)
+    }
+
+    pub fn load<P: AsRef<Path>>(config: WatcherConfig, path: P) -> io::Result<Self> {
+        Self::from_bytes(config, &std::fs::read(path)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+mod tests {
+    use super::{RootWatcher, WatcherConfig};
+    use crate::onchain::LogField;
+    use crate::serialization::to_bytes_be;
+    use ethers::providers::Provider;
+    use ethers::types::{Address, Block, Bytes, Log, H256, U256, U64};
+    use futures::executor::block_on;
+    use halo2_proofs::pasta::Fp;
+
+    fn update(root: u64, block: u64, hash: u8) -> Log {
+        Log {
+            topics: vec![H256::repeat_byte(0xee)],
+            data: Bytes::from(to_bytes_be(&Fp::from(root)).to_vec()),
+            block_number: Some(U64::from(block)),
+            block_hash: Some(H256::repeat_byte(hash)),
+            log_index: Some(U256::zero()),
+            ..Default::default()
+        }
+    }
+
+    fn block(number: u64, hash: u8) -> Block<H256> {
+        Block {
+            number: Some(U64::from(number)),
+            hash: Some(H256::repeat_byte(hash)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test() {
+        let config = WatcherConfig {
+            contract: Address::repeat_byte(0xcc),
+            event: "RootUpdated(bytes32)".to_string(),
+            field: LogField::Data(0),
+            window: 3,
+            confirmations: 2,
+            from_block: 10,
+        };
+        let mut watcher = RootWatcher::new(config.clone());
+        let logs = [update(1, 10, 0xa0), update(2, 11, 0xa1)];
+        assert_eq!(
+            watcher.apply(&logs, (12, H256::repeat_byte(0xa2))).unwrap(),
+            2
+        );
+        // Replayed logs are not recorded twice.
+        assert_eq!(
+            watcher.apply(&logs, (12, H256::repeat_byte(0xa2))).unwrap(),
+            0
+        );
+        assert_eq!(watcher.roots(), vec![Fp::zero(), Fp::from(1), Fp::from(2)]);
+        assert_eq!(watcher.position(&Fp::from(2)), Some(2));
+        assert_eq!(watcher.position(&Fp::from(3)), None);
+
+        let restored =
+            RootWatcher::from_bytes(config.clone(), &watcher.to_bytes().unwrap()).unwrap();
+        assert_eq!(restored.entries(), watcher.entries());
+        assert_eq!(restored.head, watcher.head);
+        let mut bytes = watcher.to_bytes().unwrap();
+        bytes.push(0);
+        assert!(RootWatcher::from_bytes(config.clone(), &bytes).is_err());
+
+        // Block 11 is reorged out and the new fork has a different update in block 13. The mock
+        // provider answers from the last response pushed, so the responses are pushed in reverse.
+        let (provider, mock) = Provider::mocked();
+        mock.push::<Vec<Log>, _>(vec![update(4, 13, 0xb3)]).unwrap();
+        mock.push::<Block<H256>, _>(block(14, 0xb4)).unwrap();
+        mock.push::<Block<H256>, _>(block(10, 0xa0)).unwrap();
+        mock.push::<Block<H256>, _>(block(11, 0xb1)).unwrap();
+        mock.push::<Block<H256>, _>(block(12, 0xb2)).unwrap();
+        mock.push::<U64, _>(U64::from(16)).unwrap();
+        assert_eq!(block_on(watcher.poll(&provider)).unwrap(), 1);
+        assert_eq!(watcher.roots(), vec![Fp::zero(), Fp::from(1), Fp::from(4)]);
+        assert_eq!(watcher.head, Some((14, H256::repeat_byte(0xb4))));
+
+        // Nothing new is confirmed yet.
+        mock.push::<Block<H256>, _>(block(14, 0xb4)).unwrap();
+        mock.push::<U64, _>(U64::from(16)).unwrap();
+        assert_eq!(block_on(watcher.poll(&provider)).unwrap(), 0);
+
+        let shrunk = WatcherConfig {
+            window: 1,
+            ..config
+        };
+        let restored = RootWatcher::from_bytes(shrunk, &watcher.to_bytes().unwrap()).unwrap();
+        assert_eq!(restored.roots(), vec![Fp::from(4)]);
+    }
+}
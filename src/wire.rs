@@ -0,0 +1,261 @@
+/*
+A versioned binary framing for exchanging Merkle proofs and witnesses between services. Every
+message carries the metadata needed to interpret it, so a consumer never has to guess the hash or
+tree shape from context:
+
+    | version: u8 | hash: u8 | arity: u8 | depth: u8 | root: 32 bytes LE |
+    | kind: u8 | len: u32 BE | payload |
+
+A `Path` payload is the leaf followed by `depth` siblings (32 bytes LE each) and `depth` index
+bytes, each 0 or 1. A `Proof` payload is an opaque halo2 proof. Only binary trees are defined, so
+an arity other than 2 is refused when writing as well as when reading. Parsing is strict: unknown
+versions, hash ids or payload kinds, non-canonical field elements, inconsistent lengths and
+trailing bytes are all errors. Later versions bump the version byte, so an old reader refuses them
+instead of misreading them. Decoding lives in halo2_merkle_core, for verifiers that can't link
+halo2.
+*/
+
+use crate::chips::merkle::DomainSeparation;
+use crate::error::Error;
+use crate::serialization::{to_bytes_le, CanonicalField};
+use crate::tree::MerklePath;
+use halo2_merkle_core::wire::{self, Frame, FramePayload};
+use halo2_proofs::pasta::Fp;
+
+pub(crate) use halo2_merkle_core::wire::Reader;
+pub use halo2_merkle_core::wire::WIRE_VERSION;
+
+// Bytes before the payload.
+const HEADER_LEN: usize = 4 + 32 + 1 + 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashId {
+    // The a + b placeholder hash of MerkleTreeV1/V2.
+    Dummy,
+    Poseidon(DomainSeparation),
+}
+
+impl HashId {
+    pub fn to_byte(self) -> u8 {
+        match self {
+            HashId::Dummy => 0,
+            HashId::Poseidon(DomainSeparation::Legacy) => 1,
+            HashId::Poseidon(DomainSeparation::Level) => 2,
+        }
+    }
+
+    pub fn from_byte(byte: u8) -> Result<Self, Error> {
+        match byte {
+            0 => Ok(HashId::Dummy),
+            1 => Ok(HashId::Poseidon(DomainSeparation::Legacy)),
+            2 => Ok(HashId::Poseidon(DomainSeparation::Level)),
+            _ => Err(Error::Malformed("unknown hash id")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Payload<F = Fp> {
+    Path(MerklePath<F>),
+    Proof(Vec<u8>),
+}
+
+impl<F> Payload<F> {
+    fn kind(&self) -> u8 {
+        match self {
+            Payload::Path(_) => 0,
+            Payload::Proof(_) => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleProof<F = Fp> {
+    pub hash: HashId,
+    pub arity: u8,
+    pub depth: u8,
+    pub root: F,
+    pub payload: Payload<F>,
+}
+
+// The index bits of a path of length `depth`, one byte each.
+pub(crate) fn index_bits<F: CanonicalField>(
+    depth: u8,
+    path: &MerklePath<F>,
+) -> Result<Vec<u8>, Error> {
+    if path.elements.len() != path.indices.len() {
+        return Err(Error::LengthMismatch {
+            elements: path.elements.len(),
+            indices: path.indices.len(),
+        });
+    }
+    if path.elements.len() != depth as usize {
+        return Err(Error::DepthMismatch {
+            expected: depth as usize,
+            actual: path.elements.len(),
+        });
+    }
+    path.indices
+        .iter()
+        .enumerate()
+        .map(|(layer, index)| match *index {
+            x if x == F::zero() => Ok(0),
+            x if x == F::one() => Ok(1),
+            _ => Err(Error::InvalidIndex { layer }),
+        })
+        .collect()
+}
+
+impl<F: CanonicalField> MerklePath<F> {
+    // The path payload of a proof of the given arity and depth, as every decoder builds it. Paths
+    // are only defined for binary trees, and need `depth` elements and `depth` indices of 0 or 1.
+    pub fn try_new(
+        arity: u8,
+        depth: u8,
+        leaf: F,
+        elements: Vec<F>,
+        indices: Vec<F>,
+    ) -> Result<Self, Error> {
+        if arity != 2 {
+            return Err(Error::UnsupportedArity(arity as usize));
+        }
+        let path = MerklePath {
+            leaf,
+            elements,
+            indices,
+        };
+        index_bits(depth, &path)?;
+        Ok(path)
+    }
+}
+
+pub(crate) fn index_from_bit<F: CanonicalField>(layer: usize, bit: u64) -> Result<F, Error> {
+    Ok(wire::index_from_bit(layer, bit)?)
+}
+
+impl<F: CanonicalField> MerkleProof<F> {
+    // Only binary trees have an encoding that `from_bytes` reads back.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        if self.arity != 2 {
+            return Err(Error::UnsupportedArity(self.arity as usize));
+        }
+        let payload = match &self.payload {
+            Payload::Path(path) => {
+                let mut bytes = to_bytes_le(&path.leaf).to_vec();
+                for element in &path.elements {
+                    bytes.extend_from_slice(&to_bytes_le(element));
+                }
+                bytes.extend(index_bits(self.depth, path)?);
+                bytes
+            }
+            Payload::Proof(proof) => proof.clone(),
+        };
+        let len = u32::try_from(payload.len()).map_err(|_| Error::Malformed("payload too long"))?;
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+        bytes.extend_from_slice(&[WIRE_VERSION, self.hash.to_byte(), self.arity, self.depth]);
+        bytes.extend_from_slice(&to_bytes_le(&self.root));
+        bytes.push(self.payload.kind());
+        bytes.extend_from_slice(&len.to_be_bytes());
+        bytes.extend_from_slice(&payload);
+        Ok(bytes)
+    }
+
+    // The framing is decoded by halo2_merkle_core; only the hash id is checked here.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let frame = Frame::<F>::from_bytes(bytes)?;
+        let payload = match frame.payload {
+            FramePayload::Path {
+                leaf,
+                elements,
+                indices,
+            } => Payload::Path(MerklePath {
+                leaf,
+                elements,
+                indices,
+            }),
+            FramePayload::Proof(proof) => Payload::Proof(proof),
+        };
+        Ok(Self {
+            hash: HashId::from_byte(frame.hash)?,
+            arity: frame.arity,
+            depth: frame.depth,
+            root: frame.root,
+            payload,
+        })
+    }
+}
+
+mod tests {
+    use super::{HashId, MerkleProof, Payload, WIRE_VERSION};
+    use crate::chips::merkle::DomainSeparation;
+    use crate::error::Error;
+    use crate::tree::MerkleTree;
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let separation = DomainSeparation::Level;
+        let leaves: Vec<Fp> = (0..6u64).map(Fp::from).collect();
+        let tree = MerkleTree::new(3, &leaves, separation).unwrap();
+        let proof = MerkleProof {
+            hash: HashId::Poseidon(separation),
+            arity: 2,
+            depth: 3,
+            root: tree.root(),
+            payload: Payload::Path(tree.path(5).unwrap()),
+        };
+        let bytes = proof.to_bytes().unwrap();
+        assert_eq!(bytes.len(), 41 + 32 * 4 + 3);
+        assert_eq!(bytes[0], WIRE_VERSION);
+        assert_eq!(MerkleProof::<Fp>::from_bytes(&bytes).unwrap(), proof);
+
+        let opaque = MerkleProof::<Fp> {
+            payload: Payload::Proof(vec![7; 10]),
+            ..proof.clone()
+        };
+        let opaque_bytes = opaque.to_bytes().unwrap();
+        assert_eq!(
+            MerkleProof::<Fp>::from_bytes(&opaque_bytes).unwrap(),
+            opaque
+        );
+        for payload in [proof.payload.clone(), opaque.payload.clone()] {
+            let quaternary = MerkleProof {
+                arity: 4,
+                payload,
+                ..proof.clone()
+            };
+            assert!(matches!(
+                quaternary.to_bytes(),
+                Err(Error::UnsupportedArity(4))
+            ));
+        }
+
+        let corrupt = |offset: usize, value: u8| {
+            let mut bytes = bytes.clone();
+            bytes[offset] = value;
+            MerkleProof::<Fp>::from_bytes(&bytes)
+        };
+        assert!(corrupt(0, WIRE_VERSION + 1).is_err());
+        assert!(corrupt(1, 9).is_err());
+        assert!(corrupt(2, 4).is_err());
+        assert!(corrupt(3, 4).is_err());
+        assert!(corrupt(36, 2).is_err());
+        // A sibling at or above the modulus, and an index byte that is not a bit.
+        assert!(corrupt(41 + 32 + 31, 0xff).is_err());
+        assert!(corrupt(bytes.len() - 1, 2).is_err());
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(MerkleProof::<Fp>::from_bytes(&trailing).is_err());
+        assert!(MerkleProof::<Fp>::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        let mut short = tree.path(5).unwrap();
+        short.elements.pop();
+        let invalid = MerkleProof {
+            payload: Payload::Path(short),
+            ..proof
+        };
+        assert!(invalid.to_bytes().is_err());
+    }
+}
@@ -0,0 +1,333 @@
+/*
+A canonical byte encoding of the full private witness of a membership proof, for replaying a failed
+proof on another machine or escrowing exactly what was proved, and reproducible proving from it. A
+`Witness` holds everything `MerkleCircuitBuilder` needs; the depth is the path's:
+
+    | version: u8 | hash: u8 | planner: u8 | exposure: u8 | depth: u8 | leaf: 32 bytes LE |
+    | blinding: 32 bytes LE | siblings: depth × 32 bytes LE | indices: depth bytes, each 0 or 1 |
+
+The hash byte is the wire `HashId`, which carries the domain separation, and the blinding is zero
+unless the leaf is committed. Each witness has exactly one encoding: `from_bytes` refuses unknown
+versions and enum bytes, non-canonical field elements, a blinding on an uncommitted leaf and
+trailing bytes.
+
+A `ProvingRecord` adds k and an RNG seed. The parameters are derived from k and the keys from the
+circuit shape, both deterministically, and `prove_seeded` draws the prover's randomness from
+`seeded_rng(seed)`, so `reproduce` yields a byte-identical proof on any machine, which is what an
+audit re-runs. Whoever holds the seed can recompute the proof's blinding, so keep it with the
+witness rather than with the proof.
+*/
+
+use crate::builder::{Hasher, MerkleCircuit, MerkleCircuitBuilder};
+use crate::chips::merkle::DomainSeparation;
+use crate::config::ProverConfig;
+use crate::error::Error;
+use crate::instance::{InstanceBuilder, LeafExposure};
+use crate::planner::Planner;
+use crate::proving::seeded_rng;
+use crate::serialization::to_bytes_le;
+use crate::tree::MerklePath;
+use crate::wire::{index_bits, index_from_bit, HashId, Reader};
+use halo2_proofs::{
+    pasta::{EqAffine, Fp},
+    plonk::ProvingKey,
+    poly::commitment::Params,
+};
+
+pub const WITNESS_VERSION: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Witness {
+    pub hash: HashId,
+    pub planner: Planner,
+    pub leaf_exposure: LeafExposure,
+    pub path: MerklePath,
+    // The blinding factor of a committed leaf; zero otherwise.
+    pub blinding: Fp,
+}
+
+impl Witness {
+    pub fn new(hash: HashId, path: MerklePath) -> Self {
+        Self {
+            hash,
+            planner: Planner::default(),
+            leaf_exposure: LeafExposure::default(),
+            path,
+            blinding: Fp::zero(),
+        }
+    }
+
+    pub fn with_planner(mut self, planner: Planner) -> Self {
+        self.planner = planner;
+        self
+    }
+
+    pub fn with_commitment(mut self, blinding: Fp) -> Self {
+        self.leaf_exposure = LeafExposure::Commitment;
+        self.blinding = blinding;
+        self
+    }
+
+    pub fn with_private_leaf(mut self) -> Self {
+        self.leaf_exposure = LeafExposure::Private;
+        self.blinding = Fp::zero();
+        self
+    }
+
+    pub fn builder(&self) -> MerkleCircuitBuilder {
+        let builder = MerkleCircuitBuilder::new()
+            .depth(self.path.elements.len())
+            .planner(self.planner)
+            .leaf_exposure(self.leaf_exposure)
+            .path(&self.path);
+        let builder = match self.hash {
+            HashId::Dummy => builder.hasher(Hasher::Dummy),
+            HashId::Poseidon(separation) => builder.hasher(Hasher::Poseidon).separation(separation),
+        };
+        match self.leaf_exposure {
+            LeafExposure::Commitment => builder.blinding(self.blinding),
+            _ => builder,
+        }
+    }
+
+    pub fn circuit(&self) -> Result<MerkleCircuit, Error> {
+        Ok(self.builder().build()?.0)
+    }
+
+    pub fn instances(&self) -> Result<Vec<Vec<Fp>>, Error> {
+        InstanceBuilder::for_circuit(&self.circuit()?)
+    }
+
+    // Proves with randomness drawn from `seeded_rng(seed)`, returning the proof and its instances.
+    // The same witness, parameters and seed always give the same bytes.
+    pub fn prove_seeded(
+        &self,
+        params: &Params<EqAffine>,
+        pk: &ProvingKey<EqAffine>,
+        seed: u64,
+    ) -> Result<(Vec<u8>, Vec<Vec<Fp>>), Error> {
+        let circuit = self.circuit()?;
+        let instances = InstanceBuilder::for_circuit(&circuit)?;
+        let proof = circuit.prove(params, pk, &instances, seeded_rng(seed))?;
+        Ok((proof, instances))
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let depth = u8::try_from(self.path.elements.len()).map_err(|_| Error::DepthTooLarge {
+            max: u8::MAX as usize,
+            depth: self.path.elements.len(),
+        })?;
+        if self.leaf_exposure != LeafExposure::Commitment && self.blinding != Fp::zero() {
+            return Err(Error::Malformed("blinding on an uncommitted leaf"));
+        }
+        let mut bytes = vec![
+            WITNESS_VERSION,
+            self.hash.to_byte(),
+            planner_byte(self.planner),
+            exposure_byte(self.leaf_exposure),
+            depth,
+        ];
+        bytes.extend_from_slice(&to_bytes_le(&self.path.leaf));
+        bytes.extend_from_slice(&to_bytes_le(&self.blinding));
+        for element in &self.path.elements {
+            bytes.extend_from_slice(&to_bytes_le(element));
+        }
+        bytes.extend(index_bits(depth, &self.path)?);
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader(bytes);
+        let witness = Self::read(&mut reader)?;
+        if !reader.0.is_empty() {
+            return Err(Error::Malformed("trailing bytes"));
+        }
+        Ok(witness)
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, Error> {
+        let version = reader.byte()?;
+        if version != WITNESS_VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        let hash = HashId::from_byte(reader.byte()?)?;
+        let planner = match reader.byte()? {
+            0 => Planner::Simple,
+            1 => Planner::V1,
+            _ => return Err(Error::Malformed("unknown planner")),
+        };
+        let leaf_exposure = match reader.byte()? {
+            0 => LeafExposure::Instance,
+            1 => LeafExposure::Commitment,
+            2 => LeafExposure::Private,
+            _ => return Err(Error::Malformed("unknown leaf exposure")),
+        };
+        let depth = reader.byte()? as usize;
+        let leaf = reader.field()?;
+        let blinding = reader.field()?;
+        if leaf_exposure != LeafExposure::Commitment && blinding != Fp::zero() {
+            return Err(Error::Malformed("blinding on an uncommitted leaf"));
+        }
+        let elements = (0..depth)
+            .map(|_| reader.field())
+            .collect::<Result<_, _>>()?;
+        let indices = (0..depth)
+            .map(|layer| index_from_bit(layer, reader.byte()? as u64))
+            .collect::<Result<_, Error>>()?;
+        Ok(Self {
+            hash,
+            planner,
+            leaf_exposure,
+            path: MerklePath {
+                leaf,
+                elements,
+                indices,
+            },
+            blinding,
+        })
+    }
+}
+
+fn planner_byte(planner: Planner) -> u8 {
+    match planner {
+        Planner::Simple => 0,
+        Planner::V1 => 1,
+    }
+}
+
+fn exposure_byte(exposure: LeafExposure) -> u8 {
+    match exposure {
+        LeafExposure::Instance => 0,
+        LeafExposure::Commitment => 1,
+        LeafExposure::Private => 2,
+    }
+}
+
+// Everything needed to recompute a proof byte for byte.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProvingRecord {
+    pub witness: Witness,
+    pub k: u32,
+    pub seed: u64,
+}
+
+impl ProvingRecord {
+    // The parameters for k, checked against the witness's circuit shape as `ProverConfig` does.
+    pub fn params(&self) -> Result<Params<EqAffine>, Error> {
+        let (hash, separation) = match self.witness.hash {
+            HashId::Dummy => (Hasher::Dummy, DomainSeparation::default()),
+            HashId::Poseidon(separation) => (Hasher::Poseidon, separation),
+        };
+        ProverConfig::new(self.k, self.witness.path.elements.len(), hash)
+            .with_separation(separation)
+            .with_planner(self.witness.planner)
+            .with_leaf_exposure(self.witness.leaf_exposure)
+            .params()
+    }
+
+    // Derives the parameters and keys and proves, returning the proof and its instances.
+    pub fn reproduce(&self) -> Result<(Vec<u8>, Vec<Vec<Fp>>), Error> {
+        let params = self.params()?;
+        let pk = self.witness.circuit()?.keygen(&params)?;
+        self.witness.prove_seeded(&params, &pk, self.seed)
+    }
+
+    // | k: u8 | seed: u64 BE | witness |
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let k = u8::try_from(self.k).map_err(|_| Error::Malformed("k does not fit in a byte"))?;
+        let mut bytes = vec![k];
+        bytes.extend_from_slice(&self.seed.to_be_bytes());
+        bytes.extend(self.witness.to_bytes()?);
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader(bytes);
+        let k = reader.byte()? as u32;
+        let seed: [u8; 8] = reader.take(8)?.try_into().expect("took 8 bytes");
+        Ok(Self {
+            k,
+            seed: u64::from_be_bytes(seed),
+            witness: Witness::from_bytes(reader.0)?,
+        })
+    }
+}
+
+mod tests {
+    use super::{ProvingRecord, Witness, WITNESS_VERSION};
+    use crate::chips::merkle::DomainSeparation;
+    use crate::error::Error;
+    use crate::planner::Planner;
+    use crate::proving::{verify, TEST_SEED};
+    use crate::tree::MerkleTree;
+    use crate::wire::HashId;
+    use halo2_proofs::pasta::Fp;
+
+    #[test]
+    fn test() {
+        let separation = DomainSeparation::Level;
+        let leaves: Vec<Fp> = (0..9u64).map(Fp::from).collect();
+        let tree = MerkleTree::new(3, &leaves, separation).unwrap();
+        let witness = Witness::new(HashId::Poseidon(separation), tree.path(6).unwrap());
+
+        let bytes = witness.to_bytes().unwrap();
+        assert_eq!(bytes.len(), 5 + 32 * 2 + 32 * 3 + 3);
+        assert_eq!(Witness::from_bytes(&bytes).unwrap(), witness);
+        let committed = witness
+            .clone()
+            .with_planner(Planner::V1)
+            .with_commitment(Fp::from(42));
+        let committed_bytes = committed.to_bytes().unwrap();
+        assert_eq!(Witness::from_bytes(&committed_bytes).unwrap(), committed);
+        assert_eq!(
+            Witness::from_bytes(&witness.clone().with_private_leaf().to_bytes().unwrap()).unwrap(),
+            witness.clone().with_private_leaf()
+        );
+
+        // Every field is checked, so each witness has exactly one encoding.
+        let corrupt = |offset: usize, value: u8| {
+            let mut bytes = bytes.clone();
+            bytes[offset] = value;
+            Witness::from_bytes(&bytes)
+        };
+        assert!(matches!(
+            corrupt(0, WITNESS_VERSION + 1),
+            Err(Error::UnsupportedVersion(_))
+        ));
+        assert!(corrupt(1, 9).is_err());
+        assert!(corrupt(2, 2).is_err());
+        assert!(corrupt(3, 3).is_err());
+        assert!(corrupt(5 + 32, 1).is_err());
+        assert!(corrupt(bytes.len() - 1, 2).is_err());
+        assert!(Witness::from_bytes(&[bytes.clone(), vec![0]].concat()).is_err());
+        assert!(Witness::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        // The same record proves to the same bytes, and the proof verifies.
+        let record = ProvingRecord {
+            witness: committed,
+            k: 10,
+            seed: TEST_SEED,
+        };
+        assert_eq!(
+            ProvingRecord::from_bytes(&record.to_bytes().unwrap()).unwrap(),
+            record
+        );
+        let (proof, instances) = record.reproduce().unwrap();
+        assert_eq!(
+            record.reproduce().unwrap(),
+            (proof.clone(), instances.clone())
+        );
+        let params = record.params().unwrap();
+        let pk = record.witness.circuit().unwrap().keygen(&params).unwrap();
+        verify(&params, pk.get_vk(), &proof, &instances).unwrap();
+        let reseeded = ProvingRecord {
+            seed: TEST_SEED + 1,
+            ..record.clone()
+        };
+        assert_ne!(reseeded.reproduce().unwrap().0, proof);
+        assert!(matches!(
+            ProvingRecord { k: 40, ..record }.params(),
+            Err(Error::InvalidK { k: 40, .. })
+        ));
+    }
+}
@@ -0,0 +1,294 @@
+/*
+Wiping secret witnesses from memory once they are no longer needed, with the `zeroize` crate:
+
+    let secret = Secret::new(secret);
+    let circuit = AllowlistCircuit::new(*secret.expose(), &path, scope)?;
+    let proof = prove_zeroizing(&params, &pk, circuit, &instances, OsRng)?;
+    // `circuit` was wiped after proving, `secret` is wiped when it goes out of scope.
+
+`Zeroize` is implemented for the membership circuits' witnesses, `MerklePath` and `Witness`; a
+wiped circuit has every witness value overwritten with zero and then set to unknown, so it can
+still be used for keygen. The circuits over Pallas that carry an identity secret or a private leaf
+(`AllowlistCircuit`, `StealthClaimCircuit`, `MerkleTreeCircuit`) also wipe themselves on drop.
+The field-generic MerkleTreeV2/V3 circuits can't: a `Drop` impl may not require `FieldExt`, which
+overwriting with zero needs, so they are wiped by `prove_zeroizing` (and so by
+`MerkleCircuit::prove` and `MembershipProver`) instead.
+
+Only this crate's copies are wiped. halo2 copies the witness into its own advice polynomials while
+proving and does not zeroize them.
+*/
+
+use crate::builder::MerkleCircuit;
+use crate::circuits::{
+    allowlist::AllowlistCircuit, merkle::MerkleTreeCircuit, merkle_v2::MerkleTreeV2Circuit,
+    merkle_v3::MerkleTreeV3Circuit, smt_deletion::SmtDeletionCircuit, stealth::StealthClaimCircuit,
+};
+use crate::error::Error;
+use crate::planner::Packed;
+use crate::proving::{prove_ref, RngCore};
+use crate::tree::MerklePath;
+use crate::witness::Witness;
+use ::zeroize::{Zeroize, ZeroizeOnDrop};
+use halo2_proofs::{
+    arithmetic::{Field, FieldExt},
+    circuit::Value,
+    pasta::{EqAffine, Fp},
+    plonk::{Circuit, ProvingKey},
+    poly::commitment::Params,
+};
+use std::fmt;
+use std::ptr;
+use std::sync::atomic::{compiler_fence, Ordering};
+
+// Volatile, so the write isn't optimized away as dead; `fence` keeps it ordered before what
+// follows.
+fn wipe<F: Field>(value: &mut F) {
+    // SAFETY: `value` is a valid, aligned, exclusive reference and zero is a valid F.
+    unsafe { ptr::write_volatile(value, F::zero()) };
+}
+
+fn fence() {
+    compiler_fence(Ordering::SeqCst);
+}
+
+fn wipe_value<F: Field>(value: &mut Value<F>) {
+    value.as_mut().map(wipe);
+    *value = Value::unknown();
+}
+
+// Keeps the length, so the circuit keeps its shape.
+fn wipe_values<F: Field>(values: &mut [Value<F>]) {
+    values.iter_mut().for_each(wipe_value);
+}
+
+// A native secret, such as an identity secret or a blinding salt, wiped when dropped. Debug
+// output does not show it.
+pub struct Secret<F: Field>(F);
+
+impl<F: Field> Secret<F> {
+    pub fn new(value: F) -> Self {
+        Self(value)
+    }
+
+    pub fn expose(&self) -> &F {
+        &self.0
+    }
+}
+
+impl<F: Field> From<F> for Secret<F> {
+    fn from(value: F) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<F: Field> fmt::Debug for Secret<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret(..)")
+    }
+}
+
+impl<F: Field> Zeroize for Secret<F> {
+    fn zeroize(&mut self) {
+        wipe(&mut self.0);
+        fence();
+    }
+}
+
+impl<F: Field> Drop for Secret<F> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<F: Field> ZeroizeOnDrop for Secret<F> {}
+
+impl<F: Field> Zeroize for MerklePath<F> {
+    fn zeroize(&mut self) {
+        wipe(&mut self.leaf);
+        self.elements.iter_mut().for_each(wipe);
+        self.indices.iter_mut().for_each(wipe);
+        fence();
+    }
+}
+
+impl<F: FieldExt> Zeroize for MerkleTreeV2Circuit<F> {
+    fn zeroize(&mut self) {
+        wipe_value(&mut self.leaf);
+        wipe_values(&mut self.elements);
+        wipe_values(&mut self.indices);
+        wipe_value(&mut self.blinding);
+        fence();
+    }
+}
+
+impl<F: FieldExt> Zeroize for MerkleTreeV3Circuit<F> {
+    fn zeroize(&mut self) {
+        wipe_value(&mut self.leaf);
+        wipe_values(&mut self.elements);
+        wipe_values(&mut self.indices);
+        wipe_value(&mut self.blinding);
+        if let Some(preimage) = self.preimage.as_mut() {
+            wipe_values(preimage);
+        }
+        fence();
+    }
+}
+
+impl Zeroize for SmtDeletionCircuit {
+    fn zeroize(&mut self) {
+        wipe_value(&mut self.leaf);
+        self.siblings.iter_mut().for_each(|sibling| {
+            sibling.as_mut().map(|sibling| sibling.as_mut().map(wipe));
+            *sibling = Value::unknown();
+        });
+        wipe_values(&mut self.indices);
+        fence();
+    }
+}
+
+impl<C: Zeroize> Zeroize for Packed<C> {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Zeroize for MerkleCircuit {
+    fn zeroize(&mut self) {
+        match self {
+            MerkleCircuit::Dummy(circuit) => circuit.zeroize(),
+            MerkleCircuit::Poseidon(circuit) => circuit.zeroize(),
+            MerkleCircuit::PackedDummy(circuit) => circuit.zeroize(),
+            MerkleCircuit::PackedPoseidon(circuit) => circuit.zeroize(),
+        }
+    }
+}
+
+impl Zeroize for Witness {
+    fn zeroize(&mut self) {
+        self.path.zeroize();
+        wipe(&mut self.blinding);
+        fence();
+    }
+}
+
+impl Zeroize for MerkleTreeCircuit {
+    fn zeroize(&mut self) {
+        wipe_value(&mut self.leaf);
+        wipe_values(&mut self.elements);
+        wipe_values(&mut self.indices);
+        fence();
+    }
+}
+
+impl Zeroize for AllowlistCircuit {
+    fn zeroize(&mut self) {
+        wipe_value(&mut self.secret);
+        wipe_values(&mut self.elements);
+        wipe_values(&mut self.indices);
+        wipe_value(&mut self.scope);
+        fence();
+    }
+}
+
+impl Zeroize for StealthClaimCircuit {
+    fn zeroize(&mut self) {
+        wipe_value(&mut self.secret);
+        wipe_value(&mut self.nonce);
+        wipe_values(&mut self.elements);
+        wipe_values(&mut self.indices);
+        fence();
+    }
+}
+
+macro_rules! zeroize_on_drop {
+    ($($circuit:ty),*) => {
+        $(
+            impl Drop for $circuit {
+                fn drop(&mut self) {
+                    self.zeroize();
+                }
+            }
+
+            impl ZeroizeOnDrop for $circuit {}
+        )*
+    };
+}
+
+zeroize_on_drop!(MerkleTreeCircuit, AllowlistCircuit, StealthClaimCircuit);
+
+// `proving::prove`, wiping the circuit's witness once the proof is made or has failed.
+pub fn prove_zeroizing<C: Circuit<Fp> + Zeroize>(
+    params: &Params<EqAffine>,
+    pk: &ProvingKey<EqAffine>,
+    mut circuit: C,
+    instances: &[Vec<Fp>],
+    rng: impl RngCore,
+) -> Result<Vec<u8>, Error> {
+    let proof = prove_ref(params, pk, &circuit, instances, rng);
+    circuit.zeroize();
+    proof
+}
+
+mod tests {
+    use super::{prove_zeroizing, Secret};
+    use crate::builder::{Hasher, MerkleCircuit, MerkleCircuitBuilder};
+    use crate::chips::merkle::DomainSeparation;
+    use crate::circuits::allowlist::{identity_commitment, AllowlistCircuit};
+    use crate::instance::InstanceBuilder;
+    use crate::proving::{keygen, seeded_rng, verify, TEST_SEED};
+    use crate::tree::MerkleTree;
+    use ::zeroize::Zeroize;
+    use halo2_proofs::{
+        circuit::Value,
+        pasta::{EqAffine, Fp},
+        poly::commitment::Params,
+    };
+
+    fn is_unknown(value: &Value<Fp>) -> bool {
+        let mut known = false;
+        value.map(|_| known = true);
+        !known
+    }
+
+    #[test]
+    fn test() {
+        let mut secret = Secret::new(Fp::from(42));
+        assert_eq!(*secret.expose(), Fp::from(42));
+        assert_eq!(format!("{:?}", secret), "Secret(..)");
+        secret.zeroize();
+        assert_eq!(*secret.expose(), Fp::zero());
+
+        let leaves: Vec<Fp> = (0..5u64)
+            .map(|i| identity_commitment(Fp::from(i)))
+            .collect();
+        let tree = MerkleTree::new(3, &leaves, DomainSeparation::Legacy).unwrap();
+        let mut path = tree.path(4).unwrap();
+        let mut circuit = AllowlistCircuit::new(Fp::from(4), &path, Fp::from(7)).unwrap();
+        circuit.zeroize();
+        assert!(is_unknown(&circuit.secret));
+        assert_eq!(circuit.elements.len(), 3);
+        assert!(circuit.elements.iter().all(is_unknown));
+        path.zeroize();
+        assert_eq!(path.leaf, Fp::zero());
+        assert!(path.elements.iter().all(|element| *element == Fp::zero()));
+
+        // A wiped circuit keeps its shape, and proving still works through `prove_zeroizing`.
+        let path = tree.path(2).unwrap();
+        let (circuit, _) = MerkleCircuitBuilder::new()
+            .depth(3)
+            .hasher(Hasher::Poseidon)
+            .path(&path)
+            .build()
+            .unwrap();
+        let instances = InstanceBuilder::for_circuit(&circuit).unwrap();
+        let circuit = match circuit {
+            MerkleCircuit::Poseidon(circuit) => circuit,
+            _ => unreachable!(),
+        };
+        let params: Params<EqAffine> = Params::new(10);
+        let pk = keygen(&params, &circuit).unwrap();
+        let proof =
+            prove_zeroizing(&params, &pk, circuit, &instances, seeded_rng(TEST_SEED)).unwrap();
+        verify(&params, pk.get_vk(), &proof, &instances).unwrap();
+    }
+}
@@ -0,0 +1,99 @@
+// Regenerates compat.json from the reference implementations:
+//
+//     npm install circomlibjs poseidon-lite @zk-kit/lean-imt
+//     node generate.mjs > compat.json
+//
+// and `cargo test --features semaphore,json compat` checks the crate against it.
+
+import { buildMimcSponge } from "circomlibjs";
+import { poseidon1, poseidon2 } from "poseidon-lite";
+import { LeanIMT } from "@zk-kit/lean-imt";
+
+const LEVELS = 20;
+// keccak256("tornado") mod p, Tornado's ZERO_VALUE.
+const ZERO_VALUE =
+  21663839004416932945382355908790599225266501822907911457504978515578255421292n;
+
+const hex = (x) => "0x" + BigInt(x).toString(16);
+const range = (from, to) => Array.from({ length: to - from }, (_, i) => BigInt(from + i));
+
+const mimc = await buildMimcSponge();
+// Tornado's hashLeftRight is the sponge over [left, right] with key 0.
+const hashLeftRight = (left, right) => mimc.F.toObject(mimc.multiHash([left, right], 0n, 1));
+
+const zeros = [ZERO_VALUE];
+for (let level = 1; level <= LEVELS; level++) {
+  zeros.push(hashLeftRight(zeros[level - 1], zeros[level - 1]));
+}
+
+function tornadoTree(commitments) {
+  const layers = [commitments];
+  for (let level = 0; level < LEVELS; level++) {
+    const layer = layers[level];
+    const next = [];
+    for (let i = 0; i < Math.max(layer.length, 1); i += 2) {
+      next.push(hashLeftRight(layer[i] ?? zeros[level], layer[i + 1] ?? zeros[level]));
+    }
+    layers.push(next);
+  }
+  const paths = commitments.map((_, leafIndex) => {
+    const elements = [];
+    const indices = [];
+    let index = leafIndex;
+    for (let level = 0; level < LEVELS; level++) {
+      elements.push(hex(layers[level][index ^ 1] ?? zeros[level]));
+      indices.push(index & 1);
+      index >>= 1;
+    }
+    return { leaf_index: leafIndex, elements, indices };
+  });
+  return {
+    kind: "tornado-tree",
+    source: "circomlibjs",
+    commitments: commitments.map(hex),
+    root: hex(layers[LEVELS][0]),
+    paths,
+  };
+}
+
+function leanImt(leaves) {
+  const tree = new LeanIMT((a, b) => poseidon2([a, b]));
+  tree.insertMany(leaves);
+  return {
+    kind: "lean-imt",
+    source: "zk-kit",
+    leaves: leaves.map(hex),
+    root: hex(tree.root),
+    proofs: leaves.map((_, leafIndex) => {
+      const proof = tree.generateProof(leafIndex);
+      return { leaf_index: leafIndex, index: proof.index, siblings: proof.siblings.map(hex) };
+    }),
+  };
+}
+
+const cases = [
+  ...[[1n], [1n, 2n], [0n], [0n, 0n], [123456789n, 987654321n]].map((inputs) => ({
+    kind: "poseidon",
+    source: "circomlibjs",
+    inputs: inputs.map(hex),
+    output: hex(inputs.length === 1 ? poseidon1(inputs) : poseidon2(inputs)),
+  })),
+  ...[[1n, 2n], [42n, 7n]].map(([scope, secret]) => ({
+    kind: "nullifier",
+    source: "circomlibjs",
+    scope: hex(scope),
+    secret: hex(secret),
+    output: hex(poseidon2([scope, secret])),
+  })),
+  ...[1, 2, 5, 8, 11].map((size) => leanImt(range(1, size + 1))),
+  ...zeros.map((output, level) => ({
+    kind: "tornado-zero",
+    source: "circomlibjs",
+    level,
+    output: hex(output),
+  })),
+  tornadoTree([]),
+  tornadoTree(range(1, 6)),
+];
+
+console.log(JSON.stringify({ cases }, null, 2));